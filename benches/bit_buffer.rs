@@ -0,0 +1,206 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Benchmarks of BitBuffer, compared against the LinkedList storage it used to have.
+//!
+//! Run with `cargo bench --bench bit_buffer`. Before the benchmarks run, the number of allocations
+//! each storage makes in the compressor's access pattern is printed.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use log::debug;
+use ppm_cli::bit_buffer::BitBuffer;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::LinkedList;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of bits appended in every benchmark
+const BITS: usize = 1 << 20;
+
+/// Number of bits the compressor appends, on average, between draining complete bytes
+const BITS_PER_DRAIN: usize = 5;
+
+/// Counts the allocations the program makes, so the storages' allocations can be compared
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// The operations of BitBuffer, so both storages run the same scenarios
+trait BitStorage: Default {
+    fn append(&mut self, bit: bool);
+    fn append_repeated(&mut self, bit: bool, repetitions: usize);
+    fn drain(&mut self) -> impl Iterator<Item = u8> + '_;
+}
+
+impl BitStorage for BitBuffer {
+    fn append(&mut self, bit: bool) {
+        BitBuffer::append(self, bit)
+    }
+
+    fn append_repeated(&mut self, bit: bool, repetitions: usize) {
+        BitBuffer::append_repeated(self, bit, repetitions)
+    }
+
+    fn drain(&mut self) -> impl Iterator<Item = u8> + '_ {
+        self.get_complete_bytes()
+    }
+}
+
+/// BitBuffer as it was before its complete bytes were moved from a LinkedList to a VecDeque
+/// (including its logging, so only the storage differs)
+#[derive(Default)]
+struct LinkedListBitBuffer {
+    full_bytes: LinkedList<u8>,
+    current_byte: u8,
+    current_idx: usize,
+}
+
+impl LinkedListBitBuffer {
+    fn save_current_byte(&mut self) {
+        debug!("Saving byte into BitBuffer: {:08b}", self.current_byte);
+        self.full_bytes.push_back(self.current_byte);
+        self.current_byte = 0;
+        self.current_idx = 0;
+    }
+}
+
+impl BitStorage for LinkedListBitBuffer {
+    fn append(&mut self, bit: bool) {
+        debug!("Appending bit to buffer: {}", if bit { 1 } else { 0 });
+        if bit {
+            self.current_byte |= 1 << (7 - self.current_idx);
+        }
+        self.current_idx += 1;
+        if self.current_idx >= 8 {
+            self.save_current_byte();
+        }
+    }
+
+    fn append_repeated(&mut self, bit: bool, mut repetitions: usize) {
+        debug!(
+            "Appending bits to buffer: {} {} time(s)",
+            if bit { 1 } else { 0 },
+            repetitions
+        );
+        let bit_repeated = if bit { u8::MAX } else { 0 };
+        while self.current_idx + repetitions >= 8 {
+            self.current_byte |= bit_repeated >> self.current_idx;
+            repetitions -= 8 - self.current_idx;
+            self.save_current_byte();
+        }
+        if repetitions > 0 && bit {
+            self.current_byte |= (u8::MAX << (8 - repetitions)) >> self.current_idx;
+        }
+        self.current_idx += repetitions;
+    }
+
+    fn drain(&mut self) -> impl Iterator<Item = u8> {
+        debug!(
+            "Removing {} complete bytes from buffer",
+            self.full_bytes.len()
+        );
+        std::mem::take(&mut self.full_bytes).into_iter()
+    }
+}
+
+/// Appends bits one by one, then drains them all
+fn append_then_drain<S: BitStorage>() -> usize {
+    let mut storage = S::default();
+    for i in 0..BITS {
+        storage.append(i % 3 == 0);
+    }
+    storage.drain().count()
+}
+
+/// Appends runs of repeated bits, then drains them all
+fn append_repeated_then_drain<S: BitStorage>() -> usize {
+    let mut storage = S::default();
+    for i in 0..BITS / 16 {
+        storage.append_repeated(i % 2 == 0, 16);
+    }
+    storage.drain().count()
+}
+
+/// Mimics the compressor: a few bits are appended for every symbol, and complete bytes are drained
+/// after each one
+fn compressor_pattern<S: BitStorage>() -> usize {
+    let mut storage = S::default();
+    let mut drained = 0;
+    for i in 0..BITS / BITS_PER_DRAIN {
+        storage.append(i % 2 == 0);
+        storage.append_repeated(i % 3 == 0, BITS_PER_DRAIN - 1);
+        drained += storage.drain().count();
+    }
+    drained
+}
+
+/// Returns the number of allocations _scenario_ makes
+fn count_allocations(scenario: fn() -> usize) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(scenario());
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn bench_bit_buffer(c: &mut Criterion) {
+    for (name, linked_list, bit_buffer) in [
+        (
+            "append",
+            append_then_drain::<LinkedListBitBuffer> as fn() -> usize,
+            append_then_drain::<BitBuffer> as fn() -> usize,
+        ),
+        (
+            "append_repeated",
+            append_repeated_then_drain::<LinkedListBitBuffer>,
+            append_repeated_then_drain::<BitBuffer>,
+        ),
+        (
+            "compressor_pattern",
+            compressor_pattern::<LinkedListBitBuffer>,
+            compressor_pattern::<BitBuffer>,
+        ),
+    ] {
+        println!(
+            "{name}: LinkedList made {} allocations, BitBuffer made {}",
+            count_allocations(linked_list),
+            count_allocations(bit_buffer)
+        );
+
+        let mut group = c.benchmark_group(name);
+        group.throughput(Throughput::Elements(BITS as u64));
+        group.bench_function(BenchmarkId::new("LinkedList", BITS), |b| {
+            b.iter(linked_list)
+        });
+        group.bench_function(BenchmarkId::new("BitBuffer", BITS), |b| b.iter(bit_buffer));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_bit_buffer);
+criterion_main!(benches);
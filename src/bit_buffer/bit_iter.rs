@@ -26,6 +26,30 @@ pub struct BitIterator<'a> {
 
     // In case there is an incomplete byte, hold it and the number of bits in it:
     incomplete_byte: Option<(u8, usize)>,
+
+    // The number of bits the iterator yielded so far:
+    bits_consumed: usize,
+}
+
+impl<'a> BitIterator<'a> {
+    /// Creates an iterator over the bits of _bytes_, borrowing them instead of taking ownership,
+    /// so the slice can still be used afterward.
+    pub fn from_slice(bytes: &'a [u8]) -> Self {
+        Self::from(bytes.iter().copied())
+    }
+
+    /// Returns the next bit without consuming it, or None if there are no more bits.
+    pub fn peek(&mut self) -> Option<bool> {
+        let byte = self
+            .current_byte
+            .or(self.incomplete_byte.map(|(byte, _)| byte))?;
+        Some(((byte >> (7 - self.current_idx)) & 1) == 1)
+    }
+
+    /// Returns the number of bits the iterator yielded so far.
+    pub fn bits_consumed(&self) -> usize {
+        self.bits_consumed
+    }
 }
 
 impl Iterator for BitIterator<'_> {
@@ -46,6 +70,7 @@ impl Iterator for BitIterator<'_> {
                 self.current_idx = 0;
                 self.current_byte = self.full_bytes_iter.next();
             }
+            self.bits_consumed += 1;
             debug!("Next bit in iterator: {}", if bit { 1 } else { 0 });
             return Some(bit);
         }
@@ -60,6 +85,7 @@ impl Iterator for BitIterator<'_> {
             if self.current_idx < num_bits {
                 let _ = self.incomplete_byte.insert((byte, num_bits));
             }
+            self.bits_consumed += 1;
             debug!("Next bit in iterator: {}", if bit { 1 } else { 0 });
             Some(bit)
         } else {
@@ -69,22 +95,19 @@ impl Iterator for BitIterator<'_> {
 }
 
 impl From<BitBuffer> for BitIterator<'_> {
-    fn from(mut buffer: BitBuffer) -> Self {
-        let mut full_bytes_iter = Box::new(buffer.get_complete_bytes());
+    fn from(buffer: BitBuffer) -> Self {
+        // The leftover bits are read before the complete bytes are moved out of the buffer:
+        let incomplete_byte = buffer.get_leftover_bits_with_count();
+        let mut full_bytes_iter = Box::new(buffer.full_bytes.into_iter());
         let current_idx = 0;
         let current_byte = full_bytes_iter.next();
 
-        let incomplete_byte = if buffer.current_idx > 0 {
-            Some((buffer.current_byte, buffer.current_idx))
-        } else {
-            None
-        };
-
         Self {
             full_bytes_iter,
             current_idx,
             current_byte,
             incomplete_byte,
+            bits_consumed: 0,
         }
     }
 }
@@ -102,6 +125,7 @@ impl<'a, I: IntoIterator<Item = u8> + 'a> From<I> for BitIterator<'a> {
             current_byte,
             current_idx,
             incomplete_byte,
+            bits_consumed: 0,
         }
     }
 }
@@ -20,28 +20,51 @@ pub mod bit_iter;
 mod unit_tests;
 
 use log::{debug, info};
-use std::collections::LinkedList;
+use std::collections::VecDeque;
 
 /// A buffer dedicated to bit storage
 #[derive(Debug)]
 pub struct BitBuffer {
-    full_bytes: LinkedList<u8>,
+    // Complete bytes are stored contiguously, so appending one rarely allocates:
+    full_bytes: VecDeque<u8>,
     // Bits will be added to this byte, from its MSB to the LSB to preserve insertion order
     current_byte: u8,
     current_idx: usize,
 }
 
+impl Default for BitBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BitBuffer {
     /// Initializes an empty BitBuffer.
     pub fn new() -> Self {
         info!("Created new BitBuffer");
         Self {
-            full_bytes: LinkedList::new(),
+            full_bytes: VecDeque::new(),
+            current_byte: 0,
+            current_idx: 0,
+        }
+    }
+
+    /// Initializes an empty BitBuffer with room for at least _bits_ bits, so filling it up to
+    /// that many bits doesn't reallocate. Otherwise it behaves exactly like `BitBuffer::new`.
+    pub fn with_capacity(bits: usize) -> Self {
+        info!("Created new BitBuffer with a capacity of {} bits", bits);
+        Self {
+            full_bytes: VecDeque::with_capacity(bits.div_ceil(8)),
             current_byte: 0,
             current_idx: 0,
         }
     }
 
+    /// Returns the number of bits the buffer can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        8 * self.full_bytes.capacity()
+    }
+
     /// Inserts a single bit to the end of the buffer.
     pub fn append(&mut self, bit: bool) {
         debug!("Appending bit to buffer: {}", if bit { 1 } else { 0 });
@@ -76,14 +99,15 @@ impl BitBuffer {
             self.save_current_byte();
         }
 
-        // Insert leftover bits to current_byte if needed, update current_idx:
+        // Insert leftover bits to current_byte (right after the bits already in it) if needed,
+        // update current_idx:
         if repetitions > 0 && bit {
-            self.current_byte |= u8::MAX << (8 - repetitions);
+            self.current_byte |= (u8::MAX << (8 - repetitions)) >> self.current_idx;
         }
         self.current_idx += repetitions;
     }
 
-    /// Saves the current byte into the `full_bytes` list, and resets both `current_idx` and
+    /// Saves the current byte into the `full_bytes` queue, and resets both `current_idx` and
     /// `current_idx`.
     fn save_current_byte(&mut self) {
         debug!("Saving byte into BitBuffer: {:08b}", self.current_byte);
@@ -96,12 +120,15 @@ impl BitBuffer {
     /// bits in the buffer to form a single byte, the iterator will be empty.<br>
     /// To remove ambiguity: **The bytes will not remain in the buffer after calling this
     /// function**.
-    pub fn get_complete_bytes(&mut self) -> impl Iterator<Item = u8> {
+    ///
+    /// The buffer keeps its allocated memory, so appending bits after draining the bytes doesn't
+    /// allocate again.
+    pub fn get_complete_bytes(&mut self) -> impl Iterator<Item = u8> + '_ {
         debug!(
             "Removing {} complete bytes from buffer",
             self.full_bytes.len()
         );
-        std::mem::take(&mut self.full_bytes).into_iter()
+        self.full_bytes.drain(..)
     }
 
     /// Returns the number of **bits** in the buffer
@@ -109,6 +136,20 @@ impl BitBuffer {
         8 * self.full_bytes.len() + self.current_idx
     }
 
+    /// Returns true if the buffer holds no bits, neither in complete bytes nor as leftover bits
+    pub fn is_empty(&self) -> bool {
+        self.full_bytes.is_empty() && self.current_idx == 0
+    }
+
+    /// Removes every bit from the buffer, including leftover bits. Unlike dropping the buffer and
+    /// creating a new one, this lets a long-running compression keep using the same buffer.
+    pub fn clear(&mut self) {
+        debug!("Clearing BitBuffer of {} bits", self.len());
+        self.full_bytes.clear();
+        self.current_byte = 0;
+        self.current_idx = 0;
+    }
+
     /// If the number of bits in the buffer isn't divisible by 8, there will exist 'leftover' bits,
     /// which cannot be turned into a byte without padding.
     ///
@@ -129,14 +170,47 @@ impl BitBuffer {
             None
         }
     }
+
+    /// Like `get_leftover_bits`, but also returns the number of leftover bits (the rest of the
+    /// returned byte's bits are padding).
+    ///
+    /// Note that this operation does **not** remove those leftover bits from the buffer.
+    pub fn get_leftover_bits_with_count(&self) -> Option<(u8, usize)> {
+        self.get_leftover_bits()
+            .map(|leftover| (leftover, self.current_idx))
+    }
+
+    /// Appends the bits of the given bytes to the end of the buffer, except for the padding of the
+    /// last byte: only the _last_byte_bits_ most significant bits of the last byte are appended
+    /// (at most 8).<br>
+    /// This allows appending bit sequences whose length isn't divisible by 8 bit-exactly, such as
+    /// the output of `Compressor::finalize_bits`.
+    pub fn extend_from(&mut self, bytes: &[u8], last_byte_bits: usize) {
+        let Some((&last_byte, bytes)) = bytes.split_last() else {
+            return;
+        };
+        debug!(
+            "Extending BitBuffer with {} bits",
+            8 * bytes.len() + last_byte_bits.min(8)
+        );
+
+        let bits = |byte: u8, count: usize| (0..count).map(move |i| (byte >> (7 - i)) & 1 == 1);
+        for bit in bytes
+            .iter()
+            .flat_map(|&byte| bits(byte, 8))
+            .chain(bits(last_byte, last_byte_bits.min(8)))
+        {
+            self.append(bit);
+        }
+    }
 }
 
 impl From<&[u8]> for BitBuffer {
     fn from(value: &[u8]) -> Self {
         debug!("Creating BitBuffer from slice of {} bytes", value.len());
-        // Since whose are all full bytes, add them directly to the full_bytes list:
+        // Since whose are all full bytes, add them directly to the full_bytes queue:
         Self {
-            full_bytes: LinkedList::from_iter(value.iter().copied()),
+            full_bytes: value.iter().copied().collect(),
             current_byte: 0,
             current_idx: 0,
         }
@@ -146,9 +220,9 @@ impl From<&[u8]> for BitBuffer {
 impl From<Vec<u8>> for BitBuffer {
     fn from(value: Vec<u8>) -> Self {
         debug!("Creating BitBuffer from Vec of {} bytes", value.len());
-        // Since whose are all full bytes, add them directly to the full_bytes list:
+        // Since whose are all full bytes, add them directly to the full_bytes queue:
         Self {
-            full_bytes: LinkedList::from_iter(value),
+            full_bytes: VecDeque::from(value),
             current_byte: 0,
             current_idx: 0,
         }
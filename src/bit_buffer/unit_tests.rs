@@ -26,6 +26,31 @@ fn empty_upon_initializing() {
     assert!(buffer.full_bytes.is_empty())
 }
 
+#[test]
+fn test_capacity_is_respected() {
+    let mut buffer = BitBuffer::with_capacity(1001);
+    let capacity = buffer.capacity();
+    assert!(capacity >= 1001);
+    assert!(buffer.is_empty());
+
+    // Filling the buffer up to its requested capacity doesn't reallocate, and stores the same bits
+    // as a buffer created without a capacity:
+    let mut default_buffer = BitBuffer::new();
+    for i in 0..1001 {
+        buffer.append(i % 3 == 0);
+        default_buffer.append(i % 3 == 0);
+    }
+    assert_eq!(buffer.capacity(), capacity);
+    assert_eq!(buffer.len(), default_buffer.len());
+    assert_eq!(
+        buffer.get_leftover_bits(),
+        default_buffer.get_leftover_bits()
+    );
+    assert!(buffer
+        .get_complete_bytes()
+        .eq(default_buffer.get_complete_bytes()));
+}
+
 #[test]
 fn test_less_than_byte_appends() {
     let mut buffer = BitBuffer::new();
@@ -99,6 +124,19 @@ fn test_less_than_byte_appends_repeated() {
     assert!(buffer.full_bytes.is_empty());
 }
 
+#[test]
+fn test_less_than_byte_appends_repeated_after_append() {
+    let mut buffer = BitBuffer::new();
+    buffer.append(false);
+    buffer.append(true);
+    buffer.append(false);
+    buffer.append_repeated(true, 3);
+
+    assert_eq!(buffer.current_byte, 0b01011100u8);
+    assert_eq!(buffer.current_idx, 6);
+    assert!(buffer.full_bytes.is_empty());
+}
+
 #[test]
 fn test_exactly_one_byte_appends_repeated() {
     let mut buffer = BitBuffer::new();
@@ -170,6 +208,46 @@ fn test_len_multiple_bytes() {
     assert_eq!(buffer.len(), 8 * 6);
 }
 
+#[test]
+fn test_is_empty() {
+    let mut buffer = BitBuffer::new();
+    assert!(buffer.is_empty());
+    buffer.append(false);
+    assert!(!buffer.is_empty());
+    assert!(!BitBuffer::from(vec![0]).is_empty());
+}
+
+#[test]
+fn test_clear_after_partial_appends() {
+    let mut buffer = BitBuffer::new();
+    buffer.append_repeated(true, 5);
+    buffer.clear();
+
+    assert!(buffer.is_empty());
+    assert!(buffer.get_leftover_bits().is_none());
+    assert_eq!(buffer.current_byte, 0);
+    assert_eq!(buffer.current_idx, 0);
+
+    // The cleared bits don't leak into new ones:
+    buffer.append(false);
+    assert_eq!(buffer.get_leftover_bits(), Some(0));
+}
+
+#[test]
+fn test_clear_after_full_byte_appends() {
+    let mut buffer = BitBuffer::from(vec![100, 11]);
+    buffer.append_repeated(true, 11);
+    buffer.clear();
+
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.len(), 0);
+    assert_eq!(buffer.get_complete_bytes().count(), 0);
+
+    // The buffer can be reused after clearing:
+    buffer.append_repeated(true, 8);
+    assert_eq!(buffer.get_complete_bytes().collect::<Vec<u8>>(), [u8::MAX]);
+}
+
 #[test]
 fn test_full_bytes_new_buffer() {
     let mut buffer = BitBuffer::new();
@@ -459,3 +537,109 @@ fn test_bit_iterator_from_slice() {
     assert_eq!(bits.len(), 8 * 2);
     assert_eq!(bits, expected_bits);
 }
+
+#[test]
+fn test_bit_iterator_borrows_slice() {
+    let bytes = [0b11110000u8, 0b00000001u8];
+    let slice: &[u8] = &bytes;
+
+    let bits: Vec<bool> = BitIterator::from_slice(slice).collect();
+    assert_eq!(bits.len(), 8 * 2);
+    assert_eq!(bits[..4], [true; 4]);
+    assert_eq!(bits[4..15], [false; 11]);
+    assert!(bits[15]);
+
+    // The slice wasn't moved, so it can be iterated again:
+    assert_eq!(BitIterator::from_slice(slice).collect::<Vec<_>>(), bits);
+    assert_eq!(slice, [0b11110000, 0b00000001]);
+}
+
+#[test]
+fn test_bit_iterator_peek_matches_next() {
+    let mut buffer = BitBuffer::from(vec![0b10110001u8, 0b01000000u8]);
+    buffer.append(true);
+    buffer.append(false);
+    buffer.append(true);
+    let mut bit_iterator = BitIterator::from(buffer);
+
+    // Peeking twice doesn't advance, and the next bit is always the peeked one:
+    while let Some(peeked) = bit_iterator.peek() {
+        assert_eq!(bit_iterator.peek(), Some(peeked));
+        assert_eq!(bit_iterator.next(), Some(peeked));
+    }
+    assert_eq!(bit_iterator.next(), None);
+}
+
+#[test]
+fn test_bit_iterator_counts_consumed_bits() {
+    let mut buffer = BitBuffer::from(vec![0xffu8, 0x00u8]);
+    buffer.append(true);
+    buffer.append(true);
+    let mut bit_iterator = BitIterator::from(buffer);
+    assert_eq!(bit_iterator.bits_consumed(), 0);
+
+    // Across the byte boundary and into the incomplete trailing byte:
+    for consumed in 1..=8 * 2 + 2 {
+        bit_iterator.peek();
+        assert!(bit_iterator.next().is_some());
+        assert_eq!(bit_iterator.bits_consumed(), consumed);
+    }
+    // Running out of bits consumes nothing:
+    assert_eq!(bit_iterator.next(), None);
+    assert_eq!(bit_iterator.peek(), None);
+    assert_eq!(bit_iterator.bits_consumed(), 8 * 2 + 2);
+}
+
+#[test]
+fn test_leftover_with_count() {
+    let mut buffer = BitBuffer::from(vec![0b10011010u8]);
+    assert!(buffer.get_leftover_bits_with_count().is_none());
+
+    buffer.append(true);
+    buffer.append(true);
+    assert_eq!(buffer.get_leftover_bits_with_count(), Some((0b11000000, 2)));
+}
+
+#[test]
+fn test_extend_from_partial_last_byte() {
+    let mut buffer = BitBuffer::new();
+    buffer.append(true);
+    buffer.extend_from(&[0b01010101, 0b11011111], 3);
+
+    // 1 + 8 + 3 bits, the padding of the last byte is dropped:
+    assert_eq!(buffer.len(), 12);
+    assert_eq!(buffer.get_leftover_bits_with_count(), Some((0b11100000, 4)));
+    assert_eq!(
+        buffer.get_complete_bytes().collect::<Vec<u8>>(),
+        [0b10101010]
+    );
+}
+
+#[test]
+fn test_extend_from_empty() {
+    let mut buffer = BitBuffer::new();
+    buffer.extend_from(&[], 8);
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn test_many_appends() {
+    // Mimics a long compression: a few million bits, drained every few bytes
+    const BITS: usize = 4_000_000;
+    let mut buffer = BitBuffer::new();
+    let mut bytes = Vec::with_capacity(BITS / 8);
+    for i in 0..BITS {
+        buffer.append(i % 3 == 0);
+        if i % 64 == 0 {
+            bytes.extend(buffer.get_complete_bytes());
+        }
+    }
+    buffer.append_repeated(true, BITS);
+    assert_eq!(buffer.len() + 8 * bytes.len(), 2 * BITS);
+
+    bytes.extend(buffer.get_complete_bytes());
+    assert_eq!(bytes.len(), BITS / 4);
+    // The pattern of 1 every 3 bits repeats every 3 bytes:
+    assert_eq!(bytes[..3], [0b10010010, 0b01001001, 0b00100100]);
+    assert!(bytes[BITS / 8..].iter().all(|&byte| byte == u8::MAX));
+}
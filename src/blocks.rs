@@ -0,0 +1,671 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Block mode: the input is split into fixed-size blocks, which are compressed independently of
+//! each other on several threads, and can be decompressed in parallel too.
+//!
+//! Every block is coded with a freshly flushed model, so adaptive models forget what they learned
+//! at the end of every block. This trades compression ratio for speed: smaller blocks and more
+//! threads are faster, but compress worse.
+//!
+//! A block-mode stream starts with the usual prefix (header and dictionary hash), followed by a
+//! table of the blocks and then by the compressed blocks themselves. The table holds varints of
+//! the number of blocks, followed by the original length and the compressed length of each block.
+//! If the header asks for a checksum, it ends with the usual checksum trailer, computed over the
+//! whole original data.
+
+use crate::checksum::Crc32;
+use crate::compressor::Compressor;
+use crate::header::Header;
+use crate::models::Model;
+use crate::parser::Parser;
+use crate::report::CompressionReport;
+use crate::sim::Symbol;
+use crate::stream::{
+    check_model_precision, decompress_payload, decompress_streams, decompressed_last_byte,
+    handle_compression_error, prepare_model, write_output, write_stream_prefix,
+    write_stream_trailer, CodecOptions, OutputWriteError, PartialByteError, StreamLengthError,
+    StreamPrefix,
+};
+use crate::termination::TerminationStrategy;
+use crate::varint::{read_varint, write_varint, VarintError};
+use anyhow::Result;
+use log::{debug, info};
+use std::io::{BufWriter, Read, Write};
+use std::num::NonZeroUsize;
+use std::thread;
+use thiserror::Error;
+
+/// Errors that may occur when reading the table of blocks of a block-mode stream
+#[derive(Debug, Error, PartialEq)]
+pub enum BlockTableError {
+    #[error("Failed to read the table of blocks: {0}")]
+    Varint(#[from] VarintError),
+    #[error("The table of blocks holds {0} blocks, more than the stream could hold")]
+    TooManyBlocks(u64),
+    #[error("The compressed blocks end past the end of the stream, it may be truncated")]
+    Truncated,
+}
+
+/// How block mode splits and compresses the input
+#[derive(Debug, Clone, Copy)]
+pub struct BlockOptions {
+    /// Length in bytes of every block (except for the last one, which may be shorter)
+    pub block_size: NonZeroUsize,
+    /// The number of threads compressing blocks at the same time
+    pub threads: NonZeroUsize,
+}
+
+/// An entry of the table of blocks
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BlockEntry {
+    /// Length in bytes of the block's original data
+    original_len: u64,
+    /// Length in bytes of the block's compressed data
+    compressed_len: u64,
+}
+
+/// Encodes the table of blocks, see the module's documentation
+fn encode_table(entries: &[BlockEntry]) -> Vec<u8> {
+    let mut output = Vec::new();
+    write_varint(&mut output, entries.len() as u64);
+    for entry in entries {
+        write_varint(&mut output, entry.original_len);
+        write_varint(&mut output, entry.compressed_len);
+    }
+    output
+}
+
+/// Decodes the table of blocks at the start of _input_. Returns the table and its length in bytes.
+fn decode_table(input: &[u8]) -> Result<(Vec<BlockEntry>, usize), BlockTableError> {
+    let mut remaining = input;
+    let count = read_varint(&mut remaining)?;
+    // Every entry takes at least 2 bytes, which bounds the table's allocation by the input:
+    if count > (remaining.len() / 2) as u64 {
+        return Err(BlockTableError::TooManyBlocks(count));
+    }
+
+    let entries = (0..count)
+        .map(|_| {
+            Ok(BlockEntry {
+                original_len: read_varint(&mut remaining)?,
+                compressed_len: read_varint(&mut remaining)?,
+            })
+        })
+        .collect::<Result<Vec<_>, BlockTableError>>()?;
+    Ok((entries, input.len() - remaining.len()))
+}
+
+/// The options of _options_, with the blocks flag of their header set. Every block ends with an
+/// EOF (the table of blocks only records their lengths in bytes), so the header's termination
+/// strategy is set to `TerminationStrategy::EofSymbol`.
+fn with_blocks_flag<'a>(options: &CodecOptions<'a>) -> CodecOptions<'a> {
+    CodecOptions {
+        dictionary: options.dictionary,
+        trailing: options.trailing,
+        header: options.header.map(|header| Header {
+            blocks: true,
+            termination: TerminationStrategy::EofSymbol,
+            ..header
+        }),
+        input_len: options.input_len,
+    }
+}
+
+/// Applies _work_ to every item on up to _threads_ threads, and returns the results in the order
+/// of the items. Every thread creates its own state with _new_state_ (e.g. its own model), and
+/// passes it to _work_ for each of its items.
+fn run_parallel<T, U, S, N, G>(items: &[T], threads: NonZeroUsize, new_state: N, work: G) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    N: Fn() -> S + Sync,
+    G: Fn(&mut S, &T) -> U + Sync,
+{
+    let workers = threads.get().min(items.len());
+    let (new_state, work) = (&new_state, &work);
+    let mut results: Vec<(usize, U)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|worker| {
+                scope.spawn(move || {
+                    let mut state = new_state();
+                    (worker..items.len())
+                        .step_by(workers)
+                        .map(|index| (index, work(&mut state, &items[index])))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Compresses a single block with _model_, which is flushed (and primed with the dictionary)
+/// first. Only the leading _trailing_bits_ bits of the block's last byte are compressed, unless
+/// it's 0. Returns the compressed block and the number of symbols that were skipped.
+fn compress_block<M: Model + ?Sized>(
+    block: &[u8],
+    model: &mut M,
+    parser: &mut dyn Parser,
+    options: &CodecOptions,
+    trailing_bits: u8,
+) -> Result<(Vec<u8>, usize)> {
+    prepare_model(model, parser, options.dictionary)?;
+    check_model_precision(model, options.params())?;
+    // A block rarely compresses into more bits than it holds, so its length is a good size hint:
+    let mut compressor =
+        Compressor::with_params(model, options.params()).with_output_capacity(8 * block.len());
+
+    let symbols = match block.split_last() {
+        Some((&last, body)) if trailing_bits != 0 => {
+            let mut symbols: Vec<Symbol> =
+                body.iter().flat_map(|&b| parser.parse_byte(b)).collect();
+            symbols.extend(
+                parser
+                    .parse_partial_byte(last, trailing_bits)
+                    .ok_or(PartialByteError)?,
+            );
+            symbols.extend(parser.finish());
+            symbols
+        }
+        _ => parser.parse_all(block),
+    };
+    let (mut compressed, mut skipped_symbols) = (Vec::new(), 0);
+    for symbol in symbols {
+        match compressor.load_symbol(symbol) {
+            Ok(compressed_bytes) => compressed.extend(compressed_bytes),
+            Err(e) => {
+                handle_compression_error(e, options)?;
+                skipped_symbols += 1;
+            }
+        }
+    }
+    compressed.extend(compressor.finalize()?.0);
+    Ok((compressed, skipped_symbols))
+}
+
+/// Compresses everything _reader_ holds into _writer_ in block mode (see the module's
+/// documentation). Every thread builds its own model with _new_model_ and its own parser with
+/// _new_parser_, which must build the same model and parser every time. The whole input is read
+/// into memory before it's compressed, and the header of the options (if any) is written with its
+/// blocks flag set.
+pub fn compress_blocks<R, W, M, F, P, NP>(
+    mut reader: R,
+    writer: W,
+    new_model: F,
+    new_parser: NP,
+    options: &CodecOptions,
+    block_options: BlockOptions,
+) -> Result<CompressionReport>
+where
+    R: Read,
+    W: Write,
+    M: Model,
+    F: Fn() -> M + Sync,
+    P: Parser,
+    NP: Fn() -> P + Sync,
+{
+    let options = &with_blocks_flag(options);
+    let mut input = Vec::new();
+    reader.read_to_end(&mut input)?;
+    let blocks: Vec<&[u8]> = input.chunks(block_options.block_size.get()).collect();
+    info!(
+        "Blocks: Compressing {} blocks on up to {} threads",
+        blocks.len(),
+        block_options.threads
+    );
+
+    // Only the last block holds the last byte, whose bits may be partially meaningful:
+    let trailing_bits = options.header.map_or(0, |header| header.trailing_bits);
+    let last_block = blocks.len().saturating_sub(1);
+    let indexed: Vec<(usize, &[u8])> = blocks.into_iter().enumerate().collect();
+    let compressed = run_parallel(
+        &indexed,
+        block_options.threads,
+        || (new_model(), new_parser()),
+        |(model, parser), &(index, block)| {
+            let trailing_bits = if index == last_block {
+                trailing_bits
+            } else {
+                0
+            };
+            compress_block(block, model, parser, options, trailing_bits)
+        },
+    )
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
+
+    let entries: Vec<BlockEntry> = indexed
+        .iter()
+        .zip(&compressed)
+        .map(|((_, block), (payload, _))| BlockEntry {
+            original_len: block.len() as u64,
+            compressed_len: payload.len() as u64,
+        })
+        .collect();
+    let mut report = CompressionReport {
+        input_bytes: input.len(),
+        ..Default::default()
+    };
+    let mut handle = BufWriter::new(writer);
+    report.header_bytes += write_stream_prefix(
+        &mut handle,
+        options,
+        input.len() as u64,
+        0,
+        &encode_table(&entries),
+    )?;
+    for (payload, skipped_symbols) in compressed {
+        report.payload_bytes += write_output(&mut handle, payload)?;
+        report.skipped_symbols += skipped_symbols;
+    }
+    let mut checksum = Crc32::new();
+    if let Some((&last_byte, data)) = input.split_last() {
+        checksum.update(data);
+        checksum.update(&[decompressed_last_byte(last_byte, trailing_bits)]);
+    }
+    report.footer_bytes += write_stream_trailer(&mut handle, options, &checksum)?;
+    handle.flush().map_err(OutputWriteError::from)?;
+    Ok(report)
+}
+
+/// Decompresses the block-mode stream _reader_ holds into _writer_, which was compressed by
+/// `compress_blocks` with the same model, parser and dictionary. The blocks are decompressed on
+/// up to _threads_ threads, each building its own model with _new_model_ and its own parser with
+/// _new_parser_. The header of the options (if any) doesn't need to have its blocks flag set.
+pub fn decompress_blocks<R, W, M, F, P, NP>(
+    reader: R,
+    writer: W,
+    new_model: F,
+    new_parser: NP,
+    options: &CodecOptions,
+    threads: NonZeroUsize,
+) -> Result<()>
+where
+    R: Read,
+    W: Write,
+    M: Model,
+    F: Fn() -> M + Sync,
+    P: Parser,
+    NP: Fn() -> P + Sync,
+{
+    let options = &with_blocks_flag(options);
+    decompress_streams(reader, writer, options, |input, prefix, output| {
+        let (entries, table_len) = decode_table(&input[prefix.len..])?;
+        debug!("Blocks: Decompressing {} blocks", entries.len());
+
+        // Locate every block's compressed data:
+        let mut start = prefix.len + table_len;
+        let mut blocks = Vec::with_capacity(entries.len());
+        for (index, entry) in entries.iter().enumerate() {
+            let end = usize::try_from(entry.compressed_len)
+                .ok()
+                .and_then(|len| start.checked_add(len))
+                .filter(|&end| end <= input.len())
+                .ok_or(BlockTableError::Truncated)?;
+            // Only the last block holds the last byte, whose bits may be partially meaningful:
+            let block_prefix = StreamPrefix {
+                len: 0,
+                original_len: Some(entry.original_len),
+                trailing_bits: if index + 1 == entries.len() {
+                    prefix.trailing_bits
+                } else {
+                    0
+                },
+                checksum: false,
+                termination: TerminationStrategy::EofSymbol,
+            };
+            blocks.push((&input[start..end], block_prefix));
+            start = end;
+        }
+
+        let total_len: u64 = entries.iter().map(|entry| entry.original_len).sum();
+        if let Some(expected) = prefix.original_len.filter(|&len| len != total_len) {
+            return Err(StreamLengthError {
+                expected,
+                found: total_len as usize,
+            }
+            .into());
+        }
+
+        let new_state = || (new_model(), new_parser());
+        let decompressed = run_parallel(
+            &blocks,
+            threads,
+            new_state,
+            |(model, parser), (block, prefix)| {
+                let mut decompressed = Vec::new();
+                decompress_payload(block, &mut decompressed, model, parser, options, prefix)
+                    .map(|_| decompressed)
+            },
+        );
+        for block in decompressed {
+            write_output(output, block?)?;
+        }
+        Ok(start)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::IntegrityError;
+    use crate::compress_reader;
+    use crate::models::adaptive::AdaptiveModel;
+    use crate::models::ppm::{PpmModel, DEFAULT_PPM_ORDER};
+    use crate::parser::{BitParser, ByteParser};
+    use crate::sim::{BitSIM, DefaultSIM};
+    use crate::TrailingPolicy;
+
+    fn parser(bit_mode: bool) -> Box<dyn Parser> {
+        if bit_mode {
+            Box::new(BitParser)
+        } else {
+            Box::new(ByteParser)
+        }
+    }
+
+    fn new_model(bit_mode: bool) -> Box<dyn Model> {
+        if bit_mode {
+            Box::new(PpmModel::new(BitSIM, DEFAULT_PPM_ORDER))
+        } else {
+            Box::new(PpmModel::new(DefaultSIM, DEFAULT_PPM_ORDER))
+        }
+    }
+
+    fn block_options(block_size: usize, threads: usize) -> BlockOptions {
+        BlockOptions {
+            block_size: NonZeroUsize::new(block_size).unwrap(),
+            threads: NonZeroUsize::new(threads).unwrap(),
+        }
+    }
+
+    fn compress(
+        bit_mode: bool,
+        options: &CodecOptions,
+        block_options: BlockOptions,
+        input: &[u8],
+    ) -> Vec<u8> {
+        let mut compressed = Vec::new();
+        let new_model = || new_model(bit_mode);
+        let new_parser = || parser(bit_mode);
+        compress_blocks(
+            input,
+            &mut compressed,
+            new_model,
+            new_parser,
+            options,
+            block_options,
+        )
+        .unwrap();
+        compressed
+    }
+
+    fn decompress(
+        bit_mode: bool,
+        options: &CodecOptions,
+        threads: usize,
+        compressed: &[u8],
+    ) -> Result<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        let new_model = || new_model(bit_mode);
+        let new_parser = || parser(bit_mode);
+        let threads = NonZeroUsize::new(threads).unwrap();
+        decompress_blocks(
+            compressed,
+            &mut decompressed,
+            new_model,
+            new_parser,
+            options,
+            threads,
+        )?;
+        Ok(decompressed)
+    }
+
+    fn header_options(bit_mode: bool) -> CodecOptions<'static> {
+        CodecOptions {
+            header: Some(Header {
+                bit_mode,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_block_round_trip() {
+        let input = b"the quick brown fox jumps over the lazy dog. ".repeat(40);
+        for bit_mode in [false, true] {
+            let options = header_options(bit_mode);
+            for (block_size, threads) in [(1, 3), (64, 1), (100, 4), (1000, 2), (10000, 8)] {
+                let compressed = compress(
+                    bit_mode,
+                    &options,
+                    block_options(block_size, threads),
+                    &input,
+                );
+                let decompressed = decompress(bit_mode, &options, threads, &compressed).unwrap();
+                assert_eq!(decompressed, input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty_input_round_trip() {
+        let options = header_options(false);
+        let compressed = compress(false, &options, block_options(16, 2), &[]);
+        let decompressed = decompress(false, &options, 2, &compressed).unwrap();
+        assert!(decompressed.is_empty());
+    }
+
+    #[test]
+    fn test_threads_dont_change_output() {
+        let input = b"deterministic output regardless of threads ".repeat(30);
+        let options = header_options(false);
+        let single_threaded = compress(false, &options, block_options(128, 1), &input);
+        for threads in [2, 3, 16] {
+            let compressed = compress(false, &options, block_options(128, threads), &input);
+            assert_eq!(compressed, single_threaded);
+        }
+    }
+
+    #[test]
+    fn test_single_block_matches_stream_payload() {
+        let input = b"a single block is coded like a whole stream".repeat(5);
+        let options = CodecOptions::default();
+        let compressed = compress(false, &options, block_options(input.len(), 4), &input);
+
+        let mut stream = Vec::new();
+        let mut model = new_model(false);
+        compress_reader(
+            &input[..],
+            &mut stream,
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap();
+        // Without a header, the stream holds only the table of blocks and the payload:
+        assert!(compressed.ends_with(&stream));
+        let (entries, table_len) = decode_table(&compressed).unwrap();
+        assert_eq!(table_len + stream.len(), compressed.len());
+        assert_eq!(
+            entries,
+            [BlockEntry {
+                original_len: input.len() as u64,
+                compressed_len: stream.len() as u64
+            }]
+        );
+    }
+
+    #[test]
+    fn test_blocks_flag_is_set() {
+        let options = header_options(false);
+        let compressed = compress(false, &options, block_options(8, 2), b"flagged blocks");
+        let (header, _) = Header::decode(&compressed).unwrap();
+        assert!(header.blocks);
+        assert_eq!(header.original_len, 14);
+    }
+
+    #[test]
+    fn test_trailing_bits_round_trip() {
+        // 21 meaningful bits, the last 3 bits of the last byte are padding:
+        let input = [0b10110011, 0b01011100, 0b11111000];
+        let options = CodecOptions {
+            header: Some(Header {
+                bit_mode: true,
+                trailing_bits: 5,
+                checksum: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let compressed = compress(true, &options, block_options(1, 2), &input);
+        let decompressed = decompress(true, &header_options(true), 2, &compressed).unwrap();
+        assert_eq!(decompressed, input);
+    }
+
+    #[test]
+    fn test_checksum_covers_every_block() {
+        let options = CodecOptions {
+            header: Some(Header {
+                checksum: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        for input in [&b""[..], b"checksummed blocks", &b"abc".repeat(100)] {
+            let compressed = compress(false, &options, block_options(8, 3), input);
+            let decompressed = decompress(false, &options, 3, &compressed).unwrap();
+            assert_eq!(decompressed, input);
+
+            let mut corrupted = compressed.clone();
+            *corrupted.last_mut().unwrap() ^= 1 << 5;
+            let err = decompress(false, &options, 3, &corrupted).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref(),
+                Some(IntegrityError::ChecksumMismatch { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_multi_stream_round_trip() {
+        let options = CodecOptions {
+            trailing: TrailingPolicy::MultiStream,
+            ..header_options(false)
+        };
+        let mut compressed = compress(false, &options, block_options(5, 2), b"first stream, ");
+        compressed.extend(compress(
+            false,
+            &options,
+            block_options(3, 2),
+            b"second stream",
+        ));
+        let decompressed = decompress(false, &options, 2, &compressed).unwrap();
+        assert_eq!(decompressed, b"first stream, second stream");
+    }
+
+    #[test]
+    fn test_block_length_mismatch_detected() {
+        let options = header_options(false);
+        let compressed = compress(false, &options, block_options(4, 2), b"exact length");
+
+        // Replace the header with one claiming a different length:
+        let (header, header_len) = Header::decode(&compressed).unwrap();
+        let mut corrupted = Header {
+            original_len: header.original_len + 1,
+            ..header
+        }
+        .encode();
+        corrupted.extend_from_slice(&compressed[header_len..]);
+
+        let err = decompress(false, &options, 2, &corrupted).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StreamLengthError {
+                expected: 13,
+                found: 12
+            })
+        ));
+    }
+
+    #[test]
+    fn test_truncated_blocks_detected() {
+        let options = header_options(false);
+        let compressed = compress(false, &options, block_options(4, 2), b"cut short");
+        let err = decompress(false, &options, 2, &compressed[..compressed.len() - 1]).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&BlockTableError::Truncated));
+    }
+
+    #[test]
+    fn test_table_round_trip() {
+        let entries = [
+            BlockEntry {
+                original_len: 1 << 20,
+                compressed_len: 300,
+            },
+            BlockEntry {
+                original_len: 7,
+                compressed_len: 0,
+            },
+        ];
+        let encoded = encode_table(&entries);
+        assert_eq!(
+            decode_table(&encoded),
+            Ok((entries.to_vec(), encoded.len()))
+        );
+
+        // A count larger than the input could hold isn't allocated:
+        let mut huge = Vec::new();
+        write_varint(&mut huge, u64::MAX);
+        assert_eq!(
+            decode_table(&huge),
+            Err(BlockTableError::TooManyBlocks(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn test_adaptive_model_blocks() {
+        // Every block restarts the model, so more blocks compress worse:
+        let input = b"abababababababab".repeat(64);
+        let options = CodecOptions::default();
+        let compress_with = |block_size| {
+            let mut compressed = Vec::new();
+            compress_blocks(
+                &input[..],
+                &mut compressed,
+                || AdaptiveModel::new(DefaultSIM),
+                || ByteParser,
+                &options,
+                block_options(block_size, 4),
+            )
+            .unwrap();
+            compressed.len()
+        };
+        assert!(compress_with(input.len()) < compress_with(32));
+    }
+}
@@ -0,0 +1,199 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Integrity checks of compressed streams. A stream whose header has the checksum flag set ends
+//! with a trailer holding the CRC-32 of its original data, which decompression recomputes to
+//! detect corrupted streams.
+
+use std::io::Write;
+use thiserror::Error;
+
+/// Number of bytes the checksum trailer takes at the end of a compressed stream
+pub const CHECKSUM_BYTES: usize = 4;
+
+/// The reversed polynomial of the IEEE CRC-32 (used by zip, gzip and PNG)
+const CRC32_POLYNOMIAL: u32 = 0xedb88320;
+
+/// The CRC-32 of every byte value, so the checksum is updated a byte at a time
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ CRC32_POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+};
+
+/// Errors that may occur when verifying the checksum of a compressed stream
+#[derive(Debug, Error, PartialEq)]
+pub enum IntegrityError {
+    #[error(
+        "The compressed input is too short to hold the checksum of its data, it may be truncated"
+    )]
+    MissingChecksum,
+    #[error("The decompressed data doesn't match the stream's checksum (expected {expected:08x}, found {found:08x}), the stream is corrupted")]
+    ChecksumMismatch { expected: u32, found: u32 },
+}
+
+/// A rolling CRC-32 (IEEE) checksum, computed over the bytes it's updated with
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self { state: !0 }
+    }
+
+    /// Updates the checksum with the next _bytes_ of the data
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.state = bytes.iter().fold(self.state, |crc, &byte| {
+            CRC32_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+        });
+    }
+
+    /// The checksum of all bytes seen so far
+    pub fn value(&self) -> u32 {
+        !self.state
+    }
+
+    /// Checks the checksum against the trailer at the start of _trailer_bytes_, which holds the
+    /// checksum of the original data
+    pub fn verify(&self, trailer_bytes: &[u8]) -> Result<(), IntegrityError> {
+        let expected: [u8; CHECKSUM_BYTES] = trailer_bytes
+            .get(..CHECKSUM_BYTES)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(IntegrityError::MissingChecksum)?;
+        let (expected, found) = (u32::from_be_bytes(expected), self.value());
+        if expected == found {
+            Ok(())
+        } else {
+            Err(IntegrityError::ChecksumMismatch { expected, found })
+        }
+    }
+}
+
+/// Computes the CRC-32 of _bytes_
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.value()
+}
+
+/// A writer computing the checksum of every byte written through it
+pub struct ChecksumWriter<W: Write> {
+    inner: W,
+    crc: Crc32,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            crc: Crc32::new(),
+        }
+    }
+
+    /// Returns the checksum of the bytes written since the last call (or since the writer was
+    /// created), and starts a new checksum
+    pub fn take_checksum(&mut self) -> Crc32 {
+        std::mem::take(&mut self.crc)
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.crc.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_check_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414fa339
+        );
+    }
+
+    #[test]
+    fn test_rolling_update() {
+        let mut crc = Crc32::new();
+        for chunk in [&b"1234"[..], b"", b"56789"] {
+            crc.update(chunk);
+        }
+        assert_eq!(crc.value(), crc32(b"123456789"));
+    }
+
+    #[test]
+    fn test_verify() {
+        let mut crc = Crc32::new();
+        crc.update(b"data");
+        let trailer = crc32(b"data").to_be_bytes();
+        assert_eq!(crc.verify(&trailer), Ok(()));
+        assert_eq!(
+            crc.verify(&crc32(b"date").to_be_bytes()),
+            Err(IntegrityError::ChecksumMismatch {
+                expected: crc32(b"date"),
+                found: crc32(b"data")
+            })
+        );
+        assert_eq!(
+            crc.verify(&trailer[..2]),
+            Err(IntegrityError::MissingChecksum)
+        );
+    }
+
+    #[test]
+    fn test_writer_checksums_every_stream() {
+        let mut writer = ChecksumWriter::new(Vec::new());
+        writer.write_all(b"first").unwrap();
+        assert_eq!(writer.take_checksum().value(), crc32(b"first"));
+        writer.write_all(b"second").unwrap();
+        assert_eq!(writer.take_checksum().value(), crc32(b"second"));
+        assert_eq!(writer.inner, b"firstsecond");
+    }
+}
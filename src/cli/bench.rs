@@ -0,0 +1,287 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::config::CodecConfig;
+use super::model_choice::{BuiltinModel, UserModel};
+use super::registry::{model_registry, RegisteredModel};
+use super::stats::CodecStats;
+use super::{decompress_with_model, select_model, BenchArgs, CodecDirection};
+use anyhow::{Context, Result};
+use ppm_cli::frequencies::Frequency;
+use ppm_cli::parser::{BitParser, ByteParser, Parser};
+use ppm_cli::two_pass::compress_two_pass;
+use ppm_cli::{compress_reader, CodecOptions};
+use std::fmt::{Display, Formatter};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// The results of benchmarking a single model on the input
+#[derive(Debug)]
+pub struct BenchResult {
+    /// The name of the benchmarked model
+    name: String,
+    /// Statistics of compressing the input with the model
+    compression: CodecStats,
+    /// Wall-clock time decompressing the compressed input took
+    decompression_time: Duration,
+}
+
+impl BenchResult {
+    /// The header of the table the results are printed in, aligned with the results' columns
+    pub const TABLE_HEADER: &str =
+        "model                   compressed      ratio   compress time   decompress time";
+}
+
+impl Display for BenchResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let ratio = match self.compression.ratio_percent() {
+            Some(ratio) => format!("{ratio:.2}%"),
+            None => "-".to_string(),
+        };
+        write!(
+            f,
+            "{:<20} {:>13} {:>10} {:>14.3}s {:>16.3}s",
+            self.name,
+            self.compression.output_bytes,
+            ratio,
+            self.compression.elapsed.as_secs_f64(),
+            self.decompression_time.as_secs_f64()
+        )
+    }
+}
+
+/// A model to benchmark: a registered model, or a custom model loaded from a JSON file
+#[derive(Clone, Copy)]
+enum BenchModel<'a> {
+    Builtin(&'a RegisteredModel),
+    Custom(&'a Path),
+}
+
+impl Display for BenchModel<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BenchModel::Builtin(model) => write!(f, "the {model} model"),
+            BenchModel::Custom(path) => write!(f, "the custom model {}", path.display()),
+        }
+    }
+}
+
+/// Compresses _input_ with _model_ and decompresses the result, timing both. Returns the results
+/// along with the compressed data.
+fn bench_model(
+    input: &[u8],
+    model: BenchModel,
+    args: &BenchArgs,
+) -> Result<(BenchResult, Vec<u8>)> {
+    let (name, config, mut user_model) = match model {
+        BenchModel::Builtin(model) => {
+            let mut config = CodecConfig::defaults(model.clone(), args.bit_mode);
+            if let Some(model_order) = args.model_order {
+                config.model_order = model_order;
+            }
+            (model.to_string(), config, None)
+        }
+        BenchModel::Custom(path) => {
+            let user_model = UserModel::from_file(path, Frequency::zero())?;
+            // A custom model decides the mode it compresses in:
+            let config =
+                CodecConfig::defaults(BuiltinModel::Uniform.into(), user_model.is_bit_model());
+            (user_model.name().to_string(), config, Some(user_model))
+        }
+    };
+    let mut parser: Box<dyn Parser> = if config.bit_mode {
+        Box::new(BitParser)
+    } else {
+        Box::new(ByteParser)
+    };
+    let options = CodecOptions {
+        header: Some(config.header(user_model.is_some())),
+        ..Default::default()
+    };
+
+    let mut compressed = Vec::new();
+    let start = Instant::now();
+    let report = match select_model(&config, user_model.as_mut()) {
+        Some(mut model) => {
+            compress_reader(input, &mut compressed, &mut model, &mut parser, &options)
+        }
+        None => {
+            let sim = config.sim();
+            compress_two_pass(
+                Cursor::new(input),
+                &mut compressed,
+                &mut parser,
+                sim,
+                &options,
+            )
+        }
+    }?;
+    let compression_time = start.elapsed();
+
+    // Compression adapted the custom model, so decompression starts from a new copy of it:
+    if let BenchModel::Custom(path) = model {
+        user_model = Some(UserModel::from_file(path, Frequency::zero())?);
+    }
+    let start = Instant::now();
+    decompress_with_model(
+        &compressed[..],
+        std::io::sink(),
+        &config,
+        user_model.as_mut(),
+        &mut *parser,
+        &options,
+        None,
+    )?;
+    let result = BenchResult {
+        name,
+        compression: CodecStats {
+            direction: CodecDirection::Compress,
+            input_bytes: report.input_bytes as u64,
+            output_bytes: report.output_bytes() as u64,
+            elapsed: compression_time,
+            skipped_symbols: report.skipped_symbols,
+        },
+        decompression_time: start.elapsed(),
+    };
+    Ok((result, compressed))
+}
+
+/// The path a kept compressed output of the model _name_ is written to: next to the input, named
+/// after it and the model (characters of custom models' names that may not fit in a file name are
+/// replaced)
+fn kept_output_path(input: &Path, name: &str) -> PathBuf {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let mut file_name = input.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{name}.ppm"));
+    input.with_file_name(file_name)
+}
+
+/// Benchmarks every registered model and the given custom models on _input_. Returns the results
+/// sorted from the best compression to the worst (ties are broken by compression time).<br>
+/// If _keep_ is given, it is called with the name of each model and its compressed output.
+pub fn bench_models<K>(input: &[u8], args: &BenchArgs, mut keep: K) -> Result<Vec<BenchResult>>
+where
+    K: FnMut(&str, &[u8]) -> Result<()>,
+{
+    let models = model_registry()
+        .models()
+        .iter()
+        .map(BenchModel::Builtin)
+        .chain(
+            args.custom_models
+                .iter()
+                .map(|path| BenchModel::Custom(path)),
+        );
+    let mut results = Vec::new();
+    for model in models {
+        let (result, compressed) = bench_model(input, model, args)
+            .with_context(|| format!("Failed to benchmark {model}"))?;
+        keep(&result.name, &compressed)?;
+        results.push(result);
+    }
+    results.sort_by(|a, b| {
+        let key =
+            |result: &BenchResult| (result.compression.output_bytes, result.compression.elapsed);
+        key(a).cmp(&key(b))
+    });
+    Ok(results)
+}
+
+/// Runs the bench command: benchmarks the models on the input file and prints a table of the
+/// results to stdout
+pub fn run_bench(args: &BenchArgs) -> Result<()> {
+    let input = std::fs::read(&args.file)
+        .with_context(|| format!("Failed to read the input file {}", args.file.display()))?;
+    let keep = |name: &str, compressed: &[u8]| -> Result<()> {
+        if args.keep {
+            let path = kept_output_path(&args.file, name);
+            std::fs::write(&path, compressed)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        Ok(())
+    };
+    let results = bench_models(&input, args, keep)?;
+    println!("{}", BenchResult::TABLE_HEADER);
+    for result in results {
+        println!("{result}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Cli, Commands};
+    use super::*;
+    use clap::Parser as _;
+
+    fn bench_args(args: &[&str]) -> BenchArgs {
+        let cli = Cli::try_parse_from(["ppm-cli", "bench"].iter().chain(args)).unwrap();
+        match cli.commands {
+            Some(Commands::Bench(args)) => args,
+            _ => panic!("Expected the bench command"),
+        }
+    }
+
+    #[test]
+    fn test_kept_output_path() {
+        let input = Path::new("data/input.txt");
+        assert_eq!(
+            kept_output_path(input, "two-pass"),
+            Path::new("data/input.txt.two-pass.ppm")
+        );
+        assert_eq!(
+            kept_output_path(input, "my model/v2"),
+            Path::new("data/input.txt.my_model_v2.ppm")
+        );
+    }
+
+    #[test]
+    fn test_bench_sorts_every_model_by_size() {
+        let input = b"abracadabra, abracadabra, abracadabra!".repeat(20);
+        let mut kept = Vec::new();
+        let results = bench_models(&input, &bench_args(&["input"]), |name, compressed| {
+            kept.push((name.to_string(), compressed.len() as u64));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(results.len(), model_registry().models().len());
+        for model in model_registry().models() {
+            assert!(results
+                .iter()
+                .any(|result| result.name == model.to_string()));
+        }
+        assert!(results
+            .windows(2)
+            .all(|pair| { pair[0].compression.output_bytes <= pair[1].compression.output_bytes }));
+        // Every model's output was handed over for keeping, with the size it was reported with:
+        for result in &results {
+            let size = (result.name.clone(), result.compression.output_bytes);
+            assert!(kept.contains(&size));
+        }
+    }
+}
@@ -0,0 +1,860 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::level::{LevelPreset, MAX_LEVEL};
+use super::model_choice::{mode_sim, Adaptation, BuiltinModel, ParserChoice};
+use super::registry::{model_registry, parse_model_name, ModelSettings, RegisteredModel};
+use super::CodecArgs;
+use ppm_cli::frequencies::Frequency;
+use ppm_cli::header::Header;
+use ppm_cli::models::adaptive::{DEFAULT_FREQUENCY_CAP, DEFAULT_INCREMENT};
+use ppm_cli::models::ppm::EscapeMethod;
+use ppm_cli::models::Model;
+use ppm_cli::number_types::{CodecParams, CodecParamsError, FREQUENCY_BITS, INTERVAL_BITS};
+use ppm_cli::parser::{BitParser, ByteParser, MtfParser, Parser, RleParser};
+use ppm_cli::sim::{Alphabet, SymbolIndexMapping};
+use std::num::{NonZeroU64, NonZeroU8};
+use thiserror::Error;
+
+/// Environment variable supplying a default for the `--model` option
+pub const MODEL_ENV_VAR: &str = "PPM_MODEL";
+
+/// Environment variable supplying a default for the `--bit-mode` option. Its value must be either
+/// "bit" or "byte"
+pub const MODE_ENV_VAR: &str = "PPM_MODE";
+
+/// Environment variable supplying a default for the `--model-order` option
+pub const MODEL_ORDER_ENV_VAR: &str = "PPM_MODEL_ORDER";
+
+/// The model used if neither the CLI nor the environment specify one
+pub const DEFAULT_MODEL: BuiltinModel = BuiltinModel::Uniform;
+
+/// The context order used if neither the CLI nor the environment specify one
+pub const DEFAULT_MODEL_ORDER: u8 = 2;
+
+/// The largest context order a model can be given
+pub const MAX_MODEL_ORDER: u8 = 16;
+
+/// The rescale threshold of adaptive models must be smaller than this (the largest frequency)
+pub const MAX_RESCALE_THRESHOLD: u64 = (1 << FREQUENCY_BITS) - 1;
+
+/// The increment of adaptive models must be smaller than this (the largest frequency)
+pub const MAX_INCREMENT: u64 = (1 << FREQUENCY_BITS) - 1;
+
+/// The smoothing added to the frequencies of custom models must be smaller than this (the largest
+/// frequency)
+pub const MAX_SMOOTHING: u64 = (1 << FREQUENCY_BITS) - 1;
+
+/// The model id written in the header of streams compressed by a user-defined model
+pub const CUSTOM_MODEL_ID: u8 = u8::MAX;
+
+/// Errors caused by invalid values in the configuration's environment variables, or by arguments
+/// which can't be used together
+#[derive(Debug, Error, PartialEq)]
+pub enum EnvConfigError {
+    #[error("{MODEL_ENV_VAR} holds an unknown model \"{value}\" (possible values: {possible})")]
+    UnknownModel { value: String, possible: String },
+    #[error("{MODE_ENV_VAR} must be either \"bit\" or \"byte\", but it holds \"{0}\"")]
+    UnknownMode(String),
+    #[error("{MODEL_ORDER_ENV_VAR} must be a number between 0 and {MAX_MODEL_ORDER}, but it holds \"{0}\"")]
+    InvalidModelOrder(String),
+    #[error("Invalid coding precisions: {0}")]
+    InvalidCodecParams(#[from] CodecParamsError),
+}
+
+/// Errors caused by a header of a compressed stream which the configuration can't follow
+#[derive(Debug, Error, PartialEq)]
+pub enum HeaderConfigError {
+    #[error("The stream was compressed by an unknown model (id {0}), it may have been compressed by a newer version")]
+    UnknownModel(u8),
+    #[error("The stream was compressed by a custom model, pass it with --custom-model")]
+    MissingCustomModel,
+    #[error("The stream was compressed by the builtin model \"{0}\", not by a custom model")]
+    UnexpectedCustomModel(RegisteredModel),
+    #[error("The stream was compressed with an unknown level ({0}, the maximum is {MAX_LEVEL}), it may have been compressed by a newer version")]
+    UnknownLevel(u8),
+    #[error("The stream was compressed with an invalid model order ({0}, the maximum is {MAX_MODEL_ORDER})")]
+    InvalidModelOrder(u8),
+    #[error("The stream was compressed with an invalid rescale threshold ({0}, it must be between 1 and {max})", max = MAX_RESCALE_THRESHOLD - 1)]
+    InvalidRescaleThreshold(u64),
+    #[error("The stream was compressed with an invalid increment ({0}, it must be between 1 and {max})", max = MAX_INCREMENT - 1)]
+    InvalidIncrement(u64),
+}
+
+/// The final configuration of the codec, after merging the CLI arguments, the environment
+/// variables and the built-in defaults.
+#[derive(Debug, PartialEq)]
+pub struct CodecConfig {
+    /// If the input is read bit-by-bit instead of byte-by-byte
+    pub bit_mode: bool,
+    /// The registered model to use (ignored if a custom model was given)
+    pub model: RegisteredModel,
+    /// The context order of the model (ignored by models without contexts)
+    pub model_order: u8,
+    /// The compression level whose preset chose the settings, if one was given
+    pub level: Option<u8>,
+    /// How the PPM model estimates escape probabilities (ignored by other models)
+    pub escape_method: EscapeMethod,
+    /// The frequency at which adaptive models rescale their tables
+    pub rescale_at: Frequency,
+    /// The amount adaptive order-0 models add to a compressed symbol's frequency
+    pub increment: Frequency,
+    /// The number of updates after which adaptive order-0 models halve their tables, if they age
+    /// them
+    pub half_life: Option<NonZeroU64>,
+    /// The precisions of the coding
+    pub params: CodecParams,
+    /// The alphabet of the input's bytes (ignored in bit-mode)
+    pub alphabet: Alphabet,
+    /// The parser of byte-mode: `Byte`, `Rle` or `Mtf` (ignored in bit-mode)
+    pub parser: ParserChoice,
+}
+
+impl CodecConfig {
+    /// Resolves the codec's configuration. Each setting is taken from the CLI arguments if it was
+    /// given there, otherwise from its environment variable, and otherwise from its built-in
+    /// default.
+    ///
+    /// ## Parameters:
+    /// * args - The CLI arguments of the codec.
+    /// * env - Returns the value of an environment variable, or None if it isn't set.
+    ///
+    /// ## Possible Failures:
+    /// If an environment variable that is needed (i.e: not overridden by the CLI) holds an invalid
+    /// value, or the interval and frequency bits can't be used together, an `EnvConfigError` is
+    /// returned.
+    pub fn resolve<E>(args: &CodecArgs, env: E) -> Result<Self, EnvConfigError>
+    where
+        E: Fn(&str) -> Option<String>,
+    {
+        // `--bit-mode` can only turn bit-mode on, so its absence (and the parser's) defers to the
+        // environment:
+        let bit_mode = match args.parser {
+            Some(parser) => parser == ParserChoice::Bit,
+            None if args.bit_mode => true,
+            None => env(MODE_ENV_VAR)
+                .map(|mode| match mode.trim().to_lowercase().as_str() {
+                    "bit" => Ok(true),
+                    "byte" => Ok(false),
+                    _ => Err(EnvConfigError::UnknownMode(mode)),
+                })
+                .transpose()?
+                .unwrap_or(false),
+        };
+
+        // A level's preset replaces the environment variables of the settings it chooses:
+        let preset = args.level.and_then(LevelPreset::of);
+        let model = match (&args.model, preset) {
+            (Some(model), _) => model.clone(),
+            (None, _) if args.two_pass => BuiltinModel::TwoPass.into(),
+            (None, Some(preset)) => preset.model.clone().into(),
+            (None, None) => env(MODEL_ENV_VAR)
+                .map(|model| {
+                    parse_model_name(&model).map_err(|err| EnvConfigError::UnknownModel {
+                        value: model.clone(),
+                        possible: err.possible,
+                    })
+                })
+                .transpose()?
+                .unwrap_or_else(|| DEFAULT_MODEL.into()),
+        };
+
+        let model_order = match (args.model_order, preset) {
+            (Some(order), _) => order,
+            (None, Some(preset)) => preset.model_order,
+            (None, None) => env(MODEL_ORDER_ENV_VAR)
+                .map(|order| match order.trim().parse::<u8>() {
+                    Ok(parsed) if parsed <= MAX_MODEL_ORDER => Ok(parsed),
+                    _ => Err(EnvConfigError::InvalidModelOrder(order)),
+                })
+                .transpose()?
+                .unwrap_or(DEFAULT_MODEL_ORDER),
+        };
+
+        // Clap makes sure the threshold is valid:
+        let rescale_at = args
+            .rescale_at
+            .or(preset.map(|preset| preset.rescale_at))
+            .unwrap_or(DEFAULT_FREQUENCY_CAP);
+        let rescale_at = Frequency::new(rescale_at)
+            .expect("Rescale threshold is smaller than the largest frequency");
+        let increment = Frequency::new(args.increment.unwrap_or(DEFAULT_INCREMENT))
+            .expect("Increment is smaller than the largest frequency");
+
+        // Unless given, the frequency bits are as many as the interval bits allow:
+        let interval_bits = args.interval_bits.unwrap_or(INTERVAL_BITS);
+        let frequency_bits = args
+            .frequency_bits
+            .unwrap_or(FREQUENCY_BITS.min(interval_bits.saturating_sub(2)));
+        let params = CodecParams::new(interval_bits, frequency_bits)?;
+
+        Ok(Self {
+            bit_mode,
+            model,
+            model_order,
+            level: args.level,
+            escape_method: args
+                .escape_method
+                .or(preset.map(|preset| preset.escape_method))
+                .unwrap_or_default(),
+            rescale_at,
+            increment,
+            half_life: args.half_life.and_then(NonZeroU64::new),
+            params,
+            alphabet: args.alphabet.unwrap_or_default(),
+            // Bit-mode has its own parser, and the byte parser is used in byte-mode:
+            parser: match args.parser {
+                Some(ParserChoice::Bit) | None => ParserChoice::Byte,
+                Some(parser) => parser,
+            },
+        })
+    }
+
+    /// The configuration of _model_ in the given mode, with the built-in defaults of every other
+    /// setting (ignoring the CLI arguments and the environment)
+    pub fn defaults(model: RegisteredModel, bit_mode: bool) -> Self {
+        Self {
+            bit_mode,
+            model,
+            model_order: DEFAULT_MODEL_ORDER,
+            level: None,
+            escape_method: EscapeMethod::default(),
+            rescale_at: Frequency::new(DEFAULT_FREQUENCY_CAP)
+                .expect("Default frequency cap is valid"),
+            increment: Frequency::new(DEFAULT_INCREMENT).expect("Default increment is valid"),
+            half_life: None,
+            params: CodecParams::default(),
+            alphabet: Alphabet::default(),
+            parser: ParserChoice::Byte,
+        }
+    }
+
+    /// Forms the header describing streams compressed with this configuration. _custom_model_ is
+    /// true if a user-defined model is used instead of the builtin one. The streams end with a
+    /// checksum of their data.
+    pub fn header(&self, custom_model: bool) -> Header {
+        Header {
+            bit_mode: self.bit_mode,
+            model_id: if custom_model {
+                CUSTOM_MODEL_ID
+            } else {
+                self.model.id()
+            },
+            model_order: self.model_order,
+            level: self.level.unwrap_or(0),
+            escape_method: self.escape_method,
+            rescale_at: *self.rescale_at,
+            increment: *self.increment,
+            half_life: self.half_life.map_or(0, NonZeroU64::get),
+            params: self.params,
+            checksum: true,
+            alphabet: if self.bit_mode {
+                Alphabet::default()
+            } else {
+                self.alphabet
+            },
+            rle: !self.bit_mode && self.parser == ParserChoice::Rle,
+            mtf: !self.bit_mode && self.parser == ParserChoice::Mtf,
+            ..Default::default()
+        }
+    }
+
+    /// Builds the configured model, or None for the two-pass model (which is built from the input
+    /// itself)
+    pub fn get_model(&self) -> Option<Box<dyn Model>> {
+        self.model.build(&ModelSettings {
+            sim: self.sim(),
+            model_order: self.model_order,
+            escape_method: self.escape_method,
+            adaptation: Adaptation {
+                rescale_at: self.rescale_at,
+                increment: self.increment,
+                half_life: self.half_life,
+            },
+            params: self.params,
+        })
+    }
+
+    /// The SIM of the configured mode and alphabet
+    pub fn sim(&self) -> &'static dyn SymbolIndexMapping {
+        if self.bit_mode {
+            mode_sim(true)
+        } else {
+            self.alphabet.sim()
+        }
+    }
+
+    /// The parser of the configured mode
+    pub fn parser(&self) -> Box<dyn Parser> {
+        match (self.bit_mode, self.parser) {
+            (true, _) | (false, ParserChoice::Bit) => Box::new(BitParser),
+            (false, ParserChoice::Byte) => Box::new(ByteParser),
+            (false, ParserChoice::Rle) => Box::new(RleParser::new()),
+            (false, ParserChoice::Mtf) => Box::new(MtfParser::new()),
+        }
+    }
+
+    /// Follows the header of a compressed stream, so its settings don't have to be repeated when
+    /// decompressing. The header's settings override the resolved ones.
+    ///
+    /// ## Possible Failures:
+    /// If the header's model doesn't exist, or doesn't match _custom_model_ (true if the user gave
+    /// a custom model), a `HeaderConfigError` is returned.
+    pub fn apply_header(
+        &mut self,
+        header: &Header,
+        custom_model: bool,
+    ) -> Result<(), HeaderConfigError> {
+        if header.model_order > MAX_MODEL_ORDER {
+            return Err(HeaderConfigError::InvalidModelOrder(header.model_order));
+        }
+        if header.level > MAX_LEVEL {
+            return Err(HeaderConfigError::UnknownLevel(header.level));
+        }
+        let rescale_at = Frequency::new(header.rescale_at)
+            .ok()
+            .filter(|&threshold| threshold != Frequency::zero() && threshold != Frequency::max())
+            .ok_or(HeaderConfigError::InvalidRescaleThreshold(
+                header.rescale_at,
+            ))?;
+        match (header.model_id, custom_model) {
+            (CUSTOM_MODEL_ID, true) => {}
+            (CUSTOM_MODEL_ID, false) => return Err(HeaderConfigError::MissingCustomModel),
+            (id, custom_model) => {
+                let model = model_registry()
+                    .by_id(id)
+                    .ok_or(HeaderConfigError::UnknownModel(id))?;
+                if custom_model {
+                    return Err(HeaderConfigError::UnexpectedCustomModel(model));
+                }
+                self.model = model;
+            }
+        }
+        let increment = Frequency::new(header.increment)
+            .ok()
+            .filter(|&increment| increment != Frequency::zero() && increment != Frequency::max())
+            .ok_or(HeaderConfigError::InvalidIncrement(header.increment))?;
+        self.bit_mode = header.bit_mode;
+        self.model_order = header.model_order;
+        self.level = NonZeroU8::new(header.level).map(NonZeroU8::get);
+        self.escape_method = header.escape_method;
+        self.rescale_at = rescale_at;
+        self.increment = increment;
+        self.half_life = NonZeroU64::new(header.half_life);
+        self.params = header.params;
+        self.alphabet = header.alphabet;
+        self.parser = match (header.rle, header.mtf) {
+            (true, _) => ParserChoice::Rle,
+            (false, true) => ParserChoice::Mtf,
+            (false, false) => ParserChoice::Byte,
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Commands};
+    use clap::Parser;
+    use ppm_cli::header::peek_header;
+    use ppm_cli::parser::ByteParser;
+    use ppm_cli::{compress_reader, decompress_reader, CodecOptions};
+    use std::collections::HashMap;
+
+    /// Parses the given compress arguments and resolves them against the given environment
+    fn resolve(cli_args: &[&str], env: &[(&str, &str)]) -> Result<CodecConfig, EnvConfigError> {
+        let cli = Cli::try_parse_from(["ppm-cli", "compress"].iter().chain(cli_args)).unwrap();
+        let Some(Commands::Compress(args)) = cli.commands else {
+            unreachable!("Only the compress command is parsed")
+        };
+
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        CodecConfig::resolve(&args, |key| env.get(key).cloned())
+    }
+
+    fn default_rescale_at() -> Frequency {
+        Frequency::new(DEFAULT_FREQUENCY_CAP).unwrap()
+    }
+
+    #[test]
+    fn test_builtin_defaults() {
+        let config = resolve(&[], &[]).unwrap();
+        assert_eq!(
+            config,
+            CodecConfig {
+                bit_mode: false,
+                model: DEFAULT_MODEL.into(),
+                model_order: DEFAULT_MODEL_ORDER,
+                level: None,
+                escape_method: EscapeMethod::PpmC,
+                rescale_at: default_rescale_at(),
+                increment: Frequency::one(),
+                half_life: None,
+                params: CodecParams::default(),
+                alphabet: Alphabet::Bytes,
+                parser: ParserChoice::Byte,
+            }
+        );
+    }
+
+    #[test]
+    fn test_env_supplies_defaults() {
+        let env = [
+            (MODEL_ENV_VAR, "order0"),
+            (MODE_ENV_VAR, "bit"),
+            (MODEL_ORDER_ENV_VAR, "3"),
+        ];
+        let config = resolve(&[], &env).unwrap();
+        assert_eq!(
+            config,
+            CodecConfig {
+                bit_mode: true,
+                model: BuiltinModel::Order0.into(),
+                model_order: 3,
+                level: None,
+                escape_method: EscapeMethod::PpmC,
+                rescale_at: default_rescale_at(),
+                increment: Frequency::one(),
+                half_life: None,
+                params: CodecParams::default(),
+                alphabet: Alphabet::Bytes,
+                parser: ParserChoice::Byte,
+            }
+        );
+    }
+
+    #[test]
+    fn test_cli_overrides_env() {
+        let env = [
+            (MODEL_ENV_VAR, "order0"),
+            (MODE_ENV_VAR, "byte"),
+            (MODEL_ORDER_ENV_VAR, "3"),
+        ];
+        let cli_args = ["--model", "uniform", "--bit-mode", "--model-order", "5"];
+        let config = resolve(&cli_args, &env).unwrap();
+        assert_eq!(
+            config,
+            CodecConfig {
+                bit_mode: true,
+                model: BuiltinModel::Uniform.into(),
+                model_order: 5,
+                level: None,
+                escape_method: EscapeMethod::PpmC,
+                rescale_at: default_rescale_at(),
+                increment: Frequency::one(),
+                half_life: None,
+                params: CodecParams::default(),
+                alphabet: Alphabet::Bytes,
+                parser: ParserChoice::Byte,
+            }
+        );
+    }
+
+    #[test]
+    fn test_two_pass_flag() {
+        let config = resolve(&["--two-pass"], &[(MODEL_ENV_VAR, "order0")]).unwrap();
+        assert_eq!(config.model, BuiltinModel::TwoPass.into());
+
+        // It's a model of its own, so it can't be given along with another one:
+        let cli = Cli::try_parse_from(["ppm-cli", "compress", "--two-pass", "--model", "ppm"]);
+        assert!(cli.is_err());
+    }
+
+    #[test]
+    fn test_invalid_env_values() {
+        assert!(matches!(
+            resolve(&[], &[(MODEL_ENV_VAR, "gzip")]),
+            Err(EnvConfigError::UnknownModel { .. })
+        ));
+        assert_eq!(
+            resolve(&[], &[(MODE_ENV_VAR, "nibble")]),
+            Err(EnvConfigError::UnknownMode("nibble".to_string()))
+        );
+        assert_eq!(
+            resolve(&[], &[(MODEL_ORDER_ENV_VAR, "17")]),
+            Err(EnvConfigError::InvalidModelOrder("17".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let cli_args = [
+            "--model",
+            "ppm",
+            "--bit-mode",
+            "--model-order",
+            "4",
+            "--rescale-at",
+            "300",
+            "--escape-method",
+            "ppmd",
+        ];
+        let config = resolve(&cli_args, &[]).unwrap();
+        let header = config.header(false);
+        assert_eq!(header.rescale_at, 300);
+        assert_eq!(header.escape_method, EscapeMethod::PpmD);
+
+        // Decompression doesn't need to repeat the settings:
+        let mut decompression_config = resolve(&[], &[]).unwrap();
+        decompression_config.apply_header(&header, false).unwrap();
+        assert_eq!(decompression_config, config);
+    }
+
+    #[test]
+    fn test_header_alphabet() {
+        let config = resolve(&["--alphabet", "dna", "--model", "order0"], &[]).unwrap();
+        let header = config.header(false);
+        assert_eq!(header.alphabet, Alphabet::Dna);
+        assert_eq!(config.sim().supported_symbols_count(), 6);
+        assert_eq!(config.get_model().unwrap().alphabet_size(), 6);
+
+        let mut decompression_config = resolve(&[], &[]).unwrap();
+        decompression_config.apply_header(&header, false).unwrap();
+        assert_eq!(decompression_config, config);
+
+        // Bit-mode has its own alphabet:
+        let bit_config = resolve(&[], &[(MODE_ENV_VAR, "bit")]).unwrap();
+        assert_eq!(bit_config.header(false).alphabet, Alphabet::Bytes);
+        let cli = Cli::try_parse_from(["ppm-cli", "compress", "--alphabet", "dna", "-b"]);
+        assert!(cli.is_err());
+    }
+
+    #[test]
+    fn test_header_parser() {
+        for (parser, rle, mtf) in [("rle", true, false), ("mtf", false, true)] {
+            let config = resolve(&["--parser", parser], &[(MODE_ENV_VAR, "bit")]).unwrap();
+            // The parser overrides the environment's mode:
+            assert!(!config.bit_mode);
+            let header = config.header(false);
+            assert_eq!((header.rle, header.mtf), (rle, mtf));
+
+            let mut decompression_config = resolve(&[], &[]).unwrap();
+            decompression_config.apply_header(&header, false).unwrap();
+            assert_eq!(decompression_config, config);
+        }
+
+        assert!(resolve(&["--parser", "bit"], &[]).unwrap().bit_mode);
+        let cli = Cli::try_parse_from(["ppm-cli", "compress", "--parser", "rle", "-b"]);
+        assert!(cli.is_err());
+    }
+
+    #[test]
+    fn test_header_custom_model() {
+        let mut config = resolve(&[], &[]).unwrap();
+        let custom_header = config.header(true);
+        assert_eq!(custom_header.model_id, CUSTOM_MODEL_ID);
+
+        assert_eq!(config.apply_header(&custom_header, true), Ok(()));
+        assert_eq!(
+            config.apply_header(&custom_header, false),
+            Err(HeaderConfigError::MissingCustomModel)
+        );
+        assert_eq!(
+            config.apply_header(&config.header(false), true),
+            Err(HeaderConfigError::UnexpectedCustomModel(
+                DEFAULT_MODEL.into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_header_unknown_model() {
+        let mut config = resolve(&[], &[]).unwrap();
+        let header = Header {
+            model_id: 200,
+            rescale_at: DEFAULT_FREQUENCY_CAP,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.apply_header(&header, false),
+            Err(HeaderConfigError::UnknownModel(200))
+        );
+    }
+
+    #[test]
+    fn test_header_invalid_rescale_threshold() {
+        let mut config = resolve(&[], &[]).unwrap();
+        for rescale_at in [0, MAX_RESCALE_THRESHOLD, u64::MAX] {
+            let header = Header {
+                rescale_at,
+                ..config.header(false)
+            };
+            assert_eq!(
+                config.apply_header(&header, false),
+                Err(HeaderConfigError::InvalidRescaleThreshold(rescale_at))
+            );
+        }
+    }
+
+    /// Compresses _input_ with the given config, storing its settings in the header
+    fn compress_with(config: &CodecConfig, input: &[u8]) -> Vec<u8> {
+        let options = CodecOptions {
+            header: Some(config.header(false)),
+            ..Default::default()
+        };
+        let mut model = config.get_model().unwrap();
+        let mut compressed = Vec::new();
+        compress_reader(
+            input,
+            &mut compressed,
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap();
+        compressed
+    }
+
+    #[test]
+    fn test_levels_round_trip() {
+        let input = include_bytes!("level.rs");
+        for level in 1..=MAX_LEVEL {
+            let config = resolve(&["--level", &level.to_string()], &[]).unwrap();
+            let preset = LevelPreset::of(level).unwrap();
+            assert_eq!(config.model, preset.model.clone().into());
+            assert_eq!(config.model_order, preset.model_order);
+            assert_eq!(config.escape_method, preset.escape_method);
+            assert_eq!(*config.rescale_at, preset.rescale_at);
+            let compressed = compress_with(&config, input);
+
+            // Decompression follows the level's settings from the header:
+            let (header, reader) = peek_header(&compressed[..]).unwrap();
+            let mut decompression_config = resolve(&[], &[(MODEL_ENV_VAR, "order0")]).unwrap();
+            decompression_config.apply_header(&header, false).unwrap();
+            assert_eq!(decompression_config, config);
+
+            let options = CodecOptions {
+                header: Some(header),
+                ..Default::default()
+            };
+            let mut model = decompression_config.get_model().unwrap();
+            let mut decompressed = Vec::new();
+            decompress_reader(
+                reader,
+                &mut decompressed,
+                &mut model,
+                &mut ByteParser,
+                &options,
+            )
+            .unwrap();
+            assert_eq!(decompressed, input);
+        }
+    }
+
+    #[test]
+    fn test_level_settings_precedence() {
+        // Explicit settings override the level's, which override the environment's:
+        let env = [(MODEL_ENV_VAR, "order0"), (MODEL_ORDER_ENV_VAR, "5")];
+        let config = resolve(&["--level", "8", "--model-order", "1"], &env).unwrap();
+        assert_eq!(config.model, BuiltinModel::Ppm.into());
+        assert_eq!(config.model_order, 1);
+        assert_eq!(config.escape_method, EscapeMethod::PpmD);
+        assert_eq!(config.header(false).level, 8);
+
+        let mut header = config.header(false);
+        header.level = MAX_LEVEL + 1;
+        let mut decompression_config = resolve(&[], &[]).unwrap();
+        assert_eq!(
+            decompression_config.apply_header(&header, false),
+            Err(HeaderConfigError::UnknownLevel(MAX_LEVEL + 1))
+        );
+    }
+
+    #[test]
+    fn test_higher_levels_compress_text_better() {
+        let input = include_bytes!("level.rs");
+        let compressed_len = |level: u8| {
+            let config = resolve(&["--level", &level.to_string()], &[]).unwrap();
+            compress_with(&config, input).len()
+        };
+        // The model order matters most:
+        for (lower, higher) in [(1, 2), (2, 4), (4, 6), (6, 8)] {
+            assert!(compressed_len(higher) < compressed_len(lower));
+        }
+    }
+
+    #[test]
+    fn test_rescale_threshold_round_trip() {
+        let input: Vec<u8> = b"abracadabra "
+            .repeat(50)
+            .into_iter()
+            .chain(0..=255)
+            .collect();
+        let config = resolve(&["--model", "order0", "--rescale-at", "16"], &[]).unwrap();
+        let compressed = compress_with(&config, &input);
+        // A different threshold changes the model's predictions:
+        let default_config = resolve(&["--model", "order0"], &[]).unwrap();
+        assert_ne!(compressed, compress_with(&default_config, &input));
+
+        // The threshold is read from the header, not from the decompression arguments:
+        let (header, reader) = peek_header(&compressed[..]).unwrap();
+        let mut decompression_config = resolve(&[], &[]).unwrap();
+        decompression_config.apply_header(&header, false).unwrap();
+        assert_eq!(*decompression_config.rescale_at, 16);
+
+        let options = CodecOptions {
+            header: Some(header),
+            ..Default::default()
+        };
+        let mut model = decompression_config.get_model().unwrap();
+        let mut decompressed = Vec::new();
+        decompress_reader(
+            reader,
+            &mut decompressed,
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(decompressed, input);
+    }
+
+    #[test]
+    fn test_adaptation_round_trip() {
+        // The statistics shift halfway through, which an aging model follows:
+        let mut input = b"abababababab".repeat(400);
+        input.extend(b"xyzzy".repeat(1000));
+        let cli_args = [
+            "--model",
+            "order0",
+            "--increment",
+            "8",
+            "--half-life",
+            "256",
+        ];
+        let config = resolve(&cli_args, &[]).unwrap();
+        let compressed = compress_with(&config, &input);
+        let plain_config = resolve(&["--model", "order0", "--increment", "8"], &[]).unwrap();
+        assert!(compressed.len() < compress_with(&plain_config, &input).len());
+
+        // The increment and half-life are read from the header, not from the decompression
+        // arguments:
+        let (header, reader) = peek_header(&compressed[..]).unwrap();
+        let mut decompression_config = resolve(&[], &[]).unwrap();
+        decompression_config.apply_header(&header, false).unwrap();
+        assert_eq!(*decompression_config.increment, 8);
+        assert_eq!(decompression_config.half_life, NonZeroU64::new(256));
+
+        let options = CodecOptions {
+            header: Some(header),
+            ..Default::default()
+        };
+        let mut model = decompression_config.get_model().unwrap();
+        let mut decompressed = Vec::new();
+        decompress_reader(
+            reader,
+            &mut decompressed,
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(decompressed, input);
+    }
+
+    #[test]
+    fn test_header_invalid_increment() {
+        let mut config = resolve(&[], &[]).unwrap();
+        for increment in [0, MAX_INCREMENT, u64::MAX] {
+            let header = Header {
+                increment,
+                ..config.header(false)
+            };
+            assert_eq!(
+                config.apply_header(&header, false),
+                Err(HeaderConfigError::InvalidIncrement(increment))
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_cli_rescale_threshold() {
+        for rescale_at in ["0", "2147483647"] {
+            let cli_args = ["ppm-cli", "compress", "--rescale-at", rescale_at];
+            assert!(Cli::try_parse_from(cli_args).is_err());
+        }
+    }
+
+    #[test]
+    fn test_codec_params_resolution() {
+        let config = resolve(&["--interval-bits", "20"], &[]).unwrap();
+        assert_eq!(config.params, CodecParams::new(20, 18).unwrap());
+
+        let config = resolve(&["--frequency-bits", "16"], &[]).unwrap();
+        assert_eq!(config.params, CodecParams::new(INTERVAL_BITS, 16).unwrap());
+
+        assert_eq!(
+            resolve(&["--interval-bits", "12", "--frequency-bits", "16"], &[]),
+            Err(EnvConfigError::InvalidCodecParams(
+                CodecParamsError::IntervalTooNarrow {
+                    interval_bits: 12,
+                    frequency_bits: 16
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_codec_params_round_trip() {
+        let input = b"precision is a trade-off ".repeat(40);
+        let config = resolve(
+            &[
+                "--model",
+                "ppm",
+                "--interval-bits",
+                "20",
+                "--frequency-bits",
+                "12",
+            ],
+            &[],
+        )
+        .unwrap();
+        let compressed = compress_with(&config, &input);
+
+        // The precisions are read from the header, not from the decompression arguments:
+        let (header, reader) = peek_header(&compressed[..]).unwrap();
+        let mut decompression_config = resolve(&[], &[]).unwrap();
+        decompression_config.apply_header(&header, false).unwrap();
+        assert_eq!(decompression_config.params, config.params);
+
+        let options = CodecOptions {
+            header: Some(header),
+            ..Default::default()
+        };
+        let mut model = decompression_config.get_model().unwrap();
+        let mut decompressed = Vec::new();
+        decompress_reader(
+            reader,
+            &mut decompressed,
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(decompressed, input);
+    }
+
+    #[test]
+    fn test_invalid_env_value_ignored_when_overridden() {
+        let config = resolve(&["--model", "order0"], &[(MODEL_ENV_VAR, "gzip")]).unwrap();
+        assert_eq!(config.model, BuiltinModel::Order0.into());
+    }
+}
@@ -0,0 +1,190 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::config::CUSTOM_MODEL_ID;
+use super::registry::model_registry;
+use super::stats::ratio_percent;
+use anyhow::{Context, Result};
+use clap::Args;
+use ppm_cli::header::{peek_header, Header, FORMAT_VERSION};
+use ppm_cli::termination::TerminationStrategy;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// CLI arguments for inspecting a compressed file
+#[derive(Args)]
+pub struct InfoArgs {
+    /// Path to the compressed file. Only its header is read
+    file: PathBuf,
+}
+
+/// What the header of a compressed file tells about it
+#[derive(Debug, PartialEq)]
+pub struct StreamInfo {
+    /// The header of the file's (first) stream
+    pub header: Header,
+    /// The size of the whole compressed file in bytes
+    pub compressed_bytes: u64,
+}
+
+impl StreamInfo {
+    /// The name of the model that compressed the stream
+    fn model_name(&self) -> String {
+        match self.header.model_id {
+            CUSTOM_MODEL_ID => "custom".to_string(),
+            id => model_registry()
+                .by_id(id)
+                .map_or_else(|| format!("unknown (id {id})"), |model| model.to_string()),
+        }
+    }
+}
+
+impl Display for StreamInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let header = &self.header;
+        let yes_no = |flag| if flag { "yes" } else { "no" };
+        writeln!(f, "Format version: {FORMAT_VERSION}")?;
+        writeln!(f, "Model: {}", self.model_name())?;
+        if header.level != 0 {
+            writeln!(f, "Level: {}", header.level)?;
+        }
+        writeln!(f, "Model order: {}", header.model_order)?;
+        writeln!(f, "Escape method: {}", header.escape_method)?;
+        if header.bit_mode {
+            writeln!(f, "Mode: bit")?;
+        } else {
+            let transform = match (header.rle, header.mtf) {
+                (true, _) => ", run-length encoded",
+                (false, true) => ", move-to-front transformed",
+                (false, false) => "",
+            };
+            writeln!(f, "Mode: byte (alphabet: {}){transform}", header.alphabet)?;
+        }
+        writeln!(f, "Original size: {} bytes", header.original_len)?;
+        write!(f, "Compressed size: {} bytes", self.compressed_bytes)?;
+        if let Some(ratio) = ratio_percent(header.original_len, self.compressed_bytes) {
+            write!(f, " (ratio: {ratio:.2}%)")?;
+        }
+        writeln!(f)?;
+        if header.checksum {
+            writeln!(f, "Checksum: CRC-32 of the original data")?;
+        } else {
+            writeln!(f, "Checksum: none")?;
+        }
+        match header.termination {
+            TerminationStrategy::EofSymbol => writeln!(f, "Termination: EOF symbol")?,
+            TerminationStrategy::Length(symbols) => {
+                writeln!(f, "Termination: length ({symbols} symbols)")?
+            }
+            TerminationStrategy::Both(symbols) => {
+                writeln!(f, "Termination: EOF symbol and length ({symbols} symbols)")?
+            }
+        }
+        writeln!(f, "Dictionary: {}", yes_no(header.dictionary))?;
+        writeln!(f, "Blocks: {}", yes_no(header.blocks))?;
+        writeln!(f, "Multiple files: {}", yes_no(header.files))?;
+        write!(
+            f,
+            "Precision: {} interval bits, {} frequency bits",
+            header.params.interval_bits(),
+            header.params.frequency_bits()
+        )
+    }
+}
+
+/// Reads the header of the compressed file at _path_, without decompressing it
+pub fn read_info(path: &Path) -> Result<StreamInfo> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open the compressed file {}", path.display()))?;
+    let compressed_bytes = file.metadata()?.len();
+    let (header, _) = peek_header(file)
+        .with_context(|| format!("{} isn't a valid compressed file", path.display()))?;
+    Ok(StreamInfo {
+        header,
+        compressed_bytes,
+    })
+}
+
+/// Prints what the header of the file given in _args_ tells about it
+pub fn run_info(args: &InfoArgs) -> Result<()> {
+    println!("{}", read_info(&args.file)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::config::CodecConfig;
+    use crate::cli::model_choice::BuiltinModel;
+    use ppm_cli::header::HeaderError;
+    use ppm_cli::parser::ByteParser;
+    use ppm_cli::{compress_reader, CodecOptions};
+
+    #[test]
+    fn test_info_of_compressed_file() {
+        let input = b"inspecting a header, inspecting a header".repeat(10);
+        let config = CodecConfig::defaults(BuiltinModel::Ppm.into(), false);
+        let options = CodecOptions {
+            header: Some(config.header(false)),
+            ..Default::default()
+        };
+        let mut compressed = Vec::new();
+        let mut model = config.get_model().unwrap();
+        compress_reader(
+            &input[..],
+            &mut compressed,
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap();
+
+        let path = std::env::temp_dir().join(format!("info-{}.ppm", std::process::id()));
+        std::fs::write(&path, &compressed).unwrap();
+        let info = read_info(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let info = info.unwrap();
+        assert_eq!(info.header.original_len, input.len() as u64);
+        assert_eq!(info.compressed_bytes, compressed.len() as u64);
+        assert!(info.header.checksum && !info.header.bit_mode);
+        assert_eq!(info.model_name(), "ppm");
+        let printed = info.to_string();
+        for line in [
+            format!("Format version: {FORMAT_VERSION}"),
+            "Model: ppm".to_string(),
+            "Mode: byte (alphabet: bytes)".to_string(),
+            format!("Original size: {} bytes", input.len()),
+            format!("Compressed size: {} bytes", compressed.len()),
+            "Checksum: CRC-32 of the original data".to_string(),
+        ] {
+            assert!(printed.contains(&line), "{line} missing from:\n{printed}");
+        }
+    }
+
+    #[test]
+    fn test_info_of_uncompressed_file() {
+        let path = std::env::temp_dir().join(format!("info-{}.txt", std::process::id()));
+        std::fs::write(&path, b"just some text").unwrap();
+        let err = read_info(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(err.to_string().contains("isn't a valid compressed file"));
+        assert_eq!(err.downcast_ref(), Some(&HeaderError::BadMagic));
+    }
+}
@@ -0,0 +1,137 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::model_choice::BuiltinModel;
+use ppm_cli::models::adaptive::DEFAULT_FREQUENCY_CAP;
+use ppm_cli::models::ppm::EscapeMethod;
+
+/// The highest compression level (the lowest is 1)
+pub const MAX_LEVEL: u8 = 9;
+
+/// The settings a compression level (`--level`) chooses
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelPreset {
+    pub model: BuiltinModel,
+    /// The context order (0 for models without contexts)
+    pub model_order: u8,
+    pub escape_method: EscapeMethod,
+    pub rescale_at: u64,
+}
+
+/// The presets of levels 1 to 9. Higher levels use more context, which compresses better but
+/// is slower and takes more memory:
+///
+/// | Level | Model   | Order | Escape method | Rescale threshold |
+/// |-------|---------|-------|---------------|-------------------|
+/// | 1     | uniform | -     | -             | -                 |
+/// | 2     | order0  | -     | -             | 65536             |
+/// | 3     | ppm     | 1     | PPMA          | 65536             |
+/// | 4     | ppm     | 1     | PPMC          | 65536             |
+/// | 5     | ppm     | 2     | PPMC          | 65536             |
+/// | 6     | ppm     | 2     | PPMD          | 65536             |
+/// | 7     | ppm     | 3     | PPMC          | 65536             |
+/// | 8     | ppm     | 3     | PPMD          | 65536             |
+/// | 9     | ppm     | 3     | PPMD          | 4096              |
+///
+/// Level 9 rescales sooner, so its tables follow changes in the data faster.<br>
+/// Streams store the chosen settings along with the level, so they're decompressed the same way
+/// even if the presets change.
+const PRESETS: [LevelPreset; MAX_LEVEL as usize] = [
+    preset(
+        BuiltinModel::Uniform,
+        0,
+        EscapeMethod::PpmC,
+        DEFAULT_FREQUENCY_CAP,
+    ),
+    preset(
+        BuiltinModel::Order0,
+        0,
+        EscapeMethod::PpmC,
+        DEFAULT_FREQUENCY_CAP,
+    ),
+    preset(
+        BuiltinModel::Ppm,
+        1,
+        EscapeMethod::PpmA,
+        DEFAULT_FREQUENCY_CAP,
+    ),
+    preset(
+        BuiltinModel::Ppm,
+        1,
+        EscapeMethod::PpmC,
+        DEFAULT_FREQUENCY_CAP,
+    ),
+    preset(
+        BuiltinModel::Ppm,
+        2,
+        EscapeMethod::PpmC,
+        DEFAULT_FREQUENCY_CAP,
+    ),
+    preset(
+        BuiltinModel::Ppm,
+        2,
+        EscapeMethod::PpmD,
+        DEFAULT_FREQUENCY_CAP,
+    ),
+    preset(
+        BuiltinModel::Ppm,
+        3,
+        EscapeMethod::PpmC,
+        DEFAULT_FREQUENCY_CAP,
+    ),
+    preset(
+        BuiltinModel::Ppm,
+        3,
+        EscapeMethod::PpmD,
+        DEFAULT_FREQUENCY_CAP,
+    ),
+    preset(BuiltinModel::Ppm, 3, EscapeMethod::PpmD, 1 << 12),
+];
+
+const fn preset(
+    model: BuiltinModel,
+    model_order: u8,
+    escape_method: EscapeMethod,
+    rescale_at: u64,
+) -> LevelPreset {
+    LevelPreset {
+        model,
+        model_order,
+        escape_method,
+        rescale_at,
+    }
+}
+
+impl LevelPreset {
+    /// The preset of _level_, or None if it isn't between 1 and `MAX_LEVEL`
+    pub fn of(level: u8) -> Option<&'static Self> {
+        PRESETS.get(usize::from(level).checked_sub(1)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_range() {
+        assert_eq!(LevelPreset::of(0), None);
+        assert_eq!(LevelPreset::of(1).unwrap().model, BuiltinModel::Uniform);
+        assert_eq!(LevelPreset::of(MAX_LEVEL).unwrap().model_order, 3);
+        assert_eq!(LevelPreset::of(MAX_LEVEL + 1), None);
+    }
+}
@@ -15,26 +15,72 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+mod bench;
+mod config;
+mod info;
+mod level;
 mod model_choice;
+mod progress;
+mod registry;
+mod self_test;
+mod stats;
+mod streaming;
+mod text_format;
+mod verify;
 
-use self::model_choice::BuiltinModel;
-use crate::cli::model_choice::UserModel;
-use crate::compressor::Compressor;
-use crate::models::{Model, ModelCfiError};
-use crate::sim::DefaultSIM;
+use self::bench::run_bench;
+use self::config::{
+    CodecConfig, MAX_INCREMENT, MAX_MODEL_ORDER, MAX_RESCALE_THRESHOLD, MAX_SMOOTHING,
+};
+use self::info::{run_info, InfoArgs};
+use self::level::MAX_LEVEL;
+use self::model_choice::{BuiltinModel, ParserChoice, UserModel, UserModelError};
+use self::progress::ProgressReader;
+use self::registry::{parse_model_name, RegisteredModel};
+use self::stats::{ByteCounter, CodecStats, SizeEstimate};
+use self::streaming::{run_streaming, StreamingArgs};
+use self::text_format::{decode_input, TextEncoder, TextFormat};
+use self::verify::{CompareWriter, TeeReader};
 use clap::{Args, Parser, Subcommand};
-use log::{debug, error, info};
+use log::{debug, error, info, LevelFilter};
+use ppm_cli::blocks::{compress_blocks, decompress_blocks, BlockOptions};
+use ppm_cli::dictionary::Dictionary;
+use ppm_cli::files::{encode_file_table, split_files, FileEntry, FileTableError};
+use ppm_cli::frequencies::Frequency;
+use ppm_cli::header::{peek_header, Header};
+use ppm_cli::models::ppm::EscapeMethod;
+use ppm_cli::models::Model;
+use ppm_cli::number_types::{FREQUENCY_BITS, INTERVAL_BITS, MIN_FREQUENCY_BITS};
+use ppm_cli::sim::Alphabet;
+use ppm_cli::termination::TerminationStrategy;
+use ppm_cli::two_pass::{
+    compress_two_pass, compress_two_pass_buffered, decompress_two_pass,
+    DEFAULT_TWO_PASS_BUFFER_LIMIT,
+};
+use ppm_cli::{compress_reader, decompress_reader, CodecOptions, OutputWriteError, TrailingPolicy};
 use std::fs::File;
-use std::io::{BufReader, IsTerminal, Read, Write};
-use std::path::PathBuf;
+use std::io::{BufReader, BufWriter, Cursor, IsTerminal, Read, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use thiserror::Error;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 #[command(propagate_version = true)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Cli {
     #[command(subcommand)]
-    commands: Commands,
+    commands: Option<Commands>,
+
+    /// Without a subcommand, the CLI compresses (or decompresses) like gzip does
+    #[command(flatten)]
+    streaming: StreamingArgs,
+
+    /// Logs more details about the program's run: -v logs info messages, -vv debug messages, and
+    /// -vvv trace messages. The RUST_LOG environment variable overrides this flag if set
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
 }
 
 #[derive(Subcommand)]
@@ -43,28 +89,249 @@ pub enum Commands {
     Compress(CodecArgs),
     /// Decompresses a file/piped data which was compressed using the `compress` command
     Decompress(CodecArgs),
+    /// Checks that every builtin model compresses a fixed input into the expected bytes, so files
+    /// compressed on other platforms will decompress correctly
+    SelfTest,
+    /// Compresses and decompresses a file with every builtin model (and the given custom models),
+    /// and prints their compressed sizes and times, from the best compression to the worst
+    Bench(BenchArgs),
+    /// Prints what the header of a compressed file tells about it (such as its model, mode and
+    /// original size), without decompressing it
+    Info(InfoArgs),
+}
+
+/// CLI arguments for benchmarking the models
+#[derive(Args)]
+pub struct BenchArgs {
+    /// Path to the file the models are benchmarked on. It is read into memory
+    file: PathBuf,
+
+    /// Path to a JSON file defining a custom probability model (see the compress command) to
+    /// benchmark along with the builtin models. May be given several times
+    #[arg(long = "custom-model")]
+    custom_models: Vec<PathBuf>,
+
+    /// If set, the builtin models compress bit-by-bit instead of byte-by-byte. Custom models always
+    /// compress in their own mode
+    #[arg(short, long, default_value_t = false)]
+    bit_mode: bool,
+
+    /// The context order of context-based models. If not specified, an order of 2 is used
+    #[arg(long, value_parser = clap::value_parser!(u8).range(..=MAX_MODEL_ORDER as i64))]
+    model_order: Option<u8>,
+
+    /// If set, the compressed output of every model is written next to the input file, named
+    /// <file>.<model>.ppm. Otherwise nothing is written to disk
+    #[arg(long, default_value_t = false)]
+    keep: bool,
 }
 
 /// CLI arguments for compression/decompression
 #[derive(Args)]
 pub struct CodecArgs {
-    /// Path to the file that will be read. If not specified, the input data must be piped directly
-    file: Option<PathBuf>,
+    /// Paths to the files that will be read. If not specified, the input data must be piped
+    /// directly. Compression concatenates several files into a single stream, which records their
+    /// names and lengths (the files are read into memory first). Decompression reads a single file
+    files: Vec<PathBuf>,
+
+    /// Path to the file the output will be written to. If not specified, the output is written to
+    /// stdout (which must be redirected when compressing). When decompressing a stream of several
+    /// files, this is the directory the files are written to (and if it isn't specified, the
+    /// files' contents are concatenated to stdout)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
 
     /// If set, the CLI will compress input **bit-by-bit**, which in some cases will result in
     /// better compression ratios.
-    /// By default, this option is false, and the input will be read **byte-by-byte**.
+    /// By default, this option is false, and the input will be read **byte-by-byte**. The default
+    /// can be changed by setting the PPM_MODE environment variable to "bit" or "byte".
+    /// When decompressing, the mode is read from the compressed stream instead.
     #[arg(short, long, default_value_t = false)]
     bit_mode: bool,
 
-    /// Builtin probability models
-    #[arg(long, group = "models", default_value_t = BuiltinModel::Uniform)]
-    model: BuiltinModel,
+    /// How the input is converted into symbols: "byte" (the default), "bit" (like --bit-mode),
+    /// "rle", which run-length encodes runs of identical bytes before coding byte-by-byte (helping
+    /// on highly repetitive data), or "mtf", which move-to-front transforms the bytes so recently
+    /// seen bytes become small values (helping adaptive models on text). If not specified, the
+    /// mode follows --bit-mode and the PPM_MODE environment variable. When decompressing, the
+    /// parser is read from the compressed stream instead
+    #[arg(long, conflicts_with_all = ["bit_mode", "custom_model", "alphabet"])]
+    parser: Option<ParserChoice>,
+
+    /// The name of a registered probability model, such as "order0" or "ppm" (an unknown name
+    /// lists the registered ones). If not specified, the PPM_MODEL environment variable is used,
+    /// and if it isn't set either, the uniform model is used. When decompressing, the model is read
+    /// from the compressed stream instead
+    #[arg(long, group = "models", value_parser = parse_model_name)]
+    model: Option<RegisteredModel>,
 
-    /// Custom probability models defined by the user, cannot be used with the --model option
-    /// (which provides builtin models)
+    /// A compression level from 1 (fastest) to 9 (best compression), choosing the model, its
+    /// context order, escape method and rescale threshold (uniform, order-0, then PPM of orders 1
+    /// to 3). Settings given explicitly override the level's. When decompressing, the settings are
+    /// read from the compressed stream instead
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=MAX_LEVEL as i64))]
+    level: Option<u8>,
+
+    /// Shorthand for --model two-pass: the input is read twice, first to count the frequencies of
+    /// its symbols, then to compress it with a static model of those frequencies (which are stored
+    /// in the compressed stream). Beats the order-0 adaptive model, but can't be used in block mode
+    #[arg(long, group = "models", default_value_t = false)]
+    two_pass: bool,
+
+    /// The context order of context-based models. If not specified, the PPM_MODEL_ORDER
+    /// environment variable is used, and if it isn't set either, an order of 2 is used. When
+    /// decompressing, the order is read from the compressed stream instead
+    #[arg(long, value_parser = clap::value_parser!(u8).range(..=MAX_MODEL_ORDER as i64))]
+    model_order: Option<u8>,
+
+    /// How the PPM model estimates the probability of a context seeing a new symbol - "ppma",
+    /// "ppmc" or "ppmd". PPMD usually compresses text best. If not specified, "ppmc" is used. When
+    /// decompressing, the method is read from the compressed stream instead
+    #[arg(long)]
+    escape_method: Option<EscapeMethod>,
+
+    /// Once the frequency of a single symbol in an adaptive model's table passes this threshold,
+    /// the table is rescaled. Lower values adapt faster to changes in the data, higher values
+    /// estimate probabilities more precisely. If not specified, a threshold of 65536 is used. When
+    /// decompressing, the threshold is read from the compressed stream instead
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..MAX_RESCALE_THRESHOLD))]
+    rescale_at: Option<u64>,
+
+    /// The amount the order-0 adaptive model adds to the frequency of every symbol it compresses.
+    /// Larger increments let the input's statistics outweigh the model's initial ones sooner. If
+    /// not specified, an increment of 1 is used. When decompressing, the increment is read from
+    /// the compressed stream instead
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..MAX_INCREMENT))]
+    increment: Option<u64>,
+
+    /// If set, adaptive order-0 models halve their table every time this many symbols were
+    /// compressed, so recent symbols weigh more than older ones (which helps on data whose
+    /// statistics change). If not specified, the table is only rescaled by --rescale-at. When
+    /// decompressing, the half-life is read from the compressed stream instead
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    half_life: Option<u64>,
+
+    /// The number of bits in the boundaries of the coding interval (at most 33). More bits code
+    /// probabilities more precisely. If not specified, 33 bits are used. When decompressing, the
+    /// precision is read from the compressed stream instead
+    #[arg(long, value_parser = clap::value_parser!(u32).range(2..=INTERVAL_BITS as i64))]
+    interval_bits: Option<u32>,
+
+    /// The number of bits the total frequency of a model may use (at most 31, and at most the
+    /// interval bits minus 2). Tables of adaptive models are rescaled to fit in it. If not
+    /// specified, the largest number the interval bits allow is used. When decompressing, the
+    /// precision is read from the compressed stream instead
+    #[arg(long, value_parser = clap::value_parser!(u32).range(MIN_FREQUENCY_BITS as i64..=FREQUENCY_BITS as i64))]
+    frequency_bits: Option<u32>,
+
+    /// The number of meaningful bits in the input's last byte (1 to 7), for data whose length in
+    /// bits isn't a multiple of 8. The rest of the last byte's bits are discarded, and decompressed
+    /// as zero bits. Only used when compressing, and only supported in bit-mode
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..8))]
+    trailing_bits: Option<u8>,
+
+    /// Path to a JSON file defining a custom probability model, cannot be used with the --model
+    /// option (which provides builtin models). The file holds one frequency per symbol, in the form
+    /// {"name": "...", "bit_model": false, "frequencies": [...]}. A bit-model always compresses in
+    /// bit-mode and holds 4 frequencies (bit 0, bit 1, EOF, escape), and a byte-model in byte-mode
+    /// and holds 258 frequencies (every byte value, EOF, escape)
     #[arg(long, group = "models")]
-    custom_model: Option<String>,
+    custom_model: Option<PathBuf>,
+
+    /// The alphabet of the input's bytes - "bytes" (every byte value), "dna" (A, C, G and T) or
+    /// "ascii" (bytes 0 to 127). Smaller alphabets compress better, but compression fails if the
+    /// input holds bytes outside of them. If not specified, "bytes" is used. Not supported in
+    /// bit-mode and by custom models. When decompressing, the alphabet is read from the compressed
+    /// stream instead
+    #[arg(long, conflicts_with_all = ["bit_mode", "custom_model"])]
+    alphabet: Option<Alphabet>,
+
+    /// A count added to the frequency of every symbol of the custom model, so symbols the model
+    /// gives no frequency can still be compressed (at a small cost to the others). Only used with
+    /// --custom-model, and decompression must use the same smoothing
+    #[arg(long, requires = "custom_model", value_parser = clap::value_parser!(u64).range(1..MAX_SMOOTHING))]
+    smoothing: Option<u64>,
+
+    /// Path to a preset dictionary. Its content primes the model before coding, which improves
+    /// compression of data similar to it. Decompression must use the same dictionary
+    #[arg(long, visible_alias = "prime")]
+    dictionary: Option<PathBuf>,
+
+    /// If set, statistics about the compression/decompression (such as the compression ratio and
+    /// the time it took) are printed to stderr
+    #[arg(long, default_value_t = false)]
+    stats: bool,
+
+    /// If set, the progress of reading the input is drawn on stderr: a progress bar if the input
+    /// is a file (whose size is known), or the number of bytes read so far if it is piped
+    #[arg(long, default_value_t = false)]
+    progress: bool,
+
+    /// If set, the compressed output is decompressed in memory and compared with the input before
+    /// it is written, failing with the offset of the first difference if they don't match. Only
+    /// used when compressing
+    #[arg(long, default_value_t = false)]
+    verify: bool,
+
+    /// If set, the input is compressed without writing the output anywhere, and the size of the
+    /// output (which is exactly the size compression would write) and its ratio are printed to
+    /// stderr instead. Only used when compressing
+    #[arg(long, default_value_t = false, conflicts_with = "output")]
+    estimate: bool,
+
+    /// If set, the compressed stream doesn't end with a checksum of the input. The checksum takes
+    /// 4 bytes, and lets decompression detect corrupted streams instead of silently producing
+    /// wrong data. Only used when compressing, decompression verifies the checksum of streams
+    /// that have one
+    #[arg(long, default_value_t = false)]
+    no_checksum: bool,
+
+    /// How decompression finds the end of the compressed data - "eof" ends it with an EOF symbol,
+    /// "length" records its length in the header instead (the whole output is kept in memory
+    /// until it's known), and "both" does both, checking they agree. Not supported by block mode,
+    /// whose blocks always end with an EOF. Only used when compressing, decompression reads it
+    /// from the compressed stream
+    #[arg(long, default_value_t = TerminationStrategy::EofSymbol, conflicts_with = "block_size")]
+    termination: TerminationStrategy,
+
+    /// What to do with bytes that follow the end of the compressed stream - "ignore", "error" or
+    /// "multi-stream". Only used when decompressing
+    #[arg(long, default_value_t = TrailingPolicy::Ignore)]
+    trailing: TrailingPolicy,
+
+    /// If given, the input is split into blocks of this many bytes, which are compressed in
+    /// parallel. Every block starts with a fresh model, so smaller blocks compress faster but
+    /// worse. Not supported by the two-pass model and custom models. Only used when compressing,
+    /// decompression reads it from the compressed stream
+    #[arg(long)]
+    block_size: Option<NonZeroUsize>,
+
+    /// The number of threads compressing/decompressing blocks in parallel. If not specified, the
+    /// number of available CPUs is used. Only used by streams compressed with --block-size
+    #[arg(long)]
+    threads: Option<NonZeroUsize>,
+
+    /// How the compressed data is written - "raw" bytes, or ASCII-safe "hex" or "base64" text
+    /// (which may be written to a terminal). Compression writes raw bytes by default, and
+    /// decompression detects the format of its input unless it's given
+    #[arg(long)]
+    format: Option<TextFormat>,
+}
+
+/// The operation the CLI performs on its input
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CodecDirection {
+    Compress,
+    Decompress,
+}
+
+/// Errors caused by models which can't compress/decompress in block mode
+#[derive(Debug, Error, PartialEq)]
+pub enum BlockModeError {
+    #[error("Block mode doesn't support the two-pass model, which is built from the whole input")]
+    TwoPassModel,
+    #[error("Block mode doesn't support custom models")]
+    CustomModel,
 }
 
 /// When trying to read input to compress/decompress, the following errors may occur
@@ -72,122 +339,941 @@ pub struct CodecArgs {
 pub enum InputFileError {
     #[error("No path to an input file was provided, nor was it piped into the command")]
     MissingInputFile,
+    #[error("No compressed input provided: pass the path to a compressed file, or pipe one into the command")]
+    MissingCompressedInput,
+    #[error("Decompression reads a single compressed file, but {0} files were given")]
+    MultipleCompressedInputs(usize),
     #[error("Failed to read the provided input file: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("The name of the input file {0} isn't valid UTF-8")]
+    NonUtf8FileName(PathBuf),
+    #[error("Failed to concatenate the input files: {0}")]
+    FileTableError(#[from] FileTableError),
+    #[error("Failed to read the provided dictionary file: {0}")]
+    DictionaryError(std::io::Error),
 }
 
-/// Forms a bytes iterator for compression/decompression, either from stdin or from a path to a
-/// file.<br>
-fn get_bytes_iterator(
-    file: Option<&PathBuf>,
-) -> Result<Box<dyn Iterator<Item = Result<u8, std::io::Error>>>, InputFileError> {
-    match file {
-        None => {
+/// When trying to open the output of compression/decompression, the following errors may occur
+#[derive(Debug, Error)]
+pub enum OutputFileError {
+    #[error("Refusing to write compressed data to a terminal: pass an output path with --output, or redirect the output")]
+    TerminalOutput,
+    #[error("Refusing to write the output into its input file {0}, which would be overwritten before it's read")]
+    OutputIsInput(PathBuf),
+    #[error("Failed to create the provided output file: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// Makes sure stdin can be used as input when no file was given. If stdin is an interactive
+/// terminal, nothing was piped into the command, and the missing input error of _direction_ is
+/// returned (a terminal is never treated as a compressed stream).
+fn check_stdin_input(
+    stdin_is_terminal: bool,
+    direction: CodecDirection,
+) -> Result<(), InputFileError> {
+    if !stdin_is_terminal {
+        return Ok(());
+    }
+    match direction {
+        CodecDirection::Compress => Err(InputFileError::MissingInputFile),
+        CodecDirection::Decompress => Err(InputFileError::MissingCompressedInput),
+    }
+}
+
+/// Reads the files at _paths_ into the data of a multi-file stream: the table of their names and
+/// lengths, followed by their concatenated contents (see the `files` module)
+fn read_input_files(paths: &[PathBuf]) -> Result<Vec<u8>, InputFileError> {
+    let mut entries = Vec::with_capacity(paths.len());
+    let mut contents = Vec::new();
+    for path in paths {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| InputFileError::NonUtf8FileName(path.clone()))?;
+        let content = std::fs::read(path)?;
+        entries.push(FileEntry {
+            name: name.to_string(),
+            len: content.len() as u64,
+        });
+        contents.extend(content);
+    }
+    let mut data = encode_file_table(&entries)?;
+    data.extend(contents);
+    Ok(data)
+}
+
+/// Forms a reader for compression/decompression's input, either from stdin or from paths to
+/// files. Several files are only accepted by compression, which concatenates them.<br>
+fn get_input_reader(
+    files: &[PathBuf],
+    direction: CodecDirection,
+) -> Result<Box<dyn Read>, InputFileError> {
+    match files {
+        [] => {
             let stdin = std::io::stdin();
             // If we aren't reading from the terminal, the input is piped into the command:
-            if !stdin.is_terminal() {
-                let reader = BufReader::new(stdin.lock());
-                Ok(Box::new(reader.bytes()))
-            } else {
-                Err(InputFileError::MissingInputFile)
-            }
+            check_stdin_input(stdin.is_terminal(), direction)?;
+            Ok(Box::new(BufReader::new(stdin.lock())))
         }
-        Some(path) => Ok(Box::new(BufReader::new(File::open(path)?).bytes())),
+        [path] => Ok(Box::new(BufReader::new(File::open(path)?))),
+        paths => match direction {
+            CodecDirection::Compress => Ok(Box::new(Cursor::new(read_input_files(paths)?))),
+            CodecDirection::Decompress => {
+                Err(InputFileError::MultipleCompressedInputs(paths.len()))
+            }
+        },
     }
 }
 
-/// Handles a case where compressing a symbol fails
-fn handle_compression_error(compression_err: anyhow::Error) {
-    if let Some(ModelCfiError::UnsupportedSymbol(symbol)) = compression_err.downcast_ref() {
-        error!(
-            "A symbol not supported by the model ({}) was found. Skipping it",
-            symbol
-        );
+/// The total size of the input files at _paths_, or None if it isn't known (when the input is
+/// piped, or one of the paths isn't a regular file)
+fn input_size(paths: &[PathBuf]) -> Option<u64> {
+    if paths.is_empty() {
+        return None;
+    }
+    paths.iter().try_fold(0, |total, path| {
+        let metadata = std::fs::metadata(path).ok()?;
+        metadata.is_file().then(|| total + metadata.len())
+    })
+}
+
+/// Wraps _input_ in a reader drawing its progress on stderr if the progress flag was given
+fn with_progress<'a>(input: Box<dyn Read + 'a>, args: &CodecArgs) -> Box<dyn Read + 'a> {
+    if args.progress {
+        Box::new(ProgressReader::new(
+            input,
+            input_size(&args.files),
+            std::io::stderr(),
+        ))
     } else {
-        error!("Failed to compress symbol; skipping it");
-        debug!("Compression error: {}", compression_err);
-    }
-}
-
-fn compress<I, P, M>(bytes: I, mut compressor: Compressor<M>, parser: P)
-where
-    I: Iterator<Item = Result<u8, std::io::Error>>,
-    P: crate::parser::Parser,
-    M: Model,
-{
-    info!("Compressing input stream. Unsupported or invalid symbols will be skipped");
-    // Since we'll perform many writes, get a handle to stdout in a buffer:
-    let stdout = std::io::stdout();
-    let mut handle = std::io::BufWriter::new(stdout);
-    bytes
-        // Filter bytes we can't read, parse those we can:
-        .filter_map(|result_byte| match result_byte {
-            Ok(b) => Some(parser.parse_byte(b)),
-            Err(e) => {
-                error!("Failed to read byte; skipping it");
-                debug!("IO Error: {}", e);
-                None
+        input
+    }
+}
+
+/// Makes sure stdout can be used as output when no output file was given. Compressed data is
+/// binary unless it's encoded as text, and binary data is never written to an interactive
+/// terminal. _binary_ is true if the output is binary compressed data.
+fn check_stdout_output(stdout_is_terminal: bool, binary: bool) -> Result<(), OutputFileError> {
+    if binary && stdout_is_terminal {
+        Err(OutputFileError::TerminalOutput)
+    } else {
+        Ok(())
+    }
+}
+
+/// Makes sure the output file _output_ isn't one of the _inputs_, since creating it would
+/// truncate the input before it's read. Paths are compared once canonicalized, so different paths
+/// to the same file are caught too (an output that doesn't exist yet can't be an input).
+fn check_output_path(output: &Path, inputs: &[PathBuf]) -> Result<(), OutputFileError> {
+    let Ok(output) = output.canonicalize() else {
+        return Ok(());
+    };
+    match inputs
+        .iter()
+        .find(|input| input.canonicalize().is_ok_and(|input| input == output))
+    {
+        Some(input) => Err(OutputFileError::OutputIsInput(input.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Forms the writer for compression/decompression's output, either to stdout or to a file at the
+/// given path (which is created, or truncated if it exists). _inputs_ are the input files, which
+/// the output file must not be. _binary_ is true if the output is binary compressed data.<br>
+fn get_output_writer(
+    file: Option<&PathBuf>,
+    inputs: &[PathBuf],
+    binary: bool,
+) -> Result<Box<dyn Write>, OutputFileError> {
+    match file {
+        None => {
+            let stdout = std::io::stdout();
+            check_stdout_output(stdout.is_terminal(), binary)?;
+            Ok(Box::new(stdout))
+        }
+        Some(path) => {
+            check_output_path(path, inputs)?;
+            Ok(Box::new(BufWriter::new(File::create(path)?)))
+        }
+    }
+}
+
+/// Writes the files of the decompressed multi-file _data_ into the directory _output_ (creating
+/// it if needed), or concatenates their contents to stdout if no directory is given
+fn write_split_files(data: &[u8], output: Option<&PathBuf>) -> anyhow::Result<()> {
+    let files = split_files(data)?;
+    match output {
+        Some(directory) => {
+            std::fs::create_dir_all(directory).map_err(OutputFileError::from)?;
+            for (name, content) in files {
+                debug!("Writing {} bytes to {}", content.len(), name);
+                std::fs::write(directory.join(name), content).map_err(OutputFileError::from)?;
             }
-        })
-        .flatten()
-        .flat_map(|symbol| match compressor.load_symbol(symbol) {
-            Ok(compressed_bytes) => Box::new(compressed_bytes),
-            Err(e) => {
-                handle_compression_error(e);
-                Box::new(std::iter::empty()) as Box<dyn Iterator<Item = u8>>
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            for (_, content) in files {
+                stdout.write_all(content).map_err(OutputFileError::from)?;
             }
+            stdout.flush().map_err(OutputFileError::from)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the output file at _file_ (if compression writes to a file), after compression into it
+/// failed. Outputs that aren't regular files (such as /dev/full) are left untouched.
+fn remove_partial_output(file: Option<&PathBuf>) {
+    let Some(path) = file.filter(|path| path.is_file()) else {
+        return;
+    };
+    match std::fs::remove_file(path) {
+        Ok(()) => debug!("Removed partial output file {}", path.display()),
+        Err(e) => error!(
+            "Failed to remove partial output file {}: {}",
+            path.display(),
+            e
+        ),
+    }
+}
+
+/// Reads the preset dictionary given in the codec args, if there is one
+fn load_dictionary(
+    CodecArgs { dictionary, .. }: &CodecArgs,
+) -> Result<Option<Dictionary>, InputFileError> {
+    dictionary
+        .as_deref()
+        .map(Dictionary::from_file)
+        .transpose()
+        .map_err(InputFileError::DictionaryError)
+}
+
+/// Loads the custom model given by the user, if there is one
+fn load_user_model(args: &CodecArgs) -> Result<Option<UserModel>, UserModelError> {
+    let user_model = args
+        .custom_model
+        .as_deref()
+        .map(|path| {
+            let smoothing = args.smoothing.unwrap_or_default();
+            UserModel::from_file(
+                path,
+                Frequency::new(smoothing).expect("Smoothing is in range"),
+            )
         })
-        .for_each(|compressed_byte| {
-            // Output the data (log failures to write just in case):
-            if let Err(e) = handle.write(&[compressed_byte]) {
-                error!("Failed to output compressed byte");
-                debug!("Error: {}", e);
-            }
-        });
-    if let Err(e) = handle.flush() {
-        error!("Failed to flush output");
-        debug!("Error: {}", e);
+        .transpose()?;
+    if let Some(user_model) = &user_model {
+        info!("Loaded custom model \"{}\"", user_model.name());
     }
+    Ok(user_model)
+}
+
+/// The streams a compression/decompression works with
+struct CodecStreams {
+    /// The input
+    input: Box<dyn Read>,
+    /// The header of the compressed streams
+    header: Header,
+    /// Converts between the input's bytes and symbols
+    parser: Box<dyn ppm_cli::parser::Parser>,
 }
 
-/// Converts codec args to input bytes, parser and probability model.<br>
+/// Converts codec args and their resolved configuration to input bytes, header and parser. The
+/// output is opened separately, since decompressing several files writes them only at the end.
+/// <br>
+/// When decompressing, the configuration is updated to follow the header of the compressed input.
+/// <br>
+/// If a custom model is used, it decides the mode of compression, and must match the mode of
+/// decompressed streams.
 fn parse_codec_args(
-    CodecArgs { file, bit_mode, .. }: &CodecArgs,
-) -> anyhow::Result<(
-    impl Iterator<Item = Result<u8, std::io::Error>>,
-    Box<dyn crate::parser::Parser>,
-)> {
-    let bytes = get_bytes_iterator(file.as_ref())?;
-    let parser: Box<dyn crate::parser::Parser> = if *bit_mode {
-        Box::new(crate::parser::BitParser)
+    CodecArgs {
+        files,
+        trailing_bits,
+        block_size,
+        no_checksum,
+        termination,
+        format,
+        ..
+    }: &CodecArgs,
+    config: &mut CodecConfig,
+    user_model: Option<&UserModel>,
+    direction: CodecDirection,
+) -> anyhow::Result<CodecStreams> {
+    let mut input = get_input_reader(files, direction)?;
+    let header = match direction {
+        CodecDirection::Compress => {
+            if let Some(user_model) = user_model {
+                config.bit_mode = user_model.is_bit_model();
+            }
+            Header {
+                trailing_bits: trailing_bits.unwrap_or(0),
+                blocks: block_size.is_some(),
+                files: files.len() > 1,
+                checksum: !no_checksum,
+                termination: *termination,
+                ..config.header(user_model.is_some())
+            }
+        }
+        CodecDirection::Decompress => {
+            let (header, peeked_input) = peek_header(decode_input(input, *format)?)?;
+            config.apply_header(&header, user_model.is_some())?;
+            if let Some(user_model) = user_model {
+                user_model.check_mode(config.bit_mode)?;
+            }
+            debug!(
+                "Configuration after following the stream's header: {:?}",
+                config
+            );
+            input = Box::new(peeked_input);
+            header
+        }
+    };
+    Ok(CodecStreams {
+        input,
+        header,
+        parser: config.parser(),
+    })
+}
+
+/// The number of threads block mode uses: the given number, or the number of available CPUs
+fn block_threads(threads: Option<NonZeroUsize>) -> NonZeroUsize {
+    threads.unwrap_or_else(|| std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN))
+}
+
+/// Makes sure the configured model can be used in block mode, where every thread builds its own
+/// model. _custom_model_ is true if a user-defined model is used instead of the builtin one.
+fn check_block_model(config: &CodecConfig, custom_model: bool) -> Result<(), BlockModeError> {
+    if custom_model {
+        Err(BlockModeError::CustomModel)
+    } else if config.model == BuiltinModel::TwoPass.into() {
+        Err(BlockModeError::TwoPassModel)
+    } else {
+        Ok(())
+    }
+}
+
+/// The model to code with: _user_model_ if a custom model is used, or otherwise the model _config_
+/// describes. Returns None for the two-pass model, which is built from the input itself
+fn select_model<'a>(
+    config: &CodecConfig,
+    user_model: Option<&'a mut UserModel>,
+) -> Option<Box<dyn Model + 'a>> {
+    match user_model {
+        Some(user_model) => Some(Box::new(user_model.get_model())),
+        None => config.get_model(),
+    }
+}
+
+/// Decompresses _input_ into _output_ with the model _config_ describes, or with _user_model_ if a
+/// custom model is used. Streams compressed in block mode are decompressed by _threads_ threads.
+fn decompress_with_model(
+    input: impl Read,
+    output: impl Write,
+    config: &CodecConfig,
+    user_model: Option<&mut UserModel>,
+    parser: &mut dyn ppm_cli::parser::Parser,
+    options: &CodecOptions,
+    threads: Option<NonZeroUsize>,
+) -> anyhow::Result<()> {
+    if options.header.is_some_and(|header| header.blocks) {
+        check_block_model(config, user_model.is_some())?;
+        let new_model = || config.get_model().expect("Model isn't built from input");
+        let threads = block_threads(threads);
+        // Every thread builds its own parser, like its own model:
+        let new_parser = || config.parser();
+        return decompress_blocks(input, output, new_model, new_parser, options, threads);
+    }
+    match select_model(config, user_model) {
+        Some(mut model) => decompress_reader(input, output, &mut model, parser, options),
+        None => decompress_two_pass(input, output, parser, config.sim(), options),
+    }
+}
+
+/// Decompresses _compressed_ in memory, and compares the result with the input it was compressed
+/// from: the input files in _args_, or _piped_input_ if there are none.<br>
+/// A difference is reported with the offset of its first byte.
+///
+/// _user_model_ is the custom model the compression used (if any). It's flushed before
+/// decompressing, while builtin models are constructed anew - either way decompression starts from
+/// the state compression started from.
+fn verify_compression(
+    compressed: &[u8],
+    piped_input: &[u8],
+    args: &CodecArgs,
+    config: &CodecConfig,
+    mut user_model: Option<&mut UserModel>,
+    parser: &mut dyn ppm_cli::parser::Parser,
+    options: &CodecOptions,
+) -> anyhow::Result<()> {
+    let original = if args.files.is_empty() {
+        Box::new(piped_input)
+    } else {
+        get_input_reader(&args.files, CodecDirection::Compress)?
+    };
+    let mut writer = CompareWriter::new(original);
+    // Compression adapted the custom model, so it must return to its initial state:
+    if let Some(user_model) = user_model.as_mut() {
+        user_model.get_model().flush();
+    }
+    let decompressed = decompress_with_model(
+        compressed,
+        &mut writer,
+        config,
+        user_model,
+        parser,
+        options,
+        args.threads,
+    );
+    // A difference in the data makes decompression fail to write, but it is the actual error:
+    if let Some(mismatch) = writer.mismatch() {
+        return Err(mismatch.into());
+    }
+    decompressed?;
+    Ok(writer.finish()?)
+}
+
+/// Maps the number of times the verbose flag was given to the log level it enables
+fn verbosity_level(verbose: u8) -> LevelFilter {
+    match verbose {
+        0 => LevelFilter::Error,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Initializes the logger with the level chosen by the verbose flag. Filters in RUST_LOG are
+/// applied after it, so they override it
+fn init_logger(verbose: u8) {
+    env_logger::Builder::new()
+        .filter_level(verbosity_level(verbose))
+        .parse_default_env()
+        .init();
+}
+
+/// Compresses according to _args_ (the arguments of the compress command)
+fn compress(args: &CodecArgs) -> anyhow::Result<()> {
+    let start = Instant::now();
+    let mut config = CodecConfig::resolve(args, |key| std::env::var(key).ok())?;
+    debug!("Resolved codec configuration: {:?}", config);
+    let mut user_model = load_user_model(args)?;
+    let CodecStreams {
+        input,
+        header,
+        mut parser,
+    } = parse_codec_args(
+        args,
+        &mut config,
+        user_model.as_ref(),
+        CodecDirection::Compress,
+    )?;
+    let format = args.format.unwrap_or_default();
+    let input = with_progress(input, args);
+    // Input files can be read again for verification, but piped input has to be kept:
+    let mut piped_input = Vec::new();
+    let input: Box<dyn Read + '_> = if args.verify && args.files.is_empty() {
+        Box::new(TeeReader::new(input, &mut piped_input))
+    } else {
+        input
+    };
+    // An estimate runs the whole compression, but only counts the output's bytes:
+    let mut estimated = ByteCounter::new(std::io::sink());
+    let output: Box<dyn Write + '_> = if args.estimate {
+        Box::new(&mut estimated)
     } else {
-        Box::new(crate::parser::ByteParser)
+        get_output_writer(args.output.as_ref(), &args.files, format.is_binary())?
     };
-    Ok((bytes, parser))
+    // The whole stream is encoded, so its header and checksum are decoded back as they were:
+    let mut encoder = TextEncoder::new(output, format);
+    let output: Box<dyn Write + '_> = Box::new(&mut encoder);
+    // A verified output is only written once its decompression matched the input:
+    let mut compressed = Vec::new();
+    let (output, verified_output): (Box<dyn Write + '_>, _) = if args.verify {
+        (Box::new(&mut compressed), Some(output))
+    } else {
+        (output, None)
+    };
+    let dictionary = load_dictionary(args)?;
+    let options = CodecOptions {
+        dictionary: dictionary.as_ref(),
+        header: Some(header),
+        // Several files are compressed with their table, so only a single file's size is the
+        // input's length:
+        input_len: input_size(&args.files).filter(|_| !header.files),
+        ..Default::default()
+    };
+    // Compress according to the model:
+    let report = if header.blocks {
+        check_block_model(&config, user_model.is_some())?;
+        let block_options = BlockOptions {
+            block_size: args.block_size.expect("Blocks are only used with a size"),
+            threads: block_threads(args.threads),
+        };
+        // The model was checked above, so it's a builtin model the config can build:
+        let new_model = || config.get_model().expect("Model isn't built from input");
+        let new_parser = || config.parser();
+        compress_blocks(
+            input,
+            output,
+            new_model,
+            new_parser,
+            &options,
+            block_options,
+        )
+    } else {
+        match select_model(&config, user_model.as_mut()) {
+            Some(mut model) => compress_reader(input, output, &mut model, &mut parser, &options),
+            // Files can be read twice, piped input has to be buffered:
+            None => {
+                let sim = config.sim();
+                match args.files.as_slice() {
+                    [path] => {
+                        // The file is reopened as a seekable reader instead of the input:
+                        drop(input);
+                        let file = File::open(path)?;
+                        if args.progress {
+                            let size = input_size(&args.files);
+                            let file = ProgressReader::new(file, size, std::io::stderr());
+                            compress_two_pass(file, output, &mut parser, sim, &options)
+                        } else {
+                            compress_two_pass(file, output, &mut parser, sim, &options)
+                        }
+                    }
+                    _ => compress_two_pass_buffered(
+                        input,
+                        output,
+                        &mut parser,
+                        sim,
+                        &options,
+                        DEFAULT_TWO_PASS_BUFFER_LIMIT,
+                    ),
+                }
+            }
+        }
+    };
+    // A partially written output is not a valid compressed file, so don't leave it behind:
+    let report = report.inspect_err(|_| remove_partial_output(args.output.as_ref()))?;
+    info!("Compression finished: {report}");
+    // Mapping consumes the verified output either way, which ends its borrows here:
+    let verified = verified_output.map(|mut output| {
+        verify_compression(
+            &compressed,
+            &piped_input,
+            args,
+            &config,
+            user_model.as_mut(),
+            &mut *parser,
+            &options,
+        )
+        .and_then(|()| {
+            output
+                .write_all(&compressed)
+                .and_then(|()| output.flush())
+                .map_err(|e| OutputWriteError::from(e).into())
+        })
+    });
+    if let Some(verified) = verified {
+        verified.inspect_err(|_| remove_partial_output(args.output.as_ref()))?;
+        info!("Verified that the compressed output decompresses into the input");
+    }
+    encoder
+        .finish()
+        .map_err(OutputWriteError::from)
+        .inspect_err(|_| remove_partial_output(args.output.as_ref()))?;
+    if args.estimate {
+        let estimate = SizeEstimate {
+            input_bytes: report.input_bytes as u64,
+            output_bytes: estimated.byte_count(),
+        };
+        eprintln!("{estimate}");
+    }
+    if args.stats {
+        let stats = CodecStats {
+            direction: CodecDirection::Compress,
+            input_bytes: report.input_bytes as u64,
+            output_bytes: report.output_bytes() as u64,
+            elapsed: start.elapsed(),
+            skipped_symbols: report.skipped_symbols,
+        };
+        eprintln!("{stats}");
+    }
+    Ok(())
+}
+
+/// Decompresses according to _args_ (the arguments of the decompress command)
+fn decompress(args: &CodecArgs) -> anyhow::Result<()> {
+    let start = Instant::now();
+    let mut config = CodecConfig::resolve(args, |key| std::env::var(key).ok())?;
+    debug!("Resolved codec configuration: {:?}", config);
+    let mut user_model = load_user_model(args)?;
+    let CodecStreams {
+        input,
+        header,
+        mut parser,
+    } = parse_codec_args(
+        args,
+        &mut config,
+        user_model.as_ref(),
+        CodecDirection::Decompress,
+    )?;
+    // The files of a multi-file stream are only split once all of it was decompressed:
+    let mut files_data = Vec::new();
+    let output: Box<dyn Write + '_> = if header.files {
+        Box::new(&mut files_data)
+    } else {
+        get_output_writer(args.output.as_ref(), &args.files, false)?
+    };
+    // Decompression doesn't report its sizes, so count them as they pass:
+    let input = with_progress(input, args);
+    let (mut input, mut output) = (ByteCounter::new(input), ByteCounter::new(output));
+    let dictionary = load_dictionary(args)?;
+    let options = CodecOptions {
+        dictionary: dictionary.as_ref(),
+        trailing: args.trailing,
+        header: Some(header),
+        ..Default::default()
+    };
+    decompress_with_model(
+        &mut input,
+        &mut output,
+        &config,
+        user_model.as_mut(),
+        &mut *parser,
+        &options,
+        args.threads,
+    )?;
+    let (input_bytes, output_bytes) = (input.byte_count(), output.byte_count());
+    // Dropping the input ends its progress line, before anything else is printed:
+    drop((input, output));
+    if header.files {
+        write_split_files(&files_data, args.output.as_ref())?;
+    }
+    if args.stats {
+        let stats = CodecStats {
+            direction: CodecDirection::Decompress,
+            input_bytes,
+            output_bytes,
+            elapsed: start.elapsed(),
+            skipped_symbols: 0,
+        };
+        eprintln!("{stats}");
+    }
+    Ok(())
+}
+
+/// Compresses or decompresses (according to _direction_) the input given by _args_
+fn run_codec(args: &CodecArgs, direction: CodecDirection) -> anyhow::Result<()> {
+    match direction {
+        CodecDirection::Compress => compress(args),
+        CodecDirection::Decompress => decompress(args),
+    }
 }
 
 /// Runs the CLI
 pub fn run() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    init_logger(cli.verbose);
 
     match cli.commands {
-        Commands::Compress(args) => {
-            let (bytes, parser) = parse_codec_args(&args)?;
-            // Compress according to the model:
-            match args.custom_model {
-                None => {
-                    let mut model = args.model.get_model();
-                    let compressor = Compressor::new(&mut model);
-                    compress(bytes, compressor, parser);
-                }
-                Some(model_name) => {
-                    let mut user_model: UserModel<DefaultSIM> = UserModel::from_name(&model_name)?;
-                    let compressor = Compressor::new(user_model.get_model());
-                    compress(bytes, compressor, parser);
+        Some(Commands::Compress(args)) => compress(&args)?,
+        Some(Commands::Decompress(args)) => decompress(&args)?,
+        Some(Commands::SelfTest) => {
+            self_test::run_self_test()?;
+            eprintln!("Self-test passed");
+        }
+        Some(Commands::Bench(args)) => run_bench(&args)?,
+        Some(Commands::Info(args)) => run_info(&args)?,
+        None => run_streaming(cli.streaming)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify::VerifyError;
+    use super::*;
+
+    #[test]
+    fn test_verbosity_levels() {
+        let level = |args: &[&str]| {
+            let cli = Cli::try_parse_from(["ppm-cli"].iter().chain(args)).unwrap();
+            verbosity_level(cli.verbose)
+        };
+        assert_eq!(level(&["self-test"]), LevelFilter::Error);
+        assert_eq!(level(&["-v", "self-test"]), LevelFilter::Info);
+        assert_eq!(level(&["self-test", "-vv"]), LevelFilter::Debug);
+        assert_eq!(
+            level(&["--verbose", "-vv", "self-test"]),
+            LevelFilter::Trace
+        );
+        assert_eq!(level(&["-vvvvv", "self-test"]), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn test_piped_stdin_is_accepted() {
+        assert!(check_stdin_input(false, CodecDirection::Compress).is_ok());
+        assert!(check_stdin_input(false, CodecDirection::Decompress).is_ok());
+    }
+
+    #[test]
+    fn test_terminal_stdin_missing_input_errors() {
+        assert!(matches!(
+            check_stdin_input(true, CodecDirection::Compress),
+            Err(InputFileError::MissingInputFile)
+        ));
+        assert!(matches!(
+            check_stdin_input(true, CodecDirection::Decompress),
+            Err(InputFileError::MissingCompressedInput)
+        ));
+    }
+
+    #[test]
+    fn test_terminal_stdout_rejected_only_for_binary_output() {
+        assert!(matches!(
+            check_stdout_output(true, true),
+            Err(OutputFileError::TerminalOutput)
+        ));
+        assert!(check_stdout_output(false, true).is_ok());
+        // Decompressed data is the user's original data, and text-encoded compressed data is
+        // meant to be copied, so both may be shown in a terminal:
+        assert!(check_stdout_output(true, false).is_ok());
+    }
+
+    #[test]
+    fn test_output_into_input_rejected() {
+        let directory = std::env::temp_dir().join(format!("output-input-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let input = directory.join("input.txt");
+        std::fs::write(&input, b"the input").unwrap();
+        let inputs = [input.clone()];
+
+        // The same file is caught through another path to it:
+        let other_path = directory.join(".").join("input.txt");
+        assert!(matches!(
+            check_output_path(&other_path, &inputs),
+            Err(OutputFileError::OutputIsInput(path)) if path == input
+        ));
+        // The input wasn't touched:
+        assert_eq!(std::fs::read(&input).unwrap(), b"the input");
+
+        assert!(check_output_path(&directory.join("output.ppm"), &inputs).is_ok());
+        std::fs::write(directory.join("other.txt"), b"another file").unwrap();
+        assert!(check_output_path(&directory.join("other.txt"), &inputs).is_ok());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_multiple_compressed_inputs_rejected() {
+        let paths = [PathBuf::from("a.ppm"), PathBuf::from("b.ppm")];
+        assert!(matches!(
+            get_input_reader(&paths, CodecDirection::Decompress),
+            Err(InputFileError::MultipleCompressedInputs(2))
+        ));
+    }
+
+    #[test]
+    fn test_three_files_round_trip() {
+        let directory = std::env::temp_dir().join(format!("multi-file-{}", std::process::id()));
+        let (input_dir, output_dir) = (directory.join("input"), directory.join("output"));
+        std::fs::create_dir_all(&input_dir).unwrap();
+        let files: [(&str, &[u8]); 3] = [
+            ("notes.txt", b"some notes about the other files\n"),
+            ("empty", b""),
+            ("data.bin", &[0, 1, 2, 3, 255, 254, 253]),
+        ];
+        let paths: Vec<PathBuf> = files
+            .iter()
+            .map(|(name, content)| {
+                let path = input_dir.join(name);
+                std::fs::write(&path, content).unwrap();
+                path
+            })
+            .collect();
+
+        let options = CodecOptions {
+            header: Some(Header {
+                files: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut input = get_input_reader(&paths, CodecDirection::Compress).unwrap();
+        let mut model = ppm_cli::models::adaptive::AdaptiveModel::new(ppm_cli::sim::DefaultSIM);
+        let mut parser = ppm_cli::parser::ByteParser;
+        let mut compressed = Vec::new();
+        compress_reader(
+            &mut input,
+            &mut compressed,
+            &mut model,
+            &mut parser,
+            &options,
+        )
+        .unwrap();
+        let mut decompressed = Vec::new();
+        decompress_reader(
+            &compressed[..],
+            &mut decompressed,
+            &mut model,
+            &mut parser,
+            &options,
+        )
+        .unwrap();
+
+        write_split_files(&decompressed, Some(&output_dir)).unwrap();
+        let results: Vec<Vec<u8>> = files
+            .iter()
+            .map(|(name, _)| std::fs::read(output_dir.join(name)).unwrap())
+            .collect();
+        std::fs::remove_dir_all(&directory).unwrap();
+        for ((_, content), result) in files.iter().zip(results) {
+            assert_eq!(&result, content);
+        }
+    }
+
+    #[test]
+    fn test_base64_round_trip() {
+        let directory = std::env::temp_dir().join(format!("base64-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let input = b"text formats let compressed streams be pasted as text".repeat(20);
+        let [original, encoded, decoded] = ["input", "input.ppm.b64", "decoded"]
+            .map(|name| directory.join(name).to_str().unwrap().to_string());
+        std::fs::write(&original, &input).unwrap();
+
+        let run = |args: &[&str]| {
+            let cli = Cli::try_parse_from(["ppm-cli"].iter().chain(args)).unwrap();
+            match cli.commands {
+                Some(Commands::Compress(args)) => compress(&args),
+                Some(Commands::Decompress(args)) => decompress(&args),
+                _ => panic!("Expected a codec command"),
+            }
+            .unwrap()
+        };
+        run(&["compress", &original, "-o", &encoded, "--format", "base64"]);
+        let text = std::fs::read(&encoded).unwrap();
+        assert!(text
+            .iter()
+            .all(|byte| byte.is_ascii_graphic() || *byte == b'\n'));
+
+        // The format is given, or detected from the input:
+        let mut results = Vec::new();
+        for format in [&["--format", "base64"][..], &[]] {
+            run(&[&["decompress", &encoded, "-o", &decoded], format].concat());
+            results.push(std::fs::read(&decoded).unwrap());
+        }
+        std::fs::remove_dir_all(&directory).unwrap();
+        for result in results {
+            assert_eq!(result, input);
+        }
+    }
+
+    #[test]
+    fn test_select_model_boxes_builtin_and_custom_models() {
+        let config = CodecConfig::defaults(BuiltinModel::Order0.into(), false);
+        let model: Box<dyn Model> = select_model(&config, None).unwrap();
+        assert_eq!(model.alphabet_size(), 258);
+        let two_pass = CodecConfig::defaults(BuiltinModel::TwoPass.into(), false);
+        assert!(select_model(&two_pass, None).is_none());
+
+        // A custom model is used instead of the configured one, even the two-pass model:
+        let path = std::env::temp_dir().join(format!("select-model-{}.json", std::process::id()));
+        let frequencies = vec![3; 258];
+        std::fs::write(&path, format!(r#"{{"frequencies": {frequencies:?}}}"#)).unwrap();
+        let mut user_model = UserModel::from_file(&path, Frequency::zero()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let model = select_model(&two_pass, Some(&mut user_model)).unwrap();
+        assert_eq!(*model.get_total(), 3 * 258);
+    }
+
+    #[test]
+    fn test_verification_finds_the_first_difference() {
+        let cli = Cli::try_parse_from(["ppm-cli", "compress", "--verify", "--model", "order0"]);
+        let Some(Commands::Compress(args)) = cli.unwrap().commands else {
+            panic!("Expected the compress command");
+        };
+        let config = CodecConfig::resolve(&args, |_| None).unwrap();
+        let options = CodecOptions {
+            header: Some(config.header(false)),
+            ..Default::default()
+        };
+        let mut parser = ppm_cli::parser::ByteParser;
+        let mut compressed = Vec::new();
+        let mut model = config.get_model().unwrap();
+        compress_reader(
+            &b"hello world"[..],
+            &mut compressed,
+            &mut model,
+            &mut parser,
+            &options,
+        )
+        .unwrap();
+
+        let mut verify = |input: &[u8]| {
+            verify_compression(
+                &compressed,
+                input,
+                &args,
+                &config,
+                None,
+                &mut parser,
+                &options,
+            )
+            .map_err(|e| e.downcast::<VerifyError>().unwrap())
+        };
+        assert!(verify(b"hello world").is_ok());
+        assert_eq!(
+            verify(b"hello wOrld").unwrap_err(),
+            VerifyError::Mismatch(7)
+        );
+        assert_eq!(verify(b"hello").unwrap_err(), VerifyError::Mismatch(5));
+        assert_eq!(
+            verify(b"hello world!").unwrap_err(),
+            VerifyError::Truncated(11)
+        );
+    }
+
+    #[test]
+    fn test_estimate_equals_real_output_length() {
+        let inputs: [&[u8]; 4] = [
+            b"",
+            b"a",
+            b"abracadabra abracadabra abracadabra",
+            &(0..=u8::MAX).cycle().take(3000).collect::<Vec<_>>(),
+        ];
+        for model in [
+            BuiltinModel::Order0,
+            BuiltinModel::Ppm,
+            BuiltinModel::TwoPass,
+            BuiltinModel::Order1Lazy,
+        ] {
+            for bit_mode in [false, true] {
+                let config = CodecConfig::defaults(model.clone().into(), bit_mode);
+                let options = CodecOptions {
+                    header: Some(config.header(false)),
+                    ..Default::default()
+                };
+                let compress = |input: &[u8], output: &mut dyn Write| {
+                    let mut parser = config.parser();
+                    match config.get_model() {
+                        Some(mut model) => {
+                            compress_reader(input, output, &mut model, &mut parser, &options)
+                        }
+                        None => compress_two_pass(
+                            Cursor::new(input),
+                            output,
+                            &mut parser,
+                            config.sim(),
+                            &options,
+                        ),
+                    }
+                };
+                for input in inputs {
+                    let mut estimated = ByteCounter::new(std::io::sink());
+                    let report = compress(input, &mut estimated).unwrap();
+                    let mut written = Vec::new();
+                    compress(input, &mut written).unwrap();
+                    assert_eq!(estimated.byte_count(), written.len() as u64);
+                    assert_eq!(report.output_bytes(), written.len());
                 }
             }
         }
-        Commands::Decompress(CodecArgs { .. }) => {}
     }
-    Ok(())
 }
@@ -15,60 +15,387 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::models::distributions::{
+use super::registry::ModelSettings;
+use clap::ValueEnum;
+use ppm_cli::frequencies::Frequency;
+use ppm_cli::models::adaptive::{AdaptiveModel, UpdateRule};
+use ppm_cli::models::contexts::{DenseContextTables, LazyContextTables};
+use ppm_cli::models::distributions::{
     custom::CustomDistributionModel, uniform::UniformDistributionModel,
 };
-use crate::models::Model;
-use crate::parser::{ByteParser, Parser};
-use crate::sim::{DefaultSIM, SymbolIndexMapping};
-use anyhow::Result;
-use clap::ValueEnum;
+use ppm_cli::models::order1::Order1Model;
+use ppm_cli::models::ppm::PpmModel;
+use ppm_cli::models::snapshot::{ModelSnapshot, SnapshotError};
+use ppm_cli::models::Model;
+use ppm_cli::parser::{ByteParser, Parser};
+use ppm_cli::sim::{BitSIM, DefaultSIM, SymbolIndexMapping};
 use std::fmt::{Display, Formatter};
+use std::num::NonZeroU64;
+use std::path::Path;
+use thiserror::Error;
+
+/// The SIM of models that compress in bit-mode if _bit_mode_ is true, or in byte-mode otherwise
+pub fn mode_sim(bit_mode: bool) -> &'static dyn SymbolIndexMapping {
+    if bit_mode {
+        &BitSIM
+    } else {
+        &DefaultSIM
+    }
+}
+
+/// The parsers converting the input's bytes into symbols
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ParserChoice {
+    /// Every byte is a symbol
+    Byte,
+    /// Every bit is a symbol (bit-mode)
+    Bit,
+    /// Runs of identical bytes are run-length encoded, then every byte is a symbol
+    Rle,
+    /// Every byte is move-to-front transformed into a symbol
+    Mtf,
+}
+
+/// How the tables of adaptive models learn from the input
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adaptation {
+    /// Once a single frequency passes this threshold, the table is rescaled
+    pub rescale_at: Frequency,
+    /// The amount a compressed symbol's frequency is incremented by (ignored by sub-linear
+    /// updates)
+    pub increment: Frequency,
+    /// The number of updates after which the table is halved, if it's aged
+    pub half_life: Option<NonZeroU64>,
+}
 
 /// Builtin models the user can use for compression/decompression
-#[derive(Debug, Clone, ValueEnum)]
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
 pub enum BuiltinModel {
     Uniform,
+    Order0,
+    Order0Sublinear,
+    Ppm,
+    /// Counts the input's frequencies before compressing it, and stores them in the output
+    TwoPass,
+    /// Predicts every byte by the byte before it, holding the tables of all 256 contexts
+    Order1,
+    /// Like order1, but only allocates the table of a context once the input uses it, which
+    /// saves memory on inputs with few distinct bytes
+    Order1Lazy,
 }
 
 impl BuiltinModel {
-    pub fn get_model(&self) -> impl Model {
+    /// Builds the model over the symbols of the SIM of _settings_. The model order is only used by
+    /// context-based models, the escape method only by the PPM model, and the adaptation only by
+    /// adaptive models (PPM only uses its rescale threshold). The tables of adaptive models are
+    /// kept within the frequency bits of the settings' params.<br>
+    /// Returns None for the two-pass model, which is built from the input itself by the two-pass
+    /// codec.
+    pub fn get_model(&self, settings: &ModelSettings) -> Option<Box<dyn Model>> {
+        let ModelSettings {
+            sim,
+            model_order,
+            escape_method,
+            adaptation,
+            params,
+        } = *settings;
+        let total_cap = params.max_total();
+        let adaptive = |update_rule| {
+            let model = AdaptiveModel::with_update_rule(sim, adaptation.rescale_at, update_rule)
+                .with_total_cap(total_cap)
+                .with_increment(adaptation.increment);
+            match adaptation.half_life {
+                Some(half_life) => model.with_half_life(half_life),
+                None => model,
+            }
+        };
+        // The table every context of the order-1 models starts with:
+        let order1_table = || {
+            let mut table = AdaptiveModel::initial_table(&sim, adaptation.rescale_at);
+            table.limit_total(total_cap);
+            table
+        };
+        match self {
+            BuiltinModel::Uniform => Some(Box::new(UniformDistributionModel::new(sim))),
+            BuiltinModel::Order0 => Some(Box::new(adaptive(UpdateRule::Linear))),
+            BuiltinModel::Order0Sublinear => Some(Box::new(adaptive(UpdateRule::Sublinear))),
+            BuiltinModel::Ppm => Some(Box::new(
+                PpmModel::with_frequency_cap(sim, model_order as usize, adaptation.rescale_at)
+                    .with_total_cap(total_cap)
+                    .with_escape_method(escape_method),
+            )),
+            BuiltinModel::TwoPass => None,
+            BuiltinModel::Order1 => {
+                let initial = order1_table();
+                let contexts = DenseContextTables::new(initial.clone());
+                Some(Box::new(Order1Model::with_tables(sim, contexts, initial)))
+            }
+            BuiltinModel::Order1Lazy => {
+                let initial = order1_table();
+                let contexts = LazyContextTables::new(initial.clone());
+                Some(Box::new(Order1Model::with_tables(sim, contexts, initial)))
+            }
+        }
+    }
+
+    /// The id of the model in the header of compressed streams. Ids must never change, or streams
+    /// compressed by older versions would be decompressed with the wrong model
+    pub fn id(&self) -> u8 {
         match self {
-            BuiltinModel::Uniform => UniformDistributionModel::new(DefaultSIM),
+            BuiltinModel::Uniform => 0,
+            BuiltinModel::Order0 => 1,
+            BuiltinModel::Order0Sublinear => 2,
+            BuiltinModel::Ppm => 3,
+            BuiltinModel::TwoPass => 4,
+            BuiltinModel::Order1 => 5,
+            BuiltinModel::Order1Lazy => 6,
+        }
+    }
+
+    /// Finds the builtin model with the given header id, if there is one
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::value_variants()
+            .iter()
+            .find(|model| model.id() == id)
+            .cloned()
+    }
+
+    /// The name the model is selected by
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuiltinModel::Uniform => "uniform",
+            BuiltinModel::Order0 => "order0",
+            BuiltinModel::Order0Sublinear => "order0-sublinear",
+            BuiltinModel::Ppm => "ppm",
+            BuiltinModel::TwoPass => "two-pass",
+            BuiltinModel::Order1 => "order1",
+            BuiltinModel::Order1Lazy => "order1-lazy",
         }
     }
 
     pub fn get_parser(&self) -> impl Parser {
         match self {
-            BuiltinModel::Uniform => ByteParser,
+            BuiltinModel::Uniform
+            | BuiltinModel::Order0
+            | BuiltinModel::Order0Sublinear
+            | BuiltinModel::Ppm
+            | BuiltinModel::TwoPass
+            | BuiltinModel::Order1
+            | BuiltinModel::Order1Lazy => ByteParser,
         }
     }
 }
 
 impl Display for BuiltinModel {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            BuiltinModel::Uniform => write!(f, "uniform"),
-        }
+        write!(f, "{}", self.name())
     }
 }
 
 /// Custom models made by the user
-pub struct UserModel<SIM: SymbolIndexMapping> {
+pub struct UserModel {
     /// The model's name
     name: String,
     /// If it's a bit-model or byte-model
     is_bit_model: bool,
-    /// The actual custom distribution
-    custom_distribution_model: CustomDistributionModel<SIM>,
+    /// The actual custom distribution, over the bit alphabet for bit-models and the byte alphabet
+    /// for byte-models
+    custom_distribution_model: CustomDistributionModel<&'static dyn SymbolIndexMapping>,
+}
+
+/// When trying to load a custom model, the following errors may occur
+#[derive(Debug, Error)]
+pub enum UserModelError {
+    #[error("Failed to read the custom model file: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Invalid custom model file: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    #[error("Custom model has {found} frequencies, expected exactly {expected} (one per symbol)")]
+    WrongFrequencyCount { expected: usize, found: usize },
+    #[error("Frequency of symbol {index} in the custom model is {value}, which is more than the maximum of {max}")]
+    FrequencyTooLarge { index: usize, value: u64, max: u64 },
+    #[error("Invalid custom model distribution: {0}")]
+    InvalidDistribution(anyhow::Error),
+    #[error("The stream was compressed in {stream_mode}-mode, but the custom model \"{name}\" is a {model_mode}-model")]
+    ModeMismatch {
+        name: String,
+        stream_mode: &'static str,
+        model_mode: &'static str,
+    },
 }
 
-impl<SIM: SymbolIndexMapping> UserModel<SIM> {
-    pub fn get_model(&mut self) -> &mut CustomDistributionModel<SIM> {
+impl UserModel {
+    pub fn get_model(&mut self) -> &mut CustomDistributionModel<&'static dyn SymbolIndexMapping> {
         &mut self.custom_distribution_model
     }
 
-    pub fn from_name(_name: &str) -> Result<Self> {
-        todo!("Implement according to todo-features.txt")
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_bit_model(&self) -> bool {
+        self.is_bit_model
+    }
+
+    /// Makes sure the model can decode a stream compressed in bit-mode if _bit_mode_ is true, or
+    /// in byte-mode otherwise
+    pub fn check_mode(&self, bit_mode: bool) -> Result<(), UserModelError> {
+        let mode_name = |bit| if bit { "bit" } else { "byte" };
+        if self.is_bit_model == bit_mode {
+            Ok(())
+        } else {
+            Err(UserModelError::ModeMismatch {
+                name: self.name.clone(),
+                stream_mode: mode_name(bit_mode),
+                model_mode: mode_name(self.is_bit_model),
+            })
+        }
+    }
+
+    /// Loads a custom model from a JSON file of the form
+    /// `{"name": "...", "bit_model": false, "frequencies": [...]}`, holding one frequency per index
+    /// of the SIM of the model's mode (4 for bit-models, 258 for byte-models). The name and
+    /// bit-model flag are optional. _smoothing_ is added to the frequency of every symbol (see
+    /// `CustomDistributionModel::with_smoothing`), zero keeps the model's distribution as is.
+    pub fn from_file(path: &Path, smoothing: Frequency) -> Result<Self, UserModelError> {
+        let content = std::fs::read_to_string(path)?;
+        let default_name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self::from_json(&content, default_name, smoothing)
+    }
+
+    /// Parses a custom model from the content of a custom model file. _default_name_ is used if the
+    /// content doesn't name the model
+    fn from_json(
+        content: &str,
+        default_name: String,
+        smoothing: Frequency,
+    ) -> Result<Self, UserModelError> {
+        // Custom model files are model snapshots, so exported models can be loaded directly:
+        let file: ModelSnapshot = serde_json::from_str(content)?;
+        let sim = mode_sim(file.bit_model);
+        let frequencies = file.frequencies_for(&sim).map_err(|e| match e {
+            SnapshotError::WrongFrequencyCount { expected, found } => {
+                UserModelError::WrongFrequencyCount { expected, found }
+            }
+            SnapshotError::FrequencyTooLarge { index, value } => {
+                UserModelError::FrequencyTooLarge {
+                    index,
+                    value,
+                    max: *Frequency::max(),
+                }
+            }
+            e => UserModelError::InvalidDistribution(e.into()),
+        })?;
+
+        Ok(Self {
+            name: file.name.unwrap_or(default_name),
+            is_bit_model: file.bit_model,
+            custom_distribution_model: CustomDistributionModel::with_smoothing(
+                sim,
+                &frequencies,
+                smoothing,
+            )
+            .map_err(UserModelError::InvalidDistribution)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_ids_round_trip() {
+        for model in BuiltinModel::value_variants() {
+            assert_eq!(BuiltinModel::from_id(model.id()).as_ref(), Some(model));
+        }
+        assert_eq!(BuiltinModel::from_id(u8::MAX), None);
+    }
+
+    /// Forms the content of a custom model file with the given frequencies
+    fn model_json(frequencies: &[u64], bit_model: bool) -> String {
+        format!(r#"{{"name": "test", "bit_model": {bit_model}, "frequencies": {frequencies:?}}}"#)
+    }
+
+    #[test]
+    fn test_user_model_from_json() {
+        let frequencies: Vec<u64> = (1..=BitSIM.supported_symbols_count() as u64).collect();
+        let user_model = UserModel::from_json(
+            &model_json(&frequencies, true),
+            "default".into(),
+            Frequency::zero(),
+        )
+        .expect("Valid model file rejected");
+        assert_eq!(user_model.name(), "test");
+        assert!(user_model.is_bit_model());
+        assert!(user_model.check_mode(true).is_ok());
+        assert!(matches!(
+            user_model.check_mode(false),
+            Err(UserModelError::ModeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_user_model_defaults() {
+        let frequencies = vec![1; DefaultSIM.supported_symbols_count()];
+        let content = format!(r#"{{"frequencies": {frequencies:?}}}"#);
+        let user_model = UserModel::from_json(&content, "default".into(), Frequency::zero())
+            .expect("Valid model file rejected");
+        assert_eq!(user_model.name(), "default");
+        assert!(!user_model.is_bit_model());
+    }
+
+    #[test]
+    fn test_user_model_wrong_frequency_count() {
+        let frequencies = vec![1; DefaultSIM.supported_symbols_count() - 1];
+        assert!(matches!(
+            UserModel::from_json(&model_json(&frequencies, false), String::new(), Frequency::zero()),
+            Err(UserModelError::WrongFrequencyCount { expected, found })
+                if expected == DefaultSIM.supported_symbols_count() && found == expected - 1
+        ));
+    }
+
+    #[test]
+    fn test_user_model_frequency_too_large() {
+        let mut frequencies = vec![1; DefaultSIM.supported_symbols_count()];
+        frequencies[7] = *Frequency::max() + 1;
+        assert!(matches!(
+            UserModel::from_json(
+                &model_json(&frequencies, false),
+                String::new(),
+                Frequency::zero()
+            ),
+            Err(UserModelError::FrequencyTooLarge { index: 7, .. })
+        ));
+    }
+
+    #[test]
+    fn test_user_model_alphabet_follows_mode() {
+        // A bit-model needs a frequency per bit symbol, not per byte symbol:
+        let frequencies = vec![1; DefaultSIM.supported_symbols_count()];
+        assert!(matches!(
+            UserModel::from_json(
+                &model_json(&frequencies, true),
+                String::new(),
+                Frequency::zero()
+            ),
+            Err(UserModelError::WrongFrequencyCount { expected: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn test_user_model_invalid_files() {
+        let zeros = vec![0; DefaultSIM.supported_symbols_count()];
+        assert!(matches!(
+            UserModel::from_json(&model_json(&zeros, false), String::new(), Frequency::zero()),
+            Err(UserModelError::InvalidDistribution(_))
+        ));
+        assert!(matches!(
+            UserModel::from_json("[1, 2, 3]", String::new(), Frequency::zero()),
+            Err(UserModelError::InvalidJson(_))
+        ));
     }
 }
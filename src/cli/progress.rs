@@ -0,0 +1,248 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::time::{Duration, Instant};
+
+/// The minimal time between two redraws of the progress line
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The number of characters in the bar of a progress line
+const BAR_WIDTH: usize = 30;
+
+/// The frames of the spinner shown when the size of the input is unknown
+const SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+
+/// A single line describing the progress of reading the input, drawn by the --progress flag
+#[derive(Debug)]
+struct ProgressLine {
+    /// Number of bytes read so far
+    bytes: u64,
+    /// The size of the input, or None if it isn't known (piped input)
+    total: Option<u64>,
+    /// Time passed since reading started
+    elapsed: Duration,
+    /// Number of times the line was drawn, which animates the spinner
+    frame: usize,
+}
+
+impl Display for ProgressLine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.total {
+            Some(total) => {
+                // Multi-file tables are read on top of the files' sizes, so clamp the progress:
+                let fraction = match total {
+                    0 => 1.0,
+                    _ => (self.bytes as f64 / total as f64).min(1.0),
+                };
+                let filled = (fraction * BAR_WIDTH as f64) as usize;
+                write!(
+                    f,
+                    "[{}{}] {:>3}% {} / {}",
+                    "#".repeat(filled),
+                    "-".repeat(BAR_WIDTH - filled),
+                    (fraction * 100.0) as u32,
+                    Size(self.bytes.min(total)),
+                    Size(total)
+                )?;
+            }
+            None => write!(
+                f,
+                "{} {} processed",
+                SPINNER_FRAMES[self.frame % SPINNER_FRAMES.len()],
+                Size(self.bytes)
+            )?,
+        }
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            write!(f, " ({}/s)", Size((self.bytes as f64 / seconds) as u64))?;
+        }
+        Ok(())
+    }
+}
+
+/// A number of bytes, displayed in the largest fitting decimal unit
+struct Size(u64);
+
+impl Display for Size {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+        if self.0 < 1000 {
+            return write!(f, "{} B", self.0);
+        }
+        let mut value = self.0 as f64 / 1000.0;
+        let mut unit = 0;
+        while value >= 1000.0 && unit + 1 < UNITS.len() {
+            value /= 1000.0;
+            unit += 1;
+        }
+        write!(f, "{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Wraps the input of compression/decompression, drawing the progress of reading it to _out_
+/// (stderr, so it never mixes with output piped to stdout).<br>
+/// If the size of the input is known a progress bar is drawn, otherwise a spinner with the number
+/// of bytes read. The final state of the line is drawn once the reader is dropped, if it was read.
+pub struct ProgressReader<R, W: Write> {
+    inner: R,
+    out: W,
+    bytes: u64,
+    total: Option<u64>,
+    start: Instant,
+    last_draw: Option<Instant>,
+    frame: usize,
+}
+
+impl<R, W: Write> ProgressReader<R, W> {
+    pub fn new(inner: R, total: Option<u64>, out: W) -> Self {
+        Self {
+            inner,
+            out,
+            bytes: 0,
+            total,
+            start: Instant::now(),
+            last_draw: None,
+            frame: 0,
+        }
+    }
+
+    /// Redraws the progress line, unless it was drawn recently and _force_ isn't set
+    fn draw(&mut self, force: bool) {
+        let now = Instant::now();
+        if !force
+            && self
+                .last_draw
+                .is_some_and(|last| now - last < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        self.last_draw = Some(now);
+        let line = ProgressLine {
+            bytes: self.bytes,
+            total: self.total,
+            elapsed: now - self.start,
+            frame: self.frame,
+        };
+        self.frame = self.frame.wrapping_add(1);
+        // Progress is only informative, failing to draw it shouldn't fail the operation:
+        let _ = write!(self.out, "\r{line}\x1b[K").and_then(|_| self.out.flush());
+    }
+}
+
+impl<R: Read, W: Write> Read for ProgressReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes += read as u64;
+        self.draw(false);
+        Ok(read)
+    }
+}
+
+/// The two-pass model reads its input twice, so seeking moves the progress back with the input
+impl<R: Seek, W: Write> Seek for ProgressReader<R, W> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = self.inner.seek(pos)?;
+        self.bytes = position;
+        Ok(position)
+    }
+}
+
+impl<R, W: Write> Drop for ProgressReader<R, W> {
+    fn drop(&mut self) {
+        // An input that was never read (such as a file the two-pass model reopens) has no line:
+        if self.last_draw.is_none() {
+            return;
+        }
+        self.draw(true);
+        let _ = writeln!(self.out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sizes_use_largest_fitting_unit() {
+        assert_eq!(Size(999).to_string(), "999 B");
+        assert_eq!(Size(1_500).to_string(), "1.5 KB");
+        assert_eq!(Size(2_750_000_000).to_string(), "2.8 GB");
+        assert_eq!(Size(5_000_000_000_000_000).to_string(), "5000.0 TB");
+    }
+
+    #[test]
+    fn test_progress_line_with_known_size() {
+        let line = ProgressLine {
+            bytes: 500_000,
+            total: Some(2_000_000),
+            elapsed: Duration::from_secs(1),
+            frame: 0,
+        };
+        assert_eq!(
+            line.to_string(),
+            "[#######-----------------------]  25% 500.0 KB / 2.0 MB (500.0 KB/s)"
+        );
+        // Bytes read past the known size don't overflow the bar:
+        let line = ProgressLine {
+            bytes: 3_000,
+            total: Some(2_000),
+            elapsed: Duration::ZERO,
+            frame: 0,
+        };
+        assert_eq!(
+            line.to_string(),
+            format!("[{}] 100% 2.0 KB / 2.0 KB", "#".repeat(BAR_WIDTH))
+        );
+    }
+
+    #[test]
+    fn test_progress_line_with_unknown_size() {
+        let line = ProgressLine {
+            bytes: 1_200_000,
+            total: None,
+            elapsed: Duration::from_secs(2),
+            frame: 5,
+        };
+        assert_eq!(line.to_string(), "/ 1.2 MB processed (600.0 KB/s)");
+    }
+
+    #[test]
+    fn test_progress_reader_passes_input_through() {
+        let mut out = Vec::new();
+        let mut copied = Vec::new();
+        {
+            let mut reader = ProgressReader::new(&b"some input"[..], Some(10), &mut out);
+            reader.read_to_end(&mut copied).unwrap();
+        }
+        assert_eq!(copied, b"some input");
+        // The final line is always drawn on stderr, and ends the progress line:
+        let drawn = String::from_utf8(out).unwrap();
+        assert!(drawn.contains(" 100% 10 B / 10 B"));
+        assert!(drawn.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_seeking_moves_progress_back() {
+        let mut reader = ProgressReader::new(std::io::Cursor::new(vec![0; 8]), Some(8), Vec::new());
+        reader.read_to_end(&mut Vec::new()).unwrap();
+        assert_eq!(reader.bytes, 8);
+        reader.rewind().unwrap();
+        assert_eq!(reader.bytes, 0);
+    }
+}
@@ -0,0 +1,278 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::config::CUSTOM_MODEL_ID;
+use super::model_choice::{Adaptation, BuiltinModel};
+use clap::ValueEnum;
+use ppm_cli::models::ppm::EscapeMethod;
+use ppm_cli::models::Model;
+use ppm_cli::number_types::CodecParams;
+use ppm_cli::sim::SymbolIndexMapping;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::{Arc, OnceLock};
+use thiserror::Error;
+
+/// The settings a registered model is built with
+pub struct ModelSettings {
+    /// The SIM of the mode and alphabet, the model must support its symbols
+    pub sim: &'static dyn SymbolIndexMapping,
+    /// The context order (only used by context-based models)
+    pub model_order: u8,
+    /// How escape probabilities are estimated (only used by the PPM model)
+    pub escape_method: EscapeMethod,
+    /// How the tables of adaptive models learn from the input
+    pub adaptation: Adaptation,
+    /// The precisions of the coding, which the model's tables must fit in
+    pub params: CodecParams,
+}
+
+/// Builds a model from the given settings, or returns None if the model is built from the input
+/// itself by the two-pass codec
+pub type ModelBuilder = dyn Fn(&ModelSettings) -> Option<Box<dyn Model>> + Send + Sync;
+
+/// A model selectable by name with `--model`, and by id when decompressing
+#[derive(Clone)]
+pub struct RegisteredModel {
+    name: &'static str,
+    id: u8,
+    builder: Arc<ModelBuilder>,
+}
+
+impl RegisteredModel {
+    /// A model named _name_, whose id in the header of compressed streams is _id_. Ids must never
+    /// change, or streams compressed by older versions would be decompressed with the wrong model
+    pub fn new<B>(name: &'static str, id: u8, builder: B) -> Self
+    where
+        B: Fn(&ModelSettings) -> Option<Box<dyn Model>> + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            id,
+            builder: Arc::new(builder),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Builds the model with the given _settings_, or returns None for models built from the input
+    pub fn build(&self, settings: &ModelSettings) -> Option<Box<dyn Model>> {
+        (self.builder)(settings)
+    }
+}
+
+/// Registered models are identified by their ids, which the registry keeps unique
+impl PartialEq for RegisteredModel {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Debug for RegisteredModel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RegisteredModel")
+            .field("name", &self.name)
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for RegisteredModel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl From<BuiltinModel> for RegisteredModel {
+    fn from(model: BuiltinModel) -> Self {
+        model_registry()
+            .by_id(model.id())
+            .expect("Every builtin model is registered")
+    }
+}
+
+/// Errors of registering a model whose name or id is already taken
+#[derive(Debug, Error, PartialEq)]
+pub enum RegistryError {
+    #[error("A model named \"{0}\" is already registered")]
+    DuplicateName(&'static str),
+    #[error("A model with id {0} is already registered")]
+    DuplicateId(u8),
+    #[error("Model id {CUSTOM_MODEL_ID} is reserved for custom models")]
+    ReservedId,
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error("Unknown model \"{name}\" (possible values: {possible})")]
+pub struct UnknownModelName {
+    pub name: String,
+    pub possible: String,
+}
+
+/// The models the CLI can compress with, by name and by header id
+#[derive(Default)]
+pub struct ModelRegistry {
+    models: Vec<RegisteredModel>,
+}
+
+impl ModelRegistry {
+    /// A registry of every builtin model
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        for model in BuiltinModel::value_variants() {
+            let builtin = model.clone();
+            registry
+                .register(RegisteredModel::new(
+                    model.name(),
+                    model.id(),
+                    move |settings| builtin.get_model(settings),
+                ))
+                .expect("Builtin models have unique names and ids");
+        }
+        registry
+    }
+
+    /// Adds _model_ to the registry, so it can be selected by its name and id
+    pub fn register(&mut self, model: RegisteredModel) -> Result<(), RegistryError> {
+        if model.id == CUSTOM_MODEL_ID {
+            return Err(RegistryError::ReservedId);
+        }
+        if self.models.iter().any(|other| other.name == model.name) {
+            return Err(RegistryError::DuplicateName(model.name));
+        }
+        if self.models.iter().any(|other| other.id == model.id) {
+            return Err(RegistryError::DuplicateId(model.id));
+        }
+        self.models.push(model);
+        Ok(())
+    }
+
+    /// Finds the model named _name_ (ignoring case). If there isn't one, the error lists the names
+    /// of the registered models.
+    pub fn by_name(&self, name: &str) -> Result<RegisteredModel, UnknownModelName> {
+        self.models
+            .iter()
+            .find(|model| model.name.eq_ignore_ascii_case(name))
+            .cloned()
+            .ok_or_else(|| UnknownModelName {
+                name: name.to_string(),
+                possible: self.names().join(", "),
+            })
+    }
+
+    /// Finds the model with the given header id, if there is one
+    pub fn by_id(&self, id: u8) -> Option<RegisteredModel> {
+        self.models.iter().find(|model| model.id == id).cloned()
+    }
+
+    /// The registered models, in the order they were registered
+    pub fn models(&self) -> &[RegisteredModel] {
+        &self.models
+    }
+
+    /// The names of the registered models, in the order they were registered
+    pub fn names(&self) -> Vec<&'static str> {
+        self.models.iter().map(RegisteredModel::name).collect()
+    }
+}
+
+/// The registry the CLI selects models from. New models are added by registering them in
+/// `ModelRegistry::builtin`
+pub fn model_registry() -> &'static ModelRegistry {
+    static REGISTRY: OnceLock<ModelRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(ModelRegistry::builtin)
+}
+
+/// Parses the name of a registered model, for `--model`
+pub fn parse_model_name(name: &str) -> Result<RegisteredModel, UnknownModelName> {
+    model_registry().by_name(name.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ppm_cli::models::distributions::uniform::UniformDistributionModel;
+
+    fn dummy_model(name: &'static str, id: u8) -> RegisteredModel {
+        RegisteredModel::new(name, id, |settings| {
+            Some(Box::new(UniformDistributionModel::new(settings.sim)))
+        })
+    }
+
+    #[test]
+    fn test_builtin_models_registered() {
+        for model in BuiltinModel::value_variants() {
+            let registered = parse_model_name(&model.to_string()).unwrap();
+            assert_eq!(registered.id(), model.id());
+            assert_eq!(RegisteredModel::from(model.clone()), registered);
+        }
+        assert_eq!(parse_model_name(" PPM ").unwrap().name(), "ppm");
+    }
+
+    #[test]
+    fn test_register_and_select_by_name() {
+        let mut registry = ModelRegistry::builtin();
+        registry.register(dummy_model("dummy", 100)).unwrap();
+
+        let model = registry.by_name("dummy").unwrap();
+        assert_eq!(model.id(), 100);
+        assert_eq!(registry.by_id(100), Some(model.clone()));
+        let settings = ModelSettings {
+            sim: &ppm_cli::sim::DefaultSIM,
+            model_order: 0,
+            escape_method: EscapeMethod::default(),
+            adaptation: Adaptation {
+                rescale_at: ppm_cli::frequencies::Frequency::max(),
+                increment: ppm_cli::frequencies::Frequency::new(1).unwrap(),
+                half_life: None,
+            },
+            params: CodecParams::default(),
+        };
+        assert!(model.build(&settings).is_some());
+    }
+
+    #[test]
+    fn test_register_rejects_taken_names_and_ids() {
+        let mut registry = ModelRegistry::builtin();
+        assert_eq!(
+            registry.register(dummy_model("order0", 100)),
+            Err(RegistryError::DuplicateName("order0"))
+        );
+        assert_eq!(
+            registry.register(dummy_model("dummy", BuiltinModel::Ppm.id())),
+            Err(RegistryError::DuplicateId(BuiltinModel::Ppm.id()))
+        );
+        assert_eq!(
+            registry.register(dummy_model("dummy", CUSTOM_MODEL_ID)),
+            Err(RegistryError::ReservedId)
+        );
+    }
+
+    #[test]
+    fn test_unknown_name_lists_models() {
+        let err = parse_model_name("gzip").unwrap_err();
+        assert_eq!(err.name, "gzip");
+        for model in BuiltinModel::value_variants() {
+            assert!(err.possible.contains(&model.to_string()));
+        }
+    }
+}
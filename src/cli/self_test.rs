@@ -0,0 +1,199 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::config::CodecConfig;
+use super::model_choice::BuiltinModel;
+use anyhow::Result;
+use ppm_cli::dictionary::fnv1a;
+use ppm_cli::parser::{BitParser, ByteParser, Parser};
+use ppm_cli::two_pass::compress_two_pass;
+use ppm_cli::{compress_reader, CodecOptions};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::Cursor;
+
+/// Multiplier of the linear congruential generator producing the self-test's noise
+const LCG_MULTIPLIER: u32 = 1_103_515_245;
+/// Increment of the linear congruential generator producing the self-test's noise
+const LCG_INCREMENT: u32 = 12_345;
+/// Number of pseudo-random bytes in the self-test's input
+const NOISE_BYTES: usize = 1024;
+
+/// FNV-1a hashes of the self-test's compressed input, for each builtin model in byte-mode and in
+/// bit-mode (using the default model order).<br>
+/// Compressed files must decompress identically everywhere, so these may only change along with
+/// the format version.
+const GOLDEN_HASHES: &[(BuiltinModel, bool, u32)] = &[
+    (BuiltinModel::Uniform, false, 0x297af4f6),
+    (BuiltinModel::Uniform, true, 0x6eb4513d),
+    (BuiltinModel::Order0, false, 0x147d2e94),
+    (BuiltinModel::Order0, true, 0x53f1c0af),
+    (BuiltinModel::Order0Sublinear, false, 0x022fd0ed),
+    (BuiltinModel::Order0Sublinear, true, 0xf1185fed),
+    (BuiltinModel::Ppm, false, 0xff19fe6d),
+    (BuiltinModel::Ppm, true, 0xa7db1131),
+    (BuiltinModel::TwoPass, false, 0x369fd38e),
+    (BuiltinModel::TwoPass, true, 0x3115e7e9),
+    (BuiltinModel::Order1, false, 0x4be83f2a),
+    (BuiltinModel::Order1, true, 0xb478d283),
+    (BuiltinModel::Order1Lazy, false, 0x07d9b781),
+    (BuiltinModel::Order1Lazy, true, 0xdc9edf64),
+];
+
+/// A self-test case whose compressed output differs from its golden hash
+#[derive(Debug)]
+pub struct SelfTestMismatch {
+    model: BuiltinModel,
+    bit_mode: bool,
+    expected: u32,
+    found: u32,
+}
+
+impl Display for SelfTestMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}-mode): expected hash {:08x}, found {:08x}",
+            self.model,
+            if self.bit_mode { "bit" } else { "byte" },
+            self.expected,
+            self.found
+        )
+    }
+}
+
+/// The self-test's failure, listing every case whose output changed
+#[derive(Debug)]
+pub struct SelfTestError(Vec<SelfTestMismatch>);
+
+impl Display for SelfTestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The compressed output of {} case(s) changed, so files compressed elsewhere may not \
+            decompress correctly:",
+            self.0.len()
+        )?;
+        for mismatch in &self.0 {
+            write!(f, "\n{mismatch}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for SelfTestError {}
+
+/// The fixed input of the self-test: text with repetitions, every byte value, and pseudo-random
+/// noise
+fn self_test_input() -> Vec<u8> {
+    let mut input = b"It was the best of times, it was the worst of times, it was the age of \
+        wisdom, it was the age of foolishness. "
+        .repeat(4);
+    input.extend(0..=u8::MAX);
+
+    let mut state: u32 = 0x5eed;
+    input.extend((0..NOISE_BYTES).map(|_| {
+        state = state
+            .wrapping_mul(LCG_MULTIPLIER)
+            .wrapping_add(LCG_INCREMENT);
+        (state >> 16) as u8
+    }));
+    input
+}
+
+/// Compresses the self-test's input with the given builtin model and mode, and hashes the output
+fn compressed_hash(model: &BuiltinModel, bit_mode: bool) -> Result<u32> {
+    let config = CodecConfig::defaults(model.clone().into(), bit_mode);
+    let mut parser: Box<dyn Parser> = if bit_mode {
+        Box::new(BitParser)
+    } else {
+        Box::new(ByteParser)
+    };
+    let options = CodecOptions {
+        header: Some(config.header(false)),
+        ..Default::default()
+    };
+
+    let input = self_test_input();
+    let mut compressed = Vec::new();
+    match config.get_model() {
+        Some(mut model) => compress_reader(
+            &input[..],
+            &mut compressed,
+            &mut model,
+            &mut parser,
+            &options,
+        )?,
+        None => {
+            let sim = config.sim();
+            compress_two_pass(
+                Cursor::new(input),
+                &mut compressed,
+                &mut parser,
+                sim,
+                &options,
+            )?
+        }
+    };
+    Ok(fnv1a(&compressed))
+}
+
+/// Compresses a fixed input with every builtin model, and compares the outputs against golden
+/// hashes. This catches models whose behavior isn't deterministic (for example, if it depends on a
+/// HashMap's iteration order or on floating point arithmetic), which breaks file compatibility.
+pub fn run_self_test() -> Result<()> {
+    let mut mismatches = Vec::new();
+    for (model, bit_mode, expected) in GOLDEN_HASHES {
+        let found = compressed_hash(model, *bit_mode)?;
+        if found != *expected {
+            mismatches.push(SelfTestMismatch {
+                model: model.clone(),
+                bit_mode: *bit_mode,
+                expected: *expected,
+                found,
+            });
+        }
+    }
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(SelfTestError(mismatches).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    #[test]
+    fn test_every_model_has_golden_hashes() {
+        for model in BuiltinModel::value_variants() {
+            for bit_mode in [false, true] {
+                assert!(GOLDEN_HASHES
+                    .iter()
+                    .any(|(m, b, _)| m == model && *b == bit_mode));
+            }
+        }
+    }
+
+    #[test]
+    fn test_self_test_passes() {
+        run_self_test().unwrap();
+    }
+}
@@ -0,0 +1,218 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::CodecDirection;
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use std::time::Duration;
+
+/// Wraps a reader or a writer, counting the bytes that pass through it
+pub struct ByteCounter<T> {
+    inner: T,
+    bytes: u64,
+}
+
+impl<T> ByteCounter<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner, bytes: 0 }
+    }
+
+    /// Returns the number of bytes read or written so far
+    pub fn byte_count(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl<R: Read> Read for ByteCounter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes += read as u64;
+        Ok(read)
+    }
+}
+
+impl<W: Write> Write for ByteCounter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Statistics of a compression/decompression, printed by the --stats flag
+#[derive(Debug)]
+pub struct CodecStats {
+    pub direction: CodecDirection,
+    /// Number of bytes read from the input
+    pub input_bytes: u64,
+    /// Number of bytes written to the output
+    pub output_bytes: u64,
+    /// Wall-clock time the operation took
+    pub elapsed: Duration,
+    /// Number of symbols the compression skipped, since the model couldn't compress them
+    pub skipped_symbols: usize,
+}
+
+impl CodecStats {
+    /// The size of the compressed data as a percentage of the size of the uncompressed data, or
+    /// None if there's no uncompressed data
+    pub fn ratio_percent(&self) -> Option<f64> {
+        let (uncompressed, compressed) = self.uncompressed_and_compressed();
+        ratio_percent(uncompressed, compressed)
+    }
+
+    /// Megabytes of uncompressed data processed per second, or None if no time was measured
+    pub fn throughput(&self) -> Option<f64> {
+        let (uncompressed, _) = self.uncompressed_and_compressed();
+        let seconds = self.elapsed.as_secs_f64();
+        (seconds > 0.0).then(|| uncompressed as f64 / 1e6 / seconds)
+    }
+
+    /// Returns the sizes in bytes of the uncompressed and compressed sides of the operation
+    fn uncompressed_and_compressed(&self) -> (u64, u64) {
+        match self.direction {
+            CodecDirection::Compress => (self.input_bytes, self.output_bytes),
+            CodecDirection::Decompress => (self.output_bytes, self.input_bytes),
+        }
+    }
+}
+
+impl Display for CodecStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let operation = match self.direction {
+            CodecDirection::Compress => "Compressed",
+            CodecDirection::Decompress => "Decompressed",
+        };
+        write!(
+            f,
+            "{operation} {} bytes into {} bytes",
+            self.input_bytes, self.output_bytes
+        )?;
+        if let Some(ratio) = self.ratio_percent() {
+            write!(f, " (ratio: {ratio:.2}%)")?;
+        }
+        write!(f, " in {:.3}s", self.elapsed.as_secs_f64())?;
+        if let Some(throughput) = self.throughput() {
+            write!(f, " ({throughput:.2} MB/s)")?;
+        }
+        if self.skipped_symbols > 0 {
+            write!(f, ", skipped {} unsupported symbols", self.skipped_symbols)?;
+        }
+        Ok(())
+    }
+}
+
+/// The size of _compressed_ as a percentage of _uncompressed_, or None if there's no uncompressed
+/// data
+pub fn ratio_percent(uncompressed: u64, compressed: u64) -> Option<f64> {
+    (uncompressed != 0).then(|| 100.0 * compressed as f64 / uncompressed as f64)
+}
+
+/// The size compression would produce, printed by the --estimate flag instead of writing the output
+#[derive(Debug)]
+pub struct SizeEstimate {
+    /// Number of bytes read from the input
+    pub input_bytes: u64,
+    /// Number of bytes the compression produced (and discarded)
+    pub output_bytes: u64,
+}
+
+impl Display for SizeEstimate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Estimated compressed size: {} bytes for {} bytes of input",
+            self.output_bytes, self.input_bytes
+        )?;
+        if let Some(ratio) = ratio_percent(self.input_bytes, self.output_bytes) {
+            write!(f, " (ratio: {ratio:.2}%)")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_counter() {
+        let mut reader = ByteCounter::new(&b"twelve bytes"[..]);
+        let mut writer = ByteCounter::new(Vec::new());
+        std::io::copy(&mut reader, &mut writer).unwrap();
+        assert_eq!(reader.byte_count(), 12);
+        assert_eq!(writer.byte_count(), 12);
+    }
+
+    #[test]
+    fn test_ratio_is_relative_to_uncompressed_data() {
+        let compression = CodecStats {
+            direction: CodecDirection::Compress,
+            input_bytes: 2_000_000,
+            output_bytes: 500_000,
+            elapsed: Duration::from_secs(2),
+            skipped_symbols: 0,
+        };
+        assert_eq!(compression.ratio_percent(), Some(25.0));
+        assert_eq!(compression.throughput(), Some(1.0));
+        assert_eq!(
+            compression.to_string(),
+            "Compressed 2000000 bytes into 500000 bytes (ratio: 25.00%) in 2.000s (1.00 MB/s)"
+        );
+
+        let decompression = CodecStats {
+            direction: CodecDirection::Decompress,
+            input_bytes: 500_000,
+            output_bytes: 2_000_000,
+            ..compression
+        };
+        assert_eq!(decompression.ratio_percent(), Some(25.0));
+        assert_eq!(decompression.throughput(), Some(1.0));
+    }
+
+    #[test]
+    fn test_skipped_symbols_reported() {
+        let stats = CodecStats {
+            direction: CodecDirection::Compress,
+            input_bytes: 0,
+            output_bytes: 8,
+            elapsed: Duration::ZERO,
+            skipped_symbols: 3,
+        };
+        assert_eq!(stats.ratio_percent(), None);
+        assert_eq!(
+            stats.to_string(),
+            "Compressed 0 bytes into 8 bytes in 0.000s, skipped 3 unsupported symbols"
+        );
+    }
+
+    #[test]
+    fn test_size_estimate_display() {
+        let estimate = SizeEstimate {
+            input_bytes: 400,
+            output_bytes: 100,
+        };
+        assert_eq!(
+            estimate.to_string(),
+            "Estimated compressed size: 100 bytes for 400 bytes of input (ratio: 25.00%)"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{run_codec, CodecArgs, CodecDirection};
+use clap::Args;
+use log::info;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The extension of files compressed without a subcommand
+pub const COMPRESSED_EXTENSION: &str = "ppm";
+
+/// CLI arguments for compressing/decompressing without a subcommand, following gzip's conventions:
+/// piped input is compressed to stdout, and every given file is replaced by its compression (or
+/// decompression), named by adding (or removing) the .ppm extension.
+///
+/// Whatever comes first takes precedence: a subcommand given first is run with its own options
+/// (these flags can't follow it), while after these flags (or codec options) every argument is an
+/// input file, even if it's named like a subcommand. A file named like a subcommand can be given
+/// first with a path (such as ./compress).
+#[derive(Args)]
+// Flattened into the top-level command, which shouldn't take this documentation as its description:
+#[command(about = None, long_about = None)]
+pub struct StreamingArgs {
+    /// Decompress instead of compressing
+    #[arg(short, long, default_value_t = false)]
+    decompress: bool,
+
+    /// Write the output to stdout, and keep the input files. Several input files are compressed
+    /// into a single stream
+    #[arg(
+        short = 'c',
+        long = "stdout",
+        default_value_t = false,
+        conflicts_with = "output"
+    )]
+    to_stdout: bool,
+
+    /// Keep the input files instead of removing them once their output was written
+    #[arg(short, long, default_value_t = false)]
+    keep: bool,
+
+    #[command(flatten)]
+    codec: CodecArgs,
+}
+
+/// Errors of compressing/decompressing files without a subcommand
+#[derive(Debug, Error, PartialEq)]
+pub enum StreamingError {
+    #[error("{0} doesn't have the .{COMPRESSED_EXTENSION} extension, pass an output path with --output or use --stdout")]
+    UnknownExtension(PathBuf),
+    #[error("The output file {0} already exists, remove it first or pass another output path with --output")]
+    OutputExists(PathBuf),
+}
+
+/// The path the compression of the file at _path_ is written to: _path_ with the .ppm extension
+/// appended
+fn compressed_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{COMPRESSED_EXTENSION}"));
+    path.with_file_name(file_name)
+}
+
+/// The path the decompression of the file at _path_ is written to: _path_ without its .ppm
+/// extension
+fn decompressed_path(path: &Path) -> Result<PathBuf, StreamingError> {
+    match path.extension() {
+        Some(extension) if extension == COMPRESSED_EXTENSION && path.file_stem().is_some() => {
+            Ok(path.with_extension(""))
+        }
+        _ => Err(StreamingError::UnknownExtension(path.to_path_buf())),
+    }
+}
+
+/// Compresses (or decompresses, with -d) according to _args_, like gzip does
+pub fn run_streaming(args: StreamingArgs) -> anyhow::Result<()> {
+    let direction = if args.decompress {
+        CodecDirection::Decompress
+    } else {
+        CodecDirection::Compress
+    };
+    let mut codec = args.codec;
+    // Piped input, and outputs that don't replace the input files, work like the subcommands:
+    if codec.files.is_empty() || args.to_stdout || codec.output.is_some() || codec.estimate {
+        return run_codec(&codec, direction);
+    }
+
+    for path in std::mem::take(&mut codec.files) {
+        let output = match direction {
+            CodecDirection::Compress => compressed_path(&path),
+            CodecDirection::Decompress => decompressed_path(&path)?,
+        };
+        if output.exists() {
+            return Err(StreamingError::OutputExists(output).into());
+        }
+        (codec.files, codec.output) = (vec![path.clone()], Some(output));
+        run_codec(&codec, direction)?;
+        if !args.keep {
+            std::fs::remove_file(&path)?;
+            info!("Removed the input file {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Cli;
+    use super::*;
+    use clap::Parser as _;
+
+    #[test]
+    fn test_output_paths() {
+        let path = Path::new("data/input.txt");
+        assert_eq!(compressed_path(path), Path::new("data/input.txt.ppm"));
+        assert_eq!(
+            decompressed_path(&compressed_path(path)).unwrap(),
+            Path::new("data/input.txt")
+        );
+        for path in ["data/input.txt", "data/.ppm", "input"] {
+            assert_eq!(
+                decompressed_path(Path::new(path)),
+                Err(StreamingError::UnknownExtension(path.into()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_flags_conflict_with_subcommands() {
+        let parse = |args: &[&str]| Cli::try_parse_from(["ppm-cli"].iter().chain(args));
+        let cli = parse(&["-d", "-k", "--model", "ppm", "file.ppm"]).unwrap();
+        assert!(cli.commands.is_none());
+        assert!(cli.streaming.decompress && cli.streaming.keep);
+        assert_eq!(cli.streaming.codec.files, [PathBuf::from("file.ppm")]);
+
+        assert!(parse(&["compress", "file"]).unwrap().commands.is_some());
+        assert!(parse(&["compress", "-d", "file"]).is_err());
+        let cli = parse(&["-d", "decompress"]).unwrap();
+        assert!(cli.commands.is_none());
+        assert_eq!(cli.streaming.codec.files, [PathBuf::from("decompress")]);
+        assert!(parse(&["-c", "--output", "out.ppm"]).is_err());
+        // Global flags are still accepted with a subcommand:
+        assert!(parse(&["-v", "compress", "file"]).is_ok());
+    }
+
+    #[test]
+    fn test_files_replaced_by_their_compression() {
+        let directory = std::env::temp_dir().join(format!("streaming-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("input.txt");
+        let content = b"compressed like gzip compresses, compressed like gzip compresses";
+        std::fs::write(&path, content).unwrap();
+        let run = |args: &[&str]| {
+            let cli = Cli::try_parse_from(["ppm-cli"].iter().chain(args)).unwrap();
+            run_streaming(cli.streaming)
+        };
+
+        let compressed = compressed_path(&path);
+        run(&["--model", "ppm", path.to_str().unwrap()]).unwrap();
+        assert!(!path.exists() && compressed.exists());
+        // Outputs are never overwritten:
+        std::fs::write(&path, content).unwrap();
+        let err = run(&[path.to_str().unwrap()]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&StreamingError::OutputExists(compressed.clone()))
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        run(&["-d", "-k", compressed.to_str().unwrap()]).unwrap();
+        let decompressed = std::fs::read(&path).unwrap();
+        let kept = compressed.exists();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(decompressed, content);
+        assert!(kept);
+    }
+}
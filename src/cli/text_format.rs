@@ -0,0 +1,435 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! ASCII-safe encodings of compressed streams, for pasting them into text. The whole stream
+//! (including its header and checksum) is encoded, so decoding it gives back the exact bytes
+//! compression produced.
+
+use ppm_cli::header::MAGIC;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::io::{Cursor, ErrorKind, Read, Write};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The digits of base64, in the order of their values
+const BASE64_DIGITS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The digits of hex, in the order of their values
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Character padding base64 text to a multiple of 4 characters
+const BASE64_PADDING: u8 = b'=';
+
+/// Number of bytes peeked at the start of the compressed input to detect its format, which is
+/// enough to hold the header's magic in every format
+const DETECT_BYTES: usize = 2 * MAGIC.len();
+
+/// How the compressed bytes are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextFormat {
+    /// The compressed bytes themselves
+    #[default]
+    Raw,
+    /// Two lowercase hex digits per byte
+    Hex,
+    /// Standard base64 (with padding)
+    Base64,
+}
+
+impl TextFormat {
+    /// Whether the format's output is binary data, which shouldn't be written to a terminal
+    pub fn is_binary(&self) -> bool {
+        *self == TextFormat::Raw
+    }
+
+    /// Detects the format of a compressed stream from its first bytes, which hold the header's
+    /// magic in some format. Raw is returned if they don't hold it in any of the text formats.
+    pub fn detect(prefix: &[u8]) -> TextFormat {
+        [TextFormat::Hex, TextFormat::Base64]
+            .into_iter()
+            .find(|format| {
+                let mut magic = Vec::new();
+                let mut encoder = TextEncoder::new(&mut magic, *format);
+                encoder
+                    .write_all(&MAGIC)
+                    .expect("Writing to a vector never fails");
+                // Only the digits the magic fills are compared:
+                let magic_digits = MAGIC.len() * 8 / format.bits_per_digit() as usize;
+                prefix
+                    .get(..magic_digits)
+                    .is_some_and(|prefix| prefix.eq_ignore_ascii_case(&magic[..magic_digits]))
+            })
+            .unwrap_or(TextFormat::Raw)
+    }
+
+    /// Number of bits every digit of the format holds
+    fn bits_per_digit(&self) -> u32 {
+        match self {
+            TextFormat::Raw => 8,
+            TextFormat::Hex => 4,
+            TextFormat::Base64 => 6,
+        }
+    }
+
+    /// Returns the digit whose value is _value_ (which must fit in the format's bits)
+    fn digit(&self, value: u32) -> u8 {
+        match self {
+            TextFormat::Raw => value as u8,
+            TextFormat::Hex => HEX_DIGITS[value as usize],
+            TextFormat::Base64 => BASE64_DIGITS[value as usize],
+        }
+    }
+
+    /// Returns the value of _digit_, or None if it isn't a digit of the format
+    fn value(&self, digit: u8) -> Option<u32> {
+        let digits: &[u8] = match self {
+            TextFormat::Raw => return Some(digit as u32),
+            TextFormat::Hex => HEX_DIGITS,
+            TextFormat::Base64 => BASE64_DIGITS,
+        };
+        // Hex digits may be uppercase too:
+        let digit = match self {
+            TextFormat::Hex => digit.to_ascii_lowercase(),
+            _ => digit,
+        };
+        digits
+            .iter()
+            .position(|&d| d == digit)
+            .map(|value| value as u32)
+    }
+}
+
+impl Display for TextFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextFormat::Raw => write!(f, "raw"),
+            TextFormat::Hex => write!(f, "hex"),
+            TextFormat::Base64 => write!(f, "base64"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Unknown format \"{0}\" (possible values: raw, hex, base64)")]
+pub struct UnknownTextFormat(String);
+
+impl FromStr for TextFormat {
+    type Err = UnknownTextFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(TextFormat::Raw),
+            "hex" => Ok(TextFormat::Hex),
+            "base64" => Ok(TextFormat::Base64),
+            _ => Err(UnknownTextFormat(s.to_string())),
+        }
+    }
+}
+
+/// Decoding text-encoded compressed input fails if it isn't valid text of its format
+#[derive(Debug, Error, PartialEq)]
+pub enum TextDecodeError {
+    #[error("The {format} compressed input holds the invalid character {:?}", char::from(*.byte))]
+    InvalidCharacter { format: TextFormat, byte: u8 },
+    #[error("The {0} compressed input ends in the middle of a byte")]
+    Truncated(TextFormat),
+}
+
+/// Bits that were written or read, but don't form a whole byte or digit yet
+#[derive(Debug, Default)]
+struct PendingBits {
+    bits: u32,
+    count: u32,
+}
+
+impl PendingBits {
+    /// Appends the _count_ least-significant bits of _bits_
+    fn push(&mut self, bits: u32, count: u32) {
+        self.bits = (self.bits << count) | bits;
+        self.count += count;
+    }
+
+    /// Takes the _count_ oldest bits, if there are that many
+    fn take(&mut self, count: u32) -> Option<u32> {
+        (self.count >= count).then(|| {
+            self.count -= count;
+            let taken = self.bits >> self.count;
+            self.bits &= (1 << self.count) - 1;
+            taken
+        })
+    }
+}
+
+/// Wraps a writer, encoding the bytes written to it in a format. `TextEncoder::finish` must be
+/// called after the last byte was written, to write the end of the encoding.
+pub struct TextEncoder<W: Write> {
+    inner: W,
+    format: TextFormat,
+    pending: PendingBits,
+    /// Number of digits written, which base64 pads to a multiple of 4
+    digits: usize,
+}
+
+impl<W: Write> TextEncoder<W> {
+    pub fn new(inner: W, format: TextFormat) -> Self {
+        Self {
+            inner,
+            format,
+            pending: PendingBits::default(),
+            digits: 0,
+        }
+    }
+
+    /// Writes the digit holding the leftover bits (followed by padding in base64) and a newline
+    /// ending the text, then flushes the inner writer and returns it
+    pub fn finish(mut self) -> std::io::Result<W> {
+        if !self.format.is_binary() {
+            let mut end = Vec::new();
+            let bits = self.format.bits_per_digit();
+            if self.pending.count > 0 {
+                // The leftover bits are the digit's most-significant bits:
+                let missing = bits - self.pending.count;
+                self.pending.push(0, missing);
+                end.push(self.format.digit(self.pending.bits));
+                self.digits += 1;
+            }
+            if self.format == TextFormat::Base64 {
+                end.resize(end.len() + (4 - self.digits % 4) % 4, BASE64_PADDING);
+            }
+            end.push(b'\n');
+            self.inner.write_all(&end)?;
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for TextEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.format.is_binary() {
+            return self.inner.write(buf);
+        }
+        let bits = self.format.bits_per_digit();
+        let mut text = Vec::with_capacity(buf.len() * 2);
+        for &byte in buf {
+            self.pending.push(byte as u32, 8);
+            while let Some(value) = self.pending.take(bits) {
+                text.push(self.format.digit(value));
+            }
+        }
+        self.digits += text.len();
+        self.inner.write_all(&text)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Wraps a reader of text in a format, decoding it back into bytes. Whitespace in the text is
+/// skipped (so line breaks added by pasting it are fine), and base64 padding may appear between
+/// concatenated streams.
+pub struct TextDecoder<R: Read> {
+    inner: R,
+    format: TextFormat,
+    pending: PendingBits,
+    /// Bytes that were decoded but not read yet
+    decoded: VecDeque<u8>,
+    ended: bool,
+}
+
+impl<R: Read> TextDecoder<R> {
+    pub fn new(inner: R, format: TextFormat) -> Self {
+        Self {
+            inner,
+            format,
+            pending: PendingBits::default(),
+            decoded: VecDeque::new(),
+            ended: false,
+        }
+    }
+
+    /// Decodes a single character of the text
+    fn decode_char(&mut self, byte: u8) -> Result<(), TextDecodeError> {
+        let bits = self.format.bits_per_digit();
+        match self.format.value(byte) {
+            Some(value) => {
+                self.pending.push(value, bits);
+                if let Some(byte) = self.pending.take(8) {
+                    self.decoded.push_back(byte as u8);
+                }
+            }
+            _ if byte.is_ascii_whitespace() => {}
+            // Padding ends a stream, whose leftover bits are only there to fill its last digit:
+            _ if byte == BASE64_PADDING && self.format == TextFormat::Base64 => {
+                self.pending = PendingBits::default();
+            }
+            _ => {
+                return Err(TextDecodeError::InvalidCharacter {
+                    format: self.format,
+                    byte,
+                })
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for TextDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut text = [0; 4096];
+        while self.decoded.is_empty() && !self.ended {
+            let read = self.inner.read(&mut text)?;
+            if read == 0 {
+                self.ended = true;
+                // A whole digit that doesn't complete a byte means digits are missing:
+                if self.pending.count >= self.format.bits_per_digit() {
+                    let err = TextDecodeError::Truncated(self.format);
+                    return Err(std::io::Error::new(ErrorKind::InvalidData, err));
+                }
+            }
+            for &byte in &text[..read] {
+                self.decode_char(byte)
+                    .map_err(|err| std::io::Error::new(ErrorKind::InvalidData, err))?;
+            }
+        }
+        self.decoded.read(buf)
+    }
+}
+
+/// Wraps the compressed _input_ in a reader decoding it from _format_. If no format is given, it
+/// is detected from the header's magic at the start of the input.
+pub fn decode_input<'a>(
+    input: Box<dyn Read + 'a>,
+    format: Option<TextFormat>,
+) -> std::io::Result<Box<dyn Read + 'a>> {
+    let (format, input): (_, Box<dyn Read + 'a>) = match format {
+        Some(format) => (format, input),
+        None => {
+            let mut prefix = Vec::with_capacity(DETECT_BYTES);
+            let mut input = input.take(DETECT_BYTES as u64);
+            input.read_to_end(&mut prefix)?;
+            let format = TextFormat::detect(&prefix);
+            (
+                format,
+                Box::new(Cursor::new(prefix).chain(input.into_inner())),
+            )
+        }
+    };
+    Ok(match format {
+        TextFormat::Raw => input,
+        format => Box::new(TextDecoder::new(input, format)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(bytes: &[u8], format: TextFormat) -> Vec<u8> {
+        let mut encoder = TextEncoder::new(Vec::new(), format);
+        // Written in several parts, which mustn't change the encoding:
+        for part in bytes.chunks(2) {
+            encoder.write_all(part).unwrap();
+        }
+        encoder.finish().unwrap()
+    }
+
+    fn decode(text: &[u8], format: Option<TextFormat>) -> std::io::Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        decode_input(Box::new(text), format)?.read_to_end(&mut decoded)?;
+        Ok(decoded)
+    }
+
+    #[test]
+    fn test_known_encodings() {
+        assert_eq!(encode(b"PPMC\x00\xff", TextFormat::Hex), b"50504d4300ff\n");
+        assert_eq!(encode(b"Man", TextFormat::Base64), b"TWFu\n");
+        assert_eq!(encode(b"Ma", TextFormat::Base64), b"TWE=\n");
+        assert_eq!(encode(b"M", TextFormat::Base64), b"TQ==\n");
+        assert_eq!(encode(b"raw", TextFormat::Raw), b"raw");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes: Vec<u8> = (0..=255)
+            .chain((0..100u8).map(|i| i.wrapping_mul(7)))
+            .collect();
+        for format in [TextFormat::Raw, TextFormat::Hex, TextFormat::Base64] {
+            for len in [0, 1, 2, 3, 4, bytes.len()] {
+                let encoded = encode(&bytes[..len], format);
+                assert_eq!(decode(&encoded, Some(format)).unwrap(), &bytes[..len]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_detects_format_from_magic() {
+        let stream = [&MAGIC[..], b"rest of the stream"].concat();
+        for format in [TextFormat::Raw, TextFormat::Hex, TextFormat::Base64] {
+            let encoded = encode(&stream, format);
+            assert_eq!(TextFormat::detect(&encoded), format);
+            assert_eq!(decode(&encoded, None).unwrap(), stream);
+        }
+        // Uppercase hex is detected too:
+        assert_eq!(TextFormat::detect(b"50504D43"), TextFormat::Hex);
+        // Input that isn't a stream is left for the header to reject:
+        assert_eq!(TextFormat::detect(b"PP"), TextFormat::Raw);
+    }
+
+    #[test]
+    fn test_whitespace_and_concatenation() {
+        let mut text = encode(b"first", TextFormat::Base64);
+        text.extend(encode(b"second", TextFormat::Base64));
+        text.splice(4..4, *b"\r\n  ");
+        assert_eq!(
+            decode(&text, Some(TextFormat::Base64)).unwrap(),
+            b"firstsecond"
+        );
+    }
+
+    #[test]
+    fn test_invalid_text_rejected() {
+        let error = |text: &[u8], format| {
+            let err = decode(text, Some(format)).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            err.into_inner()
+                .unwrap()
+                .downcast::<TextDecodeError>()
+                .map(|err| *err)
+                .unwrap()
+        };
+        assert_eq!(
+            error(b"50g0", TextFormat::Hex),
+            TextDecodeError::InvalidCharacter {
+                format: TextFormat::Hex,
+                byte: b'g'
+            }
+        );
+        assert_eq!(
+            error(b"505", TextFormat::Hex),
+            TextDecodeError::Truncated(TextFormat::Hex)
+        );
+        assert_eq!(
+            error(b"TWFuT", TextFormat::Base64),
+            TextDecodeError::Truncated(TextFormat::Base64)
+        );
+    }
+}
@@ -0,0 +1,190 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::io::{ErrorKind, Read, Write};
+use thiserror::Error;
+
+/// Errors found by verifying a compression, when its decompression doesn't match its input
+#[derive(Debug, Error, PartialEq)]
+pub enum VerifyError {
+    #[error(
+        "Verification failed: the decompressed data differs from the input at byte offset {0}"
+    )]
+    Mismatch(u64),
+    #[error(
+        "Verification failed: the decompressed data ends after {0} bytes, before the input does"
+    )]
+    Truncated(u64),
+}
+
+/// Wraps a reader, keeping a copy of everything read from it in _copy_. Used to verify piped input,
+/// which can't be read again.
+pub struct TeeReader<'a, R> {
+    inner: R,
+    copy: &'a mut Vec<u8>,
+}
+
+impl<'a, R> TeeReader<'a, R> {
+    pub fn new(inner: R, copy: &'a mut Vec<u8>) -> Self {
+        Self { inner, copy }
+    }
+}
+
+impl<R: Read> Read for TeeReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.copy.extend_from_slice(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// A writer comparing everything written to it with the content of _original_, instead of storing
+/// it. Writing fails once a written byte differs from the original, and the offset of the first
+/// difference is kept.
+pub struct CompareWriter<R> {
+    original: R,
+    offset: u64,
+    mismatch: Option<u64>,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> CompareWriter<R> {
+    pub fn new(original: R) -> Self {
+        Self {
+            original,
+            offset: 0,
+            mismatch: None,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// The offset of the first written byte that differs from the original, if there was one
+    pub fn mismatch(&self) -> Option<VerifyError> {
+        self.mismatch.map(VerifyError::Mismatch)
+    }
+
+    /// Makes sure everything written matched the original, and that all of the original was written
+    pub fn finish(mut self) -> Result<(), VerifyError> {
+        if let Some(mismatch) = self.mismatch() {
+            return Err(mismatch);
+        }
+        match self.original.read(&mut [0]) {
+            Ok(0) => Ok(()),
+            _ => Err(VerifyError::Truncated(self.offset)),
+        }
+    }
+
+    /// Reads up to _len_ bytes of the original into the buffer, fewer only if the original ends
+    fn read_original(&mut self, len: usize) -> std::io::Result<&[u8]> {
+        self.buffer.resize(len, 0);
+        let mut filled = 0;
+        while filled < len {
+            match self.original.read(&mut self.buffer[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(&self.buffer[..filled])
+    }
+}
+
+impl<R: Read> Write for CompareWriter<R> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(mismatch) = self.mismatch() {
+            return Err(std::io::Error::other(mismatch));
+        }
+        let offset = self.offset;
+        let original = self.read_original(buf.len())?;
+        // Bytes past the end of the original differ from it as well:
+        let difference = first_difference(original, buf);
+        if let Some(difference) = difference {
+            self.mismatch = Some(offset + difference as u64);
+            return Err(std::io::Error::other(self.mismatch().unwrap()));
+        }
+        self.offset += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the index of the first byte that differs between _a_ and _b_, which is the length of
+/// the shorter one if it is a prefix of the other, or None if they are equal
+fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    a.iter()
+        .zip(b)
+        .position(|(a, b)| a != b)
+        .or_else(|| (a.len() != b.len()).then(|| a.len().min(b.len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_difference() {
+        assert_eq!(first_difference(b"abc", b"abc"), None);
+        assert_eq!(first_difference(b"abc", b"abd"), Some(2));
+        assert_eq!(first_difference(b"abc", b"ab"), Some(2));
+        assert_eq!(first_difference(b"", b"a"), Some(0));
+    }
+
+    #[test]
+    fn test_compare_writer_accepts_the_original() {
+        let mut writer = CompareWriter::new(&b"hello world"[..]);
+        writer.write_all(b"hello").unwrap();
+        writer.write_all(b" world").unwrap();
+        assert_eq!(writer.finish(), Ok(()));
+    }
+
+    #[test]
+    fn test_compare_writer_reports_the_first_difference() {
+        let mut writer = CompareWriter::new(&b"hello world"[..]);
+        writer.write_all(b"hello").unwrap();
+        assert!(writer.write_all(b" wOrld").is_err());
+        assert_eq!(writer.mismatch(), Some(VerifyError::Mismatch(7)));
+        // Later writes keep failing with the same offset:
+        assert!(writer.write_all(b"!").is_err());
+        assert_eq!(writer.finish(), Err(VerifyError::Mismatch(7)));
+    }
+
+    #[test]
+    fn test_compare_writer_detects_length_differences() {
+        let mut writer = CompareWriter::new(&b"hello"[..]);
+        writer.write_all(b"hel").unwrap();
+        assert_eq!(writer.finish(), Err(VerifyError::Truncated(3)));
+
+        let mut writer = CompareWriter::new(&b"hello"[..]);
+        assert!(writer.write_all(b"hello!").is_err());
+        assert_eq!(writer.finish(), Err(VerifyError::Mismatch(5)));
+    }
+
+    #[test]
+    fn test_tee_reader_copies_input() {
+        let mut copy = Vec::new();
+        let mut read = Vec::new();
+        TeeReader::new(&b"piped"[..], &mut copy)
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, b"piped");
+        assert_eq!(copy, b"piped");
+    }
+}
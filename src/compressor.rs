@@ -16,14 +16,62 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use crate::bit_buffer::BitBuffer;
-use crate::interval::{Interval, IntervalState};
-use crate::models::{Model, ModelCfi};
-use crate::number_types::INTERVAL_BITS;
+use crate::frequencies::Cfi;
+use crate::interval::{RenormCore, Shift};
+use crate::models::{Model, ModelCfi, TooManyEscapesError};
+use crate::number_types::CodecParams;
 use crate::sim::Symbol;
-use anyhow::Result;
+use crate::termination::TerminationStrategy;
+use anyhow::{ensure, Result};
 use log::debug;
+use std::fmt::{Display, Formatter};
 
-pub struct Compressor<'a, M: Model> {
+/// Statistics of a finished compression
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompressionStats {
+    /// Number of symbols given to the compressor (not including the EOF ending the compression)
+    pub input_symbols: usize,
+    /// Number of bits in the compressed output, not including the padding of the last byte
+    pub output_bits: usize,
+    /// The information content of every coded CFI (including escapes and the EOF) according to
+    /// the model's probabilities, which is the least number of bits an ideal coder would output
+    pub theoretical_bits: f64,
+}
+
+impl CompressionStats {
+    /// Average number of output bits spent on each input symbol, or None if there were no input
+    /// symbols
+    pub fn bits_per_symbol(&self) -> Option<f64> {
+        (self.input_symbols != 0).then(|| self.output_bits as f64 / self.input_symbols as f64)
+    }
+
+    /// Number of bits the output spends beyond the theoretical bits, due to the finite precision
+    /// of the coding and the bits ending it
+    pub fn overhead_bits(&self) -> f64 {
+        self.output_bits as f64 - self.theoretical_bits
+    }
+}
+
+/// Returns the information content of a symbol coded with _cfi_: `-log2(width / total)` bits
+fn information_bits(cfi: &Cfi) -> f64 {
+    (*cfi.total as f64 / cfi.width() as f64).log2()
+}
+
+impl Display for CompressionStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} symbols -> {} bits",
+            self.input_symbols, self.output_bits
+        )?;
+        match self.bits_per_symbol() {
+            Some(bits_per_symbol) => write!(f, " ({bits_per_symbol:.3} bits per symbol)"),
+            None => Ok(()),
+        }
+    }
+}
+
+pub struct Compressor<'a, M: Model + ?Sized> {
     /// Number of bits that were put aside in case of near-convergence, their value is unknown until
     /// a converging bit 'b' is found, and is equal to !b, repeated N times.
     outstanding_bits: usize,
@@ -31,74 +79,112 @@ pub struct Compressor<'a, M: Model> {
     /// The buffer holding the output bits:
     output: BitBuffer,
 
-    /// The interval that the compressor uses to represent the data it compresses.
-    interval: Interval,
+    /// The renormalization core holding the interval that the compressor uses to represent the
+    /// data it compresses.
+    core: RenormCore,
 
     /// The model in charge of calculating the probabilities of symbols appearing in the data. It
     /// can dramatically increase compression rate.
     model: &'a mut M,
+
+    /// Number of symbols given to the compressor
+    input_symbols: usize,
+
+    /// Number of complete bytes that were taken out of `output` and returned to the caller
+    bytes_emitted: usize,
+
+    /// Sum of the information content of every CFI the compressor coded
+    theoretical_bits: f64,
+
+    /// How the decompressor finds the end of the data, which decides if it ends with an EOF
+    termination: TerminationStrategy,
 }
 
-impl<'a, M: Model> Compressor<'a, M> {
+impl<'a, M: Model + ?Sized> Compressor<'a, M> {
     /// Creates a new compressor object from a statistical model.
     ///
     /// Note that if the model implements the `update` and `flush` functions, it is the
     /// **responsibility of the CALLER** to make sure the state of the model is not affected by
     /// previous operations (i.e: call the `flush` function if needed).
     pub fn new(model: &'a mut M) -> Self {
+        Self::with_params(model, CodecParams::default())
+    }
+
+    /// Creates a new compressor like `Compressor::new`, whose coding uses the precisions of
+    /// _params_. The model's total frequency must fit in the frequency bits of _params_, and the
+    /// decompressor must be given the same parameters.
+    pub fn with_params(model: &'a mut M, params: CodecParams) -> Self {
         Self {
             outstanding_bits: 0,
             output: BitBuffer::new(),
-            interval: Interval::full_interval(),
+            core: RenormCore::new(params),
             model,
+            input_symbols: 0,
+            bytes_emitted: 0,
+            theoretical_bits: 0.0,
+            termination: TerminationStrategy::default(),
         }
     }
 
+    /// Sets how the decompressor finds the end of the compressed data. Unless the strategy uses
+    /// the EOF symbol, finalizing doesn't compress one. The length of the data is recorded by the
+    /// caller (e.g. in the stream's header), so the compressor ignores the strategy's length.
+    pub fn with_termination(mut self, termination: TerminationStrategy) -> Self {
+        self.termination = termination;
+        self
+    }
+
+    /// Pre-allocates the compressor's output buffer for _bits_ bits (e.g. a size hint based on the
+    /// input's length), so it isn't reallocated as the output grows. The output itself is the same
+    /// with or without the hint.
+    pub fn with_output_capacity(mut self, bits: usize) -> Self {
+        self.output = BitBuffer::with_capacity(bits);
+        self
+    }
+
+    /// Returns the number of bits the compressor produced so far: both the bits of bytes it
+    /// already returned, and the bits still waiting in its buffer.
+    pub fn compressed_bit_count(&self) -> usize {
+        8 * self.bytes_emitted + self.output.len()
+    }
+
+    /// Takes the complete bytes out of the output buffer, counting them as emitted
+    fn take_complete_bytes(&mut self) -> impl Iterator<Item = u8> + '_ {
+        self.bytes_emitted += self.output.len() / 8;
+        self.output.get_complete_bytes()
+    }
+
     /// When the interval's boundaries finally converge on a bit, the values of all remaining
     /// outstanding bits are also known (it's the inverse of the given bit).
     ///
-    /// This helper function outputs all outstanding bits to the bitbuffer, followed by the given
-    /// bit. It is also responsible for setting `self.outstanding_bits` to 0.
-    fn output_with_outstanding(&mut self, bit: bool) {
-        self.output.append(bit);
+    /// This helper function outputs all outstanding bits to _output_, followed by the given bit.
+    /// It is also responsible for setting _outstanding_bits_ to 0.
+    fn output_with_outstanding(output: &mut BitBuffer, outstanding_bits: &mut usize, bit: bool) {
+        output.append(bit);
 
-        self.output.append_repeated(!bit, self.outstanding_bits);
-        self.outstanding_bits = 0;
+        output.append_repeated(!bit, *outstanding_bits);
+        *outstanding_bits = 0;
     }
 
-    /// Processes the state of the saved interval until it is in a no-convergence state.
+    /// Processes the state of the saved interval until it is in a no-convergence state, emitting
+    /// the bits shifted out of it.
     fn process_interval_state(&mut self) {
-        // Process the state until the interval is non-converging:
-        loop {
-            let (low, high) = match self.interval.get_state() {
-                IntervalState::Converging(bit) => {
-                    self.output_with_outstanding(bit);
-
-                    // Get rid of the converging bit in the boundaries, shift 1 in for high:
-                    let low = self.interval.low() << 1u8;
-                    let high = (self.interval.high() << 1u8) | 1u8;
-                    (low, high)
-                }
-                IntervalState::NearConvergence => {
-                    // Increase the outstanding bits counter, shift out the second MSBs, and shift
-                    // in a 1 bit for high:
-                    self.outstanding_bits += 1;
-
-                    let half = self.interval.system().half();
-                    let low = (self.interval.low() << 1u8) ^ half;
-                    let high = (self.interval.high() << 1u8) | (*half + 1);
-
-                    (low, high)
+        let Self {
+            core,
+            output,
+            outstanding_bits,
+            ..
+        } = self;
+        core.renormalize(|shift, _| {
+            match shift {
+                Shift::Converge(bit) => {
+                    Self::output_with_outstanding(output, outstanding_bits, bit)
                 }
-                IntervalState::NoConvergence => break,
-            };
-            self
-                .interval
-                .set_boundaries(low, high)
-                .expect(
-                    "Removing similar bit or removing second MSB never breaks interval invariance, but it did somehow"
-                );
-        }
+                // The bit is unknown until the interval converges:
+                Shift::NearConverge => *outstanding_bits += 1,
+            }
+            true
+        });
     }
 
     /// Compresses the given symbol using arithmetic coding. This function **CANNOT** be used with
@@ -108,29 +194,105 @@ impl<'a, M: Model> Compressor<'a, M> {
     /// function returns an iterator over any COMPLETE bytes of the compression (which may be empty
     /// if the compression used less than 8 bits).<br>
     /// To retrieve the leftover bits and finish compression, call the `finalize` function
-    pub fn load_symbol(&mut self, symbol: Symbol) -> Result<impl Iterator<Item = u8>> {
+    pub fn load_symbol(&mut self, symbol: Symbol) -> Result<impl Iterator<Item = u8> + '_> {
+        self.input_symbols += 1;
+        self.encode_symbol(symbol)
+    }
+
+    /// Compresses the given symbol like `load_symbol`, without counting it as an input symbol
+    fn encode_symbol(&mut self, symbol: Symbol) -> Result<impl Iterator<Item = u8> + '_> {
         debug!("Compressor: Compressing symbol {}", symbol);
-        let cfi = self.model.get_cfi(symbol)?;
-        self.model.update(symbol, &cfi)?;
+        let max_escapes = self.model.max_escapes();
+        let mut escapes = 0;
+        loop {
+            let cfi = self.model.get_cfi(symbol)?;
+            self.model.update(symbol, &cfi)?;
 
-        match cfi {
-            ModelCfi::IndexCfi(cfi) => {
-                self.interval.update(cfi);
-                self.process_interval_state();
-            }
-            // If it's an escape CFI, repeatedly load the symbol:
-            ModelCfi::EscapeCfi(cfi) => {
-                self.interval.update(cfi);
-                self.process_interval_state();
-                return self.load_symbol(symbol);
+            match cfi {
+                ModelCfi::IndexCfi(cfi) => {
+                    let bits = self.code_cfi(cfi)?;
+                    debug!("Compressor: Coded symbol {symbol} in {bits:.3} bits");
+                    return Ok(self.take_complete_bytes());
+                }
+                // If it's an escape CFI, repeatedly load the symbol:
+                ModelCfi::EscapeCfi(cfi) => {
+                    escapes += 1;
+                    ensure!(escapes <= max_escapes, TooManyEscapesError(max_escapes));
+                    let bits = self.code_cfi(cfi)?;
+                    debug!("Compressor: Escaped from symbol {symbol} in {bits:.3} bits");
+                }
             }
         }
-        Ok(self.output.get_complete_bytes())
     }
 
-    /// Ends the compression of any bits left over from previous operations, outputting them as an
-    /// iterator of bytes.
-    pub fn finalize(mut self) -> impl Iterator<Item = u8> {
+    /// Narrows the interval to _cfi_ and outputs the bits it converged on. Returns the CFI's
+    /// information content, which is added to the theoretical bits.
+    fn code_cfi(&mut self, cfi: Cfi) -> Result<f64> {
+        let bits = information_bits(&cfi);
+        self.core.interval_mut().update(cfi)?;
+        self.process_interval_state();
+        self.theoretical_bits += bits;
+        Ok(bits)
+    }
+
+    /// Ends the current record by compressing an EOF symbol, without ending the compression. The
+    /// following symbols start a new record, which a decompressor in record mode (see
+    /// `Decompressor::with_records`) decompresses after the EOF.<br>
+    /// Like `load_symbol`, any COMPLETE bytes of the compression are returned.
+    pub fn end_record(&mut self) -> Result<impl Iterator<Item = u8> + '_> {
+        debug!("Compressor: Ending record");
+        self.encode_symbol(Symbol::Eof)
+    }
+
+    /// Ends the compression by compressing an EOF symbol (which is how the decompressor knows to
+    /// stop, unless the termination strategy doesn't use it), followed by any bits left over from
+    /// previous operations. The remaining bytes are returned as an iterator, along with the
+    /// compression's statistics.
+    pub fn finalize(mut self) -> Result<(impl Iterator<Item = u8>, CompressionStats)> {
+        let complete_bytes = self.finish()?;
+        let stats = CompressionStats {
+            input_symbols: self.input_symbols,
+            output_bits: self.compressed_bit_count(),
+            theoretical_bits: self.theoretical_bits,
+        };
+        debug!(
+            "Compressor: Output {} bits, {:.3} theoretical bits (coding overhead: {:.3} bits)",
+            stats.output_bits,
+            stats.theoretical_bits,
+            stats.overhead_bits()
+        );
+        let bytes = complete_bytes
+            .into_iter()
+            .chain(self.output.get_leftover_bits());
+        Ok((bytes, stats))
+    }
+
+    /// Ends the compression exactly like `finalize`, but returns the exact compressed bits instead
+    /// of zero-padded bytes: the remaining bytes, and the number of valid bits in the last one (the
+    /// rest are padding).<br>
+    /// This lets callers concatenate compressed streams bit-exactly (see `BitBuffer::extend_from`).
+    pub fn finalize_bits(mut self) -> Result<(Vec<u8>, usize)> {
+        let mut bytes = self.finish()?;
+        let last_byte_bits = match self.output.get_leftover_bits_with_count() {
+            Some((leftover, count)) => {
+                bytes.push(leftover);
+                count
+            }
+            None => 8,
+        };
+        Ok((bytes, last_byte_bits))
+    }
+
+    /// Compresses the EOF symbol (if the termination strategy uses it) and outputs the bits that
+    /// finish the compression. Returns the complete bytes, leaving any leftover bits in the output
+    /// buffer.
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        let mut bytes: Vec<u8> = if self.termination.uses_eof() {
+            self.encode_symbol(Symbol::Eof)?.collect()
+        } else {
+            Vec::new()
+        };
+
         // When all symbols are loaded, the possible interval boundaries are:
         // - [01yyy, 11xxx)
         // - [00yyy, 11xxx)
@@ -140,10 +302,248 @@ impl<'a, M: Model> Compressor<'a, M> {
         // adding 1 to the near-convergence counter and insert the value of low's second MSB:
         debug!("Finalizing compressor");
         self.outstanding_bits += 1;
-        self.output_with_outstanding(*((self.interval.low() >> (INTERVAL_BITS - 2)) & 1u8) == 1);
+        let interval = self.core.interval();
+        let second_msb = *((interval.low() >> (interval.system().bits() - 2)) & 1u8) == 1;
+        Self::output_with_outstanding(&mut self.output, &mut self.outstanding_bits, second_msb);
+
+        bytes.extend(self.take_complete_bytes());
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bit_buffer::bit_iter::BitIterator;
+    use crate::decompressor::Decompressor;
+    use crate::frequencies::Frequency;
+    use crate::interval::{boundary, IntervalBoundary, InvalidCfiError};
+    use crate::models::adaptive::AdaptiveModel;
+    use crate::models::distributions::uniform::UniformDistributionModel;
+    use crate::models::ppm::PpmModel;
+    use crate::models::ModelCfiError;
+    use crate::sim::DefaultSIM;
+
+    /// Processes the interval state of a compressor whose interval is [_low_, _high_], and returns
+    /// the compressor for inspection.
+    fn process(
+        model: &mut UniformDistributionModel<DefaultSIM>,
+        low: IntervalBoundary,
+        high: IntervalBoundary,
+        outstanding_bits: usize,
+    ) -> Compressor<'_, UniformDistributionModel<DefaultSIM>> {
+        let mut compressor = Compressor::new(model);
+        compressor
+            .core
+            .interval_mut()
+            .set_boundaries(low, high)
+            .unwrap();
+        compressor.outstanding_bits = outstanding_bits;
+        compressor.process_interval_state();
+        compressor
+    }
+
+    #[test]
+    fn test_converging_true_step() {
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let compressor = process(
+            &mut model,
+            boundary("1001", false),
+            boundary("1110", true),
+            2,
+        );
+
+        // The converging bit is output, followed by the outstanding bits' inverse:
+        assert_eq!(compressor.outstanding_bits, 0);
+        assert_eq!(compressor.output.len(), 3);
+        assert_eq!(compressor.output.get_leftover_bits(), Some(0b10000000));
+    }
+
+    #[test]
+    fn test_converging_false_step() {
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let compressor = process(
+            &mut model,
+            boundary("0001", false),
+            boundary("0110", true),
+            1,
+        );
+
+        assert_eq!(compressor.outstanding_bits, 0);
+        assert_eq!(compressor.output.len(), 2);
+        assert_eq!(compressor.output.get_leftover_bits(), Some(0b01000000));
+    }
+
+    #[test]
+    fn test_near_convergence_step() {
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let compressor = process(
+            &mut model,
+            boundary("01001", false),
+            boundary("10110", true),
+            0,
+        );
+
+        // Nothing is output, the bit is put aside instead:
+        assert_eq!(compressor.outstanding_bits, 1);
+        assert_eq!(compressor.output.len(), 0);
+    }
+
+    /// Compresses the given bytes, returning the exact compressed bits
+    fn compress_bits(input: &[u8]) -> (Vec<u8>, usize) {
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let mut compressor = Compressor::new(&mut model);
+        let mut compressed = Vec::new();
+        for &byte in input {
+            compressed.extend(compressor.load_symbol(Symbol::Byte(byte)).unwrap());
+        }
+        let (last_bytes, last_byte_bits) = compressor.finalize_bits().unwrap();
+        compressed.extend(last_bytes);
+        (compressed, last_byte_bits)
+    }
+
+    #[test]
+    fn test_finalize_bits_concatenates_bit_exactly() {
+        let inputs: [&[u8]; 2] = [b"first", b"second"];
+        let (first, first_last_bits) = compress_bits(inputs[0]);
+        let (second, second_last_bits) = compress_bits(inputs[1]);
+        assert!((1..=8).contains(&first_last_bits));
+
+        // The padded bytes match the ones of a regular finalize:
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let mut compressor = Compressor::new(&mut model);
+        let mut padded = Vec::new();
+        for &byte in inputs[0] {
+            padded.extend(compressor.load_symbol(Symbol::Byte(byte)).unwrap());
+        }
+        padded.extend(compressor.finalize().unwrap().0);
+        assert_eq!(padded, first);
+
+        // Concatenate both streams without padding between them:
+        let mut buffer = BitBuffer::new();
+        buffer.extend_from(&first, first_last_bits);
+        buffer.extend_from(&second, second_last_bits);
+        let first_len = 8 * (first.len() - 1) + first_last_bits;
+        assert_eq!(
+            buffer.len(),
+            first_len + 8 * (second.len() - 1) + second_last_bits
+        );
+        let bits: Vec<bool> = BitIterator::from(buffer).collect();
+
+        // Each stream decompresses from its own bits:
+        let streams = [&bits[..first_len], &bits[first_len..]];
+        for (input, stream_bits) in inputs.into_iter().zip(streams) {
+            let mut model = UniformDistributionModel::new(DefaultSIM);
+            let mut decompressor = Decompressor::new(&mut model, stream_bits.iter().copied());
+            let decompressed: Vec<u8> =
+                std::iter::from_fn(|| decompressor.get_next_byte().unwrap()).collect();
+            assert_eq!(decompressed, input);
+        }
+    }
+
+    #[test]
+    fn test_finalize_stats() {
+        let input = b"statistics";
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let mut compressor = Compressor::new(&mut model);
+        let mut compressed = Vec::new();
+        for &byte in input {
+            compressed.extend(compressor.load_symbol(Symbol::Byte(byte)).unwrap());
+            // Bits still in the buffer are counted too:
+            assert!(compressor.compressed_bit_count() >= 8 * compressed.len());
+        }
+        let (last_bytes, stats) = compressor.finalize().unwrap();
+        compressed.extend(last_bytes);
+
+        // The EOF isn't an input symbol:
+        assert_eq!(stats.input_symbols, input.len());
+        // Only the padding of the last byte isn't counted:
+        assert_eq!(stats.output_bits.div_ceil(8), compressed.len());
+        assert!(stats.bits_per_symbol().unwrap() > 8.0);
+    }
+
+    #[test]
+    fn test_theoretical_bits_match_output() {
+        let input = b"the theoretical size of the output, according to the model".repeat(20);
+        for mut model in [
+            Box::new(UniformDistributionModel::new(DefaultSIM)) as Box<dyn Model>,
+            Box::new(AdaptiveModel::new(DefaultSIM)),
+            Box::new(PpmModel::new(DefaultSIM, 2)),
+        ] {
+            let mut compressor = Compressor::new(&mut model);
+            for &byte in &input {
+                compressor
+                    .load_symbol(Symbol::Byte(byte))
+                    .unwrap()
+                    .for_each(drop);
+            }
+            let (_, stats) = compressor.finalize().unwrap();
+            assert!(stats.theoretical_bits > 0.0);
+            // The output ends with up to 2 more bits, and the coding's finite precision only loses
+            // a fraction of a bit:
+            let overhead = stats.overhead_bits();
+            assert!(
+                (0.0..2.5).contains(&overhead),
+                "Overhead of {overhead} bits"
+            );
+        }
+    }
+
+    #[test]
+    fn test_finalize_emits_eof() {
+        let input = b"eof";
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let mut compressor = Compressor::new(&mut model);
+        let mut compressed = Vec::new();
+        for &byte in input {
+            compressed.extend(compressor.load_symbol(Symbol::Byte(byte)).unwrap());
+        }
+        compressed.extend(compressor.finalize().unwrap().0);
+
+        let mut decompressor = Decompressor::from_bytes(&mut model, compressed);
+        for &byte in input {
+            assert_eq!(decompressor.get_next_byte().unwrap(), Some(byte));
+        }
+        // Decompression ends on the EOF symbol, not on a timeout error:
+        assert_eq!(decompressor.get_next_byte().unwrap(), None);
+    }
+
+    /// A broken model, assigning every symbol a CFI that exceeds the total frequency
+    struct ExceedingCfiModel;
+
+    impl Model for ExceedingCfiModel {
+        fn get_cfi(&self, _symbol: Symbol) -> Result<ModelCfi, ModelCfiError> {
+            Ok(ModelCfi::IndexCfi(Cfi {
+                start: Frequency::new(8).unwrap(),
+                end: Frequency::new(12).unwrap(),
+                total: self.get_total(),
+            }))
+        }
+
+        fn get_symbol(&self, _cumulative_frequency: Frequency) -> Option<Symbol> {
+            Some(Symbol::Byte(0))
+        }
+
+        fn get_total(&self) -> Frequency {
+            Frequency::new(10).unwrap()
+        }
+
+        fn alphabet_size(&self) -> usize {
+            1
+        }
+    }
 
-        self.output
-            .get_complete_bytes()
-            .chain(self.output.get_leftover_bits())
+    #[test]
+    fn test_invalid_cfi_error_surfaces() {
+        let mut model = ExceedingCfiModel;
+        let mut compressor = Compressor::new(&mut model);
+        let err = compressor.load_symbol(Symbol::Byte(0)).err().unwrap();
+        assert!(err.downcast_ref::<InvalidCfiError>().is_some());
+        // The interval is left untouched, instead of being corrupted:
+        assert_eq!(*compressor.core.interval().low(), 0);
+        assert_eq!(
+            compressor.core.interval().high(),
+            compressor.core.interval().system().max()
+        );
     }
 }
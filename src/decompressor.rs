@@ -15,159 +15,1143 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use crate::bit_buffer::bit_iter::BitIterator;
 use crate::frequencies::Frequency;
-use crate::interval::{Interval, IntervalState};
-use crate::models::{Model, ModelCfi};
-use crate::number_types::{CalculationsType, ConstrainedNum, INTERVAL_BITS};
+use crate::interval::{Interval, RenormCore};
+use crate::models::{Model, ModelCfi, TooManyEscapesError};
+use crate::number_types::{CalculationsType, CodecParams, ConstrainedNum, INTERVAL_BITS};
 use crate::sim::Symbol;
+use crate::termination::{TerminationError, TerminationStrategy};
 use anyhow::{anyhow, ensure, Result};
 use log::debug;
+use std::collections::VecDeque;
 use thiserror::Error;
 
-/// Upper limit for the number of bits the Decompressor will try to read after `bits_iter` will be
-/// depleted
-const TIMEOUT_BITS: usize = INTERVAL_BITS as usize;
-
-pub struct Decompressor<'a, M: Model, I: Iterator<Item = bool>> {
-    /// Iterator over compressed bits
-    bits_iter: I,
-
-    /// Current interval in the decompression stage
-    interval: Interval,
+/// The state of the arithmetic decoding, independent of where the compressed bits come from
+struct DecodingState {
+    /// The renormalization core holding the current interval in the decompression stage
+    core: RenormCore,
 
     /// Current value from the input, used to locate the next symbol's interval within the current
     /// interval
     value: ConstrainedNum<INTERVAL_BITS>,
 
+    /// Number of bits that still need to be shifted into `value` before it is first used
+    unloaded_bits: u32,
+
+    /// How the end of the data is found
+    termination: TerminationStrategy,
+
+    /// Number of data symbols decoded so far (not including escapes and EOFs)
+    data_symbols: u64,
+}
+
+impl DecodingState {
+    fn new(params: CodecParams) -> Self {
+        Self {
+            core: RenormCore::new(params),
+            value: ConstrainedNum::zero(),
+            unloaded_bits: params.interval_bits(),
+            termination: TerminationStrategy::default(),
+            data_symbols: 0,
+        }
+    }
+
+    /// Whether the data of a length-terminated stream was fully decoded. Its end isn't marked by
+    /// an EOF, so decoding another symbol would decode the padding following it.
+    fn reached_length(&self) -> bool {
+        matches!(self.termination, TerminationStrategy::Length(length) if length == self.data_symbols)
+    }
+
+    /// Checks the decoded _symbol_ against the termination strategy: an EOF must follow exactly as
+    /// many data symbols as the strategy's length (if it has one).
+    fn check_termination(&mut self, symbol: &Symbol) -> Result<(), TerminationError> {
+        let length = self.termination.length();
+        match symbol {
+            Symbol::Byte(_) | Symbol::Bit(_) if length == Some(self.data_symbols) => {
+                Err(TerminationError::MissingEof(self.data_symbols))
+            }
+            Symbol::Byte(_) | Symbol::Bit(_) => {
+                self.data_symbols += 1;
+                Ok(())
+            }
+            Symbol::Eof => match length {
+                Some(expected) if expected != self.data_symbols => {
+                    Err(TerminationError::EarlyEof {
+                        expected,
+                        found: self.data_symbols,
+                    })
+                }
+                _ => Ok(()),
+            },
+            Symbol::Esc => Ok(()),
+        }
+    }
+
+    /// Number of bits in `value`. Once this many zero bits were loaded after the end of the input,
+    /// the input can't hold an EOF the decompression hasn't reached yet
+    fn timeout_bits(&self) -> usize {
+        self.interval().system().bits() as usize
+    }
+
+    /// Shifts bits from _next_bit_ into `value`, and processes the state of the interval until it
+    /// is non-converging, after which the next symbol can be decoded.
+    ///
+    /// If _next_bit_ runs out of bits first, false is returned. Nothing is lost in that case:
+    /// calling the function again once more bits are available continues where it stopped.
+    fn fill(&mut self, mut next_bit: impl FnMut() -> Option<bool>) -> bool {
+        while self.unloaded_bits > 0 {
+            let Some(bit) = next_bit() else {
+                return false;
+            };
+            self.value = (self.value << 1u8) | ConstrainedNum::<INTERVAL_BITS>::from(bit);
+            self.unloaded_bits -= 1;
+        }
+
+        // Shift the same bits out of value as out of the interval, and consume the next bit:
+        let Self { core, value, .. } = self;
+        core.renormalize(|shift, system| {
+            let Some(bit) = next_bit() else {
+                return false;
+            };
+            *value = shift.apply(*value, system) | ConstrainedNum::<INTERVAL_BITS>::from(bit);
+            true
+        })
+    }
+
+    /// The current interval in the decompression stage
+    fn interval(&self) -> &Interval {
+        self.core.interval()
+    }
+
+    /// Calculates the cumulative frequency saved in `value` based on the state of the current
+    /// interval and model.
+    fn calc_cum_freq<M: Model + ?Sized>(&self, model: &M) -> CalculationsType {
+        // Like in `Interval::update`, the product may not fit in CalculationsType, so it's computed
+        // in a wider type. The result is smaller than the total frequency, so narrowing is lossless:
+        let total = *model.get_total() as u128;
+        let offset = (*self.value - *self.interval().low() + 1) as u128;
+        let width = (*self.interval().high() + 1 - *self.interval().low()) as u128;
+        ((total * offset - 1) / width) as CalculationsType
+    }
+
+    /// Decodes the next symbol, and updates the model and interval like the compressor did after
+    /// encoding it. Must only be called after `fill` returned true.
+    fn decode_symbol<M: Model + ?Sized>(&mut self, model: &mut M) -> Result<Symbol> {
+        // A model whose total is zero assigns no symbol a CFI, and can't locate a cumulative
+        // frequency within its total:
+        ensure!(model.get_total() != Frequency::zero(), EmptyModelError);
+
+        // Get the original current symbol:
+        let cum_freq = Frequency::new(self.calc_cum_freq(model))?;
+        debug!(
+            "Decompressor: Decompressing cumulative frequency - {}",
+            cum_freq
+        );
+        let (symbol, cfi) = model
+            .decode(cum_freq)
+            .ok_or_else(|| anyhow!("Couldn't decompress this symbol"))?;
+
+        // Follow the original compression:
+        model.update(symbol, &cfi)?;
+        let cfi = match cfi {
+            ModelCfi::IndexCfi(cfi) => cfi,
+            ModelCfi::EscapeCfi(cfi) => cfi,
+        };
+
+        self.core.interval_mut().update(cfi)?;
+        Ok(symbol)
+    }
+}
+
+pub struct Decompressor<'a, M: Model + ?Sized, I: Iterator<Item = bool>> {
+    /// Iterator over compressed bits
+    bits_iter: I,
+
+    /// The interval and value of the decompression
+    state: DecodingState,
+
     /// Probability model, must be the same as the compressor's model for the decompression to work
     model: &'a mut M,
 
     /// Counter for number of times a bit outside of `bits_iter` was inserted into `value`
     timeout_bits: usize,
+
+    /// Counter for number of bits read from `bits_iter`
+    read_bits: usize,
+
+    /// If set, an EOF only ends the current record, and decompression continues with the next
+    /// one. Otherwise an EOF ends the whole stream
+    record_mode: bool,
+
+    /// Whether an EOF ending the whole stream was decompressed
+    ended: bool,
 }
 
-impl<'a, M: Model, I: Iterator<Item = bool>> Decompressor<'a, M, I> {
-    /// Creates a new decompressor object from a statistical model and a bits iterator.
+impl<'a, M: Model + ?Sized, I: Iterator<Item = bool>> Decompressor<'a, M, I> {
+    /// Creates a new decompressor object from a statistical model and a bits iterator. Missing bits
+    /// are loaded as zeros, but a stream can't be decoded from them alone: if _compressed_bits_ is
+    /// empty, decoding fails with a [DecompressionTimeout] error before any symbol is returned
+    /// (unless a length of zero ends the stream first, see `with_termination`).
     ///
     /// Note that if the model implements the `update` and `flush` functions, it is the
     /// **responsibility of the CALLER** to make sure the state of the model is not affected by
     /// previous operations (i.e: call the `flush` function if needed).
     pub fn new(model: &'a mut M, compressed_bits: I) -> Self {
+        Self::with_params(model, compressed_bits, CodecParams::default())
+    }
+
+    /// Creates a new decompressor like `Decompressor::new`, for bits compressed with the given
+    /// coding precisions (see `Compressor::with_params`).
+    pub fn with_params(model: &'a mut M, compressed_bits: I, params: CodecParams) -> Self {
         let mut this = Self {
             bits_iter: compressed_bits,
-            interval: Interval::full_interval(),
-            value: ConstrainedNum::zero(),
+            state: DecodingState::new(params),
             model,
             timeout_bits: 0,
+            read_bits: 0,
+            record_mode: false,
+            ended: false,
         };
 
         // Load bits into value:
-        this.load_bits_to_value(INTERVAL_BITS);
+        this.process_interval_state();
+        this
+    }
+
+    /// Creates a new decompressor in record mode, for streams holding several records separated
+    /// by EOF symbols (see `Compressor::end_record`).
+    ///
+    /// In record mode, `get_next_byte` returns None at the end of every record, and calling it
+    /// again decompresses the next record. The decompressor can't tell the end of a record from
+    /// the end of the stream, so the caller must know how many records the stream holds (e.g.
+    /// from an archive's framing) and stop after the last one.
+    pub fn with_records(model: &'a mut M, compressed_bits: I) -> Self {
+        let mut this = Self::new(model, compressed_bits);
+        this.record_mode = true;
         this
     }
 
-    /// Processes the state of the interval until it is non-converging
+    /// Sets how the end of the compressed data is found (see `Compressor::with_termination`). A
+    /// length-terminated stream ends once its length was decompressed, and the length of a stream
+    /// terminated by both must match where its EOF is found.<br>
+    /// Record mode separates records with EOFs, so it should only be used with the default
+    /// `TerminationStrategy::EofSymbol`.
+    pub fn with_termination(mut self, termination: TerminationStrategy) -> Self {
+        self.state.termination = termination;
+        self
+    }
+
+    /// Loads bits from `bits_iter` until the next symbol can be decoded. If `bits_iter` is empty,
+    /// zero bits are loaded instead, so this never runs out of bits.
     fn process_interval_state(&mut self) {
+        let Self {
+            bits_iter,
+            state,
+            timeout_bits,
+            read_bits,
+            ..
+        } = self;
+        let filled = state.fill(|| match bits_iter.next() {
+            // Add 1 to timeout bits:
+            None => {
+                *timeout_bits += 1;
+                Some(false)
+            }
+            Some(b) => {
+                *read_bits += 1;
+                Some(b)
+            }
+        });
+        debug_assert!(filled, "Zero bits are loaded forever, but loading stopped");
+    }
+
+    /// Returns the length in bytes of the compressed stream, assuming the EOF was already
+    /// decompressed.
+    ///
+    /// The decompressor reads ahead of the compressed stream, so bits following it in `bits_iter`
+    /// may have been consumed already. The stream's length is derived from the interval's shifts
+    /// instead: each shift loads one bit into `value` after the initial interval bits, while the
+    /// compressor outputs one bit per shift plus 2 bits when finalizing, padded to a whole byte.
+    pub fn stream_len(&self) -> usize {
+        let loaded_bits = self.read_bits + self.timeout_bits;
+        (loaded_bits + 2 - self.state.timeout_bits()).div_ceil(8)
+    }
+
+    /// Decompresses the next byte and returns it. If the end of the original bytes was reached,
+    /// None is returned (and will be returned by every following call).<br>
+    /// If the model decodes a symbol which isn't a byte (such as a bit in bit-mode), a
+    /// [NotAByteError] error is returned.
+    ///
+    /// In record mode, None is returned at the end of each record instead, and the following call
+    /// decompresses the next record.
+    pub fn get_next_byte(&mut self) -> Result<Option<u8>> {
+        match self.get_next_symbol()? {
+            Some(Symbol::Byte(b)) => Ok(Some(b)),
+            Some(symbol) => Err(NotAByteError(symbol).into()),
+            None => Ok(None),
+        }
+    }
+
+    /// Decompresses the next data symbol (a byte, or a bit in bit-mode) and returns it. Escapes
+    /// are resolved by the decompressor itself and never returned. If the end of the original
+    /// data was reached, None is returned (and will be returned by every following call).
+    ///
+    /// In record mode, None is returned at the end of each record instead, and the following call
+    /// decompresses the next record.
+    pub fn get_next_symbol(&mut self) -> Result<Option<Symbol>> {
+        if self.ended {
+            return Ok(None);
+        }
+        if self.state.reached_length() {
+            self.ended = true;
+            return Ok(None);
+        }
+
+        let max_escapes = self.model.max_escapes();
+        let mut escapes = 0;
         loop {
-            // Simply copy the compression stage:
-            let (low, high) = match self.interval.get_state() {
-                // Remove MSB:
-                IntervalState::Converging(_) => {
-                    self.load_bits_to_value(1);
-                    let low = self.interval.low() << 1u8;
-                    let high = (self.interval.high() << 1u8) | 1u8;
-
-                    (low, high)
-                }
-                // Remove second MSB:
-                IntervalState::NearConvergence => {
-                    let half = self.interval.system().half();
-                    let low = (self.interval.low() << 1u8) ^ half;
-                    let high = (self.interval.high() << 1u8) | (*half + 1);
+            // Check if we should time out:
+            ensure!(
+                self.timeout_bits < self.state.timeout_bits(),
+                DecompressionTimeout
+            );
 
-                    // Since value < high, it must start with 01 like low:
-                    self.value = ((self.value << 1u8) ^ half) | self.get_next_bit();
+            let symbol = self.state.decode_symbol(self.model)?;
+            self.process_interval_state();
+            self.state.check_termination(&symbol)?;
 
-                    (low, high)
+            // Return the data symbol, or None if it's an EOF:
+            match symbol {
+                Symbol::Byte(_) | Symbol::Bit(_) => return Ok(Some(symbol)),
+                Symbol::Eof => {
+                    self.ended = !self.record_mode;
+                    return Ok(None);
                 }
+                // If it's an escape symbol, the next one is decoded with the lower order:
+                Symbol::Esc => {
+                    escapes += 1;
+                    ensure!(escapes <= max_escapes, TooManyEscapesError(max_escapes));
+                }
+            }
+        }
+    }
 
-                IntervalState::NoConvergence => break,
-            };
-            self
-                .interval
-                .set_boundaries(low, high)
-                .expect(
-                    "Removing similar bit or removing second MSB never breaks interval invariance, but it did somehow"
-                );
+    /// Returns an iterator decompressing the next data symbols, like repeated calls to
+    /// `get_next_symbol`. The iterator ends at the end of the original data (or of the current
+    /// record in record mode), and right after yielding an error.
+    pub fn symbols(&mut self) -> Decompressed<'_, 'a, M, I, Symbol> {
+        Decompressed::new(self, Self::get_next_symbol)
+    }
+
+    /// Returns an iterator decompressing the next bytes, like repeated calls to `get_next_byte`.
+    /// The iterator ends at the end of the original bytes (or of the current record in record
+    /// mode), and right after yielding an error.
+    pub fn bytes(&mut self) -> Decompressed<'_, 'a, M, I, u8> {
+        Decompressed::new(self, Self::get_next_byte)
+    }
+}
+
+impl<'a, 'b, M: Model + ?Sized> Decompressor<'a, M, BitIterator<'b>> {
+    /// Creates a new decompressor like `Decompressor::new`, reading the compressed bits from
+    /// _bytes_ (such as the bytes returned by the `Compressor`).
+    pub fn from_bytes<B: IntoIterator<Item = u8> + 'b>(model: &'a mut M, bytes: B) -> Self {
+        Self::new(model, BitIterator::from(bytes))
+    }
+}
+
+/// An iterator over the items a [Decompressor] decompresses (symbols or bytes), see
+/// `Decompressor::symbols` and `Decompressor::bytes`
+pub struct Decompressed<'d, 'a, M: Model + ?Sized, I: Iterator<Item = bool>, T> {
+    /// The decompressor the items are taken from
+    decompressor: &'d mut Decompressor<'a, M, I>,
+    /// Decompresses the next item, or returns None at the end of the data
+    next_item: fn(&mut Decompressor<'a, M, I>) -> Result<Option<T>>,
+    /// Whether the data ended or an error was yielded, so no more items are decompressed
+    done: bool,
+}
+
+impl<'d, 'a, M: Model + ?Sized, I: Iterator<Item = bool>, T> Decompressed<'d, 'a, M, I, T> {
+    fn new(
+        decompressor: &'d mut Decompressor<'a, M, I>,
+        next_item: fn(&mut Decompressor<'a, M, I>) -> Result<Option<T>>,
+    ) -> Self {
+        Self {
+            decompressor,
+            next_item,
+            done: false,
         }
     }
+}
 
-    /// Retrieve the next bit from the iterator as a ConstrainedNum, or returns 0 if `bits_iter` is
-    /// empty.
-    fn get_next_bit(&mut self) -> ConstrainedNum<INTERVAL_BITS> {
-        match self.bits_iter.next() {
-            // Add 1 to timeout bits:
-            None => {
-                self.timeout_bits += 1;
-                ConstrainedNum::zero()
-            }
-            Some(b) => b.into(),
+impl<M: Model + ?Sized, I: Iterator<Item = bool>, T> Iterator for Decompressed<'_, '_, M, I, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
         }
+        let next = (self.next_item)(self.decompressor).transpose();
+        // Errors such as a timeout would repeat forever, so the iterator stops after one:
+        self.done = !matches!(next, Some(Ok(_)));
+        next
+    }
+}
+
+/// A push-based decompressor, for compressed data that arrives in chunks (e.g. from a network
+/// stream).
+///
+/// Every chunk is given to `push_bytes`, which returns the bytes that could be decompressed from
+/// the data pushed so far. Decompressing a symbol requires bits beyond it, so the last symbols of
+/// the stream are only decompressed once `finish` is called to signal the end of the input.<br>
+/// The decoder outputs bytes, so the model must decode byte symbols - a decoded bit symbol is
+/// reported as a [NotAByteError] error.
+pub struct StreamingDecoder<'a, M: Model + ?Sized> {
+    /// The interval and value of the decompression
+    state: DecodingState,
+
+    /// Probability model, must be the same as the compressor's model for the decompression to work
+    model: &'a mut M,
+
+    /// Compressed bits that were pushed but not loaded yet
+    pending_bits: VecDeque<bool>,
+
+    /// Whether the end of the input was signaled. Once it is, zero bits are loaded instead of
+    /// waiting for more input
+    input_ended: bool,
+
+    /// Counter for number of zero bits loaded after the end of the input
+    timeout_bits: usize,
+
+    /// Whether the EOF was decompressed
+    ended: bool,
+
+    /// Number of escapes decompressed since the last symbol that wasn't an escape
+    escapes: usize,
+}
+
+impl<'a, M: Model + ?Sized> StreamingDecoder<'a, M> {
+    /// Creates a new streaming decoder from a statistical model.
+    ///
+    /// Like with [Decompressor::new], it is the **responsibility of the CALLER** to make sure the
+    /// state of the model is not affected by previous operations.
+    pub fn new(model: &'a mut M) -> Self {
+        Self::with_params(model, CodecParams::default())
     }
 
-    /// Shifts bits from `bits_iter` into `value`. If `bits_iter` is empty, zero bits will be
-    /// inserted into `value`.
-    fn load_bits_to_value(&mut self, bits_num: u32) {
-        for _ in 0..bits_num {
-            self.value = (self.value << 1u8) | self.get_next_bit();
+    /// Creates a new streaming decoder like `StreamingDecoder::new`, for data compressed with the
+    /// given coding precisions (see `Compressor::with_params`).
+    pub fn with_params(model: &'a mut M, params: CodecParams) -> Self {
+        Self {
+            state: DecodingState::new(params),
+            model,
+            pending_bits: VecDeque::new(),
+            input_ended: false,
+            timeout_bits: 0,
+            ended: false,
+            escapes: 0,
         }
     }
 
-    /// Calculates the cumulative frequency saved in `value` based on the state of the current
-    /// interval and model.
-    fn calc_cum_freq(&self) -> CalculationsType {
-        (*self.model.get_total() * (*self.value - *self.interval.low() + 1) - 1)
-            / (*self.interval.high() + 1 - *self.interval.low())
+    /// Sets how the end of the compressed data is found, like `Decompressor::with_termination`
+    pub fn with_termination(mut self, termination: TerminationStrategy) -> Self {
+        self.state.termination = termination;
+        self
     }
 
-    /// Decompresses the next byte and returns it. If the end of the original bytes was reached,
-    /// None is returned.
-    pub fn get_next_byte(&mut self) -> Result<Option<u8>> {
-        // Check if we should time out:
-        ensure!(self.timeout_bits < TIMEOUT_BITS, DecompressionTimeout);
+    /// Adds the next chunk of the compressed stream, and returns the bytes that can now be
+    /// decompressed. Bytes pushed after the stream's EOF was decompressed are ignored.
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Result<Vec<u8>> {
+        if !self.ended {
+            self.pending_bits.extend(
+                bytes
+                    .iter()
+                    .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1)),
+            );
+        }
+        self.decode_available()
+    }
 
-        // Get the original current symbol:
-        let cum_freq = Frequency::new(self.calc_cum_freq())?;
-        debug!(
-            "Decompressor: Decompressing cumulative frequency - {}",
-            cum_freq
-        );
-        let symbol = self
-            .model
-            .get_symbol(cum_freq)
-            .ok_or_else(|| anyhow!("Couldn't decompress this symbol"))?;
+    /// Signals the end of the compressed input, and returns the remaining decompressed bytes.<br>
+    /// If the stream ends before its data does, a [DecompressionTimeout] error is returned.
+    pub fn finish(&mut self) -> Result<Vec<u8>> {
+        self.input_ended = true;
+        let decoded = self.decode_available()?;
+        ensure!(self.ended, DecompressionTimeout);
+        Ok(decoded)
+    }
 
-        // Follow the original compression:
-        let cfi = self.model.get_cfi(symbol)?;
-        self.model.update(symbol, &cfi)?;
-        let cfi = match cfi {
-            ModelCfi::IndexCfi(cfi) => cfi,
-            ModelCfi::EscapeCfi(cfi) => cfi,
-        };
+    /// Whether the EOF of the stream was decompressed
+    pub fn is_finished(&self) -> bool {
+        self.ended
+    }
 
-        self.interval.update(cfi);
-        self.process_interval_state();
+    /// Decompresses bytes until the EOF is reached, or until more input is needed
+    fn decode_available(&mut self) -> Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        while !self.ended {
+            if self.state.reached_length() {
+                self.ended = true;
+                break;
+            }
+            if !self.process_interval_state() {
+                break;
+            }
+            // Check if we should time out:
+            ensure!(
+                self.timeout_bits < self.state.timeout_bits(),
+                DecompressionTimeout
+            );
 
-        // Return the byte representing the symbol, or None if it's an EOF:
-        match symbol {
-            Symbol::Byte(b) => Ok(Some(b)),
-            Symbol::Eof => Ok(None),
-            // If it's an escape symbol, we need to redo the function:
-            Symbol::Esc => self.get_next_byte(),
+            let symbol = self.state.decode_symbol(self.model)?;
+            self.state.check_termination(&symbol)?;
+            self.escapes = if symbol.is_escape() {
+                self.escapes + 1
+            } else {
+                0
+            };
+            let max_escapes = self.model.max_escapes();
+            ensure!(
+                self.escapes <= max_escapes,
+                TooManyEscapesError(max_escapes)
+            );
+
+            match symbol {
+                Symbol::Byte(b) => decoded.push(b),
+                Symbol::Bit(_) => return Err(NotAByteError(symbol).into()),
+                Symbol::Eof => self.ended = true,
+                Symbol::Esc => {}
+            }
         }
+        Ok(decoded)
+    }
+
+    /// Loads the pending bits until the next symbol can be decoded. Returns false if more input is
+    /// needed first.
+    fn process_interval_state(&mut self) -> bool {
+        let Self {
+            state,
+            pending_bits,
+            input_ended,
+            timeout_bits,
+            ..
+        } = self;
+        state.fill(|| match pending_bits.pop_front() {
+            Some(b) => Some(b),
+            // Once the input ended, zero bits are loaded like in the decompressor:
+            None if *input_ended => {
+                *timeout_bits += 1;
+                Some(false)
+            }
+            None => None,
+        })
     }
 }
 
 #[derive(Debug, Error)]
 #[error("Decompressor timed out: an EOF was not found in the given bits")]
 pub struct DecompressionTimeout;
+
+#[derive(Debug, Error)]
+#[error("The model's total frequency is zero, so no symbol can be decompressed with it")]
+pub struct EmptyModelError;
+
+#[derive(Debug, Error)]
+#[error("Decompressed the symbol '{0}' where a byte was expected")]
+pub struct NotAByteError(pub Symbol);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressor::Compressor;
+    use crate::frequencies::Cfi;
+    use crate::interval::{boundary, IntervalBoundary};
+    use crate::models::adaptive::AdaptiveModel;
+    use crate::models::distributions::uniform::UniformDistributionModel;
+    use crate::models::ppm::PpmModel;
+    use crate::models::ModelCfiError;
+    use crate::sim::{BitSIM, DefaultSIM};
+    use std::cell::Cell;
+
+    /// Processes the interval state of a decompressor whose interval is [_low_, _high_] and whose
+    /// value is _value_. The next bit in the input is _next_bit_.
+    fn process(
+        model: &mut UniformDistributionModel<DefaultSIM>,
+        (low, high): (IntervalBoundary, IntervalBoundary),
+        value: IntervalBoundary,
+        next_bit: bool,
+    ) -> Decompressor<'_, UniformDistributionModel<DefaultSIM>, std::vec::IntoIter<bool>> {
+        // The first INTERVAL_BITS bits are loaded on creation, then overridden:
+        let mut bits = vec![false; INTERVAL_BITS as usize];
+        bits.push(next_bit);
+
+        let mut decompressor = Decompressor::new(model, bits.into_iter());
+        decompressor
+            .state
+            .core
+            .interval_mut()
+            .set_boundaries(low, high)
+            .unwrap();
+        decompressor.state.value = value;
+        decompressor.process_interval_state();
+        decompressor
+    }
+
+    #[test]
+    fn test_converging_true_step() {
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let decompressor = process(
+            &mut model,
+            (boundary("1001", false), boundary("1110", true)),
+            boundary("1011", false),
+            true,
+        );
+
+        // The MSB is shifted out of value, and the next input bit is shifted in:
+        assert_eq!(*decompressor.state.value, *boundary("011", false) | 1);
+    }
+
+    #[test]
+    fn test_converging_false_step() {
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let decompressor = process(
+            &mut model,
+            (boundary("0001", false), boundary("0110", true)),
+            boundary("0101", false),
+            false,
+        );
+
+        assert_eq!(decompressor.state.value, boundary("101", false));
+    }
+
+    #[test]
+    fn test_near_convergence_step() {
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let decompressor = process(
+            &mut model,
+            (boundary("01001", false), boundary("10110", true)),
+            boundary("10011", false),
+            true,
+        );
+
+        // Value's second MSB is removed like the boundaries', and the next input bit is shifted in:
+        assert_eq!(*decompressor.state.value, *boundary("1011", false) | 1);
+    }
+
+    /// Compresses the given records into a single stream, separated by EOF symbols
+    fn compress_records(records: &[&[u8]]) -> Vec<u8> {
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let mut compressor = Compressor::new(&mut model);
+        let mut compressed = Vec::new();
+        for (i, record) in records.iter().enumerate() {
+            if i > 0 {
+                compressed.extend(compressor.end_record().unwrap());
+            }
+            for &byte in record.iter() {
+                compressed.extend(compressor.load_symbol(Symbol::Byte(byte)).unwrap());
+            }
+        }
+        compressed.extend(compressor.finalize().unwrap().0);
+        compressed
+    }
+
+    /// Decompresses bytes until the decompressor returns None
+    fn decompress_record<I: Iterator<Item = bool>>(
+        decompressor: &mut Decompressor<'_, AdaptiveModel<DefaultSIM>, I>,
+    ) -> Vec<u8> {
+        std::iter::from_fn(|| decompressor.get_next_byte().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_record_mode_decompresses_every_record() {
+        let records: [&[u8]; 2] = [b"first record", b"and the second one"];
+        let compressed = compress_records(&records);
+
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let mut decompressor =
+            Decompressor::with_records(&mut model, BitIterator::from(compressed.clone()));
+        assert_eq!(decompress_record(&mut decompressor), records[0]);
+        assert_eq!(decompress_record(&mut decompressor), records[1]);
+        assert_eq!(decompressor.stream_len(), compressed.len());
+    }
+
+    #[test]
+    fn test_eof_ends_stream_outside_record_mode() {
+        let records: [&[u8]; 2] = [b"first record", b"and the second one"];
+        let compressed = compress_records(&records);
+
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let mut decompressor = Decompressor::from_bytes(&mut model, compressed);
+        assert_eq!(decompress_record(&mut decompressor), records[0]);
+        // Without record mode, the first EOF ends the stream:
+        assert_eq!(decompressor.get_next_byte().unwrap(), None);
+    }
+
+    #[test]
+    fn test_bit_symbols_are_not_bytes() {
+        let bits = [true, false, false, true];
+        let mut model = AdaptiveModel::new(BitSIM);
+        let mut compressor = Compressor::new(&mut model);
+        let mut compressed = Vec::new();
+        for bit in bits {
+            compressed.extend(compressor.load_symbol(Symbol::Bit(bit)).unwrap());
+        }
+        compressed.extend(compressor.finalize().unwrap().0);
+
+        let mut model = AdaptiveModel::new(BitSIM);
+        let mut decompressor = Decompressor::from_bytes(&mut model, compressed.clone());
+        let decoded: Vec<Symbol> =
+            std::iter::from_fn(|| decompressor.get_next_symbol().unwrap()).collect();
+        assert_eq!(decoded, bits.map(Symbol::Bit));
+
+        // Bits are never mistaken for bytes:
+        let mut model = AdaptiveModel::new(BitSIM);
+        let mut decompressor = Decompressor::from_bytes(&mut model, compressed);
+        let err = decompressor.get_next_byte().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(NotAByteError(Symbol::Bit(true)))
+        ));
+    }
+
+    /// A model which holds no frequencies at all
+    struct EmptyModel;
+
+    impl Model for EmptyModel {
+        fn get_cfi(&self, symbol: Symbol) -> Result<ModelCfi, ModelCfiError> {
+            Err(ModelCfiError::EmptyCfi { symbol })
+        }
+
+        fn get_symbol(&self, _cumulative_frequency: Frequency) -> Option<Symbol> {
+            None
+        }
+
+        fn get_total(&self) -> Frequency {
+            Frequency::zero()
+        }
+
+        fn alphabet_size(&self) -> usize {
+            0
+        }
+    }
+
+    #[test]
+    fn test_empty_model_rejected() {
+        let mut model = EmptyModel;
+        let mut decompressor = Decompressor::new(&mut model, vec![true; 64].into_iter());
+        let err = decompressor.get_next_byte().unwrap_err();
+        assert!(err.downcast_ref::<EmptyModelError>().is_some());
+
+        let mut decoder = StreamingDecoder::new(&mut model);
+        let err = decoder.push_bytes(&[0xFF; 8]).unwrap_err();
+        assert!(err.downcast_ref::<EmptyModelError>().is_some());
+    }
+
+    /// A broken model, which escapes from every symbol and decodes nothing but escapes
+    struct AlwaysEscapingModel;
+
+    impl AlwaysEscapingModel {
+        fn escape_cfi() -> Cfi {
+            Cfi {
+                start: Frequency::zero(),
+                end: Frequency::one(),
+                total: Frequency::new(2).unwrap(),
+            }
+        }
+    }
+
+    impl Model for AlwaysEscapingModel {
+        fn get_cfi(&self, _symbol: Symbol) -> Result<ModelCfi, ModelCfiError> {
+            Ok(ModelCfi::EscapeCfi(Self::escape_cfi()))
+        }
+
+        fn get_symbol(&self, _cumulative_frequency: Frequency) -> Option<Symbol> {
+            Some(Symbol::Esc)
+        }
+
+        fn get_total(&self) -> Frequency {
+            Frequency::new(2).unwrap()
+        }
+
+        fn alphabet_size(&self) -> usize {
+            1
+        }
+
+        fn max_escapes(&self) -> usize {
+            3
+        }
+    }
+
+    #[test]
+    fn test_endless_escapes_rejected() {
+        let mut model = AlwaysEscapingModel;
+        let mut compressor = Compressor::new(&mut model);
+        let err = compressor.load_symbol(Symbol::Byte(0)).err().unwrap();
+        assert_eq!(err.downcast_ref(), Some(&TooManyEscapesError(3)));
+
+        // Every input decodes into escapes, which stop before overflowing the stack:
+        let mut decompressor = Decompressor::new(&mut model, vec![false; 64].into_iter());
+        let err = decompressor.get_next_byte().unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&TooManyEscapesError(3)));
+
+        let mut decoder = StreamingDecoder::new(&mut model);
+        let err = decoder.push_bytes(&[0; 8]).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&TooManyEscapesError(3)));
+    }
+
+    /// Wraps a model, and counts how many times the decompressor looks symbols up in it
+    struct CountingModel<M: Model> {
+        inner: M,
+        /// Whether `decode` forwards to the inner model's single-lookup `decode`, or looks the
+        /// symbol up twice like the trait's default implementation
+        single_lookup: bool,
+        lookups: Cell<usize>,
+    }
+
+    impl<M: Model> Model for CountingModel<M> {
+        fn get_cfi(&self, symbol: Symbol) -> Result<ModelCfi, ModelCfiError> {
+            self.lookups.set(self.lookups.get() + 1);
+            self.inner.get_cfi(symbol)
+        }
+
+        fn get_symbol(&self, cumulative_frequency: Frequency) -> Option<Symbol> {
+            self.lookups.set(self.lookups.get() + 1);
+            self.inner.get_symbol(cumulative_frequency)
+        }
+
+        fn get_total(&self) -> Frequency {
+            self.inner.get_total()
+        }
+
+        fn alphabet_size(&self) -> usize {
+            self.inner.alphabet_size()
+        }
+
+        fn max_escapes(&self) -> usize {
+            self.inner.max_escapes()
+        }
+
+        fn decode(&self, cumulative_frequency: Frequency) -> Option<(Symbol, ModelCfi)> {
+            if self.single_lookup {
+                self.lookups.set(self.lookups.get() + 1);
+                self.inner.decode(cumulative_frequency)
+            } else {
+                let symbol = self.get_symbol(cumulative_frequency)?;
+                Some((symbol, self.get_cfi(symbol).ok()?))
+            }
+        }
+
+        fn flush(&mut self) {
+            self.inner.flush()
+        }
+
+        fn update(&mut self, symbol: Symbol, model_result: &ModelCfi) -> Result<()> {
+            self.inner.update(symbol, model_result)
+        }
+    }
+
+    #[test]
+    fn test_decode_takes_a_single_lookup_per_symbol() {
+        let data = b"one lookup finds both the symbol and its interval";
+        let compressed = compress_records(&[data]);
+
+        let mut lookups = Vec::new();
+        for single_lookup in [false, true] {
+            let mut model = CountingModel {
+                inner: AdaptiveModel::new(DefaultSIM),
+                single_lookup,
+                lookups: Cell::new(0),
+            };
+            let mut decompressor = Decompressor::from_bytes(&mut model, compressed.clone());
+            let decompressed: Vec<u8> =
+                std::iter::from_fn(|| decompressor.get_next_byte().unwrap()).collect();
+            assert_eq!(decompressed, data);
+            lookups.push(model.lookups.get());
+        }
+
+        // Every byte and the EOF are looked up twice by default, and once by the adaptive model:
+        let symbols = data.len() + 1;
+        assert_eq!(lookups, [2 * symbols, symbols]);
+    }
+
+    /// Compresses _data_ with an order-2 PPM model
+    fn compress_ppm(data: &[u8]) -> Vec<u8> {
+        let mut model = PpmModel::new(DefaultSIM, 2);
+        let mut compressor = Compressor::new(&mut model);
+        let mut compressed = Vec::new();
+        for &byte in data {
+            compressed.extend(compressor.load_symbol(Symbol::Byte(byte)).unwrap());
+        }
+        compressed.extend(compressor.finalize().unwrap().0);
+        compressed
+    }
+
+    #[test]
+    fn test_from_bytes_round_trip() {
+        let data = b"decompressed straight from the compressor's bytes";
+        let compressed = compress_ppm(data);
+
+        let mut model = PpmModel::new(DefaultSIM, 2);
+        let mut decompressor = Decompressor::from_bytes(&mut model, compressed.clone());
+        let decompressed: Vec<u8> =
+            std::iter::from_fn(|| decompressor.get_next_byte().unwrap()).collect();
+        assert_eq!(decompressed, data);
+        assert_eq!(decompressor.stream_len(), compressed.len());
+    }
+
+    #[test]
+    fn test_streaming_decoder_matches_one_shot() {
+        let data = b"abracadabra, abracadabra! the streaming decoder gets this one chunk at a time";
+        let compressed = compress_ppm(data);
+
+        let mut model = PpmModel::new(DefaultSIM, 2);
+        let mut decompressor = Decompressor::from_bytes(&mut model, compressed.clone());
+        let one_shot: Vec<u8> =
+            std::iter::from_fn(|| decompressor.get_next_byte().unwrap()).collect();
+        assert_eq!(one_shot, data);
+
+        for chunk_size in [1, 3, 7, compressed.len()] {
+            let mut model = PpmModel::new(DefaultSIM, 2);
+            let mut decoder = StreamingDecoder::new(&mut model);
+            let mut streamed = Vec::new();
+            for chunk in compressed.chunks(chunk_size) {
+                streamed.extend(decoder.push_bytes(chunk).unwrap());
+            }
+            streamed.extend(decoder.finish().unwrap());
+            assert!(decoder.is_finished());
+            assert_eq!(streamed, one_shot, "Chunks of {chunk_size} bytes");
+        }
+    }
+
+    #[test]
+    fn test_streaming_decoder_decodes_before_finish() {
+        let data = [b'x'; 200];
+        let compressed = compress_ppm(&data);
+
+        let mut model = PpmModel::new(DefaultSIM, 2);
+        let mut decoder = StreamingDecoder::new(&mut model);
+        let early = decoder.push_bytes(&compressed).unwrap();
+        // Only the bytes near the end of the stream need the input to end:
+        assert!(!early.is_empty());
+        assert!(early.iter().all(|&b| b == b'x'));
+        let rest = decoder.finish().unwrap();
+        assert_eq!(early.len() + rest.len(), data.len());
+    }
+
+    #[test]
+    fn test_streaming_decoder_without_eof_times_out() {
+        let compressed = compress_ppm(b"some data");
+
+        let mut model = PpmModel::new(DefaultSIM, 2);
+        let mut decoder = StreamingDecoder::new(&mut model);
+        decoder.push_bytes(&compressed[..1]).unwrap();
+        assert!(decoder.finish().is_err());
+        assert!(!decoder.is_finished());
+    }
+
+    #[test]
+    fn test_bytes_iterator_collects_stream() {
+        // PPM escapes often while it learns, and the iterator must resolve them:
+        let data = b"an iterator over the decompressed bytes of an iterator";
+        let compressed = compress_ppm(data);
+
+        let mut model = PpmModel::new(DefaultSIM, 2);
+        let mut decompressor = Decompressor::new(&mut model, BitIterator::from_slice(&compressed));
+        let decompressed = decompressor.bytes().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(decompressed, data);
+        // The stream ended, so a new iterator is empty right away:
+        assert_eq!(decompressor.bytes().count(), 0);
+    }
+
+    #[test]
+    fn test_bytes_iterator_per_record() {
+        let records: [&[u8]; 2] = [b"first record", b"and the second one"];
+        let compressed = compress_records(&records);
+
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let mut decompressor =
+            Decompressor::with_records(&mut model, BitIterator::from_slice(&compressed));
+        for record in records {
+            let decompressed = decompressor.bytes().collect::<Result<Vec<_>>>().unwrap();
+            assert_eq!(decompressed, record);
+        }
+    }
+
+    #[test]
+    fn test_iterators_end_after_an_error() {
+        // A stream cut short never reaches its EOF, so the decompressor times out eventually:
+        let compressed = compress_ppm(b"some data which is cut short");
+        let mut model = PpmModel::new(DefaultSIM, 2);
+        let mut decompressor =
+            Decompressor::new(&mut model, BitIterator::from_slice(&compressed[..2]));
+        let results: Vec<Result<u8>> = decompressor.bytes().collect();
+        assert!(results.last().unwrap().is_err());
+        assert!(results[..results.len() - 1].iter().all(Result::is_ok));
+
+        // Bits in bit-mode aren't bytes, but they are symbols:
+        let mut model = AdaptiveModel::new(BitSIM);
+        let mut compressor = Compressor::new(&mut model);
+        let mut compressed: Vec<u8> = compressor.load_symbol(Symbol::Bit(true)).unwrap().collect();
+        compressed.extend(compressor.finalize().unwrap().0);
+
+        let mut model = AdaptiveModel::new(BitSIM);
+        let mut decompressor = Decompressor::new(&mut model, BitIterator::from_slice(&compressed));
+        let results: Vec<Result<u8>> = decompressor.bytes().collect();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+
+        let mut model = AdaptiveModel::new(BitSIM);
+        let mut decompressor = Decompressor::new(&mut model, BitIterator::from_slice(&compressed));
+        let symbols = decompressor.symbols().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(symbols, [Symbol::Bit(true)]);
+    }
+
+    /// Compresses _data_ with a uniform model, ending it according to _termination_
+    fn compress_terminated(data: &[u8], termination: TerminationStrategy) -> Vec<u8> {
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let mut compressor = Compressor::new(&mut model).with_termination(termination);
+        let mut compressed = Vec::new();
+        for &byte in data {
+            compressed.extend(compressor.load_symbol(Symbol::Byte(byte)).unwrap());
+        }
+        compressed.extend(compressor.finalize().unwrap().0);
+        compressed
+    }
+
+    /// Decompresses the symbols of _compressed_ with a uniform model, finding their end according
+    /// to _termination_. At most _limit_ symbols are decompressed.
+    fn decompress_terminated(
+        compressed: &[u8],
+        termination: TerminationStrategy,
+        limit: usize,
+    ) -> Result<Vec<Symbol>> {
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let mut decompressor =
+            Decompressor::from_bytes(&mut model, compressed.to_vec()).with_termination(termination);
+        decompressor.symbols().take(limit).collect()
+    }
+
+    #[test]
+    fn test_termination_strategies_round_trip() {
+        let data = b"every strategy finds the end of this data";
+        let length = data.len() as u64;
+        let expected: Vec<Symbol> = data.iter().map(|&byte| Symbol::Byte(byte)).collect();
+        for termination in [
+            TerminationStrategy::EofSymbol,
+            TerminationStrategy::Length(length),
+            TerminationStrategy::Both(length),
+        ] {
+            let compressed = compress_terminated(data, termination);
+            let decompressed = decompress_terminated(&compressed, termination, usize::MAX);
+            assert_eq!(decompressed.unwrap(), expected, "{termination:?}");
+
+            // The streaming decoder finds the same end:
+            let mut model = UniformDistributionModel::new(DefaultSIM);
+            let mut decoder = StreamingDecoder::new(&mut model).with_termination(termination);
+            let mut streamed = decoder.push_bytes(&compressed).unwrap();
+            streamed.extend(decoder.finish().unwrap());
+            assert_eq!(streamed, data, "{termination:?}");
+        }
+    }
+
+    #[test]
+    fn test_empty_data_round_trips() {
+        for termination in [
+            TerminationStrategy::EofSymbol,
+            TerminationStrategy::Length(0),
+            TerminationStrategy::Both(0),
+        ] {
+            let compressed = compress_terminated(b"", termination);
+            let decompressed = decompress_terminated(&compressed, termination, usize::MAX);
+            assert_eq!(decompressed.unwrap(), [], "{termination:?}");
+
+            let mut model = UniformDistributionModel::new(DefaultSIM);
+            let mut decoder = StreamingDecoder::new(&mut model).with_termination(termination);
+            let mut streamed = decoder.push_bytes(&compressed).unwrap();
+            streamed.extend(decoder.finish().unwrap());
+            assert_eq!(streamed, [], "{termination:?}");
+        }
+        // Without an EOF, the flushed bits are all an empty stream needs:
+        assert!(compress_terminated(b"", TerminationStrategy::Length(0)).len() <= 1);
+    }
+
+    #[test]
+    fn test_missing_bits_decode_no_spurious_symbol() {
+        // Creating the decompressor loads zero bits for all of its interval bits, which must not
+        // decode into a first byte of the stream:
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let mut decompressor = Decompressor::new(&mut model, std::iter::empty());
+        let error = decompressor.get_next_byte().unwrap_err();
+        assert!(error.downcast_ref::<DecompressionTimeout>().is_some());
+
+        // Unless the length says there's nothing to decode:
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let mut decompressor = Decompressor::new(&mut model, std::iter::empty())
+            .with_termination(TerminationStrategy::Length(0));
+        assert_eq!(decompressor.get_next_byte().unwrap(), None);
+    }
+
+    #[test]
+    fn test_length_termination_skips_the_eof() {
+        let data = b"no eof here";
+        let with_eof = compress_terminated(data, TerminationStrategy::EofSymbol);
+        let without_eof = compress_terminated(data, TerminationStrategy::Length(data.len() as u64));
+        assert!(without_eof.len() < with_eof.len());
+    }
+
+    #[test]
+    fn test_padding_decodes_past_an_unmarked_end() {
+        let data = b"padding follows";
+        let length = data.len() as u64;
+        let compressed = compress_terminated(data, TerminationStrategy::Length(length));
+
+        // Without an EOF, the padding bits after the data decode into an extra byte:
+        let decoded =
+            decompress_terminated(&compressed, TerminationStrategy::EofSymbol, data.len() + 1)
+                .unwrap();
+        assert_eq!(decoded.len(), data.len() + 1);
+        assert!(matches!(decoded[data.len()], Symbol::Byte(_)));
+
+        // The length stops decoding right at the end of the data:
+        let decoded =
+            decompress_terminated(&compressed, TerminationStrategy::Length(length), usize::MAX)
+                .unwrap();
+        assert_eq!(decoded.len(), data.len());
+    }
+
+    #[test]
+    fn test_both_termination_cross_checks() {
+        let data = b"cross-checked";
+        let length = data.len() as u64;
+        let compressed = compress_terminated(data, TerminationStrategy::Both(length));
+        let error = |compressed: &[u8], termination| {
+            decompress_terminated(compressed, termination, usize::MAX)
+                .unwrap_err()
+                .downcast::<TerminationError>()
+                .unwrap()
+        };
+
+        // The EOF comes before the length says the data ends:
+        assert_eq!(
+            error(&compressed, TerminationStrategy::Both(length + 1)),
+            TerminationError::EarlyEof {
+                expected: length + 1,
+                found: length
+            }
+        );
+        // The length says the data ends, but a data symbol follows:
+        assert_eq!(
+            error(&compressed, TerminationStrategy::Both(length - 1)),
+            TerminationError::MissingEof(length - 1)
+        );
+        // A length-terminated stream must not find an EOF before its end either:
+        let with_eof = compress_terminated(data, TerminationStrategy::EofSymbol);
+        assert_eq!(
+            error(&with_eof, TerminationStrategy::Length(length + 1)),
+            TerminationError::EarlyEof {
+                expected: length + 1,
+                found: length
+            }
+        );
+    }
+}
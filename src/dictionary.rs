@@ -0,0 +1,128 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::models::Model;
+use crate::parser::Parser;
+use anyhow::Result;
+use log::debug;
+use std::path::Path;
+use thiserror::Error;
+
+/// Number of bytes the dictionary's hash takes at the start of a compressed stream
+pub const DICTIONARY_HASH_BYTES: usize = 4;
+
+/// Computes the 32-bit FNV-1a hash of _bytes_. It's simple, fast and identical on every platform,
+/// which makes it suitable for identifying data (it's not cryptographically secure).
+pub fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+/// A preset dictionary, shared by the compressor and the decompressor.
+///
+/// Before any input is coded, the dictionary is run through the model so its statistics are
+/// already learned. Data similar to the dictionary therefore compresses better from its very first
+/// bytes, without storing the dictionary in every compressed file.<br>
+/// Priming only affects adaptive models, static models ignore it.
+pub struct Dictionary {
+    /// The dictionary's raw content
+    content: Vec<u8>,
+}
+
+/// Errors that may occur when verifying the dictionary of a compressed stream
+#[derive(Debug, Error, PartialEq)]
+pub enum DictionaryError {
+    #[error("The compressed input is too short to hold a dictionary hash")]
+    MissingHash,
+    #[error("The compressed input was made with a different dictionary (expected hash {expected:08x}, found {found:08x})")]
+    Mismatch { expected: u32, found: u32 },
+}
+
+impl Dictionary {
+    pub fn new(content: Vec<u8>) -> Self {
+        Self { content }
+    }
+
+    /// Reads the dictionary from the file at _path_.
+    pub fn from_file(path: &Path) -> std::io::Result<Self> {
+        Ok(Self::new(std::fs::read(path)?))
+    }
+
+    /// A 32-bit FNV-1a hash of the dictionary's content, identifying it in compressed streams.
+    pub fn hash(&self) -> u32 {
+        fnv1a(&self.content)
+    }
+
+    /// Checks that the hash at the start of a compressed stream belongs to this dictionary.
+    pub fn verify(&self, hash_bytes: &[u8]) -> Result<(), DictionaryError> {
+        let found: [u8; DICTIONARY_HASH_BYTES] = hash_bytes
+            .try_into()
+            .map_err(|_| DictionaryError::MissingHash)?;
+        let (expected, found) = (self.hash(), u32::from_be_bytes(found));
+
+        if expected == found {
+            Ok(())
+        } else {
+            Err(DictionaryError::Mismatch { expected, found })
+        }
+    }
+
+    /// Primes _model_ (see `Model::prime`) with the symbols _parser_ parses the dictionary into.
+    /// The dictionary is parsed as a whole input, so the parser is left in its initial state.
+    ///
+    /// The model should be flushed beforehand, so the compressor and decompressor models are
+    /// primed from the same state.
+    pub fn prime<M: Model + ?Sized>(&self, model: &mut M, parser: &mut dyn Parser) -> Result<()> {
+        debug!(
+            "Priming model with a {} bytes dictionary",
+            self.content.len()
+        );
+        model.prime(&parser.parse_all(&self.content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_is_fnv1a() {
+        assert_eq!(Dictionary::new(vec![]).hash(), 0x811c9dc5);
+        assert_eq!(Dictionary::new(b"a".to_vec()).hash(), 0xe40c292c);
+        assert_eq!(Dictionary::new(b"foobar".to_vec()).hash(), 0xbf9cf968);
+    }
+
+    #[test]
+    fn test_verify() {
+        let dictionary = Dictionary::new(b"dictionary".to_vec());
+        let other = Dictionary::new(b"another dictionary".to_vec());
+
+        assert_eq!(dictionary.verify(&dictionary.hash().to_be_bytes()), Ok(()));
+        assert_eq!(
+            dictionary.verify(&other.hash().to_be_bytes()),
+            Err(DictionaryError::Mismatch {
+                expected: dictionary.hash(),
+                found: other.hash()
+            })
+        );
+        assert_eq!(
+            dictionary.verify(&[1, 2]),
+            Err(DictionaryError::MissingHash)
+        );
+    }
+}
@@ -0,0 +1,285 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Multi-file streams: several files compressed into a single stream, which decompression can
+//! split back into the original files.
+//!
+//! The data of such a stream (before compression) starts with a table of the files, followed by
+//! their concatenated contents. The table holds varints of the number of files, followed by the
+//! length of each file's name, its UTF-8 bytes and the length of the file's content. Only the
+//! files' names are stored, never the directories they were in.
+
+use crate::varint::{read_varint, write_varint, VarintError};
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// Errors that may occur when forming or reading the table of files of a multi-file stream
+#[derive(Debug, Error, PartialEq)]
+pub enum FileTableError {
+    #[error("Failed to read the table of files: {0}")]
+    Varint(#[from] VarintError),
+    #[error("The table of files holds {0} files, more than the data could hold")]
+    TooManyFiles(u64),
+    #[error("The table of files holds a name which isn't valid UTF-8")]
+    NonUtf8Name,
+    #[error("\"{0}\" can't be the name of a file in a multi-file stream")]
+    InvalidName(String),
+    #[error("The file name \"{0}\" appears more than once")]
+    DuplicateName(String),
+    #[error("The data ends before the files of its table do, it may be truncated")]
+    Truncated,
+}
+
+/// A file of a multi-file stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileEntry {
+    /// The name of the file, without any directories
+    pub name: String,
+    /// Length in bytes of the file's content
+    pub len: u64,
+}
+
+/// Makes sure _name_ is a plain file name: not empty, not a special directory and without any
+/// path separators (so splitting a stream never writes outside of its output directory)
+pub fn check_file_name(name: &str) -> Result<(), FileTableError> {
+    let is_special = name.is_empty() || name == "." || name == "..";
+    if is_special || name.contains(['/', '\\', '\0']) {
+        return Err(FileTableError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Makes sure the names of _entries_ are valid file names, each used once
+fn check_entries(entries: &[FileEntry]) -> Result<(), FileTableError> {
+    let mut names = HashSet::new();
+    for entry in entries {
+        check_file_name(&entry.name)?;
+        if !names.insert(entry.name.as_str()) {
+            return Err(FileTableError::DuplicateName(entry.name.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Encodes the table of _entries_, see the module's documentation
+pub fn encode_file_table(entries: &[FileEntry]) -> Result<Vec<u8>, FileTableError> {
+    check_entries(entries)?;
+    let mut output = Vec::new();
+    write_varint(&mut output, entries.len() as u64);
+    for entry in entries {
+        write_varint(&mut output, entry.name.len() as u64);
+        output.extend_from_slice(entry.name.as_bytes());
+        write_varint(&mut output, entry.len);
+    }
+    Ok(output)
+}
+
+/// Decodes the table of files at the start of _input_. Returns the table and its length in bytes.
+pub fn decode_file_table(input: &[u8]) -> Result<(Vec<FileEntry>, usize), FileTableError> {
+    let mut remaining = input;
+    let count = read_varint(&mut remaining)?;
+    // Every entry takes at least 3 bytes, which bounds the table's allocation by the input:
+    if count > (remaining.len() / 3) as u64 {
+        return Err(FileTableError::TooManyFiles(count));
+    }
+
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let name_len = read_varint(&mut remaining)?;
+        let name_len = usize::try_from(name_len)
+            .ok()
+            .filter(|&len| len <= remaining.len())
+            .ok_or(FileTableError::Truncated)?;
+        let (name, rest) = remaining.split_at(name_len);
+        remaining = rest;
+        let name = String::from_utf8(name.to_vec()).map_err(|_| FileTableError::NonUtf8Name)?;
+        entries.push(FileEntry {
+            name,
+            len: read_varint(&mut remaining)?,
+        });
+    }
+    check_entries(&entries)?;
+    Ok((entries, input.len() - remaining.len()))
+}
+
+/// Splits the decompressed data of a multi-file stream into its files, in order: the name and
+/// content of each file. The data may also concatenate several multi-file streams (when they are
+/// decompressed as multiple streams), each starting with its own table.
+pub fn split_files(mut data: &[u8]) -> Result<Vec<(String, &[u8])>, FileTableError> {
+    let mut files = Vec::new();
+    while !data.is_empty() {
+        let (entries, table_len) = decode_file_table(data)?;
+        data = &data[table_len..];
+        for entry in entries {
+            let len = usize::try_from(entry.len)
+                .ok()
+                .filter(|&len| len <= data.len())
+                .ok_or(FileTableError::Truncated)?;
+            let (content, rest) = data.split_at(len);
+            files.push((entry.name, content));
+            data = rest;
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::Header;
+    use crate::models::ppm::{PpmModel, DEFAULT_PPM_ORDER};
+    use crate::parser::ByteParser;
+    use crate::sim::DefaultSIM;
+    use crate::{compress_reader, decompress_reader, CodecOptions};
+
+    fn entry(name: &str, len: u64) -> FileEntry {
+        FileEntry {
+            name: name.to_string(),
+            len,
+        }
+    }
+
+    #[test]
+    fn test_file_table_round_trip() {
+        let entries = [
+            entry("a.txt", 3),
+            entry("empty", 0),
+            entry("naïve.bin", 1 << 20),
+        ];
+        let mut encoded = encode_file_table(&entries).unwrap();
+        let table_len = encoded.len();
+        encoded.extend_from_slice(b"contents");
+        assert_eq!(
+            decode_file_table(&encoded),
+            Ok((entries.to_vec(), table_len))
+        );
+    }
+
+    #[test]
+    fn test_invalid_names_rejected() {
+        for name in ["", ".", "..", "dir/file", "..\\file", "nul\0"] {
+            assert_eq!(
+                encode_file_table(&[entry(name, 1)]),
+                Err(FileTableError::InvalidName(name.to_string()))
+            );
+        }
+        assert_eq!(
+            encode_file_table(&[entry("same", 1), entry("same", 2)]),
+            Err(FileTableError::DuplicateName("same".to_string()))
+        );
+
+        // Decoding checks the names too, so corrupted tables can't escape the output directory:
+        let mut table = Vec::new();
+        write_varint(&mut table, 1);
+        write_varint(&mut table, 5);
+        table.extend_from_slice(b"../up");
+        write_varint(&mut table, 0);
+        assert_eq!(
+            decode_file_table(&table),
+            Err(FileTableError::InvalidName("../up".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_corrupted_tables_rejected() {
+        let mut huge = Vec::new();
+        write_varint(&mut huge, u64::MAX);
+        assert_eq!(
+            decode_file_table(&huge),
+            Err(FileTableError::TooManyFiles(u64::MAX))
+        );
+
+        let table = encode_file_table(&[entry("longer name", 4)]).unwrap();
+        assert_eq!(
+            decode_file_table(&table[..5]),
+            Err(FileTableError::Truncated)
+        );
+
+        let mut data = encode_file_table(&[entry("file", 4)]).unwrap();
+        data.extend_from_slice(b"abc");
+        assert_eq!(split_files(&data), Err(FileTableError::Truncated));
+    }
+
+    #[test]
+    fn test_three_files_round_trip() {
+        let files: [(&str, &[u8]); 3] = [
+            ("first.txt", b"the first file's text\n"),
+            ("second.bin", &[0, 255, 1, 254]),
+            (
+                "third.txt",
+                b"and the last one, the first file's neighbour\n",
+            ),
+        ];
+        let entries: Vec<FileEntry> = files
+            .iter()
+            .map(|(name, content)| entry(name, content.len() as u64))
+            .collect();
+        let mut data = encode_file_table(&entries).unwrap();
+        for (_, content) in files {
+            data.extend_from_slice(content);
+        }
+
+        let options = CodecOptions {
+            header: Some(Header {
+                files: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut model = PpmModel::new(DefaultSIM, DEFAULT_PPM_ORDER);
+        let mut compressed = Vec::new();
+        compress_reader(
+            &data[..],
+            &mut compressed,
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap();
+        let mut decompressed = Vec::new();
+        decompress_reader(
+            &compressed[..],
+            &mut decompressed,
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap();
+
+        let split = split_files(&decompressed).unwrap();
+        let expected: Vec<(String, &[u8])> = files
+            .iter()
+            .map(|&(name, content)| (name.to_string(), content))
+            .collect();
+        assert_eq!(split, expected);
+    }
+
+    #[test]
+    fn test_concatenated_tables_split() {
+        let mut data = encode_file_table(&[entry("one", 1)]).unwrap();
+        data.push(b'1');
+        data.extend(encode_file_table(&[entry("two", 2)]).unwrap());
+        data.extend_from_slice(b"22");
+        assert_eq!(
+            split_files(&data).unwrap(),
+            [
+                ("one".to_string(), &b"1"[..]),
+                ("two".to_string(), &b"22"[..])
+            ]
+        );
+    }
+}
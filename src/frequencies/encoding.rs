@@ -0,0 +1,185 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::Frequency;
+use crate::number_types::CalculationsType;
+use crate::varint::{read_varint, write_varint, VarintError};
+use thiserror::Error;
+
+/// Errors that may occur when decoding a frequency table
+#[derive(Debug, Error, PartialEq)]
+pub enum TableDecodingError {
+    #[error("Failed to read the encoded frequency table: {0}")]
+    Varint(#[from] VarintError),
+    #[error("The encoded frequency table holds index {index}, but the table's length is {len}")]
+    IndexOutOfRange { index: usize, len: usize },
+    #[error("The encoded frequency table holds an invalid frequency ({0})")]
+    InvalidFrequency(CalculationsType),
+}
+
+/// Encodes the frequencies of a table in a compact binary format, so the table can be embedded in
+/// a compressed stream.
+///
+/// Only non-zero frequencies are stored, so the encoding's size is proportional to the number of
+/// symbols the table actually uses rather than to its length. The encoding holds varints of:
+/// 1. The number of non-zero frequencies.
+/// 2. For each non-zero frequency (sorted by index): the distance of its index from the index
+///    following the previous non-zero frequency, followed by the frequency itself.
+pub fn encode_frequencies(frequencies: &[Frequency]) -> Vec<u8> {
+    let used: Vec<(usize, &Frequency)> = frequencies
+        .iter()
+        .enumerate()
+        .filter(|(_, frequency)| ***frequency != 0)
+        .collect();
+
+    let mut output = Vec::new();
+    write_varint(&mut output, used.len() as CalculationsType);
+    let mut next_index = 0;
+    for (index, frequency) in used {
+        write_varint(&mut output, (index - next_index) as CalculationsType);
+        write_varint(&mut output, **frequency);
+        next_index = index + 1;
+    }
+    output
+}
+
+/// Decodes a frequency table of _len_ frequencies from the start of _input_, which must have been
+/// encoded by `encode_frequencies`.
+///
+/// Returns the decoded frequencies and the number of bytes the encoded table took, so anything
+/// following the table in _input_ can be read afterward.
+pub fn decode_frequencies(
+    input: &[u8],
+    len: usize,
+) -> Result<(Vec<Frequency>, usize), TableDecodingError> {
+    let mut remaining = input;
+    let mut frequencies = vec![Frequency::zero(); len];
+
+    let used = read_varint(&mut remaining)?;
+    let mut next_index: usize = 0;
+    for _ in 0..used {
+        let index = usize::try_from(read_varint(&mut remaining)?)
+            .ok()
+            .and_then(|gap| next_index.checked_add(gap))
+            .ok_or(VarintError::TooLarge)?;
+        if index >= len {
+            return Err(TableDecodingError::IndexOutOfRange { index, len });
+        }
+
+        let frequency = read_varint(&mut remaining)?;
+        frequencies[index] = match Frequency::new(frequency) {
+            Ok(frequency) if frequency != Frequency::zero() => frequency,
+            _ => return Err(TableDecodingError::InvalidFrequency(frequency)),
+        };
+        next_index = index + 1;
+    }
+    Ok((frequencies, input.len() - remaining.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::distributions::custom::CustomDistributionModel;
+    use crate::models::{Model, ModelCfi};
+    use crate::sim::{DefaultSIM, Symbol, SymbolIndexMapping};
+
+    fn frequencies(values: &[CalculationsType]) -> Vec<Frequency> {
+        values.iter().map(|&v| Frequency::new(v).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let table = frequencies(&[0, 5, 0, 0, 1, 300, 0, 70000, 0]);
+        let encoded = encode_frequencies(&table);
+        assert_eq!(
+            decode_frequencies(&encoded, table.len()),
+            Ok((table, encoded.len()))
+        );
+    }
+
+    #[test]
+    fn test_decoding_reports_table_length() {
+        let table = frequencies(&[3, 0, 9]);
+        let mut encoded = encode_frequencies(&table);
+        let table_len = encoded.len();
+        encoded.extend_from_slice(b"payload");
+
+        let (_, consumed) = decode_frequencies(&encoded, table.len()).unwrap();
+        assert_eq!(consumed, table_len);
+        assert_eq!(&encoded[consumed..], b"payload");
+    }
+
+    #[test]
+    fn test_round_trip_keeps_model_cfis() {
+        let sim = DefaultSIM;
+        let mut values = vec![0; sim.supported_symbols_count()];
+        for &byte in b"hello, world" {
+            values[sim.get_index(&Symbol::Byte(byte)).unwrap()] += 1;
+        }
+        values[sim.get_index(&Symbol::Eof).unwrap()] = 1;
+        let table = frequencies(&values);
+
+        let encoded = encode_frequencies(&table);
+        let (decoded, _) = decode_frequencies(&encoded, table.len()).unwrap();
+        let original = CustomDistributionModel::new(DefaultSIM, &table).unwrap();
+        let decoded = CustomDistributionModel::new(DefaultSIM, &decoded).unwrap();
+
+        for symbol in Symbol::all_bytes().chain([Symbol::Eof]) {
+            match (original.get_cfi(symbol), decoded.get_cfi(symbol)) {
+                (Ok(ModelCfi::IndexCfi(a)), Ok(ModelCfi::IndexCfi(b))) => assert_eq!(a, b),
+                (Err(_), Err(_)) => {}
+                _ => panic!("The models disagree on symbol {}", symbol),
+            }
+        }
+    }
+
+    #[test]
+    fn test_small_alphabet_small_encoding() {
+        let mut values = vec![0; 258];
+        values[b'a' as usize] = 40;
+        values[b'b' as usize] = 2;
+        values[b'c' as usize] = 1000;
+        let encoded = encode_frequencies(&frequencies(&values));
+
+        // 1 byte for the count, and at most 1 + 2 bytes for each used symbol:
+        assert!(encoded.len() <= 1 + 3 * 3);
+    }
+
+    #[test]
+    fn test_corrupted_tables_rejected() {
+        // Claims one frequency, but ends:
+        assert_eq!(
+            decode_frequencies(&[1], 4),
+            Err(TableDecodingError::Varint(VarintError::Truncated))
+        );
+        // Index out of range:
+        assert_eq!(
+            decode_frequencies(&[1, 4, 1], 4),
+            Err(TableDecodingError::IndexOutOfRange { index: 4, len: 4 })
+        );
+        // Zero frequencies are never stored:
+        assert_eq!(
+            decode_frequencies(&[1, 0, 0], 4),
+            Err(TableDecodingError::InvalidFrequency(0))
+        );
+        // A varint that never ends:
+        assert_eq!(
+            decode_frequencies(&[0xFF; 16], 4),
+            Err(TableDecodingError::Varint(VarintError::TooLarge))
+        );
+    }
+}
@@ -15,16 +15,42 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+pub mod encoding;
 pub mod mutable_table;
 pub mod static_table;
 #[cfg(test)]
 mod unit_tests;
 
-use crate::number_types::{ConstrainedNum, FREQUENCY_BITS};
+use crate::number_types::{CalculationsType, ConstrainedNum, FREQUENCY_BITS};
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
 
 /// Number type for all frequencies, used to limit a frequency's bits
 pub type Frequency = ConstrainedNum<FREQUENCY_BITS>;
 
+/// Errors that may occur when creating a frequency table which can't code any index
+#[derive(Debug, Error, PartialEq)]
+pub enum EmptyTableError {
+    #[error("Can't create a frequency table without any frequencies")]
+    NoFrequencies,
+    #[error("Can't create a frequency table whose frequencies are all zero, since no index could be coded with it")]
+    ZeroTotal,
+}
+
+/// Makes sure a table built from _frequencies_ can code at least one index
+fn check_not_empty(frequencies: &[Frequency]) -> Result<(), EmptyTableError> {
+    if frequencies.is_empty() {
+        Err(EmptyTableError::NoFrequencies)
+    } else if frequencies
+        .iter()
+        .all(|&frequency| frequency == Frequency::zero())
+    {
+        Err(EmptyTableError::ZeroTotal)
+    } else {
+        Ok(())
+    }
+}
+
 /// A struct describing the Cumulative Frequency Interval of a symbol
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Cfi {
@@ -33,6 +59,41 @@ pub struct Cfi {
     pub total: Frequency,
 }
 
+impl Cfi {
+    /// Returns the width of the interval, which is the frequency of its symbol
+    pub fn width(&self) -> CalculationsType {
+        *self.end - *self.start
+    }
+
+    /// Checks if the interval is empty (start == end), in which case its symbol can't be coded
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl Display for Cfi {
+    /// Shows the interval's bounds as fractions of the total, followed by their approximate values
+    /// (e.g. "[1/4 .. 3/4) ≈ [0.2500 .. 0.7500)")
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}/{} .. {}/{})",
+            *self.start, *self.total, *self.end, *self.total
+        )?;
+        // An interval of an empty table has no probabilities:
+        if self.total != Frequency::zero() {
+            let fraction = |bound: Frequency| *bound as f64 / *self.total as f64;
+            write!(
+                f,
+                " ≈ [{:.4} .. {:.4})",
+                fraction(self.start),
+                fraction(self.end)
+            )?;
+        }
+        Ok(())
+    }
+}
+
 /// A frequency table is anything that assigns Cumulative-Frequency-Intervals to indices. The
 /// following trait defines its required functions.
 pub trait FrequencyTable {
@@ -44,6 +105,41 @@ pub trait FrequencyTable {
     /// If such CFI is not found, None is returned.
     fn get_index(&self, cumulative_frequency: Frequency) -> Option<usize>;
 
+    /// Finds the index whose assigned CFI contains _cumulative_frequency_, and returns it along
+    /// with its CFI. If such CFI is not found, None is returned.<br>
+    /// Equivalent to `get_index` followed by `get_cfi`, tables whose search already computes the
+    /// CFI override it to avoid the second lookup.
+    fn locate(&self, cumulative_frequency: Frequency) -> Option<(usize, Cfi)> {
+        let index = self.get_index(cumulative_frequency)?;
+        Some((index, self.get_cfi(index)?))
+    }
+
     /// Returns the total cumulative number of frequencies saved in the table.
     fn get_total(&self) -> Frequency;
+
+    /// Returns the number of indices in the table (including indices with empty CFIs).
+    fn len(&self) -> usize;
+
+    /// Checks if the table has no indices at all.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over the indices of the table in order, yielding every index whose CFI isn't empty
+    /// along with its CFI. Useful for inspecting the distribution a model currently holds.<br>
+    /// The iterator is boxed so tables can be inspected through a `dyn FrequencyTable` too.
+    fn iter_cfis(&self) -> Box<dyn Iterator<Item = (usize, Cfi)> + '_> {
+        Box::new((0..self.len()).filter_map(|index| Some((index, self.get_cfi(index)?))))
+    }
+
+    /// Returns the (non-cumulative) frequency of every index in the table, in order. A table can
+    /// be rebuilt from them.
+    fn frequencies(&self) -> Vec<Frequency> {
+        (0..self.len())
+            .map(|index| match self.get_cfi(index) {
+                Some(cfi) => Frequency::new(cfi.width()).expect("A CFI's width is a frequency"),
+                None => Frequency::zero(),
+            })
+            .collect()
+    }
 }
@@ -25,6 +25,7 @@ fn lsb(n: usize) -> usize {
 
 /// A data structure that allows efficient calculation of cumulative summation AND mutation of
 /// values
+#[derive(Clone)]
 pub struct FenwickTree {
     // Values of the tree, allow for quick computation of cumulative sum AND mutation of values.
     // It uses Box since we never append/remove elements, only mutate them:
@@ -66,6 +67,17 @@ impl FenwickTree {
             index += lsb(index);
         }
     }
+
+    /// Subtracts a certain amount from an index in the tree in **O(log n)** time complexity.<br>
+    /// The amount must not exceed the index's value, since values are unsigned.
+    pub fn sub(&mut self, mut index: usize, amount: CalculationsType) {
+        // Shift the index by one since the fenwick tree is 1-based:
+        index += 1;
+        while index < self.data.len() {
+            self.data[index] -= amount;
+            index += lsb(index);
+        }
+    }
 }
 
 impl<const N: usize> From<&[CalculationsType; N]> for FenwickTree {
@@ -167,6 +179,18 @@ mod tests {
         assert_eq!(tree.get_sum(5), 30); // 1 + 7 + 3 + 14 + 5 = 30
     }
 
+    #[test]
+    fn test_sub() {
+        let mut tree = FenwickTree::from(&[1, 2, 3, 4, 5]);
+
+        // New tree after subtraction - [1, 2, 1, 4, 5]:
+        tree.sub(2, 2);
+
+        assert_eq!(tree.get_sum(2), 3); // 1 + 2 = 3
+        assert_eq!(tree.get_sum(3), 4); // 1 + 2 + 1 = 4
+        assert_eq!(tree.get_sum(5), 13); // 1 + 2 + 1 + 4 + 5 = 13
+    }
+
     #[test]
     fn test_edge_case_empty_values() {
         let empty: Vec<CalculationsType> = Vec::new();
@@ -18,13 +18,25 @@
 mod fenwick;
 
 use self::fenwick::FenwickTree;
-use super::{Cfi, Frequency, FrequencyTable};
+use super::{check_not_empty, Cfi, Frequency, FrequencyTable};
 
 use crate::number_types::CalculationsType;
 use anyhow::{Context, Result};
 use log::{debug, error, warn};
 
+/// What a [MutableFrequencyTable] does with an addition that doesn't fit in its total, even after
+/// rescaling the table
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The addition is dropped, leaving the index's frequency as it was
+    #[default]
+    Drop,
+    /// As much of the addition as fits is added, so the total reaches its cap
+    Saturate,
+}
+
 /// A frequency table which can be mutated
+#[derive(Clone)]
 pub struct MutableFrequencyTable {
     /// The frequencies, stored in a fenwick tree for efficient querying and mutating (O(log n))
     fenwick: FenwickTree,
@@ -32,6 +44,18 @@ pub struct MutableFrequencyTable {
     /// The total cumulative frequency. It can be computed from the fenwick tree, but saving it is
     /// easy and makes its query more efficient
     total: Frequency,
+
+    /// If set, once a single frequency in the table exceeds this cap, the whole table is rescaled.
+    /// This keeps one dominant index from making the others too expensive to code
+    frequency_cap: Option<Frequency>,
+
+    /// If an addition would make the total frequency exceed this threshold, the table is rescaled
+    /// before it. This keeps the table accepting updates instead of freezing once its total nears
+    /// the largest frequency
+    total_cap: Frequency,
+
+    /// What happens to additions that don't fit even after rescaling
+    overflow_policy: OverflowPolicy,
 }
 
 impl MutableFrequencyTable {
@@ -39,8 +63,30 @@ impl MutableFrequencyTable {
     /// The new table's length will be the length of the provided slice.
     ///
     /// The frequencies provided here should not be cumulative, and the function will fail if at
-    /// any point the sum of the slice's frequencies exceeds the allowed bits.
+    /// any point the sum of the slice's frequencies exceeds the allowed bits. It also fails with an
+    /// [EmptyTableError](super::EmptyTableError) if the slice is empty or all of its frequencies
+    /// are zero (see `MutableFrequencyTable::empty` for tables which are filled later).
     pub fn new(frequencies: &[Frequency]) -> Result<Self> {
+        check_not_empty(frequencies)?;
+        Self::from_frequencies(frequencies)
+    }
+
+    /// Creates a table of _len_ indices whose frequencies are all zero. Such a table can't code any
+    /// index until frequencies are added to it.<br>
+    /// If _frequency_cap_ is given, the table is rescaled like in
+    /// `MutableFrequencyTable::with_frequency_cap`.
+    pub fn empty(len: usize, frequency_cap: Option<Frequency>) -> Self {
+        Self {
+            fenwick: FenwickTree::new(len),
+            total: Frequency::zero(),
+            frequency_cap,
+            total_cap: Frequency::max(),
+            overflow_policy: OverflowPolicy::default(),
+        }
+    }
+
+    /// Builds the table without making sure it can code any index
+    fn from_frequencies(frequencies: &[Frequency]) -> Result<Self> {
         let fenwick = FenwickTree::from(
             &frequencies
                 .iter()
@@ -53,23 +99,160 @@ impl MutableFrequencyTable {
             msg
         })?;
 
-        Ok(Self { fenwick, total })
+        Ok(Self {
+            fenwick,
+            total,
+            frequency_cap: None,
+            total_cap: Frequency::max(),
+            overflow_policy: OverflowPolicy::default(),
+        })
+    }
+
+    /// Creates a mutable frequency table exactly like `MutableFrequencyTable::new`, except that
+    /// the table is rescaled whenever adding to it would make its total exceed _total_cap_ (instead
+    /// of only when the total would exceed the bits allowed for a frequency).
+    pub fn with_total_cap(frequencies: &[Frequency], total_cap: Frequency) -> Result<Self> {
+        let mut table = Self::new(frequencies)?;
+        table.limit_total(total_cap);
+        Ok(table)
+    }
+
+    /// Makes the table rescale whenever adding to it would make its total exceed _total_cap_, like
+    /// a table created by `MutableFrequencyTable::with_total_cap`. If the total already exceeds
+    /// _total_cap_, the table is rescaled until it fits (or until it stops shrinking).
+    pub fn limit_total(&mut self, total_cap: Frequency) {
+        self.total_cap = total_cap;
+        while self.total > total_cap {
+            let old_total = self.total;
+            self.rescale();
+            if self.total == old_total {
+                break;
+            }
+        }
+    }
+
+    /// Creates a mutable frequency table exactly like `MutableFrequencyTable::new`, except that
+    /// once the frequency of any index exceeds _frequency_cap_, the table is rescaled (see the
+    /// `rescale` method).
+    pub fn with_frequency_cap(frequencies: &[Frequency], frequency_cap: Frequency) -> Result<Self> {
+        let mut table = Self::new(frequencies)?;
+        table.frequency_cap = Some(frequency_cap);
+        Ok(table)
+    }
+
+    /// Creates a mutable frequency table exactly like `MutableFrequencyTable::new`, except that
+    /// additions which don't fit in the table's total (even after rescaling it) are handled
+    /// according to _overflow_policy_, instead of being dropped.
+    pub fn with_overflow_policy(
+        frequencies: &[Frequency],
+        overflow_policy: OverflowPolicy,
+    ) -> Result<Self> {
+        let mut table = Self::new(frequencies)?;
+        table.overflow_policy = overflow_policy;
+        Ok(table)
+    }
+
+    /// Returns the frequency of the given index (not its cumulative frequency), or None if the
+    /// index is out of the table's bounds.
+    pub fn get_frequency(&self, index: usize) -> Option<Frequency> {
+        if index < self.fenwick.len() {
+            // Invariants ensure unwrapping frequencies is safe:
+            Some(
+                Frequency::new(self.fenwick.get_sum(index + 1) - self.fenwick.get_sum(index))
+                    .expect("MutableFrequencyTable invariant violated"),
+            )
+        } else {
+            None
+        }
+    }
+
+    /// Halves every frequency in the table. Frequencies are rounded up, so an index which had a
+    /// non-zero frequency before the rescale will keep a non-zero frequency after it.
+    pub fn rescale(&mut self) {
+        let halved_frequencies: Vec<CalculationsType> = (0..self.fenwick.len())
+            .filter_map(|index| self.get_frequency(index))
+            .map(|frequency| frequency.div_ceil(2))
+            .collect();
+        self.fenwick = FenwickTree::from(&halved_frequencies);
+
+        // Halving can only decrease the total, so it must still be a valid frequency:
+        self.total = Frequency::new(self.fenwick.get_sum(self.fenwick.len()))
+            .expect("MutableFrequencyTable invariant violated");
+        debug!("MutableTable: Rescaled table, new total: {}", *self.total);
+    }
+
+    /// The table's total after adding _amount_ to one of its frequencies, or None if it would
+    /// overflow or exceed the table's total cap
+    fn total_after_adding(&self, amount: Frequency) -> Option<Frequency> {
+        self.total
+            .checked_add(amount)
+            .ok()
+            .filter(|&new_total| new_total <= self.total_cap)
     }
 
     /// Adds a certain amount to the frequency at the given index in the table.
     ///
-    /// If the result of that addition exceeds the bits allowed for a frequency, it is not saved in
-    /// the table.
+    /// If the new total would exceed the table's total cap, the table is rescaled until it fits.
+    /// If even rescaling can't make room for _amount_, the table's overflow policy decides if the
+    /// addition is dropped, or saturates the total at its cap. If the table has a frequency cap and
+    /// the index's new frequency exceeds it, the table is rescaled.
     pub fn add_frequency(&mut self, index: usize, amount: Frequency) {
         debug!("MutableTable: Adding {} to index {}", *amount, index);
+        while self.total_after_adding(amount).is_none() {
+            let old_total = self.total;
+            self.rescale();
+            // Frequencies of 1 can't be halved further, so the table may stop shrinking:
+            if self.total == old_total {
+                break;
+            }
+        }
+
         // Since `total` is the largest, if adding to it fails adding to anything else will too:
-        if let Ok(new_total) = Frequency::new(*self.total + *amount) {
-            self.total = new_total;
-            self.fenwick.add(index, *amount);
-        } else {
-            warn!("MutableTable: Failed to add to index (total overflow)")
+        let amount = match (self.total_after_adding(amount), self.overflow_policy) {
+            (Some(_), _) => amount,
+            (None, OverflowPolicy::Saturate) => {
+                debug!("MutableTable: Saturating the addition at the total's cap");
+                self.total_cap.saturating_sub(self.total)
+            }
+            (None, OverflowPolicy::Drop) => {
+                warn!("MutableTable: Failed to add to index (total exceeds its cap even after rescaling)");
+                return;
+            }
+        };
+        // The amount fits, so the total doesn't actually saturate:
+        self.total = self.total.saturating_add(amount);
+        self.fenwick.add(index, *amount);
+
+        // Rescale if the index's frequency passed the cap:
+        if let Some((cap, frequency)) = self.frequency_cap.zip(self.get_frequency(index)) {
+            if frequency > cap {
+                self.rescale();
+            }
         }
     }
+
+    /// Subtracts a certain amount from the frequency at the given index in the table.
+    ///
+    /// Frequencies are never negative, so if _amount_ exceeds the index's frequency, the frequency
+    /// is set to 0 instead. Indices out of the table's bounds are ignored.
+    pub fn sub_frequency(&mut self, index: usize, amount: Frequency) {
+        let Some(frequency) = self.get_frequency(index) else {
+            warn!(
+                "MutableTable: Failed to subtract from index {} (out of bounds)",
+                index
+            );
+            return;
+        };
+        let amount = amount.min(frequency);
+        debug!("MutableTable: Subtracting {} from index {}", *amount, index);
+
+        self.fenwick.sub(index, *amount);
+        // The total includes the index's frequency, so it's at least as large as the amount:
+        self.total = self
+            .total
+            .checked_sub(amount)
+            .expect("MutableFrequencyTable invariant violated");
+    }
 }
 
 impl FrequencyTable for MutableFrequencyTable {
@@ -81,43 +264,62 @@ impl FrequencyTable for MutableFrequencyTable {
             let end = Frequency::new(self.fenwick.get_sum(index + 1))
                 .expect("MutableFrequencyTable invariant violated");
 
-            if start == end {
-                None
-            } else {
-                Some(Cfi {
-                    start,
-                    end,
-                    total: self.total,
-                })
-            }
+            let cfi = Cfi {
+                start,
+                end,
+                total: self.total,
+            };
+            (!cfi.is_empty()).then_some(cfi)
         } else {
             None
         }
     }
 
     fn get_index(&self, cumulative_frequency: Frequency) -> Option<usize> {
-        // Implement binary search (get_sum doesn't include the index so only decrement 1 from len):
-        let (mut left, mut right) = (0, self.fenwick.len() - 1);
+        self.locate(cumulative_frequency).map(|(index, _)| index)
+    }
+
+    /// The binary search already computes the bounds of the CFI it checks, so the CFI is returned
+    /// without querying the tree again.
+    fn locate(&self, cumulative_frequency: Frequency) -> Option<(usize, Cfi)> {
+        // A table created by `empty` may have no frequencies yet (or no indices at all), so there's
+        // nothing to search:
+        if self.total == Frequency::zero() {
+            return None;
+        }
+
+        // Implement binary search over the candidate indices [left, right), so the bounds never go
+        // below zero:
+        let (mut left, mut right) = (0, self.fenwick.len());
         let cumulative_frequency = *cumulative_frequency;
 
-        while left <= right {
-            let middle = (left + right) >> 1;
+        while left < right {
+            let middle = left + (right - left) / 2;
             debug!(
                 "MutableTable: Binary Searching for index, middle={}",
                 middle
             );
 
             // Check lower bound:
-            if cumulative_frequency < self.fenwick.get_sum(middle) {
-                right = middle - 1;
+            let start = self.fenwick.get_sum(middle);
+            if cumulative_frequency < start {
+                right = middle;
+                continue;
             }
             // Check upper bound:
-            else if cumulative_frequency >= self.fenwick.get_sum(middle + 1) {
+            let end = self.fenwick.get_sum(middle + 1);
+            if cumulative_frequency >= end {
                 left = middle + 1;
             }
             // Spot on!
             else {
-                return Some(middle);
+                // Invariants ensure unwrapping frequencies is safe:
+                let cfi = Cfi {
+                    start: Frequency::new(start).expect("MutableFrequencyTable invariant violated"),
+                    end: Frequency::new(end).expect("MutableFrequencyTable invariant violated"),
+                    total: self.total,
+                };
+                return Some((middle, cfi));
             }
         }
 
@@ -127,4 +329,25 @@ impl FrequencyTable for MutableFrequencyTable {
     fn get_total(&self) -> Frequency {
         self.total
     }
+
+    fn len(&self) -> usize {
+        self.fenwick.len()
+    }
+}
+
+// Tables are serialized as their (non-cumulative) frequencies, and rebuilt from them. The caps
+// aren't a part of the distribution, so they aren't serialized:
+#[cfg(feature = "serde")]
+impl serde::Serialize for MutableFrequencyTable {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.frequencies().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MutableFrequencyTable {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let frequencies = Vec::<Frequency>::deserialize(deserializer)?;
+        Self::new(&frequencies).map_err(serde::de::Error::custom)
+    }
 }
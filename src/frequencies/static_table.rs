@@ -15,14 +15,41 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use super::{Cfi, Frequency, FrequencyTable};
+use super::{check_not_empty, Cfi, Frequency, FrequencyTable};
+use crate::number_types::CalculationsType;
 use anyhow::{Context, Result};
 use log::{debug, error};
 
+/// Minimal number of consecutive equal frequencies for them to be considered a uniform run
+const MIN_UNIFORM_RUN_LEN: usize = 8;
+
+/// A run of consecutive indices whose frequencies are all equal (and non-zero). Indices inside the
+/// run can be located arithmetically instead of being searched.
+struct UniformRun {
+    /// The first index of the run
+    first_index: usize,
+    /// The number of indices in the run
+    len: usize,
+    /// The frequency of every index in the run
+    frequency: CalculationsType,
+    /// The cumulative frequency at the start of the run
+    start: CalculationsType,
+}
+
+impl UniformRun {
+    /// The cumulative frequency at the end of the run (exclusive)
+    fn end(&self) -> CalculationsType {
+        self.start + self.frequency * self.len as CalculationsType
+    }
+}
+
 /// A frequency table whose values cannot be updated after initialization
 pub struct StaticFrequencyTable {
     /// The cumulative frequencies, stored in a box for memory optimization reasons
     cum_freqs: Box<[Frequency]>,
+
+    /// Uniform runs in the table, sorted by their cumulative frequencies
+    uniform_runs: Box<[UniformRun]>,
 }
 
 impl StaticFrequencyTable {
@@ -30,61 +57,86 @@ impl StaticFrequencyTable {
     /// The new table's length will be the length of the provided slice.
     ///
     /// The frequencies provided here should not be cumulative, and the function will fail if at
-    /// any point the sum of the slice's frequencies exceeds the allowed bits.
+    /// any point the sum of the slice's frequencies exceeds the allowed bits. It also fails with an
+    /// [EmptyTableError] if the slice is empty or all of its frequencies are zero.
     pub fn new(frequencies: &[Frequency]) -> Result<Self> {
+        check_not_empty(frequencies)?;
+
         // Initialize the cumulative frequencies vector with 0 as the first CFI's start value:
-        let mut accum = 0;
+        let mut accum = Frequency::zero();
         let mut cum_freqs = Vec::with_capacity(frequencies.len() + 1);
         cum_freqs.push(Frequency::zero());
 
         for (idx, frequency) in frequencies.iter().enumerate() {
-            // Calculate cumulative and catch any overflow:
-            accum += **frequency;
-            cum_freqs.push(Frequency::new(accum).with_context(|| {
+            // Calculate cumulative and catch any overflow, of CalculationsType or of the bits:
+            let cumulative = accum.checked_add(*frequency).with_context(|| {
                 let msg = format!("Failed to create static table, index {idx} caused an overflow");
                 error!("StaticTable: {}", msg);
                 msg
-            })?);
+            })?;
+            accum = cumulative;
+            cum_freqs.push(cumulative);
         }
 
         Ok(Self {
+            uniform_runs: Self::find_uniform_runs(frequencies, &cum_freqs),
             cum_freqs: cum_freqs.into_boxed_slice(),
         })
     }
-}
 
-impl FrequencyTable for StaticFrequencyTable {
-    fn get_cfi(&self, index: usize) -> Option<Cfi> {
-        self.cum_freqs
-            // Get start and end of the CFI:
-            .get(index)
-            .zip(self.cum_freqs.get(index + 1))
-            // Map to CFI, check if start is equal to end:
-            .and_then(|(&start, &end)| {
-                if start == end {
-                    None
-                } else {
-                    Some(Cfi {
-                        start,
-                        end,
-                        total: self.get_total(),
-                    })
-                }
-            })
+    /// Finds every run of at least `MIN_UNIFORM_RUN_LEN` equal non-zero frequencies.
+    fn find_uniform_runs(frequencies: &[Frequency], cum_freqs: &[Frequency]) -> Box<[UniformRun]> {
+        let mut runs = Vec::new();
+        let mut first_index = 0;
+        while first_index < frequencies.len() {
+            let frequency = frequencies[first_index];
+            let len = frequencies[first_index..]
+                .iter()
+                .take_while(|&&f| f == frequency)
+                .count();
+
+            if *frequency != 0 && len >= MIN_UNIFORM_RUN_LEN {
+                runs.push(UniformRun {
+                    first_index,
+                    len,
+                    frequency: *frequency,
+                    start: *cum_freqs[first_index],
+                });
+            }
+            first_index += len;
+        }
+        runs.into_boxed_slice()
     }
 
-    fn get_index(&self, cumulative_frequency: Frequency) -> Option<usize> {
+    /// Locates the index of _cumulative_frequency_ arithmetically, if it lies inside a uniform run.
+    fn get_uniform_run_index(&self, cumulative_frequency: Frequency) -> Option<usize> {
+        let cumulative_frequency = *cumulative_frequency;
+        // Runs are sorted and disjoint, find the first run which ends after the value:
+        let run_idx = self
+            .uniform_runs
+            .partition_point(|run| run.end() <= cumulative_frequency);
+        let run = self.uniform_runs.get(run_idx)?;
+
+        if cumulative_frequency < run.start {
+            return None;
+        }
+        Some(run.first_index + ((cumulative_frequency - run.start) / run.frequency) as usize)
+    }
+
+    /// General lookup of the index whose CFI contains _cumulative_frequency_, using binary search.
+    pub(super) fn search_index(&self, cumulative_frequency: Frequency) -> Option<usize> {
         // Use binary search since all frequencies are non-negative and therefor all cumulative
-        // frequencies are sorted:
-        let (mut left, mut right) = (0, self.cum_freqs.len() - 2);
+        // frequencies are sorted. The candidate indices are [left, right), so the bounds never go
+        // below zero:
+        let (mut left, mut right) = (0, self.cum_freqs.len() - 1);
 
-        while left <= right {
-            let middle = (left + right) >> 1;
+        while left < right {
+            let middle = left + (right - left) / 2;
             debug!("StaticTable: Binary Searching for index, middle={}", middle);
 
             // Check lower bound:
             if cumulative_frequency < self.cum_freqs[middle] {
-                right = middle - 1;
+                right = middle;
             }
             // Check upper bound:
             else if cumulative_frequency >= self.cum_freqs[middle + 1] {
@@ -98,9 +150,52 @@ impl FrequencyTable for StaticFrequencyTable {
 
         None
     }
+}
+
+impl FrequencyTable for StaticFrequencyTable {
+    fn get_cfi(&self, index: usize) -> Option<Cfi> {
+        self.cum_freqs
+            // Get start and end of the CFI:
+            .get(index)
+            .zip(self.cum_freqs.get(index + 1))
+            // Map to CFI, check if it's empty:
+            .map(|(&start, &end)| Cfi {
+                start,
+                end,
+                total: self.get_total(),
+            })
+            .filter(|cfi| !cfi.is_empty())
+    }
+
+    fn get_index(&self, cumulative_frequency: Frequency) -> Option<usize> {
+        // Values inside uniform runs don't need to be searched:
+        self.get_uniform_run_index(cumulative_frequency)
+            .or_else(|| self.search_index(cumulative_frequency))
+    }
 
     fn get_total(&self) -> Frequency {
         // Cumulative sum of all frequencies is always the last index in the box:
         self.cum_freqs[self.cum_freqs.len() - 1]
     }
+
+    fn len(&self) -> usize {
+        // The first cumulative frequency is the start of the first CFI, not an index:
+        self.cum_freqs.len() - 1
+    }
+}
+
+// Tables are serialized as their (non-cumulative) frequencies, and rebuilt from them:
+#[cfg(feature = "serde")]
+impl serde::Serialize for StaticFrequencyTable {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.frequencies().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for StaticFrequencyTable {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let frequencies = Vec::<Frequency>::deserialize(deserializer)?;
+        Self::new(&frequencies).map_err(serde::de::Error::custom)
+    }
 }
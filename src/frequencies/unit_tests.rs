@@ -16,8 +16,10 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use super::static_table::StaticFrequencyTable;
-use super::{Cfi, Frequency, FrequencyTable};
-use crate::frequencies::mutable_table::MutableFrequencyTable;
+use super::{Cfi, EmptyTableError, Frequency, FrequencyTable};
+use crate::frequencies::mutable_table::{MutableFrequencyTable, OverflowPolicy};
+use crate::models::distributions::custom::CustomDistributionModel;
+use crate::sim::{DefaultSIM, SymbolIndexMapping};
 
 #[test]
 fn test_static_frequency_table_creation() {
@@ -84,6 +86,27 @@ fn test_mutable_table_empty_cfi() {
     assert!(table.get_cfi(3).is_none());
 }
 
+#[test]
+fn test_cfi_width_and_display() {
+    let cfi = |start, end, total| Cfi {
+        start: Frequency::new(start).unwrap(),
+        end: Frequency::new(end).unwrap(),
+        total: Frequency::new(total).unwrap(),
+    };
+    let quarter = cfi(1, 3, 4);
+    assert_eq!(quarter.width(), 2);
+    assert!(!quarter.is_empty());
+    assert_eq!(quarter.to_string(), "[1/4 .. 3/4) ≈ [0.2500 .. 0.7500)");
+
+    let empty = cfi(2, 2, 3);
+    assert_eq!(empty.width(), 0);
+    assert!(empty.is_empty());
+    assert_eq!(empty.to_string(), "[2/3 .. 2/3) ≈ [0.6667 .. 0.6667)");
+
+    // Without a total, there are no probabilities to show:
+    assert_eq!(cfi(0, 0, 0).to_string(), "[0/0 .. 0/0)");
+}
+
 #[test]
 fn test_static_frequency_table_get_index() {
     let freqs = vec![
@@ -102,6 +125,32 @@ fn test_static_frequency_table_get_index() {
     assert_eq!(table.get_index(Frequency::new(6).unwrap()), None);
 }
 
+#[test]
+fn test_static_frequency_table_uniform_run_matches_search() {
+    // A large floored run in the middle, and a shorter run of a different frequency at the end:
+    let freqs: Vec<Frequency> = [5]
+        .into_iter()
+        .chain(std::iter::repeat_n(1, 1000))
+        .chain([3])
+        .chain(std::iter::repeat_n(2, 50))
+        .map(|f| Frequency::new(f).unwrap())
+        .collect();
+    let table = StaticFrequencyTable::new(&freqs).unwrap();
+
+    for cum_freq in 0..*table.get_total() + 1 {
+        let cum_freq = Frequency::new(cum_freq).unwrap();
+        assert_eq!(table.get_index(cum_freq), table.search_index(cum_freq));
+    }
+
+    // CFIs inside the run of ones start right after the first frequency:
+    for index in 1..=1000 {
+        let cfi = table.get_cfi(index).unwrap();
+        assert_eq!(*cfi.start, 4 + index as u64);
+        assert_eq!(*cfi.end, 5 + index as u64);
+        assert_eq!(table.get_index(cfi.start), Some(index));
+    }
+}
+
 #[test]
 fn test_static_frequency_table_overflow() {
     // This should fail if it overflows
@@ -110,6 +159,48 @@ fn test_static_frequency_table_overflow() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_empty_tables_rejected() {
+    let zeroes = [Frequency::zero(); 4];
+    for (frequencies, expected) in [
+        (&[][..], EmptyTableError::NoFrequencies),
+        (&zeroes[..], EmptyTableError::ZeroTotal),
+    ] {
+        let static_err = StaticFrequencyTable::new(frequencies).err().unwrap();
+        assert_eq!(static_err.downcast_ref(), Some(&expected));
+        let mutable_err = MutableFrequencyTable::new(frequencies).err().unwrap();
+        assert_eq!(mutable_err.downcast_ref(), Some(&expected));
+    }
+
+    // Empty mutable tables can still be created explicitly, and filled later:
+    let mut table = MutableFrequencyTable::empty(4, None);
+    assert_eq!(*table.get_total(), 0);
+    // Until they are, no cumulative frequency is located in them:
+    assert_eq!(table.get_index(Frequency::zero()), None);
+    assert_eq!(
+        MutableFrequencyTable::empty(0, None).locate(Frequency::zero()),
+        None
+    );
+    table.add_frequency(2, Frequency::one());
+    assert_eq!(
+        table
+            .iter_cfis()
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>(),
+        [2]
+    );
+}
+
+#[test]
+fn test_custom_model_rejects_zero_frequencies() {
+    let sim = DefaultSIM;
+    let frequencies = vec![Frequency::zero(); sim.supported_symbols_count()];
+    let err = CustomDistributionModel::new(sim, &frequencies)
+        .err()
+        .unwrap();
+    assert_eq!(err.downcast_ref(), Some(&EmptyTableError::ZeroTotal));
+}
+
 #[test]
 fn test_creation_and_get_cfi() {
     let freqs = vec![1, 2, 3]
@@ -155,6 +246,78 @@ fn test_get_index() {
     assert_eq!(table.get_index(Frequency::new(6).unwrap()), None); // Out of range
 }
 
+#[test]
+fn test_locate_matches_get_index_and_get_cfi() {
+    let freqs: Vec<Frequency> = [4, 0, 1, 7, 0, 0, 2]
+        .into_iter()
+        .map(|f| Frequency::new(f).unwrap())
+        .collect();
+    let mutable = MutableFrequencyTable::new(&freqs).unwrap();
+    let fixed = StaticFrequencyTable::new(&freqs).unwrap();
+
+    for table in [&mutable as &dyn FrequencyTable, &fixed] {
+        for cum_freq in 0..*table.get_total() + 1 {
+            let cum_freq = Frequency::new(cum_freq).unwrap();
+            let expected = table
+                .get_index(cum_freq)
+                .map(|index| (index, table.get_cfi(index).unwrap()));
+            assert_eq!(table.locate(cum_freq), expected);
+        }
+    }
+}
+
+#[test]
+fn test_get_index_at_lowest_boundary() {
+    // The first CFIs are empty, so the lowest boundary of a non-empty CFI is past the first index:
+    let freqs: Vec<Frequency> = [0, 0, 3, 0, 2]
+        .into_iter()
+        .map(|f| Frequency::new(f).unwrap())
+        .collect();
+    let mutable = MutableFrequencyTable::new(&freqs).unwrap();
+    let fixed = StaticFrequencyTable::new(&freqs).unwrap();
+
+    for (cum_freq, expected) in [
+        (0, Some(2)),
+        (2, Some(2)),
+        (3, Some(4)),
+        (4, Some(4)),
+        (5, None),
+    ] {
+        let cum_freq = Frequency::new(cum_freq).unwrap();
+        assert_eq!(mutable.get_index(cum_freq), expected);
+        assert_eq!(fixed.search_index(cum_freq), expected);
+    }
+
+    // A single index, where the search's first middle is 0:
+    let single = [Frequency::new(7).unwrap()];
+    let mutable = MutableFrequencyTable::new(&single).unwrap();
+    let fixed = StaticFrequencyTable::new(&single).unwrap();
+    assert_eq!(mutable.get_index(Frequency::zero()), Some(0));
+    assert_eq!(fixed.search_index(Frequency::zero()), Some(0));
+    assert_eq!(mutable.get_index(Frequency::new(7).unwrap()), None);
+    assert_eq!(fixed.search_index(Frequency::new(7).unwrap()), None);
+}
+
+#[test]
+fn test_binary_search_matches_linear_scan() {
+    let freqs: Vec<Frequency> = (0..40u64)
+        .map(|i| Frequency::new((i * 7 + 3) % 5).unwrap())
+        .collect();
+    let mutable = MutableFrequencyTable::new(&freqs).unwrap();
+    let fixed = StaticFrequencyTable::new(&freqs).unwrap();
+
+    for cum_freq in 0..*mutable.get_total() + 1 {
+        let expected = (0..freqs.len()).find(|&index| {
+            mutable
+                .get_cfi(index)
+                .is_some_and(|cfi| *cfi.start <= cum_freq && cum_freq < *cfi.end)
+        });
+        let cum_freq = Frequency::new(cum_freq).unwrap();
+        assert_eq!(mutable.get_index(cum_freq), expected);
+        assert_eq!(fixed.search_index(cum_freq), expected);
+    }
+}
+
 #[test]
 fn test_add_frequency() {
     let freqs = vec![1, 1, 1]
@@ -180,3 +343,189 @@ fn test_add_frequency() {
 
     assert_eq!(*table.get_total(), 6);
 }
+
+#[test]
+fn test_sub_frequency() {
+    let freqs = vec![2, 3, 4]
+        .into_iter()
+        .map(Frequency::new)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let mut table = MutableFrequencyTable::new(&freqs).unwrap();
+
+    // Increment, then decrement back:
+    table.add_frequency(1, Frequency::new(5).unwrap());
+    table.sub_frequency(1, Frequency::new(5).unwrap());
+    assert_eq!(*table.get_frequency(1).unwrap(), 3);
+    assert_eq!(*table.get_total(), 9);
+
+    // Decrement below the original frequency:
+    table.sub_frequency(1, Frequency::new(2).unwrap());
+    let cfi_1 = table.get_cfi(1).unwrap();
+    assert_eq!((*cfi_1.start, *cfi_1.end, *cfi_1.total), (2, 3, 7));
+    let cfi_2 = table.get_cfi(2).unwrap();
+    assert_eq!((*cfi_2.start, *cfi_2.end, *cfi_2.total), (3, 7, 7));
+    assert_eq!(table.get_index(Frequency::new(2).unwrap()), Some(1));
+    assert_eq!(table.get_index(Frequency::new(3).unwrap()), Some(2));
+}
+
+#[test]
+fn test_sub_frequency_clamps_to_zero() {
+    let freqs = vec![2, 3, 4]
+        .into_iter()
+        .map(Frequency::new)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let mut table = MutableFrequencyTable::new(&freqs).unwrap();
+
+    // Subtracting more than present only removes what's there:
+    table.sub_frequency(1, Frequency::new(100).unwrap());
+    assert_eq!(*table.get_frequency(1).unwrap(), 0);
+    assert!(table.get_cfi(1).is_none());
+    assert_eq!(*table.get_frequency(2).unwrap(), 4);
+    assert_eq!(*table.get_total(), 6);
+
+    // Out of bounds indices are ignored:
+    table.sub_frequency(3, Frequency::one());
+    assert_eq!(*table.get_total(), 6);
+}
+
+#[test]
+fn test_mutable_frequency_table_rescale() {
+    let freqs = vec![0, 1, 4, 7]
+        .into_iter()
+        .map(Frequency::new)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let mut table = MutableFrequencyTable::new(&freqs).unwrap();
+    table.rescale();
+
+    // Halved and rounded up - [0, 1, 2, 4]:
+    assert!(table.get_cfi(0).is_none());
+    assert_eq!(*table.get_frequency(1).unwrap(), 1);
+    assert_eq!(*table.get_frequency(2).unwrap(), 2);
+    assert_eq!(*table.get_frequency(3).unwrap(), 4);
+    assert_eq!(*table.get_total(), 7);
+}
+
+#[test]
+fn test_mutable_frequency_table_cap_triggers_rescale() {
+    let freqs = vec![1, 1, 1]
+        .into_iter()
+        .map(Frequency::new)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let cap = Frequency::new(8).unwrap();
+    let mut table = MutableFrequencyTable::with_frequency_cap(&freqs, cap).unwrap();
+
+    // Reaching the cap doesn't rescale:
+    table.add_frequency(2, Frequency::new(7).unwrap());
+    assert_eq!(*table.get_frequency(2).unwrap(), 8);
+    assert_eq!(*table.get_total(), 10);
+
+    // Exceeding it does - [1, 1, 9] becomes [1, 1, 5]:
+    table.add_frequency(2, Frequency::one());
+    assert_eq!(*table.get_frequency(0).unwrap(), 1);
+    assert_eq!(*table.get_frequency(1).unwrap(), 1);
+    assert_eq!(*table.get_frequency(2).unwrap(), 5);
+    assert_eq!(*table.get_total(), 7);
+}
+
+#[test]
+fn test_mutable_frequency_table_total_cap_triggers_rescale() {
+    let freqs = vec![2, 2, 4]
+        .into_iter()
+        .map(Frequency::new)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let cap = Frequency::new(10).unwrap();
+    let mut table = MutableFrequencyTable::with_total_cap(&freqs, cap).unwrap();
+
+    // Reaching the cap doesn't rescale:
+    table.add_frequency(0, Frequency::new(2).unwrap());
+    assert_eq!(*table.get_total(), 10);
+
+    // Exceeding it rescales first - [4, 2, 4] becomes [2, 1, 2], then [2, 1, 5]:
+    table.add_frequency(2, Frequency::new(3).unwrap());
+    assert_eq!(*table.get_frequency(0).unwrap(), 2);
+    assert_eq!(*table.get_frequency(1).unwrap(), 1);
+    assert_eq!(*table.get_frequency(2).unwrap(), 5);
+    assert_eq!(*table.get_total(), 8);
+
+    // Amounts that can't fit even after rescaling are refused:
+    table.add_frequency(1, Frequency::new(11).unwrap());
+    assert_eq!(*table.get_total(), 3);
+    assert_eq!(*table.get_frequency(1).unwrap(), 1);
+}
+
+#[test]
+fn test_overflow_policies_at_the_total_cap() {
+    // Frequencies of 1 can't be rescaled, so the total can't drop below 2:
+    let freqs = [Frequency::one(), Frequency::one()];
+    let room = *Frequency::max() - 2;
+    let too_much = Frequency::new(room + 1).unwrap();
+
+    let mut dropping = MutableFrequencyTable::new(&freqs).unwrap();
+    dropping.add_frequency(0, too_much);
+    assert_eq!(*dropping.get_frequency(0).unwrap(), 1);
+    assert_eq!(*dropping.get_total(), 2);
+
+    let mut saturating =
+        MutableFrequencyTable::with_overflow_policy(&freqs, OverflowPolicy::Saturate).unwrap();
+    saturating.add_frequency(0, too_much);
+    assert_eq!(*saturating.get_frequency(0).unwrap(), 1 + room);
+    assert_eq!(saturating.get_total(), Frequency::max());
+
+    // An addition that fits exactly is accepted by both:
+    let mut exact = MutableFrequencyTable::new(&freqs).unwrap();
+    exact.add_frequency(1, Frequency::new(room).unwrap());
+    assert_eq!(exact.get_total(), Frequency::max());
+}
+
+#[test]
+fn test_mutable_frequency_table_keeps_updating_near_max() {
+    let mut table = MutableFrequencyTable::new(&[Frequency::one(); 4]).unwrap();
+    let amount = Frequency::new(*Frequency::max() / 8).unwrap();
+
+    // Without rescaling, the total would pass the largest frequency long before the loop ends:
+    for step in 0..64 {
+        let index = step % 4;
+        let old_frequency = *table.get_frequency(index).unwrap();
+        table.add_frequency(index, amount);
+
+        // Every update is accepted, no matter how large the total got:
+        let new_frequency = *table.get_frequency(index).unwrap();
+        assert!(new_frequency >= *amount);
+        assert!(new_frequency > old_frequency.div_ceil(2));
+
+        // The table stays consistent with its frequencies:
+        let frequencies_sum: u64 = (0..4).map(|i| *table.get_frequency(i).unwrap()).sum();
+        assert_eq!(*table.get_total(), frequencies_sum);
+        assert_eq!(*table.get_cfi(3).unwrap().end, frequencies_sum);
+    }
+}
+
+#[test]
+fn test_iter_cfis_matches_get_cfi() {
+    let freqs = vec![3, 0, 1, 0, 0, 4]
+        .into_iter()
+        .map(Frequency::new)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let static_table = StaticFrequencyTable::new(&freqs).unwrap();
+    let mutable_table = MutableFrequencyTable::new(&freqs).unwrap();
+
+    for table in [&static_table as &dyn FrequencyTable, &mutable_table] {
+        let expected: Vec<(usize, Cfi)> = (0..freqs.len())
+            .filter_map(|index| Some((index, table.get_cfi(index)?)))
+            .collect();
+        assert_eq!(table.iter_cfis().collect::<Vec<_>>(), expected);
+
+        // Empty CFIs are skipped:
+        let indices: Vec<usize> = table.iter_cfis().map(|(index, _)| index).collect();
+        assert_eq!(indices, [0, 2, 5]);
+    }
+}
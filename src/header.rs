@@ -0,0 +1,501 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::models::ppm::EscapeMethod;
+use crate::number_types::{CodecParams, CodecParamsError};
+use crate::sim::Alphabet;
+use crate::termination::TerminationStrategy;
+use crate::varint::{read_varint, write_varint, VarintError, MAX_VARINT_BYTES};
+use anyhow::Result;
+use std::io::{Chain, Cursor, Read};
+use thiserror::Error;
+
+/// The bytes every compressed stream with a header starts with
+pub const MAGIC: [u8; 4] = *b"PPMC";
+
+/// Version of the format of compressed streams, bumped whenever it changes incompatibly
+pub const FORMAT_VERSION: u8 = 16;
+
+/// Length in bytes of the fixed-size fields of a header: the magic, version, flags, model id,
+/// model order, escape method, trailing bits, alphabet, interval bits, frequency bits,
+/// termination strategy and level
+const FIXED_HEADER_BYTES: usize = MAGIC.len() + 11;
+
+/// Largest possible length in bytes of an encoded header, whose fixed-size fields are followed by
+/// the rescale threshold, increment, half-life, original length and (if the termination strategy
+/// uses one) the data's length in symbols as varints
+pub const MAX_HEADER_BYTES: usize = FIXED_HEADER_BYTES + 5 * MAX_VARINT_BYTES;
+
+/// Flag set if the stream was compressed in bit-mode
+const BIT_MODE_FLAG: u8 = 1;
+/// Flag set if the stream was compressed with a preset dictionary
+const DICTIONARY_FLAG: u8 = 1 << 1;
+/// Flag set if the stream's data was split into independently compressed blocks
+const BLOCKS_FLAG: u8 = 1 << 2;
+/// Flag set if the stream's data concatenates several files, starting with a table of them
+const FILES_FLAG: u8 = 1 << 3;
+/// Flag set if the stream ends with a checksum of its original data
+const CHECKSUM_FLAG: u8 = 1 << 4;
+/// Flag set if runs of identical bytes were run-length encoded before coding
+const RLE_FLAG: u8 = 1 << 5;
+/// Flag set if the bytes were move-to-front transformed before coding
+const MTF_FLAG: u8 = 1 << 6;
+
+/// Errors that may occur when reading the header of a compressed stream
+#[derive(Debug, Error, PartialEq)]
+pub enum HeaderError {
+    #[error("The input is too short to hold a header ({0} bytes), it may be truncated")]
+    Truncated(usize),
+    #[error("The input doesn't start with the magic bytes of a compressed stream, so it wasn't compressed by this program")]
+    BadMagic,
+    #[error("The stream was compressed with an unsupported format version ({found}, supported version: {FORMAT_VERSION})")]
+    UnsupportedVersion { found: u8 },
+    #[error(
+        "The header holds an invalid number of trailing bits ({0}, it must be smaller than 8)"
+    )]
+    InvalidTrailingBits(u8),
+    #[error("The header holds invalid coding precisions: {0}")]
+    InvalidCodecParams(CodecParamsError),
+    #[error("The header holds an unknown escape method (id {0})")]
+    UnknownEscapeMethod(u8),
+    #[error("The header holds an unknown alphabet (id {0})")]
+    UnknownAlphabet(u8),
+    #[error("The header holds an invalid rescale threshold")]
+    InvalidRescaleThreshold,
+    #[error("The header holds an invalid increment")]
+    InvalidIncrement,
+    #[error("The header holds an invalid half-life")]
+    InvalidHalfLife,
+    #[error("The header holds an invalid original length")]
+    InvalidLength,
+    #[error("The header holds an unknown termination strategy (id {0})")]
+    UnknownTermination(u8),
+    #[error("The header holds an invalid length of the data in symbols")]
+    InvalidSymbolCount,
+    #[error("The header sets both the run-length and the move-to-front flags, but a stream is parsed by one of them at most")]
+    ConflictingParsers,
+    #[error("The stream was compressed with different settings than the first stream ({found:?} instead of {expected:?})")]
+    StreamMismatch {
+        expected: Box<Header>,
+        found: Box<Header>,
+    },
+    #[error("The stream was compressed with a preset dictionary, but none was given")]
+    MissingDictionary,
+    #[error("The stream was compressed without a preset dictionary, but one was given")]
+    UnexpectedDictionary,
+}
+
+/// The header at the start of a compressed stream, describing how to decompress it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Header {
+    /// If the input was compressed bit-by-bit
+    pub bit_mode: bool,
+    /// If a preset dictionary primed the model
+    pub dictionary: bool,
+    /// If the data was split into blocks, each compressed independently (see the `blocks` module)
+    pub blocks: bool,
+    /// If the data concatenates several files, and starts with a table of their names and lengths
+    /// (see the `files` module)
+    pub files: bool,
+    /// If the stream ends with a checksum of the original data (see the `checksum` module)
+    pub checksum: bool,
+    /// If runs of identical bytes were run-length encoded before coding (see `RleParser`)
+    pub rle: bool,
+    /// If the bytes were move-to-front transformed before coding (see `MtfParser`)
+    pub mtf: bool,
+    /// Identifies the model that compressed the stream. The meaning of each id is up to the
+    /// program writing the header
+    pub model_id: u8,
+    /// The context order of the model (ignored by models without contexts)
+    pub model_order: u8,
+    /// The compression level whose preset chose the model's settings, or 0 if they were chosen
+    /// individually. The meaning of each level is up to the program writing the header
+    pub level: u8,
+    /// How the model estimates escape probabilities (ignored by models other than PPM)
+    pub escape_method: EscapeMethod,
+    /// The frequency at which the model rescales its tables (ignored by models without adaptive
+    /// tables)
+    pub rescale_at: u64,
+    /// The amount the model adds to the frequency of a compressed symbol (ignored by models without
+    /// adaptive tables)
+    pub increment: u64,
+    /// The number of updates after which the model halves its tables, or 0 if it doesn't age them
+    /// (ignored by models without adaptive tables)
+    pub half_life: u64,
+    /// Length in bytes of the original (uncompressed) data
+    pub original_len: u64,
+    /// Number of meaningful bits in the last byte of the original data if its length in bits
+    /// isn't a multiple of 8 (1 to 7), or 0 if it is
+    pub trailing_bits: u8,
+    /// The alphabet of the data's bytes (ignored in bit-mode)
+    pub alphabet: Alphabet,
+    /// The precisions of the coding
+    pub params: CodecParams,
+    /// How decompression finds the end of the data
+    pub termination: TerminationStrategy,
+}
+
+impl Header {
+    /// Encodes the header into bytes
+    pub fn encode(&self) -> Vec<u8> {
+        let mut flags = 0;
+        if self.bit_mode {
+            flags |= BIT_MODE_FLAG;
+        }
+        if self.dictionary {
+            flags |= DICTIONARY_FLAG;
+        }
+        if self.blocks {
+            flags |= BLOCKS_FLAG;
+        }
+        if self.files {
+            flags |= FILES_FLAG;
+        }
+        if self.checksum {
+            flags |= CHECKSUM_FLAG;
+        }
+        if self.rle {
+            flags |= RLE_FLAG;
+        }
+        if self.mtf {
+            flags |= MTF_FLAG;
+        }
+
+        let mut bytes = Vec::with_capacity(MAX_HEADER_BYTES);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&[
+            FORMAT_VERSION,
+            flags,
+            self.model_id,
+            self.model_order,
+            self.escape_method.id(),
+            self.trailing_bits,
+            self.alphabet.id(),
+            self.params.interval_bits() as u8,
+            self.params.frequency_bits() as u8,
+            self.termination.id(),
+            self.level,
+        ]);
+        write_varint(&mut bytes, self.rescale_at);
+        write_varint(&mut bytes, self.increment);
+        write_varint(&mut bytes, self.half_life);
+        write_varint(&mut bytes, self.original_len);
+        if let Some(length) = self.termination.length() {
+            write_varint(&mut bytes, length);
+        }
+        bytes
+    }
+
+    /// Decodes the header at the start of _bytes_. Returns the header and its length in bytes.
+    pub fn decode(bytes: &[u8]) -> Result<(Self, usize), HeaderError> {
+        // Check the magic first, so that short inputs which aren't compressed streams are reported
+        // as such:
+        let magic_len = MAGIC.len().min(bytes.len());
+        if bytes[..magic_len] != MAGIC[..magic_len] {
+            return Err(HeaderError::BadMagic);
+        }
+        let [version, flags, model_id, model_order, escape_method, trailing_bits, alphabet, interval_bits, frequency_bits, termination, level] =
+            bytes
+                .get(MAGIC.len()..FIXED_HEADER_BYTES)
+                .and_then(|fields| <[u8; 11]>::try_from(fields).ok())
+                .ok_or(HeaderError::Truncated(bytes.len()))?;
+
+        if version != FORMAT_VERSION {
+            return Err(HeaderError::UnsupportedVersion { found: version });
+        }
+        if trailing_bits >= 8 {
+            return Err(HeaderError::InvalidTrailingBits(trailing_bits));
+        }
+        let params = CodecParams::new(interval_bits as u32, frequency_bits as u32)
+            .map_err(HeaderError::InvalidCodecParams)?;
+        let escape_method = EscapeMethod::from_id(escape_method)
+            .ok_or(HeaderError::UnknownEscapeMethod(escape_method))?;
+        let alphabet = Alphabet::from_id(alphabet).ok_or(HeaderError::UnknownAlphabet(alphabet))?;
+        let termination = TerminationStrategy::from_id(termination, 0)
+            .ok_or(HeaderError::UnknownTermination(termination))?;
+        if flags & RLE_FLAG != 0 && flags & MTF_FLAG != 0 {
+            return Err(HeaderError::ConflictingParsers);
+        }
+
+        let mut varint_bytes = &bytes[FIXED_HEADER_BYTES..];
+        let mut read_field = |invalid_field_err| {
+            read_varint(&mut varint_bytes).map_err(|err| match err {
+                VarintError::Truncated => HeaderError::Truncated(bytes.len()),
+                VarintError::TooLarge => invalid_field_err,
+            })
+        };
+        let rescale_at = read_field(HeaderError::InvalidRescaleThreshold)?;
+        let increment = read_field(HeaderError::InvalidIncrement)?;
+        let half_life = read_field(HeaderError::InvalidHalfLife)?;
+        let original_len = read_field(HeaderError::InvalidLength)?;
+        let termination = match termination.length() {
+            Some(_) => termination.with_length(read_field(HeaderError::InvalidSymbolCount)?),
+            None => termination,
+        };
+
+        let header = Self {
+            bit_mode: flags & BIT_MODE_FLAG != 0,
+            dictionary: flags & DICTIONARY_FLAG != 0,
+            blocks: flags & BLOCKS_FLAG != 0,
+            files: flags & FILES_FLAG != 0,
+            checksum: flags & CHECKSUM_FLAG != 0,
+            rle: flags & RLE_FLAG != 0,
+            mtf: flags & MTF_FLAG != 0,
+            model_id,
+            model_order,
+            level,
+            escape_method,
+            rescale_at,
+            increment,
+            half_life,
+            original_len,
+            trailing_bits,
+            alphabet,
+            params,
+            termination,
+        };
+        Ok((header, bytes.len() - varint_bytes.len()))
+    }
+
+    /// Checks if both headers describe streams compressed with the same settings, ignoring the
+    /// properties of the streams' data (such as its length and whether it's checksummed)
+    pub fn same_settings(&self, other: &Header) -> bool {
+        let normalize = |header: &Header| Header {
+            original_len: 0,
+            termination: header.termination.with_length(0),
+            trailing_bits: 0,
+            checksum: false,
+            ..*header
+        };
+        normalize(self) == normalize(other)
+    }
+}
+
+/// A reader whose bytes were peeked at, yielding the peeked bytes before the rest of its bytes
+pub type PeekedReader<R> = Chain<Cursor<Vec<u8>>, R>;
+
+/// Reads the header at the start of _reader_ without consuming it: the returned reader still
+/// yields the header's bytes, followed by the rest of _reader_.
+///
+/// This lets the caller pick the model and parser according to the header before decompressing.
+pub fn peek_header<R: Read>(reader: R) -> Result<(Header, PeekedReader<R>)> {
+    let mut header_bytes = Vec::with_capacity(MAX_HEADER_BYTES);
+    let mut reader = reader.take(MAX_HEADER_BYTES as u64);
+    reader.read_to_end(&mut header_bytes)?;
+
+    let (header, _) = Header::decode(&header_bytes)?;
+    Ok((header, Cursor::new(header_bytes).chain(reader.into_inner())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let header = Header {
+            bit_mode: true,
+            dictionary: false,
+            blocks: true,
+            files: true,
+            checksum: true,
+            rle: true,
+            mtf: false,
+            model_id: 3,
+            model_order: 5,
+            level: 7,
+            escape_method: EscapeMethod::PpmD,
+            rescale_at: 1 << 16,
+            increment: 4,
+            half_life: 1 << 12,
+            original_len: 1 << 20,
+            trailing_bits: 3,
+            alphabet: Alphabet::Dna,
+            params: CodecParams::new(24, 20).unwrap(),
+            termination: TerminationStrategy::Both(1 << 23),
+        };
+        let mut encoded = header.encode();
+        let header_len = encoded.len();
+        assert_eq!(encoded[..MAGIC.len()], MAGIC);
+
+        // The header's length is reported, so the payload after it can be found:
+        encoded.extend_from_slice(b"payload");
+        assert_eq!(Header::decode(&encoded), Ok((header, header_len)));
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        let mut encoded = Header::default().encode();
+        encoded[0] = b'X';
+        assert_eq!(Header::decode(&encoded), Err(HeaderError::BadMagic));
+        // Short inputs which aren't compressed streams aren't reported as truncated:
+        assert_eq!(Header::decode(b"hi"), Err(HeaderError::BadMagic));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let header = Header {
+            original_len: 1000,
+            ..Default::default()
+        };
+        let encoded = header.encode();
+        // Cut in the middle of the original length:
+        assert_eq!(
+            Header::decode(&encoded[..encoded.len() - 1]),
+            Err(HeaderError::Truncated(encoded.len() - 1))
+        );
+        assert_eq!(Header::decode(&[]), Err(HeaderError::Truncated(0)));
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut encoded = Header::default().encode();
+        encoded[MAGIC.len()] = FORMAT_VERSION + 1;
+        assert_eq!(
+            Header::decode(&encoded),
+            Err(HeaderError::UnsupportedVersion {
+                found: FORMAT_VERSION + 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_trailing_bits() {
+        let mut encoded = Header::default().encode();
+        encoded[MAGIC.len() + 5] = 8;
+        assert_eq!(
+            Header::decode(&encoded),
+            Err(HeaderError::InvalidTrailingBits(8))
+        );
+    }
+
+    #[test]
+    fn test_unknown_escape_method() {
+        let mut encoded = Header::default().encode();
+        encoded[MAGIC.len() + 4] = 200;
+        assert_eq!(
+            Header::decode(&encoded),
+            Err(HeaderError::UnknownEscapeMethod(200))
+        );
+    }
+
+    #[test]
+    fn test_unknown_alphabet() {
+        let mut encoded = Header::default().encode();
+        encoded[MAGIC.len() + 6] = 200;
+        assert_eq!(
+            Header::decode(&encoded),
+            Err(HeaderError::UnknownAlphabet(200))
+        );
+    }
+
+    #[test]
+    fn test_unknown_termination() {
+        let mut encoded = Header::default().encode();
+        encoded[MAGIC.len() + 9] = 200;
+        assert_eq!(
+            Header::decode(&encoded),
+            Err(HeaderError::UnknownTermination(200))
+        );
+    }
+
+    #[test]
+    fn test_termination_length_is_stored_only_when_used() {
+        let eof = Header::default();
+        let length = Header {
+            termination: TerminationStrategy::Length(1 << 20),
+            ..eof
+        };
+        let (eof_bytes, length_bytes) = (eof.encode(), length.encode());
+        assert!(eof_bytes.len() < length_bytes.len());
+        assert_eq!(
+            Header::decode(&length_bytes),
+            Ok((length, length_bytes.len()))
+        );
+        // Streams with different lengths still have the same settings:
+        let other_length = Header {
+            termination: TerminationStrategy::Length(5),
+            ..eof
+        };
+        assert!(length.same_settings(&other_length));
+        assert!(!length.same_settings(&eof));
+    }
+
+    #[test]
+    fn test_conflicting_parsers() {
+        let header = Header {
+            rle: true,
+            mtf: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Header::decode(&header.encode()),
+            Err(HeaderError::ConflictingParsers)
+        );
+    }
+
+    #[test]
+    fn test_invalid_codec_params() {
+        let mut encoded = Header::default().encode();
+        // Frequency bits that leave no room for the interval to narrow:
+        encoded[MAGIC.len() + 8] = encoded[MAGIC.len() + 7];
+        assert!(matches!(
+            Header::decode(&encoded),
+            Err(HeaderError::InvalidCodecParams(
+                CodecParamsError::FrequencyBitsOutOfRange(_)
+                    | CodecParamsError::IntervalTooNarrow { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_same_settings_ignores_length() {
+        let header = Header {
+            model_id: 2,
+            original_len: 10,
+            ..Default::default()
+        };
+        let longer = Header {
+            original_len: 20,
+            trailing_bits: 5,
+            checksum: true,
+            ..header
+        };
+        assert!(header.same_settings(&longer));
+        let other_model = Header {
+            model_id: 1,
+            ..header
+        };
+        assert!(!header.same_settings(&other_model));
+    }
+
+    #[test]
+    fn test_peek_header_keeps_bytes() {
+        let header = Header {
+            model_id: 1,
+            ..Default::default()
+        };
+        let mut stream = header.encode();
+        stream.extend_from_slice(b"payload");
+
+        let (peeked, mut reader) = peek_header(&stream[..]).unwrap();
+        assert_eq!(peeked, header);
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+        assert_eq!(read, stream);
+    }
+}
@@ -15,7 +15,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::number_types::ConstrainedNum;
+use crate::number_types::{CalculationsType, ConstrainedNum};
 use log::info;
 use thiserror::Error;
 
@@ -29,6 +29,8 @@ use thiserror::Error;
 /// BitsSystem is a struct holding special constants in this integer representation that are vital
 /// to Arithmetic Coding. The generic constant BITS is the number of bits used in the system.
 pub struct BitsSystem<const BITS: u32> {
+    /// Number of bits the system uses, at most BITS
+    bits: u32,
     /// Largest possible value in the integer representation, 0.11..1:
     max: ConstrainedNum<BITS>,
     /// Half in the integer representation, 0.10..0:
@@ -41,20 +43,31 @@ pub struct BitsSystem<const BITS: u32> {
 
 impl<const BITS: u32> BitsSystem<BITS> {
     /// Creates a new bits system. Will fail if _BITS_ is less than 2.
-    pub fn new() -> Result<Self, NotEnoughBitsForSystemError> {
-        // Check the BITS:
-        if BITS < 2 {
-            return Err(NotEnoughBitsForSystemError { bits: BITS });
+    pub fn new() -> Result<Self, BitsSystemError> {
+        Self::with_bits(BITS)
+    }
+
+    /// Creates a bits system which only uses the lowest _bits_ bits of its numbers. Will fail if
+    /// _bits_ is less than 2, or more than _BITS_.
+    pub fn with_bits(bits: u32) -> Result<Self, BitsSystemError> {
+        // Check the bits:
+        if bits < 2 {
+            return Err(BitsSystemError::NotEnoughBits { bits });
+        } else if bits > BITS {
+            return Err(BitsSystemError::TooManyBits { bits, max: BITS });
         }
-        // Create all constants, ConstraintNum will take care of everything
-        let max = ConstrainedNum::max();
-        let half = max >> 1u8;
+        // Create all constants, the number of bits was checked so they all fit:
+        let max = ConstrainedNum::new(CalculationsType::MAX >> (CalculationsType::BITS - bits))
+            .expect("Bits of the system are at most BITS");
+        // Shifting max gives 0.01..1, so flipping its bits gives 0.10..0:
+        let half = (max >> 1u8) ^ max;
         let one_fourth = half >> 1u8;
         let three_fourths = half | one_fourth;
 
-        info!("Creating a Bits System of {} bits", BITS);
+        info!("Creating a Bits System of {} bits", bits);
 
         Ok(Self {
+            bits,
             max,
             half,
             one_fourth,
@@ -62,6 +75,10 @@ impl<const BITS: u32> BitsSystem<BITS> {
         })
     }
 
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
     pub fn max(&self) -> ConstrainedNum<BITS> {
         self.max
     }
@@ -80,7 +97,39 @@ impl<const BITS: u32> BitsSystem<BITS> {
 }
 
 #[derive(Debug, Error)]
-#[error("Every Bits System must have at least 2 bits ({bits} were given)")]
-pub struct NotEnoughBitsForSystemError {
-    bits: u32,
+pub enum BitsSystemError {
+    #[error("Every Bits System must have at least 2 bits ({bits} were given)")]
+    NotEnoughBits { bits: u32 },
+    #[error("A Bits System can't use more bits than its numbers hold ({bits} were given, the maximum is {max})")]
+    TooManyBits { bits: u32, max: u32 },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constants_of_small_system() {
+        let system: BitsSystem<4> = BitsSystem::new().unwrap();
+        assert_eq!(*system.max(), 0b1111);
+        assert_eq!(*system.half(), 0b1000);
+        assert_eq!(*system.one_fourth(), 0b0100);
+        assert_eq!(*system.three_fourths(), 0b1100);
+    }
+
+    #[test]
+    fn test_too_few_bits() {
+        assert!(BitsSystem::<1>::new().is_err());
+    }
+
+    #[test]
+    fn test_fewer_bits_than_numbers_hold() {
+        let system: BitsSystem<8> = BitsSystem::with_bits(4).unwrap();
+        assert_eq!(system.bits(), 4);
+        assert_eq!(*system.max(), 0b1111);
+        assert_eq!(*system.half(), 0b1000);
+        assert_eq!(*system.one_fourth(), 0b0100);
+        assert_eq!(*system.three_fourths(), 0b1100);
+        assert!(BitsSystem::<8>::with_bits(9).is_err());
+    }
 }
@@ -16,13 +16,18 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 mod bits_system;
+mod renorm;
 
 pub use self::bits_system::BitsSystem;
+pub use self::renorm::{RenormCore, Shift};
 use crate::frequencies::Cfi;
-use crate::number_types::{CalculationsType, ConstrainedNum, INTERVAL_BITS};
+use crate::number_types::{
+    checked_scale, CalculationsType, CodecParams, ConstrainedNum, INTERVAL_BITS,
+};
 use anyhow::{anyhow, Result};
 use log::{debug, error};
 use std::fmt::{Display, Formatter};
+use thiserror::Error;
 
 /// Boundary of an interval, an integer representation of a fractional value between 0 and 1.
 pub type IntervalBoundary = ConstrainedNum<INTERVAL_BITS>;
@@ -36,41 +41,72 @@ pub struct Interval {
     high: IntervalBoundary,
     /// The BitsSystem of the interval
     system: BitsSystem<INTERVAL_BITS>,
+    /// The largest total frequency of a CFI the interval can be updated with
+    max_total: CalculationsType,
 }
 
 impl Interval {
-    /// Forms a new Interval that represents the mathematical interval [0, 1).
-    pub fn full_interval() -> Self {
-        let system: BitsSystem<INTERVAL_BITS> =
-            BitsSystem::new().expect("For some reason INTERVAL_BITS was set to less than 2 ಠ_ಠ");
+    /// Forms a new Interval that represents the mathematical interval [0, 1), whose boundaries use
+    /// the interval bits of _params_, and which can be updated with CFIs whose totals fit in their
+    /// frequency bits.
+    pub fn full_interval(params: CodecParams) -> Self {
+        let system: BitsSystem<INTERVAL_BITS> = BitsSystem::with_bits(params.interval_bits())
+            .expect("CodecParams invariant broke, interval bits don't fit in a bits system");
 
         Self {
             low: IntervalBoundary::zero(),
-            high: IntervalBoundary::max(),
+            high: system.max(),
             system,
+            max_total: *params.max_total(),
         }
     }
 
-    /// Updates the model's boundaries based on a Cumulative-Frequency-Interval.
-    pub fn update(&mut self, cfi: Cfi) {
+    /// Forms a new Interval with the given boundaries. Fails if either boundary uses more than
+    /// `INTERVAL_BITS` bits, or if the boundaries break the invariant `low < high`.
+    pub fn from_raw(low: CalculationsType, high: CalculationsType) -> Result<Self> {
+        let (low, high) = (IntervalBoundary::new(low)?, IntervalBoundary::new(high)?);
+        Self::validate_boundaries_invariant(&low, &high)?;
+
+        let mut interval = Self::full_interval(CodecParams::default());
+        (interval.low, interval.high) = (low, high);
+        Ok(interval)
+    }
+
+    /// Updates the model's boundaries based on a Cumulative-Frequency-Interval.<br>
+    /// Fails without changing the boundaries if the CFI isn't valid (i.e: it doesn't satisfy
+    /// `start < end <= total`, or its total doesn't fit in the interval's frequency bits), since it
+    /// would break the interval's invariants.
+    pub fn update(&mut self, cfi: Cfi) -> Result<(), InvalidCfiError> {
         debug!("Interval: Updating with CFI {:?}", cfi);
-        // Compute the width of the interval:
-        let width: CalculationsType = *self.high - *self.low + 1;
-
-        // ASSUMPTION - cfi.start < cfi.end <= cfi.total
-        // In that case, updating boundaries will never cause overflow, since it will compute a
-        // value smaller than or equal to the current high (at most), which must be valid.
-        // Moreover, it will not break the invariance low < high since cfi.start < cfi.end.
-        unsafe {
-            let new_low = IntervalBoundary::new_unchecked(
-                *self.low + (width * *cfi.start).div_euclid(*cfi.total),
-            );
-            let new_high = IntervalBoundary::new_unchecked(
-                *self.low + (width * *cfi.end).div_euclid(*cfi.total) - 1,
-            );
-            (self.low, self.high) = (new_low, new_high);
-            debug!("Interval: Post-update interval: {}", self)
+        if !(cfi.start < cfi.end && cfi.end <= cfi.total && *cfi.total <= self.max_total) {
+            error!("Interval: Can't update with the invalid CFI {:?}", cfi);
+            return Err(InvalidCfiError(cfi));
         }
+
+        // Compute the width of the interval, which may use one bit more than its boundaries:
+        let width: CalculationsType = match self.high - self.low {
+            Ok(difference) => *difference + 1,
+            Err(_) => return Err(InvalidCfiError(cfi)),
+        };
+        let scale = |frequency| checked_scale(width, frequency, *cfi.total);
+        let (start, end) = match (scale(*cfi.start), scale(*cfi.end)) {
+            (Ok(start), Ok(end)) => (start, end),
+            _ => return Err(InvalidCfiError(cfi)),
+        };
+
+        // Since cfi.start < cfi.end <= cfi.total, the new boundaries are at most the current high,
+        // and low < high still holds. The checks only fail if these guarantees are broken:
+        let new_low = (self.low + start).ok();
+        // The offset is subtracted first, since low + end may use one bit more than high:
+        let new_high = end
+            .checked_sub(1)
+            .and_then(|offset| (self.low + offset).ok());
+        match (new_low, new_high) {
+            (Some(new_low), Some(new_high)) => (self.low, self.high) = (new_low, new_high),
+            _ => return Err(InvalidCfiError(cfi)),
+        }
+        debug!("Interval: Post-update interval: {}", self);
+        Ok(())
     }
 
     pub fn get_state(&self) -> IntervalState {
@@ -153,11 +189,18 @@ impl Display for Interval {
             "[{:0bits$b}, {:0bits$b})",
             *self.low,
             *self.high,
-            bits = INTERVAL_BITS as usize
+            bits = self.system.bits() as usize
         )
     }
 }
 
+/// Updating an interval with a CFI that doesn't satisfy `start < end <= total` would move its
+/// boundaries outside of it, or break the invariant `low < high`. A total which doesn't fit in the
+/// frequency bits could do the same
+#[derive(Debug, Error)]
+#[error("Can't update an interval with the invalid CFI {0:?} (expected start < end <= total, and a total that fits in the frequency bits)")]
+pub struct InvalidCfiError(pub Cfi);
+
 /// The state of an interval, based on its boundaries
 pub enum IntervalState {
     /// The interval's lower and upper boundaries both have the same Most-Significant Bit, which
@@ -172,3 +215,145 @@ pub enum IntervalState {
     /// The interval's boundaries do not converge or nearly converge, which is the default state.
     NoConvergence,
 }
+
+/// Builds an interval boundary from its leading bits, filling the rest of its bits with _fill_
+#[cfg(test)]
+pub(crate) fn boundary(prefix: &str, fill: bool) -> IntervalBoundary {
+    let fill = if fill { '1' } else { '0' };
+    let bits: String = prefix
+        .chars()
+        .chain(std::iter::repeat(fill))
+        .take(INTERVAL_BITS as usize)
+        .collect();
+    IntervalBoundary::new(u64::from_str_radix(&bits, 2).unwrap()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frequencies::Frequency;
+
+    /// Value of the interval's half, 0.10..0
+    const HALF: CalculationsType = 1 << (INTERVAL_BITS - 1);
+    /// Value of the interval's one fourth, 0.010..0
+    const ONE_FOURTH: CalculationsType = HALF >> 1;
+    /// Value of the interval's three fourths, 0.110..0
+    const THREE_FOURTHS: CalculationsType = HALF | ONE_FOURTH;
+    /// Largest value of the interval, 0.11..1
+    const MAX: CalculationsType = (1 << INTERVAL_BITS) - 1;
+
+    #[test]
+    fn test_update_full_interval_with_max_frequencies() {
+        let total = Frequency::max();
+        let start = Frequency::new(*total - 2).unwrap();
+        let end = Frequency::new(*total - 1).unwrap();
+        let mut interval = Interval::from_raw(0, MAX).unwrap();
+        interval.update(Cfi { start, end, total }).unwrap();
+
+        // width * start overflows CalculationsType, the boundaries must be computed without it:
+        let width = MAX as u128 + 1;
+        let expected_low = width * (*start as u128) / (*total as u128);
+        let expected_high = width * (*end as u128) / (*total as u128) - 1;
+        assert_eq!(*interval.low() as u128, expected_low);
+        assert_eq!(*interval.high() as u128, expected_high);
+        assert!(interval.low() < interval.high());
+    }
+
+    #[test]
+    fn test_update_rejects_invalid_cfi() {
+        let frequency = |value| Frequency::new(value).unwrap();
+        let invalid_cfis = [
+            // Empty:
+            (3, 3, 10),
+            // Reversed:
+            (5, 2, 10),
+            // Exceeds the total:
+            (8, 12, 10),
+        ];
+        for (start, end, total) in invalid_cfis {
+            let mut interval = Interval::from_raw(0, MAX).unwrap();
+            let cfi = Cfi {
+                start: frequency(start),
+                end: frequency(end),
+                total: frequency(total),
+            };
+            assert!(interval.update(cfi).is_err());
+            // The boundaries are left untouched:
+            assert_eq!((*interval.low(), *interval.high()), (0, MAX));
+        }
+    }
+
+    #[test]
+    fn test_params_limit_interval_and_totals() {
+        let params = CodecParams::new(18, 16).unwrap();
+        let mut interval = Interval::full_interval(params);
+        assert_eq!(*interval.high(), (1 << 18) - 1);
+
+        // A total using more than 16 bits could make a CFI narrower than the interval's precision:
+        let cfi = |total| Cfi {
+            start: Frequency::zero(),
+            end: Frequency::one(),
+            total: Frequency::new(total).unwrap(),
+        };
+        assert!(interval.update(cfi(1 << 16)).is_err());
+        assert!(interval.update(cfi((1 << 16) - 1)).is_ok());
+    }
+
+    #[test]
+    fn test_from_raw_sets_boundaries() {
+        let interval = Interval::from_raw(ONE_FOURTH, THREE_FOURTHS).unwrap();
+        assert_eq!(*interval.low(), ONE_FOURTH);
+        assert_eq!(*interval.high(), THREE_FOURTHS);
+    }
+
+    #[test]
+    fn test_from_raw_rejects_broken_invariant() {
+        assert!(Interval::from_raw(HALF, HALF).is_err());
+        assert!(Interval::from_raw(THREE_FOURTHS, ONE_FOURTH).is_err());
+    }
+
+    #[test]
+    fn test_from_raw_rejects_too_many_bits() {
+        assert!(Interval::from_raw(0, MAX + 1).is_err());
+    }
+
+    #[test]
+    fn test_full_interval_does_not_converge() {
+        let interval = Interval::from_raw(0, MAX).unwrap();
+        assert!(matches!(interval.get_state(), IntervalState::NoConvergence));
+    }
+
+    #[test]
+    fn test_upper_half_converges_on_one() {
+        let interval = Interval::from_raw(HALF, MAX).unwrap();
+        assert!(matches!(
+            interval.get_state(),
+            IntervalState::Converging(true)
+        ));
+    }
+
+    #[test]
+    fn test_lower_half_converges_on_zero() {
+        let interval = Interval::from_raw(0, HALF - 1).unwrap();
+        assert!(matches!(
+            interval.get_state(),
+            IntervalState::Converging(false)
+        ));
+    }
+
+    #[test]
+    fn test_middle_half_nearly_converges() {
+        let interval = Interval::from_raw(ONE_FOURTH, THREE_FOURTHS - 1).unwrap();
+        assert!(matches!(
+            interval.get_state(),
+            IntervalState::NearConvergence
+        ));
+    }
+
+    #[test]
+    fn test_straddling_three_fourths_does_not_converge() {
+        // low = 01..., high = 11..., which is neither converging nor nearly converging:
+        let interval = Interval::from_raw(ONE_FOURTH, THREE_FOURTHS).unwrap();
+        assert!(matches!(interval.get_state(), IntervalState::NoConvergence));
+    }
+}
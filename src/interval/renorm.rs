@@ -0,0 +1,211 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{BitsSystem, Interval, IntervalBoundary, IntervalState};
+use crate::number_types::{CodecParams, INTERVAL_BITS};
+use log::debug;
+
+/// A transition of the interval's state machine, which shifts a single bit out of the interval's
+/// boundaries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shift {
+    /// The boundaries converged on the given bit, which is their shared MSB. The compressor outputs
+    /// it (followed by the outstanding bits), and the decompressor shifts it out of its value.
+    Converge(bool),
+
+    /// The boundaries nearly converged (low = 01XX...X and high = 10YY...Y), so their second MSB is
+    /// shifted out. The compressor can't know the bit yet and puts it aside as an outstanding bit.
+    NearConverge,
+}
+
+impl Shift {
+    /// Shifts _value_ the way this transition shifts the boundaries of an interval using _system_,
+    /// leaving its LSB zero for the bit shifted in.<br>
+    /// _value_ must be inside the interval the transition was found in (as the decompressor's value
+    /// always is), since a near-convergence assumes its second MSB is the inverse of its MSB.
+    pub fn apply(
+        self,
+        value: IntervalBoundary,
+        system: &BitsSystem<INTERVAL_BITS>,
+    ) -> IntervalBoundary {
+        let shifted = (value << 1u8) & system.max();
+        match self {
+            Self::Converge(_) => shifted,
+            // Values in the interval start with 01 or 10, so flipping the new MSB restores it:
+            Self::NearConverge => shifted ^ system.half(),
+        }
+    }
+}
+
+/// The renormalization shared by the compressor and the decompressor: it holds the coding interval
+/// and shifts bits out of it until it's non-converging, so it can be narrowed by the next CFI
+/// without losing precision.
+///
+/// The codecs only differ in what they do with every shifted bit, which is given to
+/// `RenormCore::renormalize` as a callback: the compressor emits bits, and the decompressor
+/// consumes them.
+pub struct RenormCore {
+    /// The interval whose boundaries are renormalized
+    interval: Interval,
+}
+
+impl RenormCore {
+    /// Creates a new core holding the full interval of _params_ (see `Interval::full_interval`)
+    pub fn new(params: CodecParams) -> Self {
+        Self::from_interval(Interval::full_interval(params))
+    }
+
+    /// Creates a new core holding the given interval
+    pub fn from_interval(interval: Interval) -> Self {
+        Self { interval }
+    }
+
+    pub fn interval(&self) -> &Interval {
+        &self.interval
+    }
+
+    pub fn interval_mut(&mut self) -> &mut Interval {
+        &mut self.interval
+    }
+
+    /// Shifts bits out of the interval until it is in a no-convergence state.
+    ///
+    /// Before every shift, _on_shift_ is called with the transition and the interval's bits system.
+    /// If it returns false (e.g. the decompressor ran out of bits to consume), the shift isn't made
+    /// and false is returned: calling the function again continues where it stopped. Otherwise,
+    /// true is returned once the interval is non-converging.
+    pub fn renormalize(
+        &mut self,
+        mut on_shift: impl FnMut(Shift, &BitsSystem<INTERVAL_BITS>) -> bool,
+    ) -> bool {
+        loop {
+            let shift = match self.interval.get_state() {
+                IntervalState::Converging(bit) => Shift::Converge(bit),
+                IntervalState::NearConvergence => Shift::NearConverge,
+                IntervalState::NoConvergence => return true,
+            };
+            let system = self.interval.system();
+            if !on_shift(shift, system) {
+                return false;
+            }
+
+            // Shift the bit out of both boundaries, and shift a 1 bit into high:
+            let low = shift.apply(self.interval.low(), system);
+            let high = shift.apply(self.interval.high(), system) | 1u8;
+            debug!("RenormCore: Shifting the interval with {:?}", shift);
+            self.interval.set_boundaries(low, high).expect(
+                "Removing similar bit or removing second MSB never breaks interval invariance, but it did somehow",
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interval::boundary;
+
+    /// Creates a core whose interval is [_low_, _high_]
+    fn core(low: IntervalBoundary, high: IntervalBoundary) -> RenormCore {
+        RenormCore::from_interval(Interval::from_raw(*low, *high).unwrap())
+    }
+
+    /// Renormalizes _core_ and returns the shifts it made
+    fn shifts(core: &mut RenormCore) -> Vec<Shift> {
+        let mut shifts = Vec::new();
+        assert!(core.renormalize(|shift, _| {
+            shifts.push(shift);
+            true
+        }));
+        shifts
+    }
+
+    #[test]
+    fn test_converging_true_transition() {
+        let mut core = core(boundary("1001", false), boundary("1110", true));
+        // After shifting out the 1, the interval is [001..., 110...], which doesn't converge:
+        assert_eq!(shifts(&mut core), [Shift::Converge(true)]);
+        assert_eq!(core.interval().low(), boundary("001", false));
+        assert_eq!(core.interval().high(), boundary("110", true));
+    }
+
+    #[test]
+    fn test_converging_false_transition() {
+        let mut core = core(boundary("0001", false), boundary("0110", true));
+        assert_eq!(shifts(&mut core), [Shift::Converge(false)]);
+        assert_eq!(core.interval().low(), boundary("001", false));
+        assert_eq!(core.interval().high(), boundary("110", true));
+    }
+
+    #[test]
+    fn test_near_convergence_transition() {
+        let mut core = core(boundary("01001", false), boundary("10110", true));
+        assert_eq!(shifts(&mut core), [Shift::NearConverge]);
+        assert_eq!(core.interval().low(), boundary("0001", false));
+        assert_eq!(core.interval().high(), boundary("1110", true));
+    }
+
+    #[test]
+    fn test_no_convergence_transition() {
+        let (low, high) = (boundary("001", false), boundary("110", true));
+        let mut core = core(low, high);
+        assert!(shifts(&mut core).is_empty());
+        assert_eq!(core.interval().low(), low);
+        assert_eq!(core.interval().high(), high);
+    }
+
+    #[test]
+    fn test_chained_transitions() {
+        // [00101..., 01010...] converges on 0 into [0101..., 1010...], which nearly converges:
+        let mut core = core(boundary("00101", false), boundary("01010", true));
+        assert_eq!(
+            shifts(&mut core),
+            [Shift::Converge(false), Shift::NearConverge]
+        );
+        assert_eq!(core.interval().low(), boundary("001", false));
+        assert_eq!(core.interval().high(), boundary("110", true));
+    }
+
+    #[test]
+    fn test_declined_shift_resumes() {
+        let (low, high) = (boundary("1001", false), boundary("1110", true));
+        let mut core = core(low, high);
+        assert!(!core.renormalize(|_, _| false));
+        // The declined shift wasn't made:
+        assert_eq!(core.interval().low(), low);
+        assert_eq!(core.interval().high(), high);
+
+        assert_eq!(shifts(&mut core), [Shift::Converge(true)]);
+    }
+
+    #[test]
+    fn test_apply_matches_boundaries() {
+        let interval = Interval::full_interval(CodecParams::default());
+        let system = interval.system();
+        // A value inside the interval is shifted like its boundaries, with a zero LSB:
+        let value = boundary("1011", false);
+        assert_eq!(
+            Shift::Converge(true).apply(value, system),
+            boundary("011", false)
+        );
+        let value = boundary("0110", true);
+        assert_eq!(
+            *Shift::NearConverge.apply(value, system),
+            *boundary("010", true) & !1
+        );
+    }
+}
@@ -0,0 +1,49 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Arithmetic coding + Prediction by Partial Matching.
+//!
+//! [`compress_reader`] and [`decompress_reader`] compress and decompress streams with any
+//! [`Model`](models::Model). Integrators can supply their own models by implementing the trait,
+//! and their own symbol pre-processing by implementing [`Parser`](parser::Parser).
+
+pub mod bit_buffer;
+pub mod blocks;
+pub mod checksum;
+pub mod compressor;
+pub mod decompressor;
+pub mod dictionary;
+pub mod files;
+pub mod frequencies;
+pub mod header;
+pub mod interval;
+pub mod models;
+pub mod number_types;
+pub mod parser;
+pub mod report;
+#[cfg(test)]
+mod roundtrip_tests;
+pub mod sim;
+mod stream;
+pub mod termination;
+pub mod two_pass;
+pub mod varint;
+
+pub use stream::{
+    compress_reader, decompress_reader, CodecOptions, ModelAlphabetError, ModelPrecisionError,
+    OutputWriteError, PartialByteError, TrailingDataError, TrailingPolicy, UnknownTrailingPolicy,
+};
@@ -15,24 +15,15 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+// The CLI still holds placeholders for user-defined models:
 #![allow(dead_code)]
 
-mod bit_buffer;
 mod cli;
-mod compressor;
-mod decompressor;
-mod frequencies;
-mod interval;
-mod models;
-mod number_types;
-mod parser;
-mod sim;
 
 use log::error;
 use std::process::ExitCode;
 
 fn main() -> ExitCode {
-    env_logger::init();
     if let Err(e) = cli::run() {
         error!("{}", e);
         ExitCode::FAILURE
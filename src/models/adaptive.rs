@@ -0,0 +1,435 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::frequencies::mutable_table::MutableFrequencyTable;
+use crate::frequencies::{Frequency, FrequencyTable};
+use crate::models::snapshot::{ModelSnapshot, SnapshotError};
+use crate::models::{Model, ModelCfi, ModelCfiError};
+use crate::number_types::CalculationsType;
+use crate::sim::{Symbol, SymbolIndexMapping};
+use anyhow::Result;
+use log::{debug, error, warn};
+use std::num::NonZeroU64;
+
+/// The default cap on a single symbol's frequency in an adaptive model's table. Once a symbol's
+/// frequency passes it, the table is rescaled.
+pub const DEFAULT_FREQUENCY_CAP: CalculationsType = 1 << 16;
+
+/// The amount `UpdateRule::Linear` adds to the frequency of a compressed symbol, unless the model
+/// is given another increment
+pub const DEFAULT_INCREMENT: CalculationsType = 1;
+
+/// The increment a sub-linear update gives a symbol whose frequency is 1. The increment is halved
+/// each time the frequency doubles, down to 1.
+pub const SUBLINEAR_BASE_INCREMENT: CalculationsType = 32;
+
+/// How an adaptive model increments the frequency of a compressed symbol
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum UpdateRule {
+    /// The frequency is always incremented by the model's increment (`DEFAULT_INCREMENT` unless
+    /// it's given another one, see `AdaptiveModel::with_increment`)
+    #[default]
+    Linear,
+    /// The increment shrinks as the frequency grows (`SUBLINEAR_BASE_INCREMENT` halved for every
+    /// doubling of the frequency, but at least 1), so frequencies grow roughly logarithmically
+    /// until they reach `SUBLINEAR_BASE_INCREMENT`.
+    ///
+    /// A new symbol quickly gains a significant probability, while a long run of a single symbol
+    /// can't keep growing it at the same pace, which suits bursty inputs.
+    Sublinear,
+}
+
+impl UpdateRule {
+    /// Returns the amount added to a symbol whose current frequency is _frequency_, by a model
+    /// whose linear increment is _linear_increment_
+    fn increment(&self, frequency: Frequency, linear_increment: Frequency) -> Frequency {
+        match self {
+            UpdateRule::Linear => linear_increment,
+            UpdateRule::Sublinear => {
+                let doublings = (*frequency).max(1).ilog2();
+                Frequency::new((SUBLINEAR_BASE_INCREMENT >> doublings).max(1))
+                    .expect("Increment is at most SUBLINEAR_BASE_INCREMENT")
+            }
+        }
+    }
+}
+
+/// An order-0 adaptive probability model.
+///
+/// Every symbol (except the escape symbol) starts with a frequency of 1, and each time a symbol is
+/// compressed its frequency is incremented according to the model's `UpdateRule`. To keep a
+/// single dominant symbol from making all others too expensive, the table is rescaled (halved)
+/// whenever a frequency passes a cap.
+///
+/// A model given a half-life also ages its table: every time that many symbols are compressed, the
+/// table is halved, so symbols seen recently weigh more than older ones and the model follows
+/// changes in the input's statistics.
+pub struct AdaptiveModel<SIM: SymbolIndexMapping> {
+    /// The table holding the frequencies learned so far
+    table: MutableFrequencyTable,
+    /// A mapping between symbols and indices in the table
+    sim: SIM,
+    /// Once a single frequency passes this cap, the table is rescaled
+    frequency_cap: Frequency,
+    /// How frequencies are incremented
+    update_rule: UpdateRule,
+    /// The amount `UpdateRule::Linear` adds to a compressed symbol's frequency
+    increment: Frequency,
+    /// The number of updates after which the table is halved, if the model ages its table
+    half_life: Option<NonZeroU64>,
+    /// The number of updates since the table was last halved by aging
+    updates_since_aging: u64,
+    /// The table is rescaled before an update would make its total exceed this cap
+    total_cap: Frequency,
+    /// The table the model returns to when flushed, if a snapshot was imported into it (otherwise
+    /// it returns to its initial table)
+    imported_table: Option<MutableFrequencyTable>,
+}
+
+impl<SIM: SymbolIndexMapping> AdaptiveModel<SIM> {
+    /// Creates an adaptive model with the default frequency cap.
+    ///
+    /// ## Parameters:
+    /// * sim - A mapping between symbols and indices.
+    pub fn new(sim: SIM) -> Self {
+        let frequency_cap = Frequency::new(DEFAULT_FREQUENCY_CAP)
+            .expect("Default frequency cap too large to become frequency");
+        Self::with_frequency_cap(sim, frequency_cap)
+    }
+
+    /// Creates an adaptive model with the default frequency cap, whose frequencies are updated
+    /// with `UpdateRule::Sublinear`.
+    ///
+    /// ## Parameters:
+    /// * sim - A mapping between symbols and indices.
+    pub fn sublinear(sim: SIM) -> Self {
+        let frequency_cap = Frequency::new(DEFAULT_FREQUENCY_CAP)
+            .expect("Default frequency cap too large to become frequency");
+        Self::with_update_rule(sim, frequency_cap, UpdateRule::Sublinear)
+    }
+
+    /// Creates an adaptive model whose table is rescaled once a single frequency passes
+    /// _frequency_cap_.
+    ///
+    /// ## Parameters:
+    /// * sim - A mapping between symbols and indices.
+    /// * frequency_cap - The maximal frequency a single symbol can reach before a rescale.
+    pub fn with_frequency_cap(sim: SIM, frequency_cap: Frequency) -> Self {
+        Self::with_update_rule(sim, frequency_cap, UpdateRule::default())
+    }
+
+    /// Creates an adaptive model whose table is rescaled once a single frequency passes
+    /// _frequency_cap_, and whose frequencies are incremented according to _update_rule_.
+    ///
+    /// ## Parameters:
+    /// * sim - A mapping between symbols and indices.
+    /// * frequency_cap - The maximal frequency a single symbol can reach before a rescale.
+    /// * update_rule - How the frequency of a compressed symbol is incremented.
+    pub fn with_update_rule(sim: SIM, frequency_cap: Frequency, update_rule: UpdateRule) -> Self {
+        let table = Self::initial_table(&sim, frequency_cap);
+        Self {
+            table,
+            sim,
+            frequency_cap,
+            update_rule,
+            increment: Frequency::new(DEFAULT_INCREMENT).expect("Default increment is valid"),
+            half_life: None,
+            updates_since_aging: 0,
+            total_cap: Frequency::max(),
+            imported_table: None,
+        }
+    }
+
+    /// Makes `UpdateRule::Linear` add _increment_ to the frequency of every compressed symbol
+    /// instead of `DEFAULT_INCREMENT`. Larger increments let new statistics outweigh the initial
+    /// frequencies sooner, and reach the frequency cap (rescaling the table) sooner.
+    pub fn with_increment(mut self, increment: Frequency) -> Self {
+        self.increment = increment;
+        self
+    }
+
+    /// Makes the model age its table - every _half_life_ updates the table is halved, so the weight
+    /// of a symbol's occurrence halves once _half_life_ more symbols were compressed after it.
+    pub fn with_half_life(mut self, half_life: NonZeroU64) -> Self {
+        self.half_life = Some(half_life);
+        self
+    }
+
+    /// Limits the total frequency of the model's table to _total_cap_, rescaling the table before
+    /// an update would make its total exceed it (e.g. to fit the frequency bits of
+    /// [CodecParams](crate::number_types::CodecParams)).
+    pub fn with_total_cap(mut self, total_cap: Frequency) -> Self {
+        self.total_cap = total_cap;
+        self.table.limit_total(total_cap);
+        self
+    }
+
+    /// Builds the table the model starts with - a frequency of 1 for every symbol except the
+    /// escape symbol, which is never coded by this model.
+    pub fn initial_table(sim: &SIM, frequency_cap: Frequency) -> MutableFrequencyTable {
+        let escape_index = sim.get_index(&Symbol::Esc);
+        let frequencies: Vec<Frequency> = (0..sim.supported_symbols_count())
+            .map(|index| {
+                if Some(index) == escape_index {
+                    Frequency::zero()
+                } else {
+                    Frequency::one()
+                }
+            })
+            .collect();
+
+        // A SIM can have a maximum of UNIQUE_SYMBOLS_AMOUNT which is far less than
+        // Frequency::max()
+        MutableFrequencyTable::with_frequency_cap(&frequencies, frequency_cap)
+            .expect("SIM invariant broke, supported symbols count too large to become frequency")
+    }
+}
+
+impl<SIM: SymbolIndexMapping> Model for AdaptiveModel<SIM> {
+    fn get_cfi(&self, symbol: Symbol) -> Result<ModelCfi, ModelCfiError> {
+        let index = self.sim.get_index(&symbol).ok_or_else(|| {
+            error!("Adaptive Model: Unsupported symbol \"{}\" given", symbol);
+            ModelCfiError::UnsupportedSymbol(symbol)
+        })?;
+
+        self.table
+            .get_cfi(index)
+            .map(|cfi| {
+                if symbol.is_escape() {
+                    ModelCfi::EscapeCfi(cfi)
+                } else {
+                    ModelCfi::IndexCfi(cfi)
+                }
+            })
+            .ok_or_else(|| {
+                warn!(
+                    "Adaptive Model: Empty CFI assigned to queried symbol {}",
+                    symbol
+                );
+                ModelCfiError::EmptyCfi { symbol }
+            })
+    }
+
+    fn get_symbol(&self, cumulative_frequency: Frequency) -> Option<Symbol> {
+        self.table
+            .get_index(cumulative_frequency)
+            .and_then(|index| self.sim.get_symbol(index))
+    }
+
+    fn get_total(&self) -> Frequency {
+        self.table.get_total()
+    }
+
+    fn alphabet_size(&self) -> usize {
+        self.sim.supported_symbols_count()
+    }
+
+    fn decode(&self, cumulative_frequency: Frequency) -> Option<(Symbol, ModelCfi)> {
+        let (index, cfi) = self.table.locate(cumulative_frequency)?;
+        let symbol = self.sim.get_symbol(index)?;
+        Some((symbol, ModelCfi::for_symbol(symbol, cfi)))
+    }
+
+    fn flush(&mut self) {
+        self.table = match &self.imported_table {
+            Some(table) => table.clone(),
+            None => Self::initial_table(&self.sim, self.frequency_cap),
+        };
+        self.table.limit_total(self.total_cap);
+        self.updates_since_aging = 0;
+    }
+
+    fn update(&mut self, symbol: Symbol, model_result: &ModelCfi) -> Result<()> {
+        // Only learn from symbols that were actually coded:
+        if let (ModelCfi::IndexCfi(_), Some(index)) = (model_result, self.sim.get_index(&symbol)) {
+            if let Some(frequency) = self.table.get_frequency(index) {
+                let increment = self.update_rule.increment(frequency, self.increment);
+                self.table.add_frequency(index, increment);
+            }
+            if let Some(half_life) = self.half_life {
+                self.updates_since_aging += 1;
+                if self.updates_since_aging == half_life.get() {
+                    debug!("Adaptive Model: Aging the table after {half_life} updates");
+                    self.table.rescale();
+                    self.updates_since_aging = 0;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn export(&self) -> Option<ModelSnapshot> {
+        Some(ModelSnapshot::new(&self.sim, &self.table.frequencies()))
+    }
+
+    fn import(&mut self, snapshot: &ModelSnapshot) -> Result<(), SnapshotError> {
+        let frequencies = snapshot.frequencies_for(&self.sim)?;
+        let mut table = MutableFrequencyTable::with_frequency_cap(&frequencies, self.frequency_cap)
+            .map_err(|e| SnapshotError::InvalidDistribution(e.to_string()))?;
+        table.limit_total(self.total_cap);
+        self.imported_table = Some(table.clone());
+        self.table = table;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressor::Compressor;
+    use crate::decompressor::Decompressor;
+    use crate::sim::DefaultSIM;
+
+    #[test]
+    fn test_update_increments_coded_symbol() {
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let total = *model.get_total();
+
+        let cfi = model.get_cfi(Symbol::Byte(b'a')).unwrap();
+        model.update(Symbol::Byte(b'a'), &cfi).unwrap();
+
+        assert_eq!(*model.get_total(), total + 1);
+        match model.get_cfi(Symbol::Byte(b'a')).unwrap() {
+            ModelCfi::IndexCfi(cfi) => assert_eq!(cfi.width(), 2),
+            ModelCfi::EscapeCfi(_) => panic!("Byte symbol got an escape CFI"),
+        }
+    }
+
+    #[test]
+    fn test_dominant_symbol_triggers_cap() {
+        let cap = Frequency::new(16).unwrap();
+        let mut model = AdaptiveModel::with_frequency_cap(DefaultSIM, cap);
+        let index = DefaultSIM.get_index(&Symbol::Byte(0)).unwrap();
+
+        for _ in 0..100 {
+            let cfi = model.get_cfi(Symbol::Byte(0)).unwrap();
+            model.update(Symbol::Byte(0), &cfi).unwrap();
+            assert!(model.table.get_frequency(index).unwrap() <= cap);
+        }
+
+        // Other symbols never vanish:
+        assert!(model.get_cfi(Symbol::Byte(1)).is_ok());
+    }
+
+    #[test]
+    fn test_sublinear_increments() {
+        let rule = UpdateRule::Sublinear;
+        let increment = |f| *rule.increment(Frequency::new(f).unwrap(), Frequency::one());
+
+        assert_eq!(increment(1), 32);
+        assert_eq!(increment(3), 16);
+        assert_eq!(increment(4), 8);
+        assert_eq!(increment(31), 2);
+        assert_eq!(increment(32), 1);
+        assert_eq!(increment(1 << 20), 1);
+        let linear = UpdateRule::Linear.increment(Frequency::new(7).unwrap(), Frequency::one());
+        assert_eq!(*linear, 1);
+    }
+
+    #[test]
+    fn test_custom_increment() {
+        let increment = Frequency::new(5).unwrap();
+        let mut model = AdaptiveModel::new(DefaultSIM).with_increment(increment);
+        let total = *model.get_total();
+
+        let cfi = model.get_cfi(Symbol::Byte(b'a')).unwrap();
+        model.update(Symbol::Byte(b'a'), &cfi).unwrap();
+        assert_eq!(*model.get_total(), total + 5);
+    }
+
+    /// Compresses _input_ with _model_ and decompresses it back with the flushed model. Returns
+    /// the compressed length
+    fn round_trip(model: &mut AdaptiveModel<DefaultSIM>, input: &[u8]) -> usize {
+        model.flush();
+        let mut compressor = Compressor::new(&mut *model);
+        let mut compressed = Vec::new();
+        for &byte in input {
+            compressed.extend(compressor.load_symbol(Symbol::Byte(byte)).unwrap());
+        }
+        compressed.extend(compressor.finalize().unwrap().0);
+        let compressed_len = compressed.len();
+
+        model.flush();
+        let decompressed: Vec<u8> = Decompressor::from_bytes(&mut *model, compressed)
+            .bytes()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(decompressed, input);
+        compressed_len
+    }
+
+    #[test]
+    fn test_aging_follows_distribution_shift() {
+        // The statistics of the first half are useless for the second half:
+        let mut input = b"abababababab".repeat(400);
+        input.extend(b"xyzzy".repeat(1000));
+
+        let increment = Frequency::new(8).unwrap();
+        let mut plain = AdaptiveModel::new(DefaultSIM).with_increment(increment);
+        let mut aged = AdaptiveModel::new(DefaultSIM)
+            .with_increment(increment)
+            .with_half_life(NonZeroU64::new(256).unwrap());
+        assert!(round_trip(&mut aged, &input) < round_trip(&mut plain, &input));
+    }
+
+    #[test]
+    fn test_total_cap_limits_total() {
+        let total_cap = Frequency::new(300).unwrap();
+        let mut model = AdaptiveModel::new(DefaultSIM).with_total_cap(total_cap);
+
+        for _ in 0..1000 {
+            let cfi = model.get_cfi(Symbol::Byte(0)).unwrap();
+            model.update(Symbol::Byte(0), &cfi).unwrap();
+            assert!(model.get_total() <= total_cap);
+        }
+    }
+
+    #[test]
+    fn test_flush_restores_initial_state() {
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let total = model.get_total();
+
+        let cfi = model.get_cfi(Symbol::Byte(7)).unwrap();
+        model.update(Symbol::Byte(7), &cfi).unwrap();
+        model.flush();
+
+        assert_eq!(model.get_total(), total);
+    }
+
+    #[test]
+    fn test_reused_model_must_be_flushed() {
+        let input = b"abracadabra, abracadabra";
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let mut compressor = Compressor::new(&mut model);
+        let mut compressed = Vec::new();
+        for &byte in input {
+            compressed.extend(compressor.load_symbol(Symbol::Byte(byte)).unwrap());
+        }
+        compressed.extend(compressor.finalize().unwrap().0);
+
+        let decompress = |model: &mut AdaptiveModel<DefaultSIM>| -> Result<Vec<u8>> {
+            Decompressor::from_bytes(model, compressed.clone())
+                .bytes()
+                .collect()
+        };
+        // The model still holds the statistics compression learned, so decoding goes out of sync:
+        assert!(decompress(&mut model).map_or(true, |decompressed| decompressed != input));
+
+        model.flush();
+        assert_eq!(decompress(&mut model).unwrap(), input);
+    }
+}
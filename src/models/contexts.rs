@@ -0,0 +1,170 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::frequencies::mutable_table::MutableFrequencyTable;
+use std::collections::HashMap;
+
+/// The number of possible order-1 contexts (one per previous byte)
+pub const ORDER1_CONTEXTS_AMOUNT: usize = 256;
+
+/// Storage for the frequency tables of an order-1 context model, holding one table per previous
+/// byte.
+///
+/// Every context starts with the same initial table. Implementations only differ in how they
+/// store the tables, so two storages given the same operations always hold identical tables.
+pub trait ContextTables {
+    /// Returns the table of the given context.
+    fn get(&self, context: u8) -> &MutableFrequencyTable;
+
+    /// Returns a mutable reference to the table of the given context.
+    fn get_mut(&mut self, context: u8) -> &mut MutableFrequencyTable;
+
+    /// Resets every context back to the initial table.
+    fn reset(&mut self);
+}
+
+/// Allocates the tables of all contexts upfront.
+///
+/// Lookups are a simple index, but memory usage is that of all 256 tables regardless of the
+/// input.
+pub struct DenseContextTables {
+    /// The table every context starts with
+    initial: MutableFrequencyTable,
+    /// The tables of all contexts, indexed by the context's byte
+    tables: Box<[MutableFrequencyTable]>,
+}
+
+impl DenseContextTables {
+    /// Creates a storage in which every context starts with the _initial_ table.
+    pub fn new(initial: MutableFrequencyTable) -> Self {
+        let tables = vec![initial.clone(); ORDER1_CONTEXTS_AMOUNT].into_boxed_slice();
+        Self { initial, tables }
+    }
+}
+
+impl ContextTables for DenseContextTables {
+    fn get(&self, context: u8) -> &MutableFrequencyTable {
+        &self.tables[context as usize]
+    }
+
+    fn get_mut(&mut self, context: u8) -> &mut MutableFrequencyTable {
+        &mut self.tables[context as usize]
+    }
+
+    fn reset(&mut self) {
+        self.tables.fill(self.initial.clone());
+    }
+}
+
+/// Allocates the table of a context only when it is first modified.
+///
+/// Lookups cost a hash, but memory usage is proportional to the number of distinct contexts that
+/// actually appear in the input. A context that was never modified is identical to the initial
+/// table, so reading it doesn't allocate anything.
+pub struct LazyContextTables {
+    /// The table every context starts with
+    initial: MutableFrequencyTable,
+    /// The tables of the contexts that were modified so far
+    tables: HashMap<u8, MutableFrequencyTable>,
+}
+
+impl LazyContextTables {
+    /// Creates a storage in which every context starts with the _initial_ table.
+    pub fn new(initial: MutableFrequencyTable) -> Self {
+        Self {
+            initial,
+            tables: HashMap::new(),
+        }
+    }
+
+    /// Returns the number of contexts whose table was allocated.
+    pub fn allocated_contexts(&self) -> usize {
+        self.tables.len()
+    }
+}
+
+impl ContextTables for LazyContextTables {
+    fn get(&self, context: u8) -> &MutableFrequencyTable {
+        self.tables.get(&context).unwrap_or(&self.initial)
+    }
+
+    fn get_mut(&mut self, context: u8) -> &mut MutableFrequencyTable {
+        self.tables
+            .entry(context)
+            .or_insert_with(|| self.initial.clone())
+    }
+
+    fn reset(&mut self) {
+        self.tables.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frequencies::{Frequency, FrequencyTable};
+
+    fn initial_table() -> MutableFrequencyTable {
+        MutableFrequencyTable::new(&[Frequency::one(); 8]).unwrap()
+    }
+
+    /// Asserts both storages hold identical tables in every context
+    fn assert_identical(dense: &DenseContextTables, lazy: &LazyContextTables) {
+        for context in 0..=u8::MAX {
+            let (dense_table, lazy_table) = (dense.get(context), lazy.get(context));
+            assert_eq!(dense_table.get_total(), lazy_table.get_total());
+            for index in 0..8 {
+                assert_eq!(dense_table.get_cfi(index), lazy_table.get_cfi(index));
+            }
+        }
+    }
+
+    #[test]
+    fn test_dense_and_lazy_are_identical() {
+        let mut dense = DenseContextTables::new(initial_table());
+        let mut lazy = LazyContextTables::new(initial_table());
+
+        // A deterministic mix of contexts and indices:
+        for step in 0..1000usize {
+            let context = (step * 31 % 7) as u8 * 13;
+            let index = step * 17 % 8;
+            dense
+                .get_mut(context)
+                .add_frequency(index, Frequency::one());
+            lazy.get_mut(context).add_frequency(index, Frequency::one());
+        }
+
+        assert_identical(&dense, &lazy);
+        // Only the 7 contexts used were allocated:
+        assert_eq!(lazy.allocated_contexts(), 7);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut dense = DenseContextTables::new(initial_table());
+        let mut lazy = LazyContextTables::new(initial_table());
+        dense.get_mut(3).add_frequency(2, Frequency::one());
+        lazy.get_mut(3).add_frequency(2, Frequency::one());
+
+        dense.reset();
+        lazy.reset();
+
+        assert_identical(&dense, &lazy);
+        assert_eq!(dense.get(3).get_total(), initial_table().get_total());
+        assert_eq!(lazy.allocated_contexts(), 0);
+    }
+}
@@ -17,6 +17,7 @@
 
 use crate::frequencies::static_table::StaticFrequencyTable;
 use crate::frequencies::{Frequency, FrequencyTable};
+use crate::models::snapshot::{ModelSnapshot, SnapshotError};
 use crate::models::{Model, ModelCfi, ModelCfiError};
 use crate::sim::{Symbol, SymbolIndexMapping};
 use anyhow::{anyhow, Result};
@@ -39,6 +40,8 @@ impl<SIM: SymbolIndexMapping> CustomDistributionModel<SIM> {
     ///   frequencies is determined by _sim_.
     /// ## Potential Failures
     /// If the sum of the frequencies exceeds Frequency::max(), an error will be returned.
+    /// If all of the frequencies are zero (so no symbol could be coded), an
+    /// [EmptyTableError](crate::frequencies::EmptyTableError) will be returned.
     /// If the length of _frequencies_ does not equal `sim.supported_symbols_count()`, an error will
     /// be returned.
     pub fn new(sim: SIM, frequencies: &[Frequency]) -> Result<Self> {
@@ -59,6 +62,36 @@ impl<SIM: SymbolIndexMapping> CustomDistributionModel<SIM> {
             })
         }
     }
+
+    /// Creates a model like `new`, but blends _frequencies_ with a uniform distribution: _smoothing_
+    /// is added to the frequency of every symbol except the escape (which is never coded). This
+    /// gives symbols a custom distribution misses a small probability instead of none, so they can
+    /// still be compressed.
+    ///
+    /// ## Potential Failures
+    /// The same as `new`, and an error is also returned if a smoothed frequency exceeds
+    /// Frequency::max().
+    pub fn with_smoothing(
+        sim: SIM,
+        frequencies: &[Frequency],
+        smoothing: Frequency,
+    ) -> Result<Self> {
+        let smoothed = frequencies
+            .iter()
+            .enumerate()
+            .map(|(index, &frequency)| {
+                if sim
+                    .get_symbol(index)
+                    .is_some_and(|symbol| symbol.is_escape())
+                {
+                    Ok(frequency)
+                } else {
+                    frequency.checked_add(smoothing)
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(sim, &smoothed)
+    }
 }
 
 impl<SIM: SymbolIndexMapping> Model for CustomDistributionModel<SIM> {
@@ -98,4 +131,19 @@ impl<SIM: SymbolIndexMapping> Model for CustomDistributionModel<SIM> {
     fn get_total(&self) -> Frequency {
         self.table.get_total()
     }
+
+    fn alphabet_size(&self) -> usize {
+        self.sim.supported_symbols_count()
+    }
+
+    fn export(&self) -> Option<ModelSnapshot> {
+        Some(ModelSnapshot::new(&self.sim, &self.table.frequencies()))
+    }
+
+    fn import(&mut self, snapshot: &ModelSnapshot) -> Result<(), SnapshotError> {
+        let frequencies = snapshot.frequencies_for(&self.sim)?;
+        self.table = StaticFrequencyTable::new(&frequencies)
+            .map_err(|e| SnapshotError::InvalidDistribution(e.to_string()))?;
+        Ok(())
+    }
 }
@@ -59,7 +59,7 @@ impl<SIM: SymbolIndexMapping> Model for UniformDistributionModel<SIM> {
             }
         };
 
-        if cfi.start == cfi.end {
+        if cfi.is_empty() {
             warn!(
                 "Uniform Distribution Model: Empty CFI assigned to queried symbol {}",
                 symbol
@@ -84,4 +84,8 @@ impl<SIM: SymbolIndexMapping> Model for UniformDistributionModel<SIM> {
         Frequency::new(self.0.supported_symbols_count() as CalculationsType)
             .expect("SIM invariant broke, supported symbols count too large to become frequency")
     }
+
+    fn alphabet_size(&self) -> usize {
+        self.0.supported_symbols_count()
+    }
 }
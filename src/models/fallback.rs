@@ -0,0 +1,248 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::frequencies::Frequency;
+use crate::models::{Model, ModelCfi, ModelCfiError};
+use crate::sim::Symbol;
+use anyhow::Result;
+use log::debug;
+
+/// A model coding symbols with a primary model, which falls back to a secondary model for the
+/// symbols the primary can't code. This generalizes PPM's fallback to shorter contexts to any pair
+/// of models.
+///
+/// A symbol the primary model doesn't support (or assigns an empty CFI) is coded by escaping: the
+/// primary model's escape CFI hands the symbol over to the fallback model, which codes it. The
+/// next symbol starts with the primary model again.<br>
+/// Every escape of the primary model hands the symbol over, so the primary model should be one
+/// that doesn't escape by itself (such as distribution and adaptive models), and it must assign
+/// the escape symbol a non-empty CFI.
+pub struct FallbackModel<A: Model, B: Model> {
+    /// The model every symbol is coded with first
+    primary: A,
+    /// The model coding the symbols the primary model escaped from
+    fallback: B,
+    /// Whether the primary model escaped, so the current symbol is coded by the fallback model
+    escaped: bool,
+}
+
+impl<A: Model, B: Model> FallbackModel<A, B> {
+    /// Creates a model coding symbols with _primary_, which falls back to _fallback_ for symbols
+    /// it can't code.
+    pub fn new(primary: A, fallback: B) -> Self {
+        Self {
+            primary,
+            fallback,
+            escaped: false,
+        }
+    }
+
+    /// Returns the model every symbol is coded with first
+    pub fn primary(&self) -> &A {
+        &self.primary
+    }
+
+    /// Returns the model coding the symbols the primary model can't code
+    pub fn fallback(&self) -> &B {
+        &self.fallback
+    }
+
+    /// Returns the escape CFI handing _symbol_ over to the fallback model. Fails with _err_ (the
+    /// reason the primary model can't code _symbol_) if the primary model can't escape either.
+    fn escape_to_fallback(
+        &self,
+        symbol: Symbol,
+        err: ModelCfiError,
+    ) -> Result<ModelCfi, ModelCfiError> {
+        match self.primary.get_cfi(Symbol::Esc) {
+            Ok(ModelCfi::IndexCfi(cfi) | ModelCfi::EscapeCfi(cfi)) => {
+                debug!("Fallback Model: Escaping to the fallback model for symbol {symbol}");
+                Ok(ModelCfi::EscapeCfi(cfi))
+            }
+            Err(_) => Err(err),
+        }
+    }
+}
+
+impl<A: Model, B: Model> Model for FallbackModel<A, B> {
+    fn get_cfi(&self, symbol: Symbol) -> Result<ModelCfi, ModelCfiError> {
+        if self.escaped {
+            return self.fallback.get_cfi(symbol);
+        }
+        match self.primary.get_cfi(symbol) {
+            Ok(model_cfi) => Ok(model_cfi),
+            Err(err) if symbol.is_escape() => Err(err),
+            Err(err) => self.escape_to_fallback(symbol, err),
+        }
+    }
+
+    fn get_symbol(&self, cumulative_frequency: Frequency) -> Option<Symbol> {
+        if self.escaped {
+            self.fallback.get_symbol(cumulative_frequency)
+        } else {
+            self.primary.get_symbol(cumulative_frequency)
+        }
+    }
+
+    fn get_total(&self) -> Frequency {
+        if self.escaped {
+            self.fallback.get_total()
+        } else {
+            self.primary.get_total()
+        }
+    }
+
+    fn alphabet_size(&self) -> usize {
+        // Symbols of both models can be coded:
+        self.primary
+            .alphabet_size()
+            .max(self.fallback.alphabet_size())
+    }
+
+    fn max_escapes(&self) -> usize {
+        // The primary model's escapes, the escape to the fallback model, and the fallback's escapes:
+        self.primary.max_escapes() + 1 + self.fallback.max_escapes()
+    }
+
+    fn decode(&self, cumulative_frequency: Frequency) -> Option<(Symbol, ModelCfi)> {
+        if self.escaped {
+            self.fallback.decode(cumulative_frequency)
+        } else {
+            self.primary.decode(cumulative_frequency)
+        }
+    }
+
+    fn flush(&mut self) {
+        self.primary.flush();
+        self.fallback.flush();
+        self.escaped = false;
+    }
+
+    fn update(&mut self, symbol: Symbol, model_result: &ModelCfi) -> Result<()> {
+        match (self.escaped, model_result) {
+            // The compressor updates with the escaped symbol and the decompressor with the escape
+            // itself, so the primary model always learns the escape to stay in sync:
+            (false, ModelCfi::EscapeCfi(_)) => {
+                self.primary.update(Symbol::Esc, model_result)?;
+                self.escaped = true;
+            }
+            (false, ModelCfi::IndexCfi(_)) => self.primary.update(symbol, model_result)?,
+            (true, _) => {
+                self.fallback.update(symbol, model_result)?;
+                // Once the fallback model coded the symbol, the next one starts with the primary:
+                self.escaped = matches!(model_result, ModelCfi::EscapeCfi(_));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::adaptive::AdaptiveModel;
+    use crate::models::distributions::custom::CustomDistributionModel;
+    use crate::models::distributions::uniform::UniformDistributionModel;
+    use crate::parser::ByteParser;
+    use crate::sim::{DefaultSIM, SymbolIndexMapping};
+    use crate::{compress_reader, decompress_reader, CodecOptions};
+
+    /// A distribution over the lowercase letters, the EOF and the escape only
+    fn letters_model() -> CustomDistributionModel<DefaultSIM> {
+        let frequencies: Vec<Frequency> = (0..DefaultSIM.supported_symbols_count())
+            .map(|index| match DefaultSIM.get_symbol(index).unwrap() {
+                Symbol::Byte(byte) if byte.is_ascii_lowercase() => Frequency::new(10).unwrap(),
+                Symbol::Byte(_) => Frequency::zero(),
+                _ => Frequency::one(),
+            })
+            .collect();
+        CustomDistributionModel::new(DefaultSIM, &frequencies).unwrap()
+    }
+
+    fn round_trip<M: Model>(model: &mut M, input: &[u8]) -> Vec<u8> {
+        let options = CodecOptions::default();
+        let mut compressed = Vec::new();
+        compress_reader(input, &mut compressed, model, &mut ByteParser, &options).unwrap();
+        let mut decompressed = Vec::new();
+        decompress_reader(
+            &compressed[..],
+            &mut decompressed,
+            model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap();
+        decompressed
+    }
+
+    #[test]
+    fn test_escapes_to_fallback() {
+        let mut model =
+            FallbackModel::new(letters_model(), UniformDistributionModel::new(DefaultSIM));
+        // The primary model codes letters by itself:
+        assert!(matches!(
+            model.get_cfi(Symbol::Byte(b'a')),
+            Ok(ModelCfi::IndexCfi(_))
+        ));
+        assert_eq!(model.get_total(), model.primary().get_total());
+
+        // Other bytes escape to the fallback model, which codes them:
+        let escape = model.get_cfi(Symbol::Byte(b'!')).unwrap();
+        assert!(matches!(escape, ModelCfi::EscapeCfi(_)));
+        model.update(Symbol::Byte(b'!'), &escape).unwrap();
+        assert_eq!(model.get_total(), model.fallback().get_total());
+        let cfi = model.get_cfi(Symbol::Byte(b'!')).unwrap();
+        assert!(matches!(cfi, ModelCfi::IndexCfi(_)));
+        model.update(Symbol::Byte(b'!'), &cfi).unwrap();
+
+        // The next symbol starts with the primary model again:
+        assert_eq!(model.get_total(), model.primary().get_total());
+    }
+
+    #[test]
+    fn test_round_trip_through_fallback() {
+        let input = b"hello, world! only the primary model codes lowercase letters.";
+        let mut model =
+            FallbackModel::new(letters_model(), UniformDistributionModel::new(DefaultSIM));
+        assert_eq!(round_trip(&mut model, input), input);
+
+        // Both models are kept in sync when they adapt:
+        let mut model = FallbackModel::new(
+            AdaptiveModel::new(DefaultSIM),
+            AdaptiveModel::new(DefaultSIM),
+        );
+        assert_eq!(round_trip(&mut model, input), input);
+    }
+
+    #[test]
+    fn test_unsupported_without_escape() {
+        let mut frequencies = vec![Frequency::one(); DefaultSIM.supported_symbols_count()];
+        frequencies[DefaultSIM.get_index(&Symbol::Esc).unwrap()] = Frequency::zero();
+        frequencies[DefaultSIM.get_index(&Symbol::Byte(b'z')).unwrap()] = Frequency::zero();
+        let model = FallbackModel::new(
+            CustomDistributionModel::new(DefaultSIM, &frequencies).unwrap(),
+            UniformDistributionModel::new(DefaultSIM),
+        );
+        // Without an escape CFI, the primary model's error is reported:
+        assert_eq!(
+            model.get_cfi(Symbol::Byte(b'z')).err(),
+            Some(ModelCfiError::EmptyCfi {
+                symbol: Symbol::Byte(b'z')
+            })
+        );
+    }
+}
@@ -15,11 +15,18 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+pub mod adaptive;
+pub mod contexts;
 pub mod distributions;
+pub mod fallback;
+pub mod order1;
+pub mod ppm;
+pub mod snapshot;
 
 use crate::frequencies::{Cfi, Frequency};
 use crate::sim::Symbol;
-use anyhow::Result;
+use anyhow::{ensure, Result};
+use snapshot::{ModelSnapshot, SnapshotError};
 use thiserror::Error;
 
 /// Outputs of a probability model, wrapping CFIs to provide information for model-updating.
@@ -29,13 +36,24 @@ pub enum ModelCfi {
 
     /// Either a CFI of an escape symbol, OR a CFI given by the model to alert the decompression
     /// of something. If received during the compression of a non-escape symbol, the compressor
-    /// needs to re-compress the symbol until the model outputs either a IndexCFI or
-    /// UnsupportedIndex
+    /// needs to re-compress the symbol until the model outputs either an IndexCFI or an error
     EscapeCfi(Cfi),
 }
 
-/// Errors that might occur when getting a CFI from a model:
-#[derive(Debug, Error)]
+impl ModelCfi {
+    /// Wraps the CFI the model assigned to _symbol_, as an escape CFI if _symbol_ is an escape
+    pub fn for_symbol(symbol: Symbol, cfi: Cfi) -> Self {
+        if symbol.is_escape() {
+            ModelCfi::EscapeCfi(cfi)
+        } else {
+            ModelCfi::IndexCfi(cfi)
+        }
+    }
+}
+
+/// Errors that might occur when getting a CFI from a model. Both describe the queried symbol, not
+/// its index in the model's tables:
+#[derive(Debug, Error, PartialEq)]
 pub enum ModelCfiError {
     #[error("The model does not support the symbol \"{0}\", yet it was queried")]
     UnsupportedSymbol(Symbol),
@@ -43,6 +61,12 @@ pub enum ModelCfiError {
     EmptyCfi { symbol: Symbol },
 }
 
+/// A model escaped more times in a row than `Model::max_escapes` allows, so it would never code a
+/// symbol
+#[derive(Debug, Error, PartialEq)]
+#[error("The model escaped more than {0} times in a row without coding a symbol")]
+pub struct TooManyEscapesError(pub usize);
+
 /// A trait defining the behavior of a probability model
 pub trait Model {
     /// Computes a Cumulative-Frequency-Interval for a given symbol.
@@ -57,7 +81,7 @@ pub trait Model {
     /// A CFI assigned to that symbol in the model, or an escape CFI leading to that CFI.
     ///
     /// ## Possible Failures:
-    /// Each model should return `ModelCfiError::UnsupportedIndex` if _symbol_ is not a part of
+    /// Each model should return `ModelCfiError::UnsupportedSymbol` if _symbol_ is not a part of
     /// their allowed symbols.
     /// Additionally, each model should return a `ModelCfiError::EmptyCfi` if the CFI assigned to
     /// the given symbol is empty (i.e: its start value equals its end value)
@@ -73,6 +97,57 @@ pub trait Model {
     /// Returns the total cumulative frequencies in the table currently used by the model.
     fn get_total(&self) -> Frequency;
 
+    /// Returns the number of symbols the model supports (the number of indices in its SIM,
+    /// including EOF and escape). It doesn't change while the model is used.
+    fn alphabet_size(&self) -> usize;
+
+    /// Returns the largest number of escapes the model codes in a row before coding a symbol (one
+    /// for every lower order it falls back to). The compressor and decompressor fail with a
+    /// [TooManyEscapesError] past it, instead of escaping forever with a broken model.
+    ///
+    /// The default is zero, for models that never escape.
+    fn max_escapes(&self) -> usize {
+        0
+    }
+
+    /// Finds the symbol whose CFI contains _cumulative_frequency_ (like `get_symbol`), and returns
+    /// it along with its CFI (like `get_cfi`). If no adequate CFI is found, None is returned.
+    ///
+    /// The default implementation calls `get_symbol` and then `get_cfi`, which looks the
+    /// symbol up twice. Models whose search already finds the symbol's CFI should override it, so
+    /// decompression takes a single lookup per symbol.
+    fn decode(&self, cumulative_frequency: Frequency) -> Option<(Symbol, ModelCfi)> {
+        let symbol = self.get_symbol(cumulative_frequency)?;
+        Some((symbol, self.get_cfi(symbol).ok()?))
+    }
+
+    /// Learns _symbols_ as if they were compressed, without coding anything, so the model starts
+    /// with the statistics of a representative prefix. Priming the compressor's and decompressor's
+    /// models (from the same state) with the same symbols gives them the same distribution.
+    ///
+    /// The default implementation calls `get_cfi` + `update` for every symbol (following escapes
+    /// like the compressor), so it does nothing for models whose `update` does nothing.
+    ///
+    /// ## Possible Failures:
+    /// Propagates errors of `get_cfi` and `update`, and fails with a [TooManyEscapesError] if the
+    /// model escapes more than `max_escapes` times in a row.
+    fn prime(&mut self, symbols: &[Symbol]) -> Result<()> {
+        let max_escapes = self.max_escapes();
+        for &symbol in symbols {
+            let mut escapes = 0;
+            loop {
+                let cfi = self.get_cfi(symbol)?;
+                self.update(symbol, &cfi)?;
+                if let ModelCfi::IndexCfi(_) = cfi {
+                    break;
+                }
+                escapes += 1;
+                ensure!(escapes <= max_escapes, TooManyEscapesError(max_escapes));
+            }
+        }
+        Ok(())
+    }
+
     /// Resets the state of the model. Must be called between independent uses of the model (for
     /// example, decompression after compression) to avoid unexpected behaviour.
     fn flush(&mut self) {}
@@ -91,4 +166,181 @@ pub trait Model {
     fn update(&mut self, symbol: Symbol, model_result: &ModelCfi) -> Result<()> {
         Ok(())
     }
+
+    /// Exports the distribution the model currently holds, so a trained model can be saved and
+    /// imported later. Returns None if the model's state isn't a single distribution (such as
+    /// context-based models), which is the default.
+    fn export(&self) -> Option<ModelSnapshot> {
+        None
+    }
+
+    /// Replaces the model's distribution with the one in _snapshot_. Flushing the model returns it
+    /// to the imported distribution.
+    ///
+    /// ## Possible Failures:
+    /// Fails with a `SnapshotError` if the snapshot doesn't fit the model's symbols, or if the
+    /// model can't import snapshots at all (the default).
+    #[allow(unused_variables)]
+    fn import(&mut self, snapshot: &ModelSnapshot) -> Result<(), SnapshotError> {
+        Err(SnapshotError::Unsupported)
+    }
+}
+
+impl<M: Model + ?Sized> Model for Box<M> {
+    fn get_cfi(&self, symbol: Symbol) -> Result<ModelCfi, ModelCfiError> {
+        (**self).get_cfi(symbol)
+    }
+
+    fn get_symbol(&self, cumulative_frequency: Frequency) -> Option<Symbol> {
+        (**self).get_symbol(cumulative_frequency)
+    }
+
+    fn get_total(&self) -> Frequency {
+        (**self).get_total()
+    }
+
+    fn alphabet_size(&self) -> usize {
+        (**self).alphabet_size()
+    }
+
+    fn max_escapes(&self) -> usize {
+        (**self).max_escapes()
+    }
+
+    fn decode(&self, cumulative_frequency: Frequency) -> Option<(Symbol, ModelCfi)> {
+        (**self).decode(cumulative_frequency)
+    }
+
+    fn prime(&mut self, symbols: &[Symbol]) -> Result<()> {
+        (**self).prime(symbols)
+    }
+
+    fn flush(&mut self) {
+        (**self).flush()
+    }
+
+    fn update(&mut self, symbol: Symbol, model_result: &ModelCfi) -> Result<()> {
+        (**self).update(symbol, model_result)
+    }
+
+    fn export(&self) -> Option<ModelSnapshot> {
+        (**self).export()
+    }
+
+    fn import(&mut self, snapshot: &ModelSnapshot) -> Result<(), SnapshotError> {
+        (**self).import(snapshot)
+    }
+}
+
+impl<M: Model + ?Sized> Model for &mut M {
+    fn get_cfi(&self, symbol: Symbol) -> Result<ModelCfi, ModelCfiError> {
+        (**self).get_cfi(symbol)
+    }
+
+    fn get_symbol(&self, cumulative_frequency: Frequency) -> Option<Symbol> {
+        (**self).get_symbol(cumulative_frequency)
+    }
+
+    fn get_total(&self) -> Frequency {
+        (**self).get_total()
+    }
+
+    fn alphabet_size(&self) -> usize {
+        (**self).alphabet_size()
+    }
+
+    fn max_escapes(&self) -> usize {
+        (**self).max_escapes()
+    }
+
+    fn decode(&self, cumulative_frequency: Frequency) -> Option<(Symbol, ModelCfi)> {
+        (**self).decode(cumulative_frequency)
+    }
+
+    fn prime(&mut self, symbols: &[Symbol]) -> Result<()> {
+        (**self).prime(symbols)
+    }
+
+    fn flush(&mut self) {
+        (**self).flush()
+    }
+
+    fn update(&mut self, symbol: Symbol, model_result: &ModelCfi) -> Result<()> {
+        (**self).update(symbol, model_result)
+    }
+
+    fn export(&self) -> Option<ModelSnapshot> {
+        (**self).export()
+    }
+
+    fn import(&mut self, snapshot: &ModelSnapshot) -> Result<(), SnapshotError> {
+        (**self).import(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::adaptive::AdaptiveModel;
+    use crate::models::distributions::custom::CustomDistributionModel;
+    use crate::models::distributions::uniform::UniformDistributionModel;
+    use crate::models::fallback::FallbackModel;
+    use crate::models::order1::Order1Model;
+    use crate::models::ppm::PpmModel;
+    use crate::sim::{BitSIM, DefaultSIM, SymbolIndexMapping};
+
+    #[test]
+    fn test_unsupported_symbol_variant() {
+        let frequencies = vec![Frequency::one(); DefaultSIM.supported_symbols_count()];
+        let models: [Box<dyn Model>; 5] = [
+            Box::new(UniformDistributionModel::new(DefaultSIM)),
+            Box::new(CustomDistributionModel::new(DefaultSIM, &frequencies).unwrap()),
+            Box::new(AdaptiveModel::new(DefaultSIM)),
+            Box::new(Order1Model::new(DefaultSIM)),
+            Box::new(PpmModel::new(DefaultSIM, 2)),
+        ];
+        // Byte models don't support bit symbols:
+        for model in models {
+            assert_eq!(
+                model.get_cfi(Symbol::Bit(true)).err(),
+                Some(ModelCfiError::UnsupportedSymbol(Symbol::Bit(true)))
+            );
+        }
+    }
+
+    #[test]
+    fn test_alphabet_size() {
+        for sim in [&DefaultSIM as &'static dyn SymbolIndexMapping, &BitSIM] {
+            let frequencies = vec![Frequency::one(); sim.supported_symbols_count()];
+            let models: [Box<dyn Model>; 6] = [
+                Box::new(UniformDistributionModel::new(sim)),
+                Box::new(CustomDistributionModel::new(sim, &frequencies).unwrap()),
+                Box::new(AdaptiveModel::new(sim)),
+                Box::new(Order1Model::new(sim)),
+                Box::new(PpmModel::new(sim, 2)),
+                Box::new(FallbackModel::new(
+                    PpmModel::new(sim, 2),
+                    UniformDistributionModel::new(sim),
+                )),
+            ];
+            for model in models {
+                assert_eq!(model.alphabet_size(), sim.supported_symbols_count());
+            }
+        }
+        assert_eq!(DefaultSIM.supported_symbols_count(), 258);
+        assert_eq!(BitSIM.supported_symbols_count(), 4);
+    }
+
+    #[test]
+    fn test_empty_cfi_variant() {
+        let mut frequencies = vec![Frequency::one(); DefaultSIM.supported_symbols_count()];
+        frequencies[DefaultSIM.get_index(&Symbol::Byte(b'z')).unwrap()] = Frequency::zero();
+        let model = CustomDistributionModel::new(DefaultSIM, &frequencies).unwrap();
+        assert_eq!(
+            model.get_cfi(Symbol::Byte(b'z')).err(),
+            Some(ModelCfiError::EmptyCfi {
+                symbol: Symbol::Byte(b'z')
+            })
+        );
+    }
 }
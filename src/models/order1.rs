@@ -0,0 +1,213 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::frequencies::mutable_table::MutableFrequencyTable;
+use crate::frequencies::{Frequency, FrequencyTable};
+use crate::models::adaptive::{AdaptiveModel, DEFAULT_FREQUENCY_CAP};
+use crate::models::contexts::{ContextTables, DenseContextTables};
+use crate::models::{Model, ModelCfi, ModelCfiError};
+use crate::sim::{Symbol, SymbolIndexMapping};
+use anyhow::Result;
+use log::{error, warn};
+
+/// An adaptive model which keeps a separate frequency table for every previous byte, so each
+/// symbol is predicted by the byte that came before it.
+pub struct Order1Model<SIM: SymbolIndexMapping, C: ContextTables = DenseContextTables> {
+    /// The tables of the contexts, keyed by the previous byte
+    contexts: C,
+    /// The table used before any byte was coded
+    fallback: MutableFrequencyTable,
+    /// The table every context (and the fallback) starts with
+    initial: MutableFrequencyTable,
+    /// The last byte coded, which selects the current context
+    previous_byte: Option<u8>,
+    /// A mapping between symbols and indices in the tables
+    sim: SIM,
+}
+
+impl<SIM: SymbolIndexMapping> Order1Model<SIM> {
+    /// Creates an order-1 model with the default frequency cap, holding the tables of all 256
+    /// contexts.
+    ///
+    /// ## Parameters:
+    /// * sim - A mapping between symbols and indices.
+    pub fn new(sim: SIM) -> Self {
+        let frequency_cap = Frequency::new(DEFAULT_FREQUENCY_CAP)
+            .expect("Default frequency cap too large to become frequency");
+        let initial = AdaptiveModel::initial_table(&sim, frequency_cap);
+        Self::with_tables(sim, DenseContextTables::new(initial.clone()), initial)
+    }
+}
+
+impl<SIM: SymbolIndexMapping, C: ContextTables> Order1Model<SIM, C> {
+    /// Creates an order-1 model whose contexts are stored in _contexts_.
+    ///
+    /// ## Parameters:
+    /// * sim - A mapping between symbols and indices.
+    /// * contexts - The storage of the contexts' tables, every context must start with _initial_.
+    /// * initial - The table the model starts with before any byte was coded.
+    pub fn with_tables(sim: SIM, contexts: C, initial: MutableFrequencyTable) -> Self {
+        Self {
+            contexts,
+            fallback: initial.clone(),
+            initial,
+            previous_byte: None,
+            sim,
+        }
+    }
+
+    /// Returns the table of the current context
+    fn table(&self) -> &MutableFrequencyTable {
+        match self.previous_byte {
+            Some(byte) => self.contexts.get(byte),
+            None => &self.fallback,
+        }
+    }
+}
+
+impl<SIM: SymbolIndexMapping, C: ContextTables> Model for Order1Model<SIM, C> {
+    fn get_cfi(&self, symbol: Symbol) -> Result<ModelCfi, ModelCfiError> {
+        let index = self.sim.get_index(&symbol).ok_or_else(|| {
+            error!("Order-1 Model: Unsupported symbol \"{}\" given", symbol);
+            ModelCfiError::UnsupportedSymbol(symbol)
+        })?;
+
+        self.table()
+            .get_cfi(index)
+            .map(|cfi| {
+                if symbol.is_escape() {
+                    ModelCfi::EscapeCfi(cfi)
+                } else {
+                    ModelCfi::IndexCfi(cfi)
+                }
+            })
+            .ok_or_else(|| {
+                warn!(
+                    "Order-1 Model: Empty CFI assigned to queried symbol {}",
+                    symbol
+                );
+                ModelCfiError::EmptyCfi { symbol }
+            })
+    }
+
+    fn get_symbol(&self, cumulative_frequency: Frequency) -> Option<Symbol> {
+        self.table()
+            .get_index(cumulative_frequency)
+            .and_then(|index| self.sim.get_symbol(index))
+    }
+
+    fn get_total(&self) -> Frequency {
+        self.table().get_total()
+    }
+
+    fn alphabet_size(&self) -> usize {
+        self.sim.supported_symbols_count()
+    }
+
+    fn decode(&self, cumulative_frequency: Frequency) -> Option<(Symbol, ModelCfi)> {
+        let (index, cfi) = self.table().locate(cumulative_frequency)?;
+        let symbol = self.sim.get_symbol(index)?;
+        Some((symbol, ModelCfi::for_symbol(symbol, cfi)))
+    }
+
+    fn flush(&mut self) {
+        self.contexts.reset();
+        self.fallback = self.initial.clone();
+        self.previous_byte = None;
+    }
+
+    fn update(&mut self, symbol: Symbol, model_result: &ModelCfi) -> Result<()> {
+        // Only learn from symbols that were actually coded, in the context they were coded in:
+        if let (ModelCfi::IndexCfi(_), Some(index)) = (model_result, self.sim.get_index(&symbol)) {
+            let table = match self.previous_byte {
+                Some(byte) => self.contexts.get_mut(byte),
+                None => &mut self.fallback,
+            };
+            table.add_frequency(index, Frequency::one());
+        }
+
+        // The coded byte is the context of the next symbol:
+        if let Symbol::Byte(byte) = symbol {
+            self.previous_byte = Some(byte);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::contexts::LazyContextTables;
+    use crate::sim::DefaultSIM;
+
+    /// Codes _symbol_ with the model, like the compressor does
+    fn code(model: &mut impl Model, symbol: Symbol) {
+        let cfi = model.get_cfi(symbol).unwrap();
+        model.update(symbol, &cfi).unwrap();
+    }
+
+    #[test]
+    fn test_update_learns_in_active_context() {
+        let mut model = Order1Model::new(DefaultSIM);
+        let initial_total = model.get_total();
+
+        // 'b' is coded in the fallback context, then 'c' in the context of 'b':
+        code(&mut model, Symbol::Byte(b'b'));
+        code(&mut model, Symbol::Byte(b'c'));
+        assert_eq!(*model.fallback.get_total(), *initial_total + 1);
+        assert_eq!(*model.contexts.get(b'b').get_total(), *initial_total + 1);
+        // The context of 'c' is active now, and learned nothing yet:
+        assert_eq!(model.get_total(), initial_total);
+
+        // 'c' was only learned after 'b':
+        let c_index = DefaultSIM.get_index(&Symbol::Byte(b'c')).unwrap();
+        assert_eq!(*model.contexts.get(b'b').get_frequency(c_index).unwrap(), 2);
+        assert_eq!(*model.contexts.get(b'a').get_frequency(c_index).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_flush_resets_every_context() {
+        let mut model = Order1Model::new(DefaultSIM);
+        let initial_total = model.get_total();
+        for &byte in b"abcabc" {
+            code(&mut model, Symbol::Byte(byte));
+        }
+
+        model.flush();
+
+        assert_eq!(model.previous_byte, None);
+        assert_eq!(model.get_total(), initial_total);
+        for context in 0..=u8::MAX {
+            assert_eq!(model.contexts.get(context).get_total(), initial_total);
+        }
+    }
+
+    #[test]
+    fn test_lazy_contexts_match_dense() {
+        let mut dense = Order1Model::new(DefaultSIM);
+        let initial = dense.initial.clone();
+        let mut lazy =
+            Order1Model::with_tables(DefaultSIM, LazyContextTables::new(initial.clone()), initial);
+
+        for &byte in b"the lazy storage behaves like the dense one" {
+            let symbol = Symbol::Byte(byte);
+            assert_eq!(dense.get_total(), lazy.get_total());
+            code(&mut dense, symbol);
+            code(&mut lazy, symbol);
+        }
+    }
+}
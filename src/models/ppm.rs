@@ -0,0 +1,731 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::frequencies::mutable_table::MutableFrequencyTable;
+use crate::frequencies::static_table::StaticFrequencyTable;
+use crate::frequencies::{Frequency, FrequencyTable};
+use crate::models::adaptive::DEFAULT_FREQUENCY_CAP;
+use crate::models::{Model, ModelCfi, ModelCfiError};
+use crate::sim::{Symbol, SymbolIndexMapping};
+use anyhow::Result;
+use log::{debug, error, warn};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The default maximal context order of a PPM model
+pub const DEFAULT_PPM_ORDER: usize = 2;
+
+/// How a PPM model estimates the probability of escaping from a context, which is the
+/// probability that the context sees a new symbol
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EscapeMethod {
+    /// Method A: the escape always has a frequency of 1
+    PpmA,
+    /// Method C: the escape's frequency is the number of distinct symbols the context saw
+    #[default]
+    PpmC,
+    /// Method D: like method C, but a symbol's frequency grows by 2 for every occurrence after its
+    /// first, which halves the escape's probability compared to method C
+    PpmD,
+}
+
+impl EscapeMethod {
+    /// The id of the method in the header of compressed streams. Ids must never change, or
+    /// streams compressed by older versions would be decompressed with the wrong method
+    pub fn id(&self) -> u8 {
+        match self {
+            EscapeMethod::PpmA => 0,
+            EscapeMethod::PpmC => 1,
+            EscapeMethod::PpmD => 2,
+        }
+    }
+
+    /// Returns the method whose id is _id_, or None if no method has it
+    pub fn from_id(id: u8) -> Option<Self> {
+        [EscapeMethod::PpmA, EscapeMethod::PpmC, EscapeMethod::PpmD]
+            .into_iter()
+            .find(|method| method.id() == id)
+    }
+
+    /// The amount added to a symbol's frequency when a context sees it again
+    fn seen_symbol_increment(&self) -> Frequency {
+        match self {
+            EscapeMethod::PpmA | EscapeMethod::PpmC => Frequency::one(),
+            EscapeMethod::PpmD => Frequency::new(2).expect("2 is a valid frequency"),
+        }
+    }
+
+    /// The amount added to the escape's frequency when a context sees a new symbol, given the
+    /// escape's current frequency in the context
+    fn escape_increment(&self, escape_frequency: Frequency) -> Frequency {
+        match self {
+            EscapeMethod::PpmA if escape_frequency != Frequency::zero() => Frequency::zero(),
+            _ => Frequency::one(),
+        }
+    }
+}
+
+impl Display for EscapeMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EscapeMethod::PpmA => write!(f, "ppma"),
+            EscapeMethod::PpmC => write!(f, "ppmc"),
+            EscapeMethod::PpmD => write!(f, "ppmd"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Unknown escape method \"{0}\" (possible values: ppma, ppmc, ppmd)")]
+pub struct UnknownEscapeMethod(String);
+
+impl FromStr for EscapeMethod {
+    type Err = UnknownEscapeMethod;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ppma" => Ok(EscapeMethod::PpmA),
+            "ppmc" => Ok(EscapeMethod::PpmC),
+            "ppmd" => Ok(EscapeMethod::PpmD),
+            _ => Err(UnknownEscapeMethod(s.to_string())),
+        }
+    }
+}
+
+/// A Prediction by Partial Matching model.
+///
+/// The model keeps a frequency table for every context (sequence of up to `order` previous
+/// symbols) it encountered. A symbol is first coded in the longest context seen so far. If that
+/// context never saw the symbol, an escape is coded instead, and the next shorter context is
+/// tried, down to the order -1 context, in which every symbol (except the escape) is equally
+/// likely.
+///
+/// Escape frequencies follow method C by default (a context's escape frequency is the number of
+/// distinct symbols it saw), see [EscapeMethod] for the others.
+///
+/// When escaping to a lower order, the symbols the escaped contexts saw are excluded from the lower
+/// orders until the symbol is coded (the symbol can't be one of them, or it wouldn't have been
+/// escaped), which leaves more of the interval to the remaining symbols.
+pub struct PpmModel<SIM: SymbolIndexMapping> {
+    /// A mapping between symbols and indices in the tables
+    sim: SIM,
+    /// The maximal context order
+    order: usize,
+    /// The table of every context seen so far, keyed by the indices of the context's symbols
+    contexts: HashMap<Vec<usize>, MutableFrequencyTable>,
+    /// The order -1 table, assigns the same frequency to every symbol except the escape symbol
+    fallback: StaticFrequencyTable,
+    /// Indices of the last (up to `order`) coded symbols, the oldest symbol first. It's kept
+    /// contiguous, so contexts can be looked up by slices of it
+    history: VecDeque<usize>,
+    /// The order of the context currently used for coding, None for the order -1 context
+    active_order: Option<usize>,
+    /// Marks the indices excluded while coding the current symbol, since contexts it escaped from
+    /// saw them
+    excluded: Vec<bool>,
+    /// The table of the active context without the excluded indices, or None if it excludes none
+    excluded_table: Option<StaticFrequencyTable>,
+    /// Once a single frequency in a context passes this cap, the context's table is rescaled
+    frequency_cap: Frequency,
+    /// Tables of contexts are rescaled before an update would make their total exceed this cap
+    total_cap: Frequency,
+    /// How the frequencies of the escape and of the symbols grow in every context
+    escape_method: EscapeMethod,
+}
+
+impl<SIM: SymbolIndexMapping> PpmModel<SIM> {
+    /// Creates a PPM model.
+    ///
+    /// ## Parameters:
+    /// * sim - A mapping between symbols and indices. Must support the escape symbol.
+    /// * order - The maximal context order (the number of previous symbols a context holds).
+    pub fn new(sim: SIM, order: usize) -> Self {
+        let frequency_cap = Frequency::new(DEFAULT_FREQUENCY_CAP)
+            .expect("Default frequency cap too large to become frequency");
+        Self::with_frequency_cap(sim, order, frequency_cap)
+    }
+
+    /// Creates a PPM model whose context tables are rescaled once a single frequency in them
+    /// passes _frequency_cap_.
+    ///
+    /// ## Parameters:
+    /// * sim - A mapping between symbols and indices. Must support the escape symbol.
+    /// * order - The maximal context order (the number of previous symbols a context holds).
+    /// * frequency_cap - The maximal frequency a single symbol can reach before a rescale.
+    pub fn with_frequency_cap(sim: SIM, order: usize, frequency_cap: Frequency) -> Self {
+        let escape_index = sim.get_index(&Symbol::Esc);
+        let frequencies: Vec<Frequency> = (0..sim.supported_symbols_count())
+            .map(|index| {
+                if Some(index) == escape_index {
+                    Frequency::zero()
+                } else {
+                    Frequency::one()
+                }
+            })
+            .collect();
+        // A SIM can have a maximum of UNIQUE_SYMBOLS_AMOUNT which is far less than
+        // Frequency::max()
+        let fallback = StaticFrequencyTable::new(&frequencies)
+            .expect("SIM invariant broke, supported symbols count too large to become frequency");
+
+        Self {
+            sim,
+            order,
+            contexts: HashMap::new(),
+            fallback,
+            history: VecDeque::with_capacity(order + 1),
+            active_order: None,
+            excluded: vec![false; frequencies.len()],
+            excluded_table: None,
+            frequency_cap,
+            total_cap: Frequency::max(),
+            escape_method: EscapeMethod::default(),
+        }
+    }
+
+    /// Limits the total frequency of every context's table to _total_cap_, rescaling a table before
+    /// an update would make its total exceed it (e.g. to fit the frequency bits of
+    /// [CodecParams](crate::number_types::CodecParams)).
+    pub fn with_total_cap(mut self, total_cap: Frequency) -> Self {
+        self.total_cap = total_cap;
+        self.contexts
+            .values_mut()
+            .for_each(|table| table.limit_total(total_cap));
+        self
+    }
+
+    /// Estimates escape probabilities with _escape_method_ instead of method C. The compressor and
+    /// decompressor must use the same method, and it should be set before any symbol is coded.
+    pub fn with_escape_method(mut self, escape_method: EscapeMethod) -> Self {
+        self.escape_method = escape_method;
+        self
+    }
+
+    /// Returns the index of the escape symbol in the tables.
+    fn escape_index(&self) -> usize {
+        self.sim
+            .get_index(&Symbol::Esc)
+            .expect("PPM model's SIM must support the escape symbol")
+    }
+
+    /// Returns the symbols of the context of the given order, or None if not enough symbols were
+    /// coded yet.
+    fn context_key(&self, order: usize) -> Option<&[usize]> {
+        let (history, _) = self.history.as_slices();
+        history
+            .len()
+            .checked_sub(order)
+            .map(|start| &history[start..])
+    }
+
+    /// Returns the table of the context of the given order, or None if it wasn't seen yet.
+    fn context(&self, order: usize) -> Option<&MutableFrequencyTable> {
+        self.context_key(order)
+            .and_then(|key| self.contexts.get(key))
+    }
+
+    /// Returns the highest order below _limit_ whose context was already seen.
+    fn highest_seen_order_below(&self, limit: usize) -> Option<usize> {
+        (0..limit)
+            .rev()
+            .find(|&order| self.context(order).is_some())
+    }
+
+    /// Returns the table of the context currently used for coding, including excluded indices.
+    fn active_context_table(&self) -> &dyn FrequencyTable {
+        match self.active_order {
+            Some(order) => self
+                .context(order)
+                .expect("Active context is always a context that was seen"),
+            None => &self.fallback,
+        }
+    }
+
+    /// Returns the table coding the current symbol: the active context's table without the
+    /// excluded indices.
+    fn active_table(&self) -> &dyn FrequencyTable {
+        match &self.excluded_table {
+            Some(table) => table,
+            None => self.active_context_table(),
+        }
+    }
+
+    /// Excludes the symbols of the active context (which was just escaped from), then moves to the
+    /// next shorter context that was seen and removes the excluded indices from its table.
+    fn escape(&mut self) {
+        let escape_index = self.escape_index();
+        let seen: Vec<usize> = self
+            .active_table()
+            .iter_cfis()
+            .map(|(index, _)| index)
+            .filter(|&index| index != escape_index)
+            .collect();
+        for index in seen {
+            self.excluded[index] = true;
+        }
+        self.active_order = self
+            .active_order
+            .and_then(|order| self.highest_seen_order_below(order));
+
+        let table = self.active_context_table();
+        let mut excludes_any = false;
+        let frequencies: Vec<Frequency> = (0..table.len())
+            .map(|index| match table.get_cfi(index) {
+                Some(_) if self.excluded[index] => {
+                    excludes_any = true;
+                    Frequency::zero()
+                }
+                Some(cfi) => Frequency::new(cfi.width()).expect("A CFI's width is a frequency"),
+                None => Frequency::zero(),
+            })
+            .collect();
+        // The coded symbol is never excluded, so only a corrupted stream can exclude every index.
+        // Both sides keep the whole table then, so they stay in sync:
+        self.excluded_table = excludes_any
+            .then(|| StaticFrequencyTable::new(&frequencies).ok())
+            .flatten();
+    }
+
+    /// Forgets the exclusions, once the current symbol was coded
+    fn clear_exclusions(&mut self) {
+        self.excluded.fill(false);
+        self.excluded_table = None;
+    }
+
+    /// Updates the contexts after the symbol at _index_ was coded in the active context, then
+    /// moves to the longest context following the symbol.
+    fn learn(&mut self, index: usize) {
+        let escape_index = self.escape_index();
+
+        let (history, _) = self.history.as_slices();
+
+        // The context the symbol was coded in saw it once more:
+        if let Some(order) = self.active_order {
+            let key = &history[history.len() - order..];
+            if let Some(table) = self.contexts.get_mut(key) {
+                table.add_frequency(index, self.escape_method.seen_symbol_increment());
+            }
+        }
+
+        // Every higher-order context escaped, so the symbol is new to them (or they are new):
+        let first_new_order = self.active_order.map_or(0, |order| order + 1);
+        for order in first_new_order..=self.order.min(history.len()) {
+            let key = history[history.len() - order..].to_vec();
+            let table = self.contexts.entry(key).or_insert_with(|| {
+                let mut table = MutableFrequencyTable::empty(
+                    self.sim.supported_symbols_count(),
+                    Some(self.frequency_cap),
+                );
+                table.limit_total(self.total_cap);
+                table
+            });
+            table.add_frequency(index, Frequency::one());
+            let escape_frequency = table
+                .get_frequency(escape_index)
+                .unwrap_or(Frequency::zero());
+            let escape_increment = self.escape_method.escape_increment(escape_frequency);
+            if escape_increment != Frequency::zero() {
+                table.add_frequency(escape_index, escape_increment);
+            }
+        }
+
+        self.clear_exclusions();
+        // Advance the history:
+        self.history.push_back(index);
+        if self.history.len() > self.order {
+            self.history.pop_front();
+        }
+        self.history.make_contiguous();
+        self.active_order = self.highest_seen_order_below(self.order + 1);
+        debug!(
+            "PPM Model: Coding in context of order {:?}",
+            self.active_order
+        );
+    }
+}
+
+impl<SIM: SymbolIndexMapping> Model for PpmModel<SIM> {
+    fn get_cfi(&self, symbol: Symbol) -> Result<ModelCfi, ModelCfiError> {
+        let index = self.sim.get_index(&symbol).ok_or_else(|| {
+            error!("PPM Model: Unsupported symbol \"{}\" given", symbol);
+            ModelCfiError::UnsupportedSymbol(symbol)
+        })?;
+        let table = self.active_table();
+
+        match table.get_cfi(index) {
+            Some(cfi) if symbol.is_escape() => Ok(ModelCfi::EscapeCfi(cfi)),
+            Some(cfi) => Ok(ModelCfi::IndexCfi(cfi)),
+            // A symbol the context hasn't seen is reached by escaping to a lower order:
+            None if self.active_order.is_some() && !symbol.is_escape() => table
+                .get_cfi(self.escape_index())
+                .map(ModelCfi::EscapeCfi)
+                .ok_or(ModelCfiError::EmptyCfi {
+                    symbol: Symbol::Esc,
+                }),
+            None => {
+                warn!("PPM Model: Empty CFI assigned to queried symbol {}", symbol);
+                Err(ModelCfiError::EmptyCfi { symbol })
+            }
+        }
+    }
+
+    fn get_symbol(&self, cumulative_frequency: Frequency) -> Option<Symbol> {
+        self.active_table()
+            .get_index(cumulative_frequency)
+            .and_then(|index| self.sim.get_symbol(index))
+    }
+
+    fn get_total(&self) -> Frequency {
+        self.active_table().get_total()
+    }
+
+    fn alphabet_size(&self) -> usize {
+        self.sim.supported_symbols_count()
+    }
+
+    fn max_escapes(&self) -> usize {
+        // From the highest order down to order 0, after which order -1 codes every symbol:
+        self.order + 1
+    }
+
+    fn decode(&self, cumulative_frequency: Frequency) -> Option<(Symbol, ModelCfi)> {
+        let (index, cfi) = self.active_table().locate(cumulative_frequency)?;
+        let symbol = self.sim.get_symbol(index)?;
+        Some((symbol, ModelCfi::for_symbol(symbol, cfi)))
+    }
+
+    fn flush(&mut self) {
+        self.contexts.clear();
+        self.history.clear();
+        self.active_order = None;
+        self.clear_exclusions();
+    }
+
+    fn update(&mut self, symbol: Symbol, model_result: &ModelCfi) -> Result<()> {
+        match model_result {
+            // Escaping moves to the next shorter context that was seen:
+            ModelCfi::EscapeCfi(_) => self.escape(),
+            ModelCfi::IndexCfi(_) => {
+                if let Some(index) = self.sim.get_index(&symbol) {
+                    self.learn(index);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressor::Compressor;
+    use crate::decompressor::Decompressor;
+    use crate::parser::ByteParser;
+    use crate::sim::DefaultSIM;
+    use crate::{compress_reader, CodecOptions};
+
+    /// Codes _symbol_ like the compressor does, returns the number of escapes it took
+    fn code(model: &mut PpmModel<DefaultSIM>, symbol: Symbol) -> usize {
+        let mut escapes = 0;
+        loop {
+            let cfi = model.get_cfi(symbol).unwrap();
+            model.update(symbol, &cfi).unwrap();
+            match cfi {
+                ModelCfi::IndexCfi(_) => return escapes,
+                ModelCfi::EscapeCfi(_) => escapes += 1,
+            }
+        }
+    }
+
+    #[test]
+    fn test_new_symbols_escape_to_lower_orders() {
+        let mut model = PpmModel::new(DefaultSIM, 2);
+
+        // Nothing was seen yet, the order -1 context is used right away:
+        assert_eq!(code(&mut model, Symbol::Byte(b'a')), 0);
+        // Order 0 context exists now, and doesn't know 'b':
+        assert_eq!(code(&mut model, Symbol::Byte(b'b')), 1);
+        // Order 1 context "b" is new, and order 0 context doesn't know 'c':
+        assert_eq!(code(&mut model, Symbol::Byte(b'c')), 1);
+        // Contexts "bc" and "c" are new, so coding starts at order 0, which knows 'a':
+        assert_eq!(code(&mut model, Symbol::Byte(b'a')), 0);
+        // Order 1 context "a" only saw 'b':
+        assert_eq!(code(&mut model, Symbol::Byte(b'a')), 1);
+    }
+
+    #[test]
+    fn test_repeated_context_predicts_without_escape() {
+        let mut model = PpmModel::new(DefaultSIM, 2);
+        for &byte in b"abcabc" {
+            code(&mut model, Symbol::Byte(byte));
+        }
+
+        // The order 2 context "bc" saw 'a' before:
+        assert_eq!(model.active_order, Some(2));
+        assert_eq!(code(&mut model, Symbol::Byte(b'a')), 0);
+    }
+
+    #[test]
+    fn test_escape_frequency_counts_distinct_symbols() {
+        let mut model = PpmModel::new(DefaultSIM, 0);
+        for &byte in b"aab" {
+            code(&mut model, Symbol::Byte(byte));
+        }
+
+        let escape = match model.get_cfi(Symbol::Esc).unwrap() {
+            ModelCfi::EscapeCfi(cfi) => cfi.width(),
+            ModelCfi::IndexCfi(_) => panic!("Escape symbol got an index CFI"),
+        };
+        assert_eq!(escape, 2);
+        // The first 'a' was coded in order -1, so order 0 saw it only once afterwards:
+        assert_eq!(*model.get_total(), 2 + 1 + 2);
+    }
+
+    #[test]
+    fn test_priming_improves_compression() {
+        let prefix: Vec<Symbol> = b"the quick brown fox jumps over the lazy dog. "
+            .repeat(4)
+            .into_iter()
+            .map(Symbol::Byte)
+            .collect();
+        let input = b"the lazy dog jumps over the quick brown fox.";
+        let compress = |model: &mut PpmModel<DefaultSIM>| {
+            let mut compressor = Compressor::new(model);
+            let mut compressed = Vec::new();
+            for &byte in input {
+                compressed.extend(compressor.load_symbol(Symbol::Byte(byte)).unwrap());
+            }
+            compressed.extend(compressor.finalize().unwrap().0);
+            compressed
+        };
+
+        let mut primed = PpmModel::new(DefaultSIM, 2);
+        primed.prime(&prefix).unwrap();
+        let primed_compressed = compress(&mut primed);
+        let unprimed_compressed = compress(&mut PpmModel::new(DefaultSIM, 2));
+        assert!(primed_compressed.len() < unprimed_compressed.len());
+
+        // Priming a fresh model the same way reconstructs the compressor's starting distribution:
+        let mut model = PpmModel::new(DefaultSIM, 2);
+        model.prime(&prefix).unwrap();
+        let mut decompressor = Decompressor::from_bytes(&mut model, primed_compressed);
+        let decompressed: Vec<u8> = decompressor.bytes().map(Result::unwrap).collect();
+        assert_eq!(decompressed, input);
+    }
+
+    #[test]
+    fn test_flush_forgets_contexts() {
+        let mut model = PpmModel::new(DefaultSIM, 2);
+        let total = model.get_total();
+        for &byte in b"abc" {
+            code(&mut model, Symbol::Byte(byte));
+        }
+        model.flush();
+
+        assert_eq!(model.active_order, None);
+        assert_eq!(model.get_total(), total);
+    }
+
+    #[test]
+    fn test_total_cap_limits_contexts() {
+        let total_cap = Frequency::new(64).unwrap();
+        let mut model = PpmModel::new(DefaultSIM, 1).with_total_cap(total_cap);
+
+        for &byte in b"abcd".repeat(100).iter() {
+            code(&mut model, Symbol::Byte(byte));
+            assert!(model
+                .contexts
+                .values()
+                .all(|table| table.get_total() <= total_cap));
+        }
+    }
+
+    #[test]
+    fn test_frequency_cap_halves_dominant_context() {
+        let cap = Frequency::new(16).unwrap();
+        let new_model = || PpmModel::with_frequency_cap(DefaultSIM, 0, cap);
+        let mut model = new_model();
+        let index = |byte| DefaultSIM.get_index(&Symbol::Byte(byte)).unwrap();
+        let frequency = |model: &PpmModel<DefaultSIM>, byte| {
+            model.contexts[&Vec::new()]
+                .get_frequency(index(byte))
+                .unwrap()
+        };
+
+        // A rare symbol in the order 0 context, then a dominant one:
+        let mut input = b"ba".to_vec();
+        input.extend(vec![b'a'; 100]);
+        input.push(b'b');
+        let mut halvings = 0;
+        for &byte in &input {
+            let before = model
+                .contexts
+                .get(&Vec::new())
+                .map(|_| frequency(&model, b'a'));
+            code(&mut model, Symbol::Byte(byte));
+            let after = frequency(&model, b'a');
+            assert!(after <= cap);
+            if before.is_some_and(|before| after < before) {
+                // The whole context was halved, rounding up:
+                assert_eq!(*after, (*before.unwrap() + 1).div_ceil(2));
+                halvings += 1;
+            }
+        }
+        assert!(halvings > 0);
+        assert_ne!(frequency(&model, b'b'), Frequency::zero());
+
+        let mut compressed = Vec::new();
+        let mut compressor_model = new_model();
+        compress_reader(
+            &input[..],
+            &mut compressed,
+            &mut compressor_model,
+            &mut ByteParser,
+            &CodecOptions::default(),
+        )
+        .unwrap();
+        let mut decompressor_model = new_model();
+        let mut decompressor = Decompressor::from_bytes(&mut decompressor_model, compressed);
+        let decompressed: Vec<u8> = decompressor.bytes().map(Result::unwrap).collect();
+        assert_eq!(decompressed, input);
+    }
+
+    #[test]
+    fn test_escape_methods_keep_valid_distributions() {
+        // Order 0 sees 'a' twice (the first one was coded in order -1) and 'b' once:
+        for (method, expected) in [
+            (EscapeMethod::PpmA, [2, 1, 1]),
+            (EscapeMethod::PpmC, [2, 1, 2]),
+            (EscapeMethod::PpmD, [3, 1, 2]),
+        ] {
+            let mut model = PpmModel::new(DefaultSIM, 0).with_escape_method(method);
+            for &byte in b"aab" {
+                code(&mut model, Symbol::Byte(byte));
+            }
+            let table = model.context(0).unwrap();
+            let frequencies = [b'a', b'b'].map(|byte| {
+                *table
+                    .get_frequency(DefaultSIM.get_index(&Symbol::Byte(byte)).unwrap())
+                    .unwrap()
+            });
+            let escape = *table.get_frequency(model.escape_index()).unwrap();
+            assert_eq!([frequencies[0], frequencies[1], escape], expected);
+
+            // The CFIs of every context tile the whole range of the context's total:
+            for table in model.contexts.values() {
+                let mut end = 0;
+                for (_, cfi) in table.iter_cfis() {
+                    assert_eq!(*cfi.start, end);
+                    end = *cfi.end;
+                }
+                assert_eq!(end, *table.get_total());
+            }
+        }
+    }
+
+    #[test]
+    fn test_ppmd_beats_ppma_on_text() {
+        let text = b"It is a truth universally acknowledged, that a single man in possession of \
+            a good fortune, must be in want of a wife. However little known the feelings or views \
+            of such a man may be on his first entering a neighbourhood, this truth is so well \
+            fixed in the minds of the surrounding families, that he is considered the rightful \
+            property of some one or other of their daughters.";
+        let compressed_len = |method| {
+            let mut model = PpmModel::new(DefaultSIM, DEFAULT_PPM_ORDER).with_escape_method(method);
+            let mut compressed = Vec::new();
+            let options = CodecOptions::default();
+            compress_reader(
+                &text[..],
+                &mut compressed,
+                &mut model,
+                &mut ByteParser,
+                &options,
+            )
+            .unwrap();
+            compressed.len()
+        };
+        assert!(compressed_len(EscapeMethod::PpmD) < compressed_len(EscapeMethod::PpmA));
+    }
+
+    #[test]
+    fn test_escape_method_names_and_ids() {
+        for method in [EscapeMethod::PpmA, EscapeMethod::PpmC, EscapeMethod::PpmD] {
+            assert_eq!(method.to_string().parse::<EscapeMethod>().unwrap(), method);
+            assert_eq!(EscapeMethod::from_id(method.id()), Some(method));
+        }
+        assert!("ppmz".parse::<EscapeMethod>().is_err());
+        assert_eq!(EscapeMethod::from_id(3), None);
+    }
+
+    #[test]
+    fn test_escape_excludes_seen_symbols() {
+        let mut model = PpmModel::new(DefaultSIM, 1);
+        for &byte in b"abac" {
+            code(&mut model, Symbol::Byte(byte));
+        }
+        // Context "c" is new, so coding starts in order 0, which saw 'a', 'b' and 'c':
+        assert_eq!(model.active_order, Some(0));
+        let escape = model.get_cfi(Symbol::Byte(b'd')).unwrap();
+        model.update(Symbol::Byte(b'd'), &escape).unwrap();
+
+        // The order -1 context doesn't give any of the seen symbols a part of the interval:
+        assert_eq!(model.active_order, None);
+        for byte in [b'a', b'b', b'c'] {
+            assert!(model.excluded[DefaultSIM.get_index(&Symbol::Byte(byte)).unwrap()]);
+            assert!(model.get_cfi(Symbol::Byte(byte)).is_err());
+        }
+        assert_eq!(*model.get_total(), *model.fallback.get_total() - 3);
+
+        // Coding the symbol clears the exclusions:
+        let cfi = model.get_cfi(Symbol::Byte(b'd')).unwrap();
+        model.update(Symbol::Byte(b'd'), &cfi).unwrap();
+        assert!(model.excluded.iter().all(|&excluded| !excluded));
+        assert!(model.excluded_table.is_none());
+    }
+
+    #[test]
+    fn test_exclusions_match_on_both_sides() {
+        let input = b"abracadabra, abracadabra! cadabra abra";
+        let mut encoder = PpmModel::new(DefaultSIM, 2);
+        let mut decoder = PpmModel::new(DefaultSIM, 2);
+
+        for &byte in input {
+            let symbol = Symbol::Byte(byte);
+            loop {
+                // The decoder finds the symbol from a frequency inside the encoder's CFI:
+                let cfi = encoder.get_cfi(symbol).unwrap();
+                let (ModelCfi::IndexCfi(inner) | ModelCfi::EscapeCfi(inner)) = &cfi;
+                assert_eq!(decoder.get_total(), inner.total);
+                let (decoded, decoded_cfi) = decoder.decode(inner.start).unwrap();
+                let (ModelCfi::IndexCfi(decoded_inner) | ModelCfi::EscapeCfi(decoded_inner)) =
+                    &decoded_cfi;
+                assert_eq!(decoded_inner, inner);
+
+                encoder.update(symbol, &cfi).unwrap();
+                decoder.update(decoded, &decoded_cfi).unwrap();
+                assert_eq!(encoder.excluded, decoder.excluded);
+                assert_eq!(encoder.active_order, decoder.active_order);
+                if let ModelCfi::IndexCfi(_) = cfi {
+                    assert_eq!(decoded, symbol);
+                    break;
+                }
+            }
+        }
+    }
+}
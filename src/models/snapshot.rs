@@ -0,0 +1,157 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::frequencies::Frequency;
+use crate::sim::{Symbol, SymbolIndexMapping};
+use thiserror::Error;
+
+/// Errors caused by importing a snapshot into a model which can't hold it
+#[derive(Debug, Error, PartialEq)]
+pub enum SnapshotError {
+    #[error("The model can't import snapshots")]
+    Unsupported,
+    #[error("The snapshot is of a {found}-model, but the model is a {expected}-model")]
+    ModeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("The snapshot has {found} frequencies, but the model has {expected} symbols")]
+    WrongFrequencyCount { expected: usize, found: usize },
+    #[error("The frequency of index {index} in the snapshot ({value}) is too large")]
+    FrequencyTooLarge { index: usize, value: u64 },
+    #[error("The snapshot's distribution is invalid: {0}")]
+    InvalidDistribution(String),
+}
+
+/// The distribution a model holds at some point, which can be saved and imported into a model
+/// later (see `Model::export` and `Model::import`).<br>
+/// With the `serde` feature, it is serialized in the format of the CLI's custom model files:
+/// `{"name": "...", "bit_model": false, "frequencies": [...]}`, so an exported model can be given
+/// to `--custom-model`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct ModelSnapshot {
+    /// The model's name, if it has one
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub name: Option<String>,
+    /// If the model codes bits rather than bytes
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bit_model: bool,
+    /// The frequency of every symbol, ordered by the symbols' indices in the SIM of the model's
+    /// mode
+    pub frequencies: Vec<u64>,
+}
+
+impl ModelSnapshot {
+    /// Forms the snapshot of a model whose table holds _frequencies_, mapped by _sim_
+    pub fn new(sim: &impl SymbolIndexMapping, frequencies: &[Frequency]) -> Self {
+        Self {
+            name: None,
+            bit_model: is_bit_sim(sim),
+            frequencies: frequencies.iter().map(|frequency| **frequency).collect(),
+        }
+    }
+
+    /// Returns the snapshot's frequencies, after making sure they fit a model whose symbols are
+    /// mapped by _sim_
+    pub fn frequencies_for(
+        &self,
+        sim: &impl SymbolIndexMapping,
+    ) -> Result<Vec<Frequency>, SnapshotError> {
+        let mode_name = |bit_model| if bit_model { "bit" } else { "byte" };
+        if self.bit_model != is_bit_sim(sim) {
+            return Err(SnapshotError::ModeMismatch {
+                expected: mode_name(is_bit_sim(sim)),
+                found: mode_name(self.bit_model),
+            });
+        }
+        let expected = sim.supported_symbols_count();
+        if self.frequencies.len() != expected {
+            return Err(SnapshotError::WrongFrequencyCount {
+                expected,
+                found: self.frequencies.len(),
+            });
+        }
+        self.frequencies
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| {
+                Frequency::new(value).map_err(|_| SnapshotError::FrequencyTooLarge { index, value })
+            })
+            .collect()
+    }
+}
+
+/// Checks if _sim_ maps bit symbols (rather than bytes)
+fn is_bit_sim(sim: &impl SymbolIndexMapping) -> bool {
+    sim.get_index(&Symbol::Bit(false)).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::adaptive::AdaptiveModel;
+    use crate::models::Model;
+    use crate::sim::{BitSIM, DefaultSIM};
+
+    /// Trains an adaptive model on some text
+    fn trained_model() -> AdaptiveModel<DefaultSIM> {
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        for &byte in b"the quick brown fox jumps over the lazy dog, then the dog sleeps" {
+            let symbol = Symbol::Byte(byte);
+            let cfi = model.get_cfi(symbol).unwrap();
+            model.update(symbol, &cfi).unwrap();
+        }
+        model
+    }
+
+    #[test]
+    fn test_imported_distribution_survives_flush() {
+        let snapshot = trained_model().export().unwrap();
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        model.import(&snapshot).unwrap();
+        model.flush();
+        assert_eq!(model.export(), Some(snapshot));
+    }
+
+    #[test]
+    fn test_mismatching_snapshots_rejected() {
+        let snapshot = trained_model().export().unwrap();
+        assert_eq!(
+            AdaptiveModel::new(BitSIM).import(&snapshot),
+            Err(SnapshotError::ModeMismatch {
+                expected: "bit",
+                found: "byte"
+            })
+        );
+        let short = ModelSnapshot {
+            frequencies: vec![1; 3],
+            ..snapshot
+        };
+        assert_eq!(
+            AdaptiveModel::new(DefaultSIM).import(&short),
+            Err(SnapshotError::WrongFrequencyCount {
+                expected: DefaultSIM.supported_symbols_count(),
+                found: 3
+            })
+        );
+    }
+}
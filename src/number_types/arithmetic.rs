@@ -0,0 +1,116 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Checked helpers for the frequency and interval calculations in `CalculationsType`. The sizes in
+// `sizes.rs` keep the codec's own values far from overflowing, but tables and intervals are
+// built from values they can't control, so these report an overflow instead of wrapping.
+
+use super::sizes::CalculationsType;
+use thiserror::Error;
+
+/// Errors of calculations whose result doesn't fit in `CalculationsType`
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ArithmeticError {
+    #[error("Adding the frequency {frequency} to the cumulative frequency {cumulative} overflows")]
+    CumulativeOverflow {
+        cumulative: CalculationsType,
+        frequency: CalculationsType,
+    },
+    #[error("Scaling by a total frequency of 0")]
+    ZeroTotal,
+    #[error("Scaling the width {width} by {frequency}/{total} overflows")]
+    ScaleOverflow {
+        width: CalculationsType,
+        frequency: CalculationsType,
+        total: CalculationsType,
+    },
+}
+
+/// Adds _frequency_ to the cumulative frequency _cumulative_, failing instead of overflowing
+pub fn checked_cum_add(
+    cumulative: CalculationsType,
+    frequency: CalculationsType,
+) -> Result<CalculationsType, ArithmeticError> {
+    cumulative
+        .checked_add(frequency)
+        .ok_or(ArithmeticError::CumulativeOverflow {
+            cumulative,
+            frequency,
+        })
+}
+
+/// Computes `width * frequency / total`, rounded down.<br>
+/// The product is computed in a wider type, so it never overflows itself. The result fits back in
+/// `CalculationsType` whenever `frequency <= total` (it is at most _width_ then), otherwise it may
+/// not, which is an error.
+pub fn checked_scale(
+    width: CalculationsType,
+    frequency: CalculationsType,
+    total: CalculationsType,
+) -> Result<CalculationsType, ArithmeticError> {
+    if total == 0 {
+        return Err(ArithmeticError::ZeroTotal);
+    }
+    let scaled = width as u128 * frequency as u128 / total as u128;
+    CalculationsType::try_from(scaled).map_err(|_| ArithmeticError::ScaleOverflow {
+        width,
+        frequency,
+        total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX: CalculationsType = CalculationsType::MAX;
+
+    #[test]
+    fn test_cum_add_near_max() {
+        assert_eq!(checked_cum_add(MAX - 5, 5), Ok(MAX));
+        assert_eq!(
+            checked_cum_add(MAX - 5, 6),
+            Err(ArithmeticError::CumulativeOverflow {
+                cumulative: MAX - 5,
+                frequency: 6
+            })
+        );
+        assert!(checked_cum_add(MAX, MAX).is_err());
+    }
+
+    #[test]
+    fn test_scale_uses_wide_intermediates() {
+        // The product overflows CalculationsType, but the result doesn't:
+        assert_eq!(checked_scale(MAX, MAX - 1, MAX), Ok(MAX - 1));
+        assert_eq!(checked_scale(MAX, 3, 4), Ok(MAX / 4 * 3 + 2));
+        assert_eq!(checked_scale(10, 1, 3), Ok(3));
+    }
+
+    #[test]
+    fn test_scale_past_max_fails() {
+        assert_eq!(
+            checked_scale(MAX, 2, 1),
+            Err(ArithmeticError::ScaleOverflow {
+                width: MAX,
+                frequency: 2,
+                total: 1
+            })
+        );
+        assert!(checked_scale(MAX, MAX, MAX - 1).is_err());
+        assert_eq!(checked_scale(5, 1, 0), Err(ArithmeticError::ZeroTotal));
+    }
+}
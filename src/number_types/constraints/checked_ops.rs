@@ -0,0 +1,46 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{BitsConstraintError, CalculationsType, ConstrainedNum};
+use std::ops::{Add, Mul, Sub};
+
+// Unlike the bit operations, arithmetic can't be masked back into BITS without changing its
+// result, so the operators return the checked result instead of wrapping:
+
+impl<const BITS: u32, T: Into<CalculationsType>> Add<T> for ConstrainedNum<BITS> {
+    type Output = Result<Self, BitsConstraintError<BITS>>;
+
+    fn add(self, rhs: T) -> Self::Output {
+        self.checked_add(rhs)
+    }
+}
+
+impl<const BITS: u32, T: Into<CalculationsType>> Sub<T> for ConstrainedNum<BITS> {
+    type Output = Result<Self, BitsConstraintError<BITS>>;
+
+    fn sub(self, rhs: T) -> Self::Output {
+        self.checked_sub(rhs)
+    }
+}
+
+impl<const BITS: u32, T: Into<CalculationsType>> Mul<T> for ConstrainedNum<BITS> {
+    type Output = Result<Self, BitsConstraintError<BITS>>;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        self.checked_mul(rhs)
+    }
+}
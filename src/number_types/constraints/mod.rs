@@ -16,6 +16,7 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 mod bit_ops;
+mod checked_ops;
 #[cfg(test)]
 mod unit_tests;
 
@@ -31,6 +32,11 @@ const fn get_used_bits_num(n: CalculationsType) -> u32 {
 
 /// A numerical struct restricting the value it holds to have a limited amount of bits
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "CalculationsType", try_from = "CalculationsType")
+)]
 pub struct ConstrainedNum<const BITS: u32>(CalculationsType);
 
 impl<const BITS: u32> ConstrainedNum<BITS> {
@@ -58,7 +64,8 @@ impl<const BITS: u32> ConstrainedNum<BITS> {
     }
 
     /// Creates a new ConstrainedNum without checking neither the **BITS** nor the number itself.
-    /// <br>
+    ///
+    /// # Safety
     /// It is up to the caller of the function to ensure that:
     /// 1) 0 < **BITS** <= `CalculationsType::BITS`
     /// 2) `value` uses at most **BITS** bits.
@@ -87,6 +94,54 @@ impl<const BITS: u32> ConstrainedNum<BITS> {
             Self((1 << BITS) - 1)
         }
     }
+
+    /// Adds _rhs_ to the number.<br>
+    /// Fails if the sum overflows `CalculationsType`, or uses more bits than BITS.
+    pub fn checked_add<T: Into<CalculationsType>>(
+        self,
+        rhs: T,
+    ) -> Result<Self, BitsConstraintError<BITS>> {
+        self.0
+            .checked_add(rhs.into())
+            .ok_or(BitsConstraintError::OutOfRange)
+            .and_then(Self::new)
+    }
+
+    /// Subtracts _rhs_ from the number.<br>
+    /// Fails if _rhs_ is larger than the number, since the result would be negative.
+    pub fn checked_sub<T: Into<CalculationsType>>(
+        self,
+        rhs: T,
+    ) -> Result<Self, BitsConstraintError<BITS>> {
+        self.0
+            .checked_sub(rhs.into())
+            .ok_or(BitsConstraintError::OutOfRange)
+            .and_then(Self::new)
+    }
+
+    /// Adds _rhs_ to the number, clamping the sum to `max()` if it uses more bits than BITS (or
+    /// overflows `CalculationsType`).
+    pub fn saturating_add<T: Into<CalculationsType>>(self, rhs: T) -> Self {
+        self.checked_add(rhs).unwrap_or(Self::max())
+    }
+
+    /// Subtracts _rhs_ from the number, clamping the difference to `zero()` if _rhs_ is larger
+    /// than the number.
+    pub fn saturating_sub<T: Into<CalculationsType>>(self, rhs: T) -> Self {
+        self.checked_sub(rhs).unwrap_or(Self::zero())
+    }
+
+    /// Multiplies the number by _rhs_.<br>
+    /// Fails if the product overflows `CalculationsType`, or uses more bits than BITS.
+    pub fn checked_mul<T: Into<CalculationsType>>(
+        self,
+        rhs: T,
+    ) -> Result<Self, BitsConstraintError<BITS>> {
+        self.0
+            .checked_mul(rhs.into())
+            .ok_or(BitsConstraintError::OutOfRange)
+            .and_then(Self::new)
+    }
 }
 
 // Implement display that shows all bits:
@@ -111,6 +166,14 @@ impl<const BITS: u32> From<ConstrainedNum<BITS>> for CalculationsType {
     }
 }
 
+impl<const BITS: u32> TryFrom<CalculationsType> for ConstrainedNum<BITS> {
+    type Error = BitsConstraintError<BITS>;
+
+    fn try_from(value: CalculationsType) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
 impl<const BITS: u32> From<bool> for ConstrainedNum<BITS> {
     fn from(value: bool) -> Self {
         if value {
@@ -141,4 +204,8 @@ pub enum BitsConstraintError<const BITS: u32> {
         value: CalculationsType,
         used_bits: u32,
     },
+
+    /// The result of an arithmetic operation is negative or overflows CalculationsType
+    #[error("The result of the calculation is out of CalculationsType's range")]
+    OutOfRange,
 }
@@ -174,3 +174,114 @@ fn shl_with_large_value_masks_correctly() {
     let result = a << 4u8;
     assert_eq!(result.0, 0b000); // Masked to 3 bits
 }
+
+#[test]
+fn checked_add_at_bit_limit() {
+    let a = C4::new(0b1010).unwrap();
+    assert_eq!(a.checked_add(5u8).unwrap().0, 0b1111);
+    assert!(matches!(
+        a.checked_add(6u8),
+        Err(BitsConstraintError::ValueUsesTooManyBits {
+            value: 16,
+            used_bits: 5
+        })
+    ));
+    assert_eq!(C4::max().checked_add(C4::zero()).unwrap(), C4::max());
+}
+
+#[test]
+fn checked_add_overflowing_calculation_type() {
+    type Full = ConstrainedNum<{ CalculationsType::BITS }>;
+    assert_eq!(Full::max().checked_add(0u8).unwrap(), Full::max());
+    assert!(matches!(
+        Full::max().checked_add(1u8),
+        Err(BitsConstraintError::OutOfRange)
+    ));
+    assert!(matches!(
+        Full::max().checked_mul(2u8),
+        Err(BitsConstraintError::OutOfRange)
+    ));
+}
+
+#[test]
+fn checked_sub_below_zero() {
+    let a = C3::new(0b101).unwrap();
+    assert_eq!(a.checked_sub(5u8).unwrap(), C3::zero());
+    assert!(matches!(
+        a.checked_sub(6u8),
+        Err(BitsConstraintError::OutOfRange)
+    ));
+    assert!(matches!(
+        C3::zero().checked_sub(C3::one()),
+        Err(BitsConstraintError::OutOfRange)
+    ));
+}
+
+#[test]
+fn saturating_add_clamps_to_max() {
+    let a = C4::new(0b1010).unwrap();
+    assert_eq!(a.saturating_add(5u8).0, 0b1111);
+    assert_eq!(a.saturating_add(6u8), C4::max());
+
+    type Full = ConstrainedNum<{ CalculationsType::BITS }>;
+    assert_eq!(Full::max().saturating_add(1u8), Full::max());
+}
+
+#[test]
+fn saturating_sub_clamps_to_zero() {
+    let a = C3::new(0b101).unwrap();
+    assert_eq!(a.saturating_sub(5u8), C3::zero());
+    assert_eq!(a.saturating_sub(6u8), C3::zero());
+    assert_eq!(a.saturating_sub(C3::one()).0, 0b100);
+}
+
+#[test]
+fn checked_mul_at_bit_limit() {
+    let a = C4::new(5).unwrap();
+    assert_eq!(a.checked_mul(3u8).unwrap().0, 15);
+    assert!(matches!(
+        a.checked_mul(4u8),
+        Err(BitsConstraintError::ValueUsesTooManyBits {
+            value: 20,
+            used_bits: 5
+        })
+    ));
+    assert_eq!(C4::max().checked_mul(0u8).unwrap(), C4::zero());
+}
+
+#[test]
+fn operators_in_range() {
+    let a = C4::new(0b1010).unwrap();
+    assert_eq!((a + 5u8).unwrap().0, 0b1111);
+    assert_eq!((a - 10u8).unwrap(), C4::zero());
+    assert_eq!((C4::new(5).unwrap() * 3u8).unwrap().0, 15);
+    // Constrained numbers of any BITS are operands too:
+    assert_eq!((a + C3::one()).unwrap().0, 0b1011);
+}
+
+#[test]
+fn operators_out_of_range() {
+    let a = C4::new(0b1010).unwrap();
+    assert!(matches!(
+        a + 6u8,
+        Err(BitsConstraintError::ValueUsesTooManyBits {
+            value: 16,
+            used_bits: 5
+        })
+    ));
+    assert!(matches!(a - 11u8, Err(BitsConstraintError::OutOfRange)));
+    assert!(matches!(
+        a * 2u8,
+        Err(BitsConstraintError::ValueUsesTooManyBits { value: 20, .. })
+    ));
+
+    type Full = ConstrainedNum<{ CalculationsType::BITS }>;
+    assert!(matches!(
+        Full::max() + 1u8,
+        Err(BitsConstraintError::OutOfRange)
+    ));
+    assert!(matches!(
+        Full::max() * 2u8,
+        Err(BitsConstraintError::OutOfRange)
+    ));
+}
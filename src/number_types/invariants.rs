@@ -0,0 +1,35 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Compile-time checks of the invariants the sizes in `sizes.rs` must satisfy. Breaking one of them
+// doesn't fail any single calculation, it silently breaks the coding, so it stops the build
+// instead.
+
+use super::sizes::{CalculationsType, FREQUENCY_BITS, INTERVAL_BITS};
+
+// The decoder and the model need 2 bits of room in the interval to decode every symbol, even
+// rare ones:
+const _: () = assert!(
+    INTERVAL_BITS >= FREQUENCY_BITS + 2,
+    "INTERVAL_BITS must be at least FREQUENCY_BITS + 2"
+);
+
+// Multiplying an interval's width by a frequency must never overflow:
+const _: () = assert!(
+    CalculationsType::BITS >= INTERVAL_BITS + FREQUENCY_BITS,
+    "CalculationsType must have at least INTERVAL_BITS + FREQUENCY_BITS bits"
+);
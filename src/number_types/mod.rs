@@ -14,9 +14,15 @@
 //
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
+mod arithmetic;
 mod constraints;
+mod invariants;
+mod params;
 mod sizes;
 
-// Bring them to scope, spare users from specifying `sizes::` or `constraints::`:
+// Bring them to scope, spare users from specifying `sizes::`, `params::`, `arithmetic::` or
+// `constraints::`:
+pub use arithmetic::*;
 pub use constraints::*;
+pub use params::*;
 pub use sizes::*;
@@ -0,0 +1,130 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::sizes::{CalculationsType, FREQUENCY_BITS, INTERVAL_BITS};
+use crate::frequencies::Frequency;
+use crate::sim::UNIQUE_SYMBOLS_AMOUNT;
+use thiserror::Error;
+
+/// The smallest number of frequency bits, enough for a table in which every symbol of the default
+/// SIM has a frequency of 1
+pub const MIN_FREQUENCY_BITS: u32 = usize::BITS - UNIQUE_SYMBOLS_AMOUNT.leading_zeros();
+
+/// Errors caused by a combination of precisions the codec can't work with
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CodecParamsError {
+    #[error("Interval bits must be at most {INTERVAL_BITS}, but {0} were given")]
+    TooManyIntervalBits(u32),
+    #[error("Frequency bits must be between {MIN_FREQUENCY_BITS} and {FREQUENCY_BITS}, but {0} were given")]
+    FrequencyBitsOutOfRange(u32),
+    #[error("Interval bits must be at least frequency bits + 2, but {interval_bits} interval bits were given with {frequency_bits} frequency bits")]
+    IntervalTooNarrow {
+        interval_bits: u32,
+        frequency_bits: u32,
+    },
+}
+
+/// The precision of the arithmetic coding, chosen at runtime.
+///
+/// `INTERVAL_BITS` and `FREQUENCY_BITS` are the largest precisions the number types can hold, and
+/// the parameters may use any number of bits up to them. Streams must be decompressed with the
+/// parameters they were compressed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodecParams {
+    /// Number of bits in the boundaries of the coding interval
+    interval_bits: u32,
+    /// Number of bits the total frequency of a model may use
+    frequency_bits: u32,
+}
+
+impl CodecParams {
+    /// Creates the parameters, making sure the interval is wide enough to code every symbol: its
+    /// width never drops below a quarter of its range, which must be larger than any total
+    /// frequency, so `interval_bits >= frequency_bits + 2` must hold.
+    pub fn new(interval_bits: u32, frequency_bits: u32) -> Result<Self, CodecParamsError> {
+        if interval_bits > INTERVAL_BITS {
+            Err(CodecParamsError::TooManyIntervalBits(interval_bits))
+        } else if !(MIN_FREQUENCY_BITS..=FREQUENCY_BITS).contains(&frequency_bits) {
+            Err(CodecParamsError::FrequencyBitsOutOfRange(frequency_bits))
+        } else if interval_bits < frequency_bits + 2 {
+            Err(CodecParamsError::IntervalTooNarrow {
+                interval_bits,
+                frequency_bits,
+            })
+        } else {
+            Ok(Self {
+                interval_bits,
+                frequency_bits,
+            })
+        }
+    }
+
+    pub fn interval_bits(&self) -> u32 {
+        self.interval_bits
+    }
+
+    pub fn frequency_bits(&self) -> u32 {
+        self.frequency_bits
+    }
+
+    /// The largest total frequency a model may have for its symbols to be coded
+    pub fn max_total(&self) -> Frequency {
+        Frequency::new((1 << self.frequency_bits) - 1 as CalculationsType)
+            .expect("Frequency bits are at most FREQUENCY_BITS")
+    }
+}
+
+impl Default for CodecParams {
+    /// The largest precision the number types support
+    fn default() -> Self {
+        Self::new(INTERVAL_BITS, FREQUENCY_BITS).expect("Default precisions are valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params_validation() {
+        assert!(CodecParams::new(INTERVAL_BITS, FREQUENCY_BITS).is_ok());
+        assert!(CodecParams::new(18, 16).is_ok());
+        assert_eq!(
+            CodecParams::new(INTERVAL_BITS + 1, 16),
+            Err(CodecParamsError::TooManyIntervalBits(INTERVAL_BITS + 1))
+        );
+        assert_eq!(
+            CodecParams::new(20, MIN_FREQUENCY_BITS - 1),
+            Err(CodecParamsError::FrequencyBitsOutOfRange(
+                MIN_FREQUENCY_BITS - 1
+            ))
+        );
+        assert_eq!(
+            CodecParams::new(17, 16),
+            Err(CodecParamsError::IntervalTooNarrow {
+                interval_bits: 17,
+                frequency_bits: 16
+            })
+        );
+    }
+
+    #[test]
+    fn test_max_total() {
+        assert_eq!(*CodecParams::new(18, 16).unwrap().max_total(), 0xFFFF);
+        assert_eq!(CodecParams::default().max_total(), Frequency::max());
+    }
+}
@@ -23,9 +23,9 @@ pub const FREQUENCY_BITS: u32 = 31;
 
 /// Maximum number of bits an interval value can have (must satisfy:
 /// `INTERVAL_BITS >= 2 + FREQUENCY_BITS` to provide the decoder + model enough room to decode all
-/// symbols, even rare ones)
+/// symbols, even rare ones, which `invariants.rs` checks at compile time)
 pub const INTERVAL_BITS: u32 = 33;
 
 /// The type assigned to perform all calculations, avoids both overflow and underflow (must satisfy:
-/// `CALCULATIONS_TYPE_BITS >= INTERVAL_BITS + FREQUENCY_BITS`
+/// `CalculationsType::BITS >= INTERVAL_BITS + FREQUENCY_BITS`, checked at compile time too)
 pub type CalculationsType = u64;
@@ -17,47 +17,483 @@
 
 use crate::sim::Symbol;
 
-/// A trait for pre-processing raw byte values into compressible Symbols.
+/// A trait for pre-processing raw byte values into compressible Symbols.<br>
+/// Parsers may keep state between the bytes of their input (e.g. to make use of repetitions
+/// between them), so every input must be parsed from start to end, followed by a call to `finish`.
 pub trait Parser {
-    /// Compresses a single byte into at least one Symbol.<br>
-    fn parse_byte(&self, byte: u8) -> Vec<Symbol>;
+    /// Parses the next byte of the input into symbols.<br>
+    /// A parser which buffers the byte may return no symbols, and parse it in a later call (or in
+    /// `finish`).
+    fn parse_byte(&mut self, byte: u8) -> Vec<Symbol>;
+
+    /// Parses only the _bits_ most significant bits of a byte (1 to 7), for data whose length in
+    /// bits isn't a multiple of 8. Such a byte is always the last byte of its input, and each of
+    /// its bits is parsed into a symbol of its own.<br>
+    /// Returns None if the parser can't split a byte's bits into separate symbols, which is the
+    /// default.
+    fn parse_partial_byte(&mut self, _byte: u8, _bits: u8) -> Option<Vec<Symbol>> {
+        None
+    }
+
+    /// Ends the input - returns the symbols of anything the parser still buffers, and returns it to
+    /// its initial state, so it can parse another input.<br>
+    /// The default implementation is for parsers that don't buffer anything, and returns no
+    /// symbols.
+    fn finish(&mut self) -> Vec<Symbol> {
+        Vec::new()
+    }
+
+    /// Creates the inverse of the parser, in its initial state.
+    fn unparser(&self) -> Box<dyn Unparser>;
+
+    /// Parses _bytes_ as a whole input - every byte, followed by the symbols of `finish`.
+    fn parse_all(&mut self, bytes: &[u8]) -> Vec<Symbol> {
+        let mut symbols: Vec<Symbol> = bytes
+            .iter()
+            .flat_map(|&byte| self.parse_byte(byte))
+            .collect();
+        symbols.extend(self.finish());
+        symbols
+    }
 }
 
 impl<P: Parser + ?Sized> Parser for Box<P> {
-    fn parse_byte(&self, byte: u8) -> Vec<Symbol> {
+    fn parse_byte(&mut self, byte: u8) -> Vec<Symbol> {
         (**self).parse_byte(byte)
     }
+
+    fn parse_partial_byte(&mut self, byte: u8, bits: u8) -> Option<Vec<Symbol>> {
+        (**self).parse_partial_byte(byte, bits)
+    }
+
+    fn finish(&mut self) -> Vec<Symbol> {
+        (**self).finish()
+    }
+
+    fn unparser(&self) -> Box<dyn Unparser> {
+        (**self).unparser()
+    }
+}
+
+/// Inverse of a [Parser] - reconstructs the original bytes from the symbols they were parsed into.
+/// <br>
+/// Like parsers, unparsers may keep state between calls, so the symbols of an input must be given
+/// in order, followed by a call to `finish`.
+pub trait Unparser {
+    /// Reconstructs the bytes of the next _symbols_ of the input. The symbols may be split between
+    /// calls anywhere - bytes whose symbols weren't all given yet are returned by a later call.<br>
+    /// Symbols the parser doesn't parse bytes into (such as `Symbol::Eof`) are skipped.
+    fn unparse(&mut self, symbols: &[Symbol]) -> Vec<u8>;
+
+    /// Like `unparse`, for the last symbols of data whose last byte was parsed by
+    /// `Parser::parse_partial_byte` with the same _bits_. The bits of the last byte which weren't
+    /// parsed are set to 0.<br>
+    /// Returns None if the parser can't split a byte's bits into separate symbols, which is the
+    /// default.
+    fn unparse_partial(&mut self, _symbols: &[Symbol], _bits: u8) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Ends the input - returns the bytes of anything the unparser still buffers, and returns it to
+    /// its initial state.<br>
+    /// The default implementation is for unparsers that don't buffer anything, and returns no
+    /// bytes.
+    fn finish(&mut self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Unparses _symbols_ as a whole input - all of them, followed by the bytes of `finish`.
+    fn unparse_all(&mut self, symbols: &[Symbol]) -> Vec<u8> {
+        let mut bytes = self.unparse(symbols);
+        bytes.extend(self.finish());
+        bytes
+    }
 }
 
-/// Regular parser - parses bytes directly into a `Symbol::Byte`
+impl<U: Unparser + ?Sized> Unparser for Box<U> {
+    fn unparse(&mut self, symbols: &[Symbol]) -> Vec<u8> {
+        (**self).unparse(symbols)
+    }
+
+    fn unparse_partial(&mut self, symbols: &[Symbol], bits: u8) -> Option<Vec<u8>> {
+        (**self).unparse_partial(symbols, bits)
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        (**self).finish()
+    }
+}
+
+/// Returns the values of the `Symbol::Byte` symbols in _symbols_, skipping any other symbol.
+fn data_bytes(symbols: &[Symbol]) -> impl Iterator<Item = u8> + '_ {
+    symbols.iter().filter_map(|symbol| match symbol {
+        Symbol::Byte(b) => Some(*b),
+        _ => None,
+    })
+}
+
+/// Returns the values of the `Symbol::Bit` symbols in _symbols_, skipping any other symbol.
+fn data_bits(symbols: &[Symbol]) -> impl Iterator<Item = bool> + '_ {
+    symbols.iter().filter_map(|symbol| match symbol {
+        Symbol::Bit(bit) => Some(*bit),
+        _ => None,
+    })
+}
+
+/// Regular parser - parses bytes directly into a `Symbol::Byte`.<br>
+/// It keeps no state, and is its own unparser.
 pub struct ByteParser;
 impl Parser for ByteParser {
-    fn parse_byte(&self, byte: u8) -> Vec<Symbol> {
+    fn parse_byte(&mut self, byte: u8) -> Vec<Symbol> {
         vec![Symbol::Byte(byte)]
     }
+
+    fn unparser(&self) -> Box<dyn Unparser> {
+        Box::new(ByteParser)
+    }
+}
+
+impl Unparser for ByteParser {
+    fn unparse(&mut self, symbols: &[Symbol]) -> Vec<u8> {
+        data_bytes(symbols).collect()
+    }
 }
 
-/// Parser for binary symbols - each byte is parsed into 8 symbols, where each symbol is either
-/// `Symbol::Byte(0)` or `Symbol::Byte(1)` (depending on the corresponding bit value).<br>
+/// Parser for binary symbols - each byte is parsed into 8 `Symbol::Bit` symbols, one per bit.<br>
 /// Bits are parsed in big-endian.
 pub struct BitParser;
 impl Parser for BitParser {
-    fn parse_byte(&self, byte: u8) -> Vec<Symbol> {
+    fn parse_byte(&mut self, byte: u8) -> Vec<Symbol> {
         let mut symbols = Vec::with_capacity(8);
         let mut mask: u8 = 0b10000000;
 
         for _ in 0..8 {
-            let symbol = if byte & mask != 0 {
-                Symbol::Byte(1)
-            } else {
-                Symbol::Byte(0)
-            };
-            symbols.push(symbol);
+            symbols.push(Symbol::Bit(byte & mask != 0));
             mask >>= 1;
         }
 
         symbols
     }
+
+    fn parse_partial_byte(&mut self, byte: u8, bits: u8) -> Option<Vec<Symbol>> {
+        let mut symbols = self.parse_byte(byte);
+        symbols.truncate(bits as usize);
+        Some(symbols)
+    }
+
+    fn unparser(&self) -> Box<dyn Unparser> {
+        Box::new(BitUnparser::default())
+    }
+}
+
+/// Inverse of [BitParser] - reassembles every 8 bit symbols into a byte
+#[derive(Default)]
+pub struct BitUnparser {
+    /// Bits of a byte whose other bits weren't given yet
+    pending: Vec<bool>,
+}
+
+impl BitUnparser {
+    /// Adds the bits of _symbols_ to the pending bits, and reassembles every 8 of them into a
+    /// byte. Less than 8 bits are left pending.
+    fn unparse_bits(&mut self, symbols: &[Symbol]) -> Vec<u8> {
+        self.pending.extend(data_bits(symbols));
+        let chunks = self.pending.chunks_exact(8);
+        let remainder = chunks.remainder().len();
+        let bytes = chunks.map(bits_to_byte).collect();
+        self.pending.drain(..self.pending.len() - remainder);
+        bytes
+    }
+}
+
+impl Unparser for BitUnparser {
+    fn unparse(&mut self, symbols: &[Symbol]) -> Vec<u8> {
+        self.unparse_bits(symbols)
+    }
+
+    /// Reassembles every 8 bit symbols into a byte, and the trailing group of bits into the last
+    /// byte (padding it with zero bits). A trailing group with a different number of bits than
+    /// _bits_ can't come from `parse_partial_byte`, and is dropped.
+    fn unparse_partial(&mut self, symbols: &[Symbol], bits: u8) -> Option<Vec<u8>> {
+        let mut bytes = self.unparse_bits(symbols);
+        let remainder = std::mem::take(&mut self.pending);
+        if remainder.len() == bits as usize {
+            bytes.push(bits_to_byte(&remainder) << (8 - remainder.len()));
+        }
+        Some(bytes)
+    }
+
+    /// A trailing group of less than 8 bits can't come from `parse_byte`, and is dropped.
+    fn finish(&mut self) -> Vec<u8> {
+        self.pending.clear();
+        Vec::new()
+    }
+}
+
+/// The byte starting every run-length token of [RleParser]
+pub const RLE_ESCAPE: u8 = 0xFF;
+
+/// The shortest run of identical bytes [RleParser] encodes as a run-length token
+pub const MIN_RLE_RUN: usize = 4;
+
+/// The longest run of identical bytes a single run-length token holds
+pub const MAX_RLE_RUN: usize = MIN_RLE_RUN - 1 + u8::MAX as usize;
+
+/// Run-length parser - runs of at least `MIN_RLE_RUN` identical bytes are parsed into the 3 byte
+/// symbols `RLE_ESCAPE, count, value` (the run's length is `count + MIN_RLE_RUN - 1`), so highly
+/// repetitive data is coded in far fewer symbols. Longer runs than `MAX_RLE_RUN` take several
+/// tokens.<br>
+/// Other bytes are parsed like `ByteParser`, except for `RLE_ESCAPE` itself, which is parsed into
+/// `RLE_ESCAPE, 0`.
+///
+/// A run is only parsed once it ends, so the parser buffers the current run until a different byte
+/// (or `finish`) ends it.
+#[derive(Default)]
+pub struct RleParser {
+    /// The value and length of the current run
+    run: Option<(u8, usize)>,
+}
+
+impl RleParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a run of _len_ (at most `MAX_RLE_RUN`) bytes of _value_
+    fn parse_run(value: u8, len: usize) -> Vec<Symbol> {
+        let bytes = match len {
+            len if len >= MIN_RLE_RUN => vec![RLE_ESCAPE, (len + 1 - MIN_RLE_RUN) as u8, value],
+            len if value == RLE_ESCAPE => [RLE_ESCAPE, 0].repeat(len),
+            len => vec![value; len],
+        };
+        bytes.into_iter().map(Symbol::Byte).collect()
+    }
+}
+
+impl Parser for RleParser {
+    fn parse_byte(&mut self, byte: u8) -> Vec<Symbol> {
+        match self.run {
+            Some((value, len)) if value == byte && len < MAX_RLE_RUN => {
+                self.run = Some((value, len + 1));
+                Vec::new()
+            }
+            _ => {
+                let symbols = self.finish();
+                self.run = Some((byte, 1));
+                symbols
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Vec<Symbol> {
+        self.run
+            .take()
+            .map_or_else(Vec::new, |(value, len)| Self::parse_run(value, len))
+    }
+
+    fn unparser(&self) -> Box<dyn Unparser> {
+        Box::new(RleUnparser::default())
+    }
+}
+
+/// A run-length token whose bytes weren't all unparsed yet
+enum PartialToken {
+    /// Only `RLE_ESCAPE` was unparsed
+    Escape,
+    /// `RLE_ESCAPE` and the run's count were unparsed
+    Count(u8),
+}
+
+/// Inverse of [RleParser] - expands every run-length token back into its run
+#[derive(Default)]
+pub struct RleUnparser {
+    token: Option<PartialToken>,
+}
+
+impl Unparser for RleUnparser {
+    fn unparse(&mut self, symbols: &[Symbol]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(symbols.len());
+        for byte in data_bytes(symbols) {
+            match self.token.take() {
+                None if byte == RLE_ESCAPE => self.token = Some(PartialToken::Escape),
+                None => bytes.push(byte),
+                Some(PartialToken::Escape) if byte == 0 => bytes.push(RLE_ESCAPE),
+                Some(PartialToken::Escape) => self.token = Some(PartialToken::Count(byte)),
+                Some(PartialToken::Count(count)) => {
+                    let len = count as usize + MIN_RLE_RUN - 1;
+                    bytes.extend(std::iter::repeat_n(byte, len));
+                }
+            }
+        }
+        bytes
+    }
+
+    /// A token cut short by the end of the input can't come from `RleParser`, and is dropped.
+    fn finish(&mut self) -> Vec<u8> {
+        self.token = None;
+        Vec::new()
+    }
+}
+
+/// The list of byte values a move-to-front transform starts with
+fn initial_mtf_list() -> [u8; 256] {
+    std::array::from_fn(|i| i as u8)
+}
+
+/// Moves the byte at _index_ of _list_ to its front, and returns it
+fn move_to_front(list: &mut [u8; 256], index: usize) -> u8 {
+    let byte = list[index];
+    list.copy_within(..index, 1);
+    list[0] = byte;
+    byte
+}
+
+/// Move-to-front parser - every byte is parsed into its index in a list of all byte values, and
+/// then moved to the front of the list. Recently seen bytes get small indices, so text (and other
+/// data with local repetitions) is parsed into symbols clustered near zero, which a skewed
+/// frequency model codes well.<br>
+/// The list starts in order of the byte values, and is kept for the whole input.
+pub struct MtfParser {
+    list: [u8; 256],
+}
+
+impl MtfParser {
+    pub fn new() -> Self {
+        Self {
+            list: initial_mtf_list(),
+        }
+    }
+}
+
+impl Default for MtfParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parser for MtfParser {
+    fn parse_byte(&mut self, byte: u8) -> Vec<Symbol> {
+        let index = self
+            .list
+            .iter()
+            .position(|&b| b == byte)
+            .expect("The list holds every byte value");
+        move_to_front(&mut self.list, index);
+        vec![Symbol::Byte(index as u8)]
+    }
+
+    fn finish(&mut self) -> Vec<Symbol> {
+        self.list = initial_mtf_list();
+        Vec::new()
+    }
+
+    fn unparser(&self) -> Box<dyn Unparser> {
+        Box::new(MtfUnparser {
+            list: initial_mtf_list(),
+        })
+    }
+}
+
+/// Inverse of [MtfParser] - replaces every index with the byte at that index of the list
+pub struct MtfUnparser {
+    list: [u8; 256],
+}
+
+impl Unparser for MtfUnparser {
+    fn unparse(&mut self, symbols: &[Symbol]) -> Vec<u8> {
+        data_bytes(symbols)
+            .map(|index| move_to_front(&mut self.list, index as usize))
+            .collect()
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        self.list = initial_mtf_list();
+        Vec::new()
+    }
+}
+
+/// Builds a number out of bits, given from the most significant bit to the least significant one
+fn bits_to_byte(bits: &[bool]) -> u8 {
+    bits.iter().fold(0, |byte, &bit| (byte << 1) | bit as u8)
+}
+
+/// Layers two parsers into a pipeline - each byte is parsed by `A`, and the values of the
+/// resulting `Symbol::Byte` symbols are parsed again by `B`.<br>
+/// Symbols `A` outputs which aren't `Symbol::Byte` have no byte value, and are passed through `B`
+/// as they are.
+///
+/// Unparsing applies the inverse chain - `B` is unparsed first, and its output is unparsed by `A`.
+pub struct ChainedParser<A: Parser, B: Parser> {
+    first: A,
+    second: B,
+}
+
+impl<A: Parser, B: Parser> ChainedParser<A, B> {
+    /// Creates a parser that applies _first_, then _second_.
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+
+    /// Parses the symbols `A` output with `B`
+    fn parse_second(&mut self, symbols: Vec<Symbol>) -> Vec<Symbol> {
+        symbols
+            .into_iter()
+            .flat_map(|symbol| match symbol {
+                Symbol::Byte(b) => self.second.parse_byte(b),
+                other => vec![other],
+            })
+            .collect()
+    }
+}
+
+impl<A: Parser, B: Parser> Parser for ChainedParser<A, B> {
+    fn parse_byte(&mut self, byte: u8) -> Vec<Symbol> {
+        let symbols = self.first.parse_byte(byte);
+        self.parse_second(symbols)
+    }
+
+    /// Ends `A` first, since the symbols it still buffers must be parsed by `B` before `B` ends.
+    fn finish(&mut self) -> Vec<Symbol> {
+        let symbols = self.first.finish();
+        let mut symbols = self.parse_second(symbols);
+        symbols.extend(self.second.finish());
+        symbols
+    }
+
+    fn unparser(&self) -> Box<dyn Unparser> {
+        Box::new(ChainedUnparser {
+            first: self.first.unparser(),
+            second: self.second.unparser(),
+        })
+    }
+}
+
+/// Inverse of [ChainedParser]
+struct ChainedUnparser {
+    first: Box<dyn Unparser>,
+    second: Box<dyn Unparser>,
+}
+
+impl ChainedUnparser {
+    /// Unparses the bytes `B`'s unparser output with `A`'s unparser
+    fn unparse_first(&mut self, bytes: Vec<u8>) -> Vec<u8> {
+        let intermediate: Vec<Symbol> = bytes.into_iter().map(Symbol::Byte).collect();
+        self.first.unparse(&intermediate)
+    }
+}
+
+impl Unparser for ChainedUnparser {
+    fn unparse(&mut self, symbols: &[Symbol]) -> Vec<u8> {
+        let bytes = self.second.unparse(symbols);
+        self.unparse_first(bytes)
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        let bytes = self.second.finish();
+        let mut bytes = self.unparse_first(bytes);
+        bytes.extend(self.first.finish());
+        bytes
+    }
 }
 
 #[cfg(test)]
@@ -66,7 +502,7 @@ mod tests {
 
     #[test]
     fn test_byte_parser_all_bytes() {
-        let parser = ByteParser;
+        let mut parser = ByteParser;
         for byte in 0..=255 {
             let result = parser.parse_byte(byte);
             let expected = vec![Symbol::Byte(byte)];
@@ -76,68 +512,279 @@ mod tests {
 
     #[test]
     fn test_bit_parser_all_zero() {
-        let parser = BitParser;
+        let mut parser = BitParser;
         let result = parser.parse_byte(0u8);
-        let expected = vec![Symbol::Byte(0); 8];
+        let expected = vec![Symbol::Bit(false); 8];
         assert_eq!(result, expected);
     }
 
     #[test]
     fn test_bit_parser_all_one() {
-        let parser = BitParser;
+        let mut parser = BitParser;
         let result = parser.parse_byte(0b11111111);
-        let expected = vec![Symbol::Byte(1); 8];
+        let expected = vec![Symbol::Bit(true); 8];
         assert_eq!(result, expected);
     }
 
     #[test]
     fn test_bit_parser_alternating_bits() {
-        let parser = BitParser;
+        let mut parser = BitParser;
         let result = parser.parse_byte(0b10101010);
         let expected = vec![
-            Symbol::Byte(1),
-            Symbol::Byte(0),
-            Symbol::Byte(1),
-            Symbol::Byte(0),
-            Symbol::Byte(1),
-            Symbol::Byte(0),
-            Symbol::Byte(1),
-            Symbol::Byte(0),
+            Symbol::Bit(true),
+            Symbol::Bit(false),
+            Symbol::Bit(true),
+            Symbol::Bit(false),
+            Symbol::Bit(true),
+            Symbol::Bit(false),
+            Symbol::Bit(true),
+            Symbol::Bit(false),
         ];
         assert_eq!(result, expected);
     }
 
     #[test]
     fn test_bit_parser_reverse_alternating_bits() {
-        let parser = BitParser;
+        let mut parser = BitParser;
         let result = parser.parse_byte(0b01010101);
         let expected = vec![
-            Symbol::Byte(0),
-            Symbol::Byte(1),
-            Symbol::Byte(0),
-            Symbol::Byte(1),
-            Symbol::Byte(0),
-            Symbol::Byte(1),
-            Symbol::Byte(0),
-            Symbol::Byte(1),
+            Symbol::Bit(false),
+            Symbol::Bit(true),
+            Symbol::Bit(false),
+            Symbol::Bit(true),
+            Symbol::Bit(false),
+            Symbol::Bit(true),
+            Symbol::Bit(false),
+            Symbol::Bit(true),
         ];
         assert_eq!(result, expected);
     }
 
     #[test]
     fn test_bit_parser_random_bits() {
-        let parser = BitParser;
+        let mut parser = BitParser;
         let result = parser.parse_byte(0b11001001);
         let expected = vec![
-            Symbol::Byte(1),
-            Symbol::Byte(1),
-            Symbol::Byte(0),
-            Symbol::Byte(0),
-            Symbol::Byte(1),
-            Symbol::Byte(0),
-            Symbol::Byte(0),
-            Symbol::Byte(1),
+            Symbol::Bit(true),
+            Symbol::Bit(true),
+            Symbol::Bit(false),
+            Symbol::Bit(false),
+            Symbol::Bit(true),
+            Symbol::Bit(false),
+            Symbol::Bit(false),
+            Symbol::Bit(true),
+        ];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_bit_parser_unparse() {
+        let symbols: Vec<Symbol> = [0b11001001, 0b00000001]
+            .into_iter()
+            .flat_map(|b| BitParser.parse_byte(b))
+            .chain([Symbol::Bit(true), Symbol::Eof])
+            .collect();
+
+        // The trailing bit and the EOF are ignored:
+        let mut unparser = BitParser.unparser();
+        assert_eq!(unparser.unparse_all(&symbols), vec![0b11001001, 0b00000001]);
+    }
+
+    #[test]
+    fn test_bit_parser_partial_round_trip() {
+        let mut parser = BitParser;
+        let symbols: Vec<Symbol> = parser
+            .parse_byte(0b11001001)
+            .into_iter()
+            .chain(parser.parse_partial_byte(0b10111111, 3).unwrap())
+            .collect();
+        assert_eq!(symbols.len(), 11);
+
+        // The bits that weren't parsed are zeroed:
+        assert_eq!(
+            parser.unparser().unparse_partial(&symbols, 3),
+            Some(vec![0b11001001, 0b10100000])
+        );
+        // Byte parsers can't parse partial bytes:
+        assert_eq!(ByteParser.parse_partial_byte(0b10111111, 3), None);
+    }
+
+    #[test]
+    fn test_stateless_parsers_finish_empty() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let mut byte_parser = ByteParser;
+        let mut bit_parser = BitParser;
+        let parsers: [&mut dyn Parser; 2] = [&mut byte_parser, &mut bit_parser];
+        for parser in parsers {
+            // Every byte is parsed on its own, so nothing is left for the end of the input:
+            let symbols: Vec<Symbol> = bytes.iter().flat_map(|&b| parser.parse_byte(b)).collect();
+            assert!(parser.finish().is_empty());
+            assert_eq!(parser.parse_all(&bytes), symbols);
+
+            let mut unparser = parser.unparser();
+            assert_eq!(unparser.unparse(&symbols), bytes);
+            assert!(unparser.finish().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_unparse_split_symbols() {
+        let bytes: Vec<u8> = b"abbcccdddd".repeat(30);
+        let mut rle_parser = RleParser::new();
+        let mut mtf_parser = MtfParser::new();
+        let parsers: [&mut dyn Parser; 4] = [
+            &mut ByteParser,
+            &mut BitParser,
+            &mut rle_parser,
+            &mut mtf_parser,
         ];
+        for parser in parsers {
+            let symbols = parser.parse_all(&bytes);
+            // Splitting the symbols anywhere (even within a byte or a token) gives the same bytes:
+            for split in [1, 2, 3, 7] {
+                let mut unparser = parser.unparser();
+                let mut unparsed: Vec<u8> = symbols
+                    .chunks(split)
+                    .flat_map(|chunk| unparser.unparse(chunk))
+                    .collect();
+                unparsed.extend(unparser.finish());
+                assert_eq!(unparsed, bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rle_parser_tokens() {
+        // The values of the byte symbols the parser outputs:
+        let bytes = |input: &[u8]| ByteParser.unparse(&RleParser::new().parse_all(input));
+        // Short runs are left as they are, and the escape byte is escaped:
+        assert_eq!(bytes(b"aaab"), b"aaab");
+        assert_eq!(bytes(&[RLE_ESCAPE, 7]), [RLE_ESCAPE, 0, 7]);
+        // Runs of at least the minimal length become a single token:
+        assert_eq!(bytes(b"xaaaa"), [b'x', RLE_ESCAPE, 1, b'a']);
+        assert_eq!(bytes(&[RLE_ESCAPE; 5]), [RLE_ESCAPE, 2, RLE_ESCAPE]);
+        // A run longer than a token holds is split, and its rest is left as it is if it's short:
+        assert_eq!(bytes(&[0; MAX_RLE_RUN + 2]), [RLE_ESCAPE, u8::MAX, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_rle_parser_buffers_runs() {
+        let mut parser = RleParser::new();
+        // A run is only parsed once a different byte ends it:
+        assert!(parser.parse_byte(b'a').is_empty());
+        assert!(parser.parse_byte(b'a').is_empty());
+        assert_eq!(
+            parser.parse_byte(b'b'),
+            vec![Symbol::Byte(b'a'), Symbol::Byte(b'a')]
+        );
+        // The last run is parsed by finish, which starts the parser over:
+        assert_eq!(parser.finish(), vec![Symbol::Byte(b'b')]);
+        assert!(parser.finish().is_empty());
+    }
+
+    #[test]
+    fn test_rle_parser_round_trip() {
+        let mut bytes: Vec<u8> = (0..=255).collect();
+        bytes.extend([RLE_ESCAPE; 300]);
+        bytes.extend([0; 100_000]);
+        bytes.extend(b"abbcccddddeeeee");
+        let mut parser = RleParser::new();
+        let symbols = parser.parse_all(&bytes);
+        assert!(symbols.len() < bytes.len() / 10);
+        assert_eq!(parser.unparser().unparse_all(&symbols), bytes);
+    }
+
+    #[test]
+    fn test_mtf_parser_indices() {
+        let indices = |bytes: &[u8]| ByteParser.unparse(&MtfParser::new().parse_all(bytes));
+        // A byte's first index is its value, and a repeated byte is at the front:
+        assert_eq!(indices(b"aab"), [b'a', 0, b'b']);
+        // Every byte seen since a byte was last seen is in front of it:
+        assert_eq!(indices(b"abcba"), [b'a', b'b', b'c', 1, 2]);
+
+        // finish starts the list over:
+        let mut parser = MtfParser::new();
+        assert_eq!(parser.parse_all(b"b"), parser.parse_all(b"b"));
+    }
+
+    #[test]
+    fn test_mtf_parser_round_trip() {
+        let text = b"the move-to-front transform clusters the indices of text near zero. ";
+        let bytes: Vec<u8> = text.repeat(2000).into_iter().chain(0..=255).collect();
+
+        let mut parser = MtfParser::new();
+        let symbols = parser.parse_all(&bytes);
+        let small = symbols
+            .iter()
+            .filter(|symbol| matches!(symbol, Symbol::Byte(index) if *index < 16))
+            .count();
+        assert!(small > symbols.len() / 2);
+        assert_eq!(parser.unparser().unparse_all(&symbols), bytes);
+    }
+
+    /// Test parser which splits a byte into its two nibbles
+    struct NibbleParser;
+    impl Parser for NibbleParser {
+        fn parse_byte(&mut self, byte: u8) -> Vec<Symbol> {
+            vec![Symbol::Byte(byte >> 4), Symbol::Byte(byte & 0xF)]
+        }
+
+        fn unparser(&self) -> Box<dyn Unparser> {
+            Box::new(NibbleUnparser(None))
+        }
+    }
+
+    /// Inverse of [NibbleParser], holding the high nibble of a byte whose low nibble wasn't given
+    /// yet
+    struct NibbleUnparser(Option<u8>);
+    impl Unparser for NibbleUnparser {
+        fn unparse(&mut self, symbols: &[Symbol]) -> Vec<u8> {
+            data_bytes(symbols)
+                .filter_map(|nibble| match self.0.take() {
+                    Some(high) => Some((high << 4) | nibble),
+                    None => {
+                        self.0 = Some(nibble);
+                        None
+                    }
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_chained_parser() {
+        let mut parser = ChainedParser::new(NibbleParser, BitParser);
+
+        // Each nibble is split into 8 bits, the first 4 of which are always 0:
+        let result = parser.parse_byte(0b10100011);
+        let expected: Vec<Symbol> = [0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 1]
+            .into_iter()
+            .map(|bit| Symbol::Bit(bit == 1))
+            .collect();
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_chained_parser_round_trip() {
+        let mut parser = ChainedParser::new(NibbleParser, BitParser);
+        let bytes: Vec<u8> = (0..=255).collect();
+        let symbols = parser.parse_all(&bytes);
+
+        assert_eq!(symbols.len(), bytes.len() * 16);
+        assert_eq!(parser.unparser().unparse_all(&symbols), bytes);
+    }
+
+    #[test]
+    fn test_chained_parser_finishes_both() {
+        // The run buffered by the first parser is parsed by the second one when the input ends:
+        let mut parser = ChainedParser::new(RleParser::new(), MtfParser::new());
+        let bytes = b"aaaaaaaa";
+        let symbols = parser.parse_all(bytes);
+        assert_eq!(
+            ByteParser.unparse(&symbols),
+            // RLE's token is [RLE_ESCAPE, 5, b'a'], and the bytes moved to the front are skipped:
+            [RLE_ESCAPE, 5 + 1, b'a' + 1]
+        );
+        assert_eq!(parser.unparser().unparse_all(&symbols), bytes);
+    }
 }
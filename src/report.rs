@@ -0,0 +1,109 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt::{Display, Formatter};
+
+/// Sizes of the parts of a compression's output, used to report the real compression ratio
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompressionReport {
+    /// Number of bytes read from the input
+    pub input_bytes: usize,
+    /// Number of bytes written before the compressed stream (e.g. the dictionary's hash)
+    pub header_bytes: usize,
+    /// Number of bytes of the compressed stream itself, including its padding
+    pub payload_bytes: usize,
+    /// Number of bytes written after the compressed stream
+    pub footer_bytes: usize,
+    /// Number of symbols that weren't compressed, since the model couldn't compress them
+    pub skipped_symbols: usize,
+}
+
+impl CompressionReport {
+    /// Total number of bytes written, including all overhead
+    pub fn output_bytes(&self) -> usize {
+        self.header_bytes + self.payload_bytes + self.footer_bytes
+    }
+
+    /// The ratio between the output's size and the input's size (lower is better). Above 1.0, the
+    /// output is larger than the input.<br>
+    /// Returns None if the input is empty.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.input_bytes != 0).then(|| self.output_bytes() as f64 / self.input_bytes as f64)
+    }
+
+    /// Returns true if the output is larger than the input
+    pub fn is_expansion(&self) -> bool {
+        self.output_bytes() > self.input_bytes
+    }
+}
+
+impl Display for CompressionReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} bytes -> {} bytes (header: {}, payload: {}, footer: {})",
+            self.input_bytes,
+            self.output_bytes(),
+            self.header_bytes,
+            self.payload_bytes,
+            self.footer_bytes
+        )?;
+        match self.ratio() {
+            Some(ratio) if self.is_expansion() => {
+                write!(
+                    f,
+                    ", ratio: {ratio:.3} (EXPANDED - output is larger than input)"
+                )
+            }
+            Some(ratio) => write!(f, ", ratio: {ratio:.3}"),
+            None => write!(f, ", ratio: undefined (empty input)"),
+        }?;
+        if self.skipped_symbols > 0 {
+            write!(f, ", skipped symbols: {}", self.skipped_symbols)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ratio_includes_overhead() {
+        let report = CompressionReport {
+            input_bytes: 100,
+            header_bytes: 4,
+            payload_bytes: 40,
+            footer_bytes: 6,
+            ..Default::default()
+        };
+        assert_eq!(report.output_bytes(), 50);
+        assert_eq!(report.ratio(), Some(0.5));
+        assert!(!report.is_expansion());
+    }
+
+    #[test]
+    fn test_empty_input_has_no_ratio() {
+        let report = CompressionReport {
+            payload_bytes: 1,
+            ..Default::default()
+        };
+        assert_eq!(report.ratio(), None);
+        assert!(report.to_string().contains("undefined"));
+    }
+}
@@ -0,0 +1,436 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Property-based round-trip tests: for every model configuration the crate supports, compressing
+// some input and decompressing the result must give back the exact same input.
+//
+// Covered models: uniform, custom, adaptive order-0 (with linear and sub-linear updates), PPM of
+// orders 0 to `MAX_PPM_ORDER`, and order-1 models (with dense context tables).
+//
+// Excluded models:
+// * The fallback model and the two-pass model - they wrap or are built from the models above, and
+//   their own modules round-trip them.
+
+use crate::bit_buffer::bit_iter::BitIterator;
+use crate::compressor::Compressor;
+use crate::decompressor::Decompressor;
+use crate::frequencies::Frequency;
+use crate::models::adaptive::{AdaptiveModel, UpdateRule};
+use crate::models::distributions::custom::CustomDistributionModel;
+use crate::models::distributions::uniform::UniformDistributionModel;
+use crate::models::order1::Order1Model;
+use crate::models::ppm::PpmModel;
+use crate::models::Model;
+use crate::number_types::{
+    CalculationsType, CodecParams, FREQUENCY_BITS, INTERVAL_BITS, MIN_FREQUENCY_BITS,
+};
+use crate::parser::{BitParser, ByteParser, MtfParser, Parser, RleParser, Unparser};
+use crate::sim::{BitSIM, DefaultSIM, Symbol, SymbolIndexMapping, UNIQUE_SYMBOLS_AMOUNT};
+use proptest::prelude::*;
+use std::num::NonZeroU64;
+
+/// Upper bound on the input's length, keeps the tests fast enough for CI
+const MAX_INPUT_LEN: usize = 256;
+
+/// Upper bound on a single frequency in a random custom model. Keeping it small guarantees the
+/// total never comes close to `Frequency::max()`
+const MAX_CUSTOM_FREQUENCY: CalculationsType = 1000;
+
+/// Upper bound on the frequency cap of a random adaptive model. Small caps make sure rescales
+/// happen even on short inputs
+const MAX_FREQUENCY_CAP: CalculationsType = 64;
+
+/// Upper bound on the context order of a random PPM model
+const MAX_PPM_ORDER: usize = 3;
+
+/// Upper bound on the input's length in the randomized adaptive model tests, which is longer than
+/// `MAX_INPUT_LEN` so tables are rescaled and aged many times
+const MAX_ADAPTIVE_INPUT_LEN: usize = 4096;
+
+/// Upper bound on the increment of a random adaptive model
+const MAX_INCREMENT: CalculationsType = 64;
+
+/// Upper bound on the half-life of a random adaptive model
+const MAX_HALF_LIFE: u64 = 512;
+
+#[derive(Debug, Clone)]
+enum ModelConfig {
+    Uniform,
+    /// Custom distribution, holds the frequencies of every byte and EOF (escape is never coded).
+    /// In bit-mode, only the first frequencies are used, one per bit and EOF
+    Custom(Vec<CalculationsType>),
+    /// Adaptive order-0 distribution, holds the frequency cap of the model
+    Adaptive(CalculationsType),
+    /// Adaptive order-0 distribution with sub-linear updates, holds the frequency cap of the model
+    SublinearAdaptive(CalculationsType),
+    /// PPM model, holds the maximal context order of the model
+    Ppm(usize),
+    /// Order-1 model, keyed on the previous byte
+    Order1,
+}
+
+impl ModelConfig {
+    fn strategy() -> impl Strategy<Value = Self> {
+        prop_oneof![
+            Just(ModelConfig::Uniform),
+            prop::collection::vec(1..=MAX_CUSTOM_FREQUENCY, UNIQUE_SYMBOLS_AMOUNT - 1)
+                .prop_map(ModelConfig::Custom),
+            (1..=MAX_FREQUENCY_CAP).prop_map(ModelConfig::Adaptive),
+            (1..=MAX_FREQUENCY_CAP).prop_map(ModelConfig::SublinearAdaptive),
+            (0..=MAX_PPM_ORDER).prop_map(ModelConfig::Ppm),
+            Just(ModelConfig::Order1),
+        ]
+    }
+
+    /// Builds the model over the bit alphabet if _bit_mode_ is true, or the byte alphabet otherwise
+    fn build(&self, bit_mode: bool) -> Box<dyn Model> {
+        let sim: &'static dyn SymbolIndexMapping = if bit_mode { &BitSIM } else { &DefaultSIM };
+        match self {
+            ModelConfig::Uniform => Box::new(UniformDistributionModel::new(sim)),
+            ModelConfig::Custom(frequencies) => {
+                let frequencies: Vec<Frequency> = frequencies
+                    .iter()
+                    .take(sim.supported_symbols_count() - 1)
+                    // The escape symbol is never compressed, so it gets no frequency:
+                    .chain(std::iter::once(&0))
+                    .map(|&f| Frequency::new(f).unwrap())
+                    .collect();
+                Box::new(CustomDistributionModel::new(sim, &frequencies).unwrap())
+            }
+            ModelConfig::Adaptive(frequency_cap) => Box::new(AdaptiveModel::with_frequency_cap(
+                sim,
+                Frequency::new(*frequency_cap).unwrap(),
+            )),
+            ModelConfig::SublinearAdaptive(frequency_cap) => {
+                Box::new(AdaptiveModel::with_update_rule(
+                    sim,
+                    Frequency::new(*frequency_cap).unwrap(),
+                    UpdateRule::Sublinear,
+                ))
+            }
+            ModelConfig::Ppm(order) => Box::new(PpmModel::new(sim, *order)),
+            ModelConfig::Order1 => Box::new(Order1Model::new(sim)),
+        }
+    }
+}
+
+/// Compresses the symbols the parser produces from _input_.
+fn compress<M: Model>(model: &mut M, parser: &mut dyn Parser, input: &[u8]) -> Vec<u8> {
+    let mut compressor = Compressor::new(model);
+    let mut output = Vec::new();
+    for symbol in parser.parse_all(input) {
+        output.extend(compressor.load_symbol(symbol).unwrap());
+    }
+    output.extend(compressor.finalize().unwrap().0);
+    output
+}
+
+/// Decompresses _compressed_ until an EOF is found, and unparses the symbols with the parser.
+fn decompress<M: Model>(model: &mut M, parser: &dyn Parser, compressed: Vec<u8>) -> Vec<u8> {
+    let compressed_len = compressed.len();
+    let mut decompressor = Decompressor::from_bytes(model, compressed);
+    let mut symbols = Vec::new();
+    while let Some(symbol) = decompressor.get_next_symbol().unwrap() {
+        symbols.push(symbol);
+    }
+    assert_eq!(decompressor.stream_len(), compressed_len);
+    parser.unparser().unparse_all(&symbols)
+}
+
+/// Random parameters of an adaptive order-0 model, and the coding precision it's used with
+#[derive(Debug, Clone)]
+struct AdaptiveConfig {
+    frequency_cap: CalculationsType,
+    increment: CalculationsType,
+    half_life: Option<u64>,
+    update_rule: UpdateRule,
+    interval_bits: u32,
+    frequency_bits: u32,
+}
+
+impl AdaptiveConfig {
+    fn strategy() -> impl Strategy<Value = Self> {
+        let precision = (MIN_FREQUENCY_BITS..=FREQUENCY_BITS).prop_flat_map(|frequency_bits| {
+            (frequency_bits + 2..=INTERVAL_BITS)
+                .prop_map(move |interval_bits| (interval_bits, frequency_bits))
+        });
+        let update_rule = prop_oneof![Just(UpdateRule::Linear), Just(UpdateRule::Sublinear)];
+        (
+            1..=MAX_FREQUENCY_CAP,
+            1..=MAX_INCREMENT,
+            prop::option::of(1..=MAX_HALF_LIFE),
+            update_rule,
+            precision,
+        )
+            .prop_map(
+                |(
+                    frequency_cap,
+                    increment,
+                    half_life,
+                    update_rule,
+                    (interval_bits, frequency_bits),
+                )| {
+                    AdaptiveConfig {
+                        frequency_cap,
+                        increment,
+                        half_life,
+                        update_rule,
+                        interval_bits,
+                        frequency_bits,
+                    }
+                },
+            )
+    }
+
+    fn params(&self) -> CodecParams {
+        CodecParams::new(self.interval_bits, self.frequency_bits).unwrap()
+    }
+
+    fn build(&self) -> AdaptiveModel<DefaultSIM> {
+        let frequency_cap = Frequency::new(self.frequency_cap).unwrap();
+        let model = AdaptiveModel::with_update_rule(DefaultSIM, frequency_cap, self.update_rule)
+            .with_total_cap(self.params().max_total())
+            .with_increment(Frequency::new(self.increment).unwrap());
+        match self.half_life.and_then(NonZeroU64::new) {
+            Some(half_life) => model.with_half_life(half_life),
+            None => model,
+        }
+    }
+}
+
+/// Random input for the adaptive model tests - either uniformly random bytes, or bytes from a
+/// tiny alphabet, which push a few frequencies up to the caps
+fn adaptive_input_strategy() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        prop::collection::vec(any::<u8>(), 0..MAX_ADAPTIVE_INPUT_LEN),
+        prop::collection::vec(0..4u8, 0..MAX_ADAPTIVE_INPUT_LEN),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn decompress_inverts_compress(
+        input in prop::collection::vec(any::<u8>(), 0..MAX_INPUT_LEN),
+        config in ModelConfig::strategy(),
+        bit_mode in any::<bool>(),
+        byte_parser in 0..3u8,
+    ) {
+        let mut parser: Box<dyn Parser> = match (bit_mode, byte_parser) {
+            (true, _) => Box::new(BitParser),
+            (false, 0) => Box::new(ByteParser),
+            (false, 1) => Box::new(RleParser::new()),
+            (false, _) => Box::new(MtfParser::new()),
+        };
+        let mut model = config.build(bit_mode);
+
+        let compressed = compress(&mut model, &mut parser, &input);
+        model.flush();
+        let decompressed = decompress(&mut model, &parser, compressed);
+
+        prop_assert_eq!(decompressed, input);
+    }
+}
+
+proptest! {
+    // The inputs are long, so fewer cases keep the test fast. Failing cases are saved to
+    // `proptest-regressions/roundtrip_tests.txt`, and re-run before any new case:
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn adaptive_models_round_trip(
+        input in adaptive_input_strategy(),
+        config in AdaptiveConfig::strategy(),
+    ) {
+        let params = config.params();
+        let mut model = config.build();
+        let mut compressor = Compressor::with_params(&mut model, params);
+        let mut compressed = Vec::new();
+        for &byte in &input {
+            compressed.extend(compressor.load_symbol(Symbol::Byte(byte)).unwrap());
+        }
+        compressed.extend(compressor.finalize().unwrap().0);
+        model.flush();
+
+        let compressed_len = compressed.len();
+        let bits = BitIterator::from(compressed);
+        let mut decompressor = Decompressor::with_params(&mut model, bits, params);
+        let decompressed: Vec<u8> =
+            std::iter::from_fn(|| decompressor.get_next_byte().unwrap()).collect();
+        prop_assert_eq!(decompressed, input);
+        prop_assert_eq!(decompressor.stream_len(), compressed_len);
+    }
+}
+
+#[test]
+fn reduced_precision_round_trips() {
+    let input = b"fewer bits make the coding less precise, not less correct. ".repeat(30);
+    for (interval_bits, frequency_bits) in [(11, 9), (18, 16), (INTERVAL_BITS, 20)] {
+        let params = CodecParams::new(interval_bits, frequency_bits).unwrap();
+        let models: [Box<dyn Model>; 3] = [
+            Box::new(UniformDistributionModel::new(DefaultSIM)),
+            Box::new(AdaptiveModel::new(DefaultSIM).with_total_cap(params.max_total())),
+            Box::new(PpmModel::new(DefaultSIM, 2).with_total_cap(params.max_total())),
+        ];
+        for mut model in models {
+            let mut compressor = Compressor::with_params(&mut model, params);
+            let mut compressed = Vec::new();
+            for &byte in &input {
+                compressed.extend(compressor.load_symbol(Symbol::Byte(byte)).unwrap());
+            }
+            compressed.extend(compressor.finalize().unwrap().0);
+            model.flush();
+
+            let compressed_len = compressed.len();
+            let bits = BitIterator::from(compressed);
+            let mut decompressor = Decompressor::with_params(&mut model, bits, params);
+            let decompressed: Vec<u8> =
+                std::iter::from_fn(|| decompressor.get_next_byte().unwrap()).collect();
+            assert_eq!(decompressed, input, "{interval_bits} interval bits");
+            assert_eq!(decompressor.stream_len(), compressed_len);
+        }
+    }
+}
+
+#[test]
+fn rle_parser_round_trips_long_runs() {
+    let mut input = vec![0x00; 10_000];
+    input.extend_from_slice(b"a break in the run");
+    input.extend(vec![0xFF; 1000]);
+
+    for config in [
+        ModelConfig::Adaptive(MAX_FREQUENCY_CAP),
+        ModelConfig::Ppm(2),
+    ] {
+        let mut model = config.build(false);
+        let rle_compressed = compress(&mut model, &mut RleParser::new(), &input);
+        model.flush();
+        // Context models predict runs well on their own, but an order-0 model can't:
+        if let ModelConfig::Adaptive(_) = config {
+            let byte_compressed = compress(&mut model, &mut ByteParser, &input);
+            model.flush();
+            assert!(rle_compressed.len() < byte_compressed.len());
+        }
+
+        let decompressed = decompress(&mut model, &RleParser::new(), rle_compressed);
+        assert_eq!(decompressed, input, "{config:?}");
+    }
+}
+
+#[test]
+fn mtf_parser_round_trips_text() {
+    let input = b"move-to-front, then code the small indices. ".repeat(50);
+    let mut model = ModelConfig::Adaptive(MAX_FREQUENCY_CAP).build(false);
+    let compressed = compress(&mut model, &mut MtfParser::new(), &input);
+    model.flush();
+    let decompressed = decompress(&mut model, &MtfParser::new(), compressed);
+
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn capped_context_with_dominant_symbol_round_trips() {
+    // One dominant symbol with a few rare ones, long enough for the cap to trigger many times:
+    let mut input = vec![b'a'; 2000];
+    input.extend_from_slice(b"rare");
+    input.extend(vec![b'a'; 2000]);
+
+    let mut model = ModelConfig::Adaptive(32).build(false);
+    let compressed = compress(&mut model, &mut ByteParser, &input);
+    model.flush();
+    let decompressed = decompress(&mut model, &ByteParser, compressed);
+
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn ppm_compresses_english_better_than_uniform() {
+    let input = b"It was the best of times, it was the worst of times, it was the age of wisdom, it \
+        was the age of foolishness, it was the epoch of belief, it was the epoch of incredulity, it \
+        was the season of Light, it was the season of Darkness, it was the spring of hope, it was \
+        the winter of despair.";
+
+    let mut uniform = ModelConfig::Uniform.build(false);
+    let uniform_compressed = compress(&mut uniform, &mut ByteParser, input);
+
+    let mut ppm = ModelConfig::Ppm(2).build(false);
+    let ppm_compressed = compress(&mut ppm, &mut ByteParser, input);
+    ppm.flush();
+    let decompressed = decompress(&mut ppm, &ByteParser, ppm_compressed.clone());
+
+    assert_eq!(decompressed, input);
+    assert!(ppm_compressed.len() < uniform_compressed.len());
+}
+
+#[test]
+fn order1_compresses_repetitive_input_better_than_uniform() {
+    let input = b"abcd".repeat(500);
+
+    let mut uniform = ModelConfig::Uniform.build(false);
+    let uniform_compressed = compress(&mut uniform, &mut ByteParser, &input);
+
+    let mut order1 = ModelConfig::Order1.build(false);
+    let order1_compressed = compress(&mut order1, &mut ByteParser, &input);
+    order1.flush();
+    let decompressed = decompress(&mut order1, &ByteParser, order1_compressed.clone());
+
+    assert_eq!(decompressed, input);
+    // Every byte fully predicts the next one, so the order-1 model should need a fraction of the
+    // uniform model's 8 bits per byte:
+    assert!(order1_compressed.len() * 4 < uniform_compressed.len());
+}
+
+#[test]
+fn sublinear_adaptive_beats_linear_on_bursty_input() {
+    // Long runs of a few symbols, switching between them:
+    let input: Vec<u8> = b"abcadbca"
+        .iter()
+        .flat_map(|&byte| std::iter::repeat_n(byte, 1000))
+        .collect();
+
+    let mut linear = AdaptiveModel::new(DefaultSIM);
+    let linear_compressed = compress(&mut linear, &mut ByteParser, &input);
+
+    let mut sublinear = AdaptiveModel::sublinear(DefaultSIM);
+    let sublinear_compressed = compress(&mut sublinear, &mut ByteParser, &input);
+    sublinear.flush();
+    let decompressed = decompress(&mut sublinear, &ByteParser, sublinear_compressed.clone());
+
+    assert_eq!(decompressed, input);
+    assert!(sublinear_compressed.len() < linear_compressed.len());
+}
+
+#[test]
+fn smoothing_codes_symbols_missing_from_custom_distribution() {
+    // Only 'a' and EOF have a frequency:
+    let mut frequencies = vec![Frequency::new(0).unwrap(); DefaultSIM.supported_symbols_count()];
+    frequencies[b'a' as usize] = Frequency::new(100).unwrap();
+    frequencies[DefaultSIM.get_index(&Symbol::Eof).unwrap()] = Frequency::new(1).unwrap();
+    let input = b"aaaaaaaazaaaa";
+
+    let mut unsmoothed = CustomDistributionModel::new(DefaultSIM, &frequencies).unwrap();
+    let mut compressor = Compressor::new(&mut unsmoothed);
+    assert!(input
+        .iter()
+        .any(|&byte| compressor.load_symbol(Symbol::Byte(byte)).is_err()));
+
+    let smoothing = Frequency::new(1).unwrap();
+    let mut smoothed =
+        CustomDistributionModel::with_smoothing(DefaultSIM, &frequencies, smoothing).unwrap();
+    let compressed = compress(&mut smoothed, &mut ByteParser, input);
+    let decompressed = decompress(&mut smoothed, &ByteParser, compressed);
+    assert_eq!(decompressed, input);
+}
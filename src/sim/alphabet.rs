@@ -0,0 +1,161 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{DefaultSIM, RestrictedSIM, SymbolIndexMapping};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::LazyLock;
+use thiserror::Error;
+
+/// The SIM of the DNA alphabet
+static DNA_SIM: LazyLock<RestrictedSIM> = LazyLock::new(|| RestrictedSIM::new(*b"ACGT"));
+
+/// The SIM of the ASCII alphabet
+static ASCII_SIM: LazyLock<RestrictedSIM> = LazyLock::new(|| RestrictedSIM::new(0..=0x7f));
+
+/// The alphabets of byte-mode streams. Models over a restricted alphabet spend no probability on
+/// bytes outside of it, which can't be compressed with it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Alphabet {
+    /// Every byte value
+    #[default]
+    Bytes,
+    /// The nucleotides of DNA sequences: A, C, G and T
+    Dna,
+    /// The 7-bit ASCII characters (bytes 0 to 127)
+    Ascii,
+}
+
+impl Alphabet {
+    /// The id of the alphabet in the header of compressed streams. Ids must never change, or
+    /// streams compressed by older versions would be decompressed with the wrong alphabet
+    pub fn id(&self) -> u8 {
+        match self {
+            Alphabet::Bytes => 0,
+            Alphabet::Dna => 1,
+            Alphabet::Ascii => 2,
+        }
+    }
+
+    /// Returns the alphabet whose id is _id_, or None if no alphabet has it
+    pub fn from_id(id: u8) -> Option<Self> {
+        [Alphabet::Bytes, Alphabet::Dna, Alphabet::Ascii]
+            .into_iter()
+            .find(|alphabet| alphabet.id() == id)
+    }
+
+    /// The SIM of the alphabet's bytes and the metadata symbols
+    pub fn sim(&self) -> &'static dyn SymbolIndexMapping {
+        match self {
+            Alphabet::Bytes => &DefaultSIM,
+            Alphabet::Dna => &*DNA_SIM,
+            Alphabet::Ascii => &*ASCII_SIM,
+        }
+    }
+}
+
+impl Display for Alphabet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Alphabet::Bytes => write!(f, "bytes"),
+            Alphabet::Dna => write!(f, "dna"),
+            Alphabet::Ascii => write!(f, "ascii"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Unknown alphabet \"{0}\" (possible values: bytes, dna, ascii)")]
+pub struct UnknownAlphabet(String);
+
+impl FromStr for Alphabet {
+    type Err = UnknownAlphabet;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bytes" => Ok(Alphabet::Bytes),
+            "dna" => Ok(Alphabet::Dna),
+            "ascii" => Ok(Alphabet::Ascii),
+            _ => Err(UnknownAlphabet(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::Header;
+    use crate::models::adaptive::AdaptiveModel;
+    use crate::parser::ByteParser;
+    use crate::sim::Symbol;
+    use crate::stream::{compress_reader, decompress_reader, CodecOptions};
+
+    #[test]
+    fn test_ids_and_names_round_trip() {
+        for alphabet in [Alphabet::Bytes, Alphabet::Dna, Alphabet::Ascii] {
+            assert_eq!(Alphabet::from_id(alphabet.id()), Some(alphabet));
+            assert_eq!(alphabet.to_string().parse::<Alphabet>().unwrap(), alphabet);
+        }
+        assert_eq!(Alphabet::from_id(u8::MAX), None);
+        assert!("rna".parse::<Alphabet>().is_err());
+    }
+
+    #[test]
+    fn test_alphabet_sizes() {
+        assert_eq!(Alphabet::Bytes.sim().supported_symbols_count(), 258);
+        assert_eq!(Alphabet::Dna.sim().supported_symbols_count(), 6);
+        assert_eq!(Alphabet::Ascii.sim().supported_symbols_count(), 130);
+        assert_eq!(Alphabet::Ascii.sim().get_index(&Symbol::Byte(0x80)), None);
+    }
+
+    /// Compresses _input_ with an order-0 model over _alphabet_
+    fn compress(input: &[u8], alphabet: Alphabet, options: &CodecOptions) -> Vec<u8> {
+        let mut compressed = Vec::new();
+        let mut model = AdaptiveModel::new(alphabet.sim());
+        compress_reader(input, &mut compressed, &mut model, &mut ByteParser, options).unwrap();
+        compressed
+    }
+
+    #[test]
+    fn test_dna_beats_full_alphabet() {
+        // A short FASTA-like sequence, without its description line:
+        let input =
+            b"GATCCTCCATATACAACGGTATCTCCACCTCAGGTTTAGATCTCAACAACGGAACCATTGCCGACATGAGACAG".repeat(4);
+        let options = CodecOptions {
+            header: Some(Header {
+                alphabet: Alphabet::Dna,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let dna = compress(&input, Alphabet::Dna, &options);
+        let bytes = compress(&input, Alphabet::Bytes, &CodecOptions::default());
+        assert!(dna.len() < bytes.len());
+
+        let mut decompressed = Vec::new();
+        let mut model = AdaptiveModel::new(Alphabet::Dna.sim());
+        decompress_reader(
+            &dna[..],
+            &mut decompressed,
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(decompressed, input);
+    }
+}
@@ -15,9 +15,25 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+pub mod alphabet;
+pub mod restricted;
 pub mod symbol;
 
-pub use symbol::{Symbol, UNIQUE_SYMBOLS_AMOUNT};
+pub use alphabet::Alphabet;
+pub use restricted::RestrictedSIM;
+pub use symbol::{Symbol, UNIQUE_BIT_SYMBOLS_AMOUNT, UNIQUE_SYMBOLS_AMOUNT};
+
+/// The index of EOF in `DefaultSIM`, right after the byte values
+pub const DEFAULT_EOF_INDEX: usize = 256;
+
+/// The index of escape in `DefaultSIM`, its last index
+pub const DEFAULT_ESC_INDEX: usize = 257;
+
+/// The index of EOF in `BitSIM`, right after the bit values
+pub const BIT_EOF_INDEX: usize = 2;
+
+/// The index of escape in `BitSIM`, its last index
+pub const BIT_ESC_INDEX: usize = 3;
 
 pub trait SymbolIndexMapping {
     /// Computes a unique index for _symbol_. If _symbol_ is not supported by the mapping, None is
@@ -34,23 +50,40 @@ pub trait SymbolIndexMapping {
     fn supported_symbols_count(&self) -> usize;
 }
 
-/// Default implementation of Symbol-Index Mapping, supports every symbol.
+impl<S: SymbolIndexMapping + ?Sized> SymbolIndexMapping for &S {
+    fn get_index(&self, symbol: &Symbol) -> Option<usize> {
+        (**self).get_index(symbol)
+    }
+
+    fn get_symbol(&self, index: usize) -> Option<Symbol> {
+        (**self).get_symbol(index)
+    }
+
+    fn supported_symbols_count(&self) -> usize {
+        (**self).supported_symbols_count()
+    }
+}
+
+/// Default implementation of Symbol-Index Mapping, supports every byte value and the metadata
+/// symbols. Bit symbols are not supported, they're mapped by `BitSIM`.
+#[derive(Clone, Copy, Debug)]
 pub struct DefaultSIM;
 
 impl SymbolIndexMapping for DefaultSIM {
     fn get_index(&self, symbol: &Symbol) -> Option<usize> {
         match symbol {
             Symbol::Byte(b) => Some(*b as usize),
-            Symbol::Eof => Some(256),
-            Symbol::Esc => Some(257),
+            Symbol::Bit(_) => None,
+            Symbol::Eof => Some(DEFAULT_EOF_INDEX),
+            Symbol::Esc => Some(DEFAULT_ESC_INDEX),
         }
     }
 
     fn get_symbol(&self, index: usize) -> Option<Symbol> {
         match index {
             byte @ 0..256 => Some(Symbol::Byte(byte as u8)),
-            256 => Some(Symbol::Eof),
-            257 => Some(Symbol::Esc),
+            DEFAULT_EOF_INDEX => Some(Symbol::Eof),
+            DEFAULT_ESC_INDEX => Some(Symbol::Esc),
             _ => None,
         }
     }
@@ -59,3 +92,72 @@ impl SymbolIndexMapping for DefaultSIM {
         UNIQUE_SYMBOLS_AMOUNT
     }
 }
+
+/// Symbol-Index Mapping for bit-mode, supports the two bit values and the metadata symbols. Byte
+/// symbols are not supported.
+#[derive(Clone, Copy, Debug)]
+pub struct BitSIM;
+
+impl SymbolIndexMapping for BitSIM {
+    fn get_index(&self, symbol: &Symbol) -> Option<usize> {
+        match symbol {
+            Symbol::Bit(bit) => Some(*bit as usize),
+            Symbol::Byte(_) => None,
+            Symbol::Eof => Some(BIT_EOF_INDEX),
+            Symbol::Esc => Some(BIT_ESC_INDEX),
+        }
+    }
+
+    fn get_symbol(&self, index: usize) -> Option<Symbol> {
+        match index {
+            0 => Some(Symbol::Bit(false)),
+            1 => Some(Symbol::Bit(true)),
+            BIT_EOF_INDEX => Some(Symbol::Eof),
+            BIT_ESC_INDEX => Some(Symbol::Esc),
+            _ => None,
+        }
+    }
+
+    fn supported_symbols_count(&self) -> usize {
+        UNIQUE_BIT_SYMBOLS_AMOUNT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that _sim_ maps every index it supports to a symbol which is mapped back to it
+    fn assert_bijective(sim: &impl SymbolIndexMapping) {
+        for index in 0..sim.supported_symbols_count() {
+            let symbol = sim.get_symbol(index).expect("Supported index isn't mapped");
+            assert_eq!(sim.get_index(&symbol), Some(index));
+        }
+        assert_eq!(sim.get_symbol(sim.supported_symbols_count()), None);
+    }
+
+    #[test]
+    fn test_sims_are_bijective() {
+        assert_bijective(&DefaultSIM);
+        assert_bijective(&BitSIM);
+        assert_bijective(&RestrictedSIM::new(*b"ACGT"));
+        assert_bijective(&RestrictedSIM::new([]));
+    }
+
+    #[test]
+    fn test_bit_alphabet_is_separate() {
+        assert_eq!(BitSIM.supported_symbols_count(), 4);
+        // Bytes never map to bit indices, and bits never map to byte indices:
+        for byte in 0..=u8::MAX {
+            assert_eq!(BitSIM.get_index(&Symbol::Byte(byte)), None);
+        }
+        for bit in [false, true] {
+            assert_eq!(DefaultSIM.get_index(&Symbol::Bit(bit)), None);
+        }
+        // The only symbols shared by both alphabets are the metadata symbols:
+        for index in 0..BitSIM.supported_symbols_count() {
+            let symbol = BitSIM.get_symbol(index).unwrap();
+            assert_eq!(DefaultSIM.get_index(&symbol).is_some(), !symbol.is_data());
+        }
+    }
+}
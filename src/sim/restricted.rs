@@ -0,0 +1,143 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{Symbol, SymbolIndexMapping};
+
+/// Symbol-Index Mapping for a restricted alphabet of bytes (such as DNA's A, C, G and T), which
+/// only supports the bytes it's given and the metadata symbols. The bytes get contiguous indices
+/// in ascending order, followed by EOF and escape, so models over it don't spend frequencies on
+/// bytes which never appear.
+///
+/// Headers only record the builtin alphabets (see [Alphabet](super::Alphabet)), so streams over
+/// other restricted alphabets are decompressed from headerless streams.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RestrictedSIM {
+    /// The supported bytes, sorted and without duplicates. Each byte's index is its position
+    bytes: Vec<u8>,
+}
+
+impl RestrictedSIM {
+    /// Creates a mapping supporting the given bytes (duplicates are ignored) and the metadata
+    /// symbols
+    pub fn new<I: IntoIterator<Item = u8>>(bytes: I) -> Self {
+        let mut bytes: Vec<u8> = bytes.into_iter().collect();
+        bytes.sort_unstable();
+        bytes.dedup();
+        Self { bytes }
+    }
+
+    /// The supported bytes, in the order of their indices
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl SymbolIndexMapping for RestrictedSIM {
+    fn get_index(&self, symbol: &Symbol) -> Option<usize> {
+        match symbol {
+            Symbol::Byte(byte) => self.bytes.binary_search(byte).ok(),
+            Symbol::Bit(_) => None,
+            Symbol::Eof => Some(self.bytes.len()),
+            Symbol::Esc => Some(self.bytes.len() + 1),
+        }
+    }
+
+    fn get_symbol(&self, index: usize) -> Option<Symbol> {
+        match index.checked_sub(self.bytes.len()) {
+            None => Some(Symbol::Byte(self.bytes[index])),
+            Some(0) => Some(Symbol::Eof),
+            Some(1) => Some(Symbol::Esc),
+            Some(_) => None,
+        }
+    }
+
+    fn supported_symbols_count(&self) -> usize {
+        // Every byte, EOF and escape:
+        self.bytes.len() + 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::adaptive::AdaptiveModel;
+    use crate::parser::ByteParser;
+    use crate::sim::{DefaultSIM, DEFAULT_EOF_INDEX, DEFAULT_ESC_INDEX};
+    use crate::stream::{compress_reader, decompress_reader, CodecOptions};
+
+    #[test]
+    fn test_dna_alphabet_indices() {
+        let sim = RestrictedSIM::new(*b"TGCATT");
+        assert_eq!(sim.bytes(), b"ACGT");
+        assert_eq!(sim.supported_symbols_count(), 6);
+        for (index, &byte) in b"ACGT".iter().enumerate() {
+            assert_eq!(sim.get_index(&Symbol::Byte(byte)), Some(index));
+            assert_eq!(sim.get_symbol(index), Some(Symbol::Byte(byte)));
+        }
+        assert_eq!(sim.get_index(&Symbol::Eof), Some(4));
+        assert_eq!(sim.get_index(&Symbol::Esc), Some(5));
+        assert_eq!(sim.get_symbol(4), Some(Symbol::Eof));
+        assert_eq!(sim.get_symbol(5), Some(Symbol::Esc));
+        assert_eq!(sim.get_symbol(6), None);
+        // Bytes outside the alphabet and bits aren't supported:
+        assert_eq!(sim.get_index(&Symbol::Byte(b'N')), None);
+        assert_eq!(sim.get_index(&Symbol::Bit(true)), None);
+    }
+
+    #[test]
+    fn test_full_alphabet_matches_default_sim() {
+        let sim = RestrictedSIM::new(0..=u8::MAX);
+        assert_eq!(
+            sim.supported_symbols_count(),
+            DefaultSIM.supported_symbols_count()
+        );
+        for symbol in Symbol::all_bytes().chain([Symbol::Eof, Symbol::Esc]) {
+            assert_eq!(sim.get_index(&symbol), DefaultSIM.get_index(&symbol));
+        }
+        assert_eq!(sim.get_index(&Symbol::Eof), Some(DEFAULT_EOF_INDEX));
+        assert_eq!(sim.get_index(&Symbol::Esc), Some(DEFAULT_ESC_INDEX));
+    }
+
+    #[test]
+    fn test_dna_round_trip() {
+        let input = b"GATTACACCGTAGGATTACA".repeat(20);
+        let options = CodecOptions::default();
+        let mut model = AdaptiveModel::new(RestrictedSIM::new(*b"ACGT"));
+        let mut compressed = Vec::new();
+        compress_reader(
+            &input[..],
+            &mut compressed,
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap();
+        // Every base takes at most 2 bits or so:
+        assert!(compressed.len() < input.len() / 3);
+
+        let mut decompressed = Vec::new();
+        decompress_reader(
+            &compressed[..],
+            &mut decompressed,
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(decompressed, input);
+    }
+}
@@ -20,12 +20,17 @@ use std::fmt::{Display, Formatter};
 /// The number of unique symbols (256 byte values + 1 EOF + 1 ESCAPE)
 pub const UNIQUE_SYMBOLS_AMOUNT: usize = 258;
 
+/// The number of unique symbols in bit-mode (2 bit values + 1 EOF + 1 ESCAPE)
+pub const UNIQUE_BIT_SYMBOLS_AMOUNT: usize = 4;
+
 /// A symbol in the compression/decompression process, its possible values contain all byte values
-/// plus additional metadata values
+/// and bit values, plus additional metadata values
 #[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
 pub enum Symbol {
     /// A byte value
     Byte(u8),
+    /// A single bit value, used in bit-mode
+    Bit(bool),
     /// An End-Of-File value
     Eof,
     /// An 'escape' value
@@ -33,15 +38,27 @@ pub enum Symbol {
 }
 
 impl Symbol {
+    /// Iterates over the symbols of every byte value, in ascending order
+    pub fn all_bytes() -> impl Iterator<Item = Symbol> {
+        (0..=u8::MAX).map(Symbol::Byte)
+    }
+
     pub fn is_escape(&self) -> bool {
         matches!(self, Symbol::Esc)
     }
+
+    /// Whether the symbol carries data of the original input (a byte or a bit value), rather than
+    /// metadata
+    pub fn is_data(&self) -> bool {
+        matches!(self, Symbol::Byte(_) | Symbol::Bit(_))
+    }
 }
 
 impl Display for Symbol {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Symbol::Byte(b) => write!(f, "{}", b),
+            Symbol::Bit(bit) => write!(f, "bit {}", *bit as u8),
             Symbol::Eof => write!(f, "EOF"),
             Symbol::Esc => write!(f, "ESCAPE"),
         }
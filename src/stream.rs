@@ -0,0 +1,1568 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::bit_buffer::bit_iter::BitIterator;
+use crate::checksum::{ChecksumWriter, Crc32, CHECKSUM_BYTES};
+use crate::compressor::Compressor;
+use crate::decompressor::Decompressor;
+use crate::dictionary::{Dictionary, DICTIONARY_HASH_BYTES};
+use crate::header::{Header, HeaderError};
+use crate::models::{Model, ModelCfiError};
+use crate::number_types::CodecParams;
+use crate::parser::{Parser, Unparser};
+use crate::report::CompressionReport;
+use crate::sim::{Alphabet, BitSIM, Symbol, SymbolIndexMapping};
+use crate::termination::TerminationStrategy;
+use anyhow::Result;
+use log::{debug, error, info};
+use std::fmt::{Display, Formatter};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// What the decompression does with bytes that follow the end of a compressed stream
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TrailingPolicy {
+    /// Silently ignore the trailing bytes
+    #[default]
+    Ignore,
+    /// Fail the decompression
+    Error,
+    /// Decompress the trailing bytes as another compressed stream
+    MultiStream,
+}
+
+impl Display for TrailingPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrailingPolicy::Ignore => write!(f, "ignore"),
+            TrailingPolicy::Error => write!(f, "error"),
+            TrailingPolicy::MultiStream => write!(f, "multi-stream"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Unknown trailing bytes policy \"{0}\" (possible values: ignore, error, multi-stream)")]
+pub struct UnknownTrailingPolicy(String);
+
+impl FromStr for TrailingPolicy {
+    type Err = UnknownTrailingPolicy;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(TrailingPolicy::Ignore),
+            "error" => Ok(TrailingPolicy::Error),
+            "multi-stream" => Ok(TrailingPolicy::MultiStream),
+            _ => Err(UnknownTrailingPolicy(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Found {0} unexpected bytes after the end of the compressed stream")]
+pub struct TrailingDataError(pub usize);
+
+/// Errors that may occur while writing the output of compression/decompression
+#[derive(Debug, Error)]
+pub enum OutputWriteError {
+    #[error("The output can't hold any more bytes (is the disk full?): {0}")]
+    OutputFull(std::io::Error),
+    #[error("Failed to write the output: {0}")]
+    IoError(std::io::Error),
+}
+
+impl From<std::io::Error> for OutputWriteError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            ErrorKind::StorageFull | ErrorKind::WriteZero => OutputWriteError::OutputFull(err),
+            _ => OutputWriteError::IoError(err),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("The stream decompressed into {found} bytes, but its header says the original data had {expected} bytes (it may be corrupted)")]
+pub struct StreamLengthError {
+    pub expected: u64,
+    pub found: usize,
+}
+
+#[derive(Debug, Error)]
+#[error("The stream holds more data than the {expected} bytes its header says the original data had (it may be corrupted)")]
+pub struct ExtraDataError {
+    pub expected: u64,
+}
+
+#[derive(Debug, Error)]
+#[error(
+    "The input's length changed while compressing it (expected {expected} bytes, but read {found})"
+)]
+pub struct InputLengthChangedError {
+    pub expected: u64,
+    pub found: usize,
+}
+
+#[derive(Debug, Error)]
+#[error("The parser can't split a byte into bits, so the data's length in bits must be a multiple of 8 (only bit-mode supports other lengths)")]
+pub struct PartialByteError;
+
+/// Options shared by compression and decompression. Both sides must use the same dictionary.
+#[derive(Default)]
+pub struct CodecOptions<'a> {
+    /// A preset dictionary priming the model, if any
+    pub dictionary: Option<&'a Dictionary>,
+    /// What decompression does with bytes after the compressed stream (unused by compression)
+    pub trailing: TrailingPolicy,
+    /// The header of the compressed streams, or None if they have no header. Compression writes
+    /// it (setting its dictionary flag according to `dictionary`), and decompression makes sure
+    /// every stream starts with it
+    pub header: Option<Header>,
+    /// The length in bytes of the input, if it's known before compressing it (unused by
+    /// decompression). The header records the length, so compression can only write it before the
+    /// payload if the length is known - otherwise the payload is kept in memory until the input
+    /// ends. An input of another length fails the compression
+    pub input_len: Option<u64>,
+}
+
+impl CodecOptions<'_> {
+    /// The coding precisions of the streams: those of the header, or the default ones if there is
+    /// no header
+    pub(crate) fn params(&self) -> CodecParams {
+        self.header
+            .map_or_else(CodecParams::default, |header| header.params)
+    }
+
+    /// Whether the header restricts the streams' bytes to an alphabet smaller than every byte
+    /// value
+    pub(crate) fn restricts_alphabet(&self) -> bool {
+        self.header
+            .is_some_and(|header| !header.bit_mode && header.alphabet != Alphabet::Bytes)
+    }
+}
+
+/// A model whose total frequency doesn't fit in the frequency bits of the coding can't code its
+/// symbols
+#[derive(Debug, Error)]
+#[error("The model's total frequency ({total}) is larger than the frequency bits allow (at most {max_total}), use more frequency bits or a model with smaller frequencies")]
+pub struct ModelPrecisionError {
+    pub total: u64,
+    pub max_total: u64,
+}
+
+/// A stream must be decompressed by a model over the alphabet of the mode it was compressed in
+#[derive(Debug, Error)]
+#[error("The stream was compressed in {mode}-mode, whose alphabet has {expected} symbols, but the model supports {found} symbols")]
+pub struct ModelAlphabetError {
+    pub mode: &'static str,
+    pub expected: usize,
+    pub found: usize,
+}
+
+/// Makes sure _model_ supports the alphabet of the mode _header_'s stream was compressed in
+pub(crate) fn check_model_alphabet<M: Model + ?Sized>(
+    model: &M,
+    header: &Header,
+) -> Result<(), ModelAlphabetError> {
+    let (mode, expected) = if header.bit_mode {
+        ("bit", BitSIM.supported_symbols_count())
+    } else {
+        ("byte", header.alphabet.sim().supported_symbols_count())
+    };
+    let found = model.alphabet_size();
+    if found == expected {
+        Ok(())
+    } else {
+        Err(ModelAlphabetError {
+            mode,
+            expected,
+            found,
+        })
+    }
+}
+
+/// Makes sure _model_ can be used with the coding precisions of _params_
+pub(crate) fn check_model_precision<M: Model + ?Sized>(
+    model: &M,
+    params: CodecParams,
+) -> Result<(), ModelPrecisionError> {
+    let (total, max_total) = (model.get_total(), params.max_total());
+    if total > max_total {
+        Err(ModelPrecisionError {
+            total: *total,
+            max_total: *max_total,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Handles a case where compressing a symbol fails, by logging it so the symbol is skipped.<br>
+/// Skipping a byte outside of a restricted alphabet (see _options_) would lose data the alphabet
+/// was chosen for, so its error is returned instead.
+pub(crate) fn handle_compression_error(
+    compression_err: anyhow::Error,
+    options: &CodecOptions,
+) -> Result<()> {
+    match compression_err.downcast_ref() {
+        Some(ModelCfiError::UnsupportedSymbol(_)) if options.restricts_alphabet() => {
+            return Err(compression_err)
+        }
+        Some(ModelCfiError::UnsupportedSymbol(symbol)) => error!(
+            "A symbol not supported by the model ({}) was found. Skipping it",
+            symbol
+        ),
+        _ => {
+            error!("Failed to compress symbol; skipping it");
+            debug!("Compression error: {}", compression_err);
+        }
+    }
+    Ok(())
+}
+
+/// Number of bytes `write_output` collects before writing them to its handle
+const OUTPUT_CHUNK_BYTES: usize = 256;
+
+/// Writes the bytes of _bytes_ to _handle_, and returns the number of bytes written. The bytes
+/// are written in chunks rather than one by one, and short writes are retried until every byte
+/// was written.
+pub(crate) fn write_output<W: Write>(
+    handle: &mut W,
+    bytes: impl IntoIterator<Item = u8>,
+) -> Result<usize, OutputWriteError> {
+    let (mut chunk, mut chunk_len, mut written) = ([0; OUTPUT_CHUNK_BYTES], 0, 0);
+    for byte in bytes {
+        chunk[chunk_len] = byte;
+        chunk_len += 1;
+        if chunk_len == OUTPUT_CHUNK_BYTES {
+            handle.write_all(&chunk)?;
+            (written, chunk_len) = (written + chunk_len, 0);
+        }
+    }
+    if chunk_len > 0 {
+        handle.write_all(&chunk[..chunk_len])?;
+    }
+    Ok(written + chunk_len)
+}
+
+/// Flushes _model_ and primes it with _dictionary_, if one is given
+pub(crate) fn prepare_model<M: Model + ?Sized>(
+    model: &mut M,
+    parser: &mut dyn Parser,
+    dictionary: Option<&Dictionary>,
+) -> Result<()> {
+    // The models of the compressor and decompressor must start from the same state:
+    model.flush();
+    if let Some(dictionary) = dictionary {
+        dictionary.prime(model, parser)?;
+    }
+    Ok(())
+}
+
+/// Compresses everything _reader_ holds into _writer_, and reports the sizes of the output's
+/// parts.
+///
+/// The model is flushed before compression starts, so it doesn't matter what it was used for
+/// before. Symbols the model doesn't support are logged and skipped, unless they're bytes outside
+/// of the header's restricted alphabet, which stop the compression. Failing to write the output
+/// stops the compression with an [OutputWriteError].
+pub fn compress_reader<R, W, M>(
+    reader: R,
+    writer: W,
+    model: &mut M,
+    parser: &mut dyn Parser,
+    options: &CodecOptions,
+) -> Result<CompressionReport>
+where
+    R: Read,
+    W: Write,
+    M: Model + ?Sized,
+{
+    compress_stream(
+        reader,
+        writer,
+        model,
+        parser,
+        options,
+        &[],
+        options.input_len,
+    )
+}
+
+/// Writes the start of a compressed stream to _handle_: the header (if the options use one), the
+/// dictionary's hash (if the options use a dictionary) and _model_data_. The header records the
+/// data's length in bytes, and its length in coded symbols if its termination strategy uses it.
+/// Returns the number of bytes written.
+pub(crate) fn write_stream_prefix<W: Write>(
+    handle: &mut W,
+    options: &CodecOptions,
+    original_len: u64,
+    data_symbols: u64,
+    model_data: &[u8],
+) -> Result<usize, OutputWriteError> {
+    let mut written = 0;
+    if let Some(header) = options.header {
+        let header = Header {
+            dictionary: options.dictionary.is_some(),
+            original_len,
+            termination: header.termination.with_length(data_symbols),
+            ..header
+        };
+        written += write_output(handle, header.encode())?;
+    }
+    // The dictionary's hash lets the decompressor make sure it uses the same dictionary:
+    if let Some(dictionary) = options.dictionary {
+        written += write_output(handle, dictionary.hash().to_be_bytes())?;
+    }
+    written += write_output(handle, model_data.iter().copied())?;
+    Ok(written)
+}
+
+/// Writes the end of a compressed stream to _handle_: the checksum of its original data, if the
+/// header of the options asks for one. Returns the number of bytes written.
+pub(crate) fn write_stream_trailer<W: Write>(
+    handle: &mut W,
+    options: &CodecOptions,
+    checksum: &Crc32,
+) -> Result<usize, OutputWriteError> {
+    match options.header {
+        Some(header) if header.checksum => write_output(handle, checksum.value().to_be_bytes()),
+        _ => Ok(0),
+    }
+}
+
+/// Returns the last byte of the original data as decompression reproduces it: only its leading
+/// _trailing_bits_ bits are kept, unless it's 0
+pub(crate) fn decompressed_last_byte(byte: u8, trailing_bits: u8) -> u8 {
+    match trailing_bits {
+        0 => byte,
+        bits => byte & !(0xff >> bits),
+    }
+}
+
+/// Compresses everything _reader_ holds into a single compressed stream in _writer_, like
+/// `compress_reader`. The stream starts with the header, the dictionary's hash and then
+/// _model_data_: data the decompression needs in order to build the model (it's empty for models
+/// that don't depend on the input).
+///
+/// The header holds the input's length. If _input_len_ doesn't give it in advance, or the header's
+/// termination strategy needs the number of coded symbols, the compressed payload is kept in
+/// memory until all of the input was read. If the header asks for a checksum, the stream ends with
+/// the checksum of the input.
+pub(crate) fn compress_stream<R, W, M>(
+    reader: R,
+    writer: W,
+    model: &mut M,
+    parser: &mut dyn Parser,
+    options: &CodecOptions,
+    model_data: &[u8],
+    input_len: Option<u64>,
+) -> Result<CompressionReport>
+where
+    R: Read,
+    W: Write,
+    M: Model + ?Sized,
+{
+    info!("Compressing input stream. Unsupported symbols will be skipped");
+    prepare_model(model, parser, options.dictionary)?;
+    check_model_precision(model, options.params())?;
+    let termination = options
+        .header
+        .map_or_else(TerminationStrategy::default, |header| header.termination);
+    let mut compressor =
+        Compressor::with_params(model, options.params()).with_termination(termination);
+    let mut report = CompressionReport::default();
+
+    // Since we'll perform many writes, wrap the output in a buffer:
+    let mut handle = BufWriter::new(writer);
+    let buffer_payload =
+        options.header.is_some() && (input_len.is_none() || termination.length().is_some());
+    if !buffer_payload {
+        report.header_bytes +=
+            write_stream_prefix(&mut handle, options, input_len.unwrap_or(0), 0, model_data)?;
+    }
+
+    // Only the leading bits of the last byte are compressed if the header says the data isn't
+    // byte-aligned:
+    let trailing_bits = options.header.map_or(0, |header| header.trailing_bits);
+    let mut bytes = BufReader::new(reader).bytes().peekable();
+
+    let (mut payload, mut checksum) = (Vec::new(), Crc32::new());
+    // Skipped symbols aren't decompressed, so they don't count towards the data's length:
+    let mut data_symbols = 0;
+    let mut load_symbols = |symbols: Vec<Symbol>| -> Result<()> {
+        for symbol in symbols {
+            let loaded = compressor.load_symbol(symbol);
+            data_symbols += loaded.is_ok() as u64;
+            match loaded {
+                Ok(compressed_bytes) if buffer_payload => payload.extend(compressed_bytes),
+                Ok(compressed_bytes) => {
+                    report.payload_bytes += write_output(&mut handle, compressed_bytes)?
+                }
+                Err(e) => {
+                    handle_compression_error(e, options)?;
+                    report.skipped_symbols += 1;
+                }
+            }
+        }
+        Ok(())
+    };
+    let mut input_bytes = 0;
+    while let Some(byte) = bytes.next() {
+        let byte = byte?;
+        input_bytes += 1;
+        let symbols = if trailing_bits != 0 && bytes.peek().is_none() {
+            checksum.update(&[decompressed_last_byte(byte, trailing_bits)]);
+            parser
+                .parse_partial_byte(byte, trailing_bits)
+                .ok_or(PartialByteError)?
+        } else {
+            checksum.update(&[byte]);
+            parser.parse_byte(byte)
+        };
+        load_symbols(symbols)?;
+    }
+    // The parser may still buffer the end of the input:
+    load_symbols(parser.finish())?;
+    report.input_bytes = input_bytes;
+    if let Some(expected) = input_len.filter(|&len| len != report.input_bytes as u64) {
+        return Err(InputLengthChangedError {
+            expected,
+            found: report.input_bytes,
+        }
+        .into());
+    }
+    // Finalizing ends the stream with an EOF symbol (unless its length is used instead), which
+    // tells the decompressor to stop:
+    let (last_bytes, stats) = compressor.finalize()?;
+    payload.extend(last_bytes);
+    info!("Compressor finished: {stats}");
+
+    if buffer_payload {
+        let original_len = report.input_bytes as u64;
+        report.header_bytes +=
+            write_stream_prefix(&mut handle, options, original_len, data_symbols, model_data)?;
+    }
+    report.payload_bytes += write_output(&mut handle, payload)?;
+    report.footer_bytes += write_stream_trailer(&mut handle, options, &checksum)?;
+    handle.flush().map_err(OutputWriteError::from)?;
+    Ok(report)
+}
+
+/// Makes sure the header at the start of _input_ matches the settings of _expected_ and the
+/// dictionary usage of the decompression. Returns the header and its length in bytes.
+fn verify_header(
+    input: &[u8],
+    expected: Header,
+    has_dictionary: bool,
+) -> Result<(Header, usize), HeaderError> {
+    let (found, header_len) = Header::decode(input)?;
+    match (found.dictionary, has_dictionary) {
+        (true, false) => return Err(HeaderError::MissingDictionary),
+        (false, true) => return Err(HeaderError::UnexpectedDictionary),
+        _ => {}
+    }
+    // The dictionary flag was already checked against the decompression itself:
+    let expected = Header {
+        dictionary: found.dictionary,
+        ..expected
+    };
+    if !found.same_settings(&expected) {
+        return Err(HeaderError::StreamMismatch {
+            expected: Box::new(expected),
+            found: Box::new(found),
+        });
+    }
+    Ok((found, header_len))
+}
+
+/// The start of a compressed stream: its header and the dictionary's hash, if the options use them
+pub(crate) struct StreamPrefix {
+    /// Length in bytes of the prefix
+    pub len: usize,
+    /// The length of the original data according to the header, if there is one
+    pub original_len: Option<u64>,
+    /// Number of meaningful bits in the last byte of the original data, or 0 if all of its bits
+    /// are meaningful
+    pub trailing_bits: u8,
+    /// If the stream ends with a checksum of the original data
+    pub checksum: bool,
+    /// How the end of the stream's data is found
+    pub termination: TerminationStrategy,
+}
+
+/// Verifies the prefix at the start of _input_ against _options_
+pub(crate) fn read_stream_prefix(input: &[u8], options: &CodecOptions) -> Result<StreamPrefix> {
+    let mut prefix = StreamPrefix {
+        len: 0,
+        original_len: None,
+        trailing_bits: 0,
+        checksum: false,
+        termination: TerminationStrategy::default(),
+    };
+    if let Some(header) = options.header {
+        let (found, header_len) = verify_header(input, header, options.dictionary.is_some())?;
+        (prefix.len, prefix.original_len) = (header_len, Some(found.original_len));
+        (prefix.trailing_bits, prefix.checksum) = (found.trailing_bits, found.checksum);
+        prefix.termination = found.termination;
+    }
+    if let Some(dictionary) = options.dictionary {
+        let hash_len = DICTIONARY_HASH_BYTES.min(input.len() - prefix.len);
+        dictionary.verify(&input[prefix.len..prefix.len + hash_len])?;
+        prefix.len += hash_len;
+    }
+    Ok(prefix)
+}
+
+/// Decompresses the payload at the start of _input_, writing its bytes to _output_. The payload's
+/// data must match the original length and trailing bits of _prefix_. Returns the length in bytes
+/// of the payload.
+pub(crate) fn decompress_payload<W, M>(
+    input: &[u8],
+    output: &mut W,
+    model: &mut M,
+    parser: &mut dyn Parser,
+    options: &CodecOptions,
+    prefix: &StreamPrefix,
+) -> Result<usize>
+where
+    W: Write,
+    M: Model + ?Sized,
+{
+    prepare_model(model, parser, options.dictionary)?;
+    if let Some(header) = &options.header {
+        check_model_alphabet(model, header)?;
+    }
+    check_model_precision(model, options.params())?;
+    let bits = BitIterator::from_slice(input);
+    let mut decompressor = Decompressor::with_params(model, bits, options.params())
+        .with_termination(prefix.termination);
+
+    // The parser reconstructs the original bytes (e.g. reassembles every 8 bits in bit-mode):
+    let mut unparser = parser.unparser();
+    let mut bytes = match prefix.original_len {
+        Some(original_len) => decompress_exact_len(
+            &mut decompressor,
+            &mut *unparser,
+            original_len,
+            prefix.trailing_bits,
+        )?,
+        None => {
+            // The compressor ends its stream with a `Symbol::Eof`, which the decompressor reports
+            // by returning None. Every other decoded symbol is a data symbol (escapes are resolved
+            // by the decompressor itself):
+            let symbols = decompressor.symbols().collect::<Result<Vec<_>>>()?;
+            match prefix.trailing_bits {
+                0 => unparser.unparse(&symbols),
+                bits => unparser
+                    .unparse_partial(&symbols, bits)
+                    .ok_or(PartialByteError)?,
+            }
+        }
+    };
+    bytes.extend(unparser.finish());
+    // A corrupted payload may decode into other symbols, or find a spurious EOF:
+    if let Some(expected) = prefix.original_len.filter(|&len| len != bytes.len() as u64) {
+        return Err(StreamLengthError {
+            expected,
+            found: bytes.len(),
+        }
+        .into());
+    }
+    write_output(output, bytes)?;
+    Ok(decompressor.stream_len())
+}
+
+/// Decompresses the data symbols of _decompressor_ with _unparser_ until they make up the
+/// _original_len_ bytes the header gives, and returns the bytes (without those the unparser still
+/// buffers). The last byte is partial if _trailing_bits_ isn't 0, and then its bits are the last
+/// symbols (parsers split partial bytes into a symbol per bit).<br>
+/// The length ends the data rather than the EOF or the padding after it, though the end of the
+/// stream (its EOF, or its length in symbols) must still follow the data.
+fn decompress_exact_len<M, I>(
+    decompressor: &mut Decompressor<'_, M, I>,
+    unparser: &mut dyn Unparser,
+    original_len: u64,
+    trailing_bits: u8,
+) -> Result<Vec<u8>>
+where
+    M: Model + ?Sized,
+    I: Iterator<Item = bool>,
+{
+    let full_bytes = original_len - u64::from(trailing_bits != 0);
+    let mut bytes = Vec::new();
+    while (bytes.len() as u64) < full_bytes {
+        match decompressor.get_next_symbol()? {
+            Some(symbol) => bytes.extend(unparser.unparse(&[symbol])),
+            // The data ended early, which the caller reports:
+            None => return Ok(bytes),
+        }
+    }
+    if trailing_bits != 0 {
+        let mut bits = Vec::with_capacity(trailing_bits as usize);
+        while bits.len() < trailing_bits as usize {
+            match decompressor.get_next_symbol()? {
+                Some(symbol) => bits.push(symbol),
+                None => break,
+            }
+        }
+        bytes.extend(
+            unparser
+                .unparse_partial(&bits, trailing_bits)
+                .ok_or(PartialByteError)?,
+        );
+    }
+    match decompressor.get_next_symbol()? {
+        Some(_) => Err(ExtraDataError {
+            expected: original_len,
+        }
+        .into()),
+        None => Ok(bytes),
+    }
+}
+
+/// Decompresses the single compressed stream at the start of _input_, whose prefix is _prefix_,
+/// writing its bytes to _output_. Returns the length in bytes of the stream (without its trailer).
+fn decompress_stream<W, M>(
+    input: &[u8],
+    prefix: &StreamPrefix,
+    output: &mut W,
+    model: &mut M,
+    parser: &mut dyn Parser,
+    options: &CodecOptions,
+) -> Result<usize>
+where
+    W: Write,
+    M: Model + ?Sized,
+{
+    let payload_len =
+        decompress_payload(&input[prefix.len..], output, model, parser, options, prefix)?;
+    Ok(prefix.len + payload_len)
+}
+
+/// Decompresses the compressed streams _reader_ holds into _writer_, using _decompress_stream_ to
+/// decompress each stream after reading its prefix (it returns the length in bytes of the stream
+/// at the start of its input, without its trailer). The checksum of every stream that has one is
+/// verified against its decompressed data, and bytes following the first stream are handled
+/// according to `options.trailing`.
+pub(crate) fn decompress_streams<R, W, F>(
+    mut reader: R,
+    writer: W,
+    options: &CodecOptions,
+    mut decompress_stream: F,
+) -> Result<()>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(&[u8], &StreamPrefix, &mut ChecksumWriter<BufWriter<W>>) -> Result<usize>,
+{
+    info!("Decompressing input stream");
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let mut input = &bytes[..];
+    let mut handle = ChecksumWriter::new(BufWriter::new(writer));
+
+    loop {
+        let prefix = read_stream_prefix(input, options)?;
+        let stream_len = decompress_stream(input, &prefix, &mut handle)?;
+        // A truncated stream is padded by the decompressor, so it may seem longer than the input:
+        input = &input[stream_len.min(input.len())..];
+        let checksum = handle.take_checksum();
+        if prefix.checksum {
+            checksum.verify(input)?;
+            input = &input[CHECKSUM_BYTES..];
+        }
+        if input.is_empty() {
+            break;
+        }
+
+        match options.trailing {
+            TrailingPolicy::Ignore => {
+                debug!("Ignoring {} bytes after the compressed stream", input.len());
+                break;
+            }
+            TrailingPolicy::Error => {
+                handle.flush().map_err(OutputWriteError::from)?;
+                return Err(TrailingDataError(input.len()).into());
+            }
+            TrailingPolicy::MultiStream => {
+                debug!(
+                    "Decompressing another stream from the remaining {} bytes",
+                    input.len()
+                );
+            }
+        }
+    }
+
+    handle.flush().map_err(OutputWriteError::from)?;
+    Ok(())
+}
+
+/// Decompresses the compressed stream _reader_ holds into _writer_. The model, parser and
+/// dictionary must be the same ones used for compression.
+///
+/// The model is flushed before decompression starts, so it doesn't matter what it was used for
+/// before. Bytes following the compressed stream are handled according to `options.trailing`.
+pub fn decompress_reader<R, W, M>(
+    reader: R,
+    writer: W,
+    model: &mut M,
+    parser: &mut dyn Parser,
+    options: &CodecOptions,
+) -> Result<()>
+where
+    R: Read,
+    W: Write,
+    M: Model + ?Sized,
+{
+    decompress_streams(reader, writer, options, |input, prefix, output| {
+        decompress_stream(input, prefix, output, model, parser, options)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::IntegrityError;
+    use crate::dictionary::DictionaryError;
+    use crate::frequencies::Frequency;
+    use crate::models::adaptive::AdaptiveModel;
+    use crate::models::distributions::custom::CustomDistributionModel;
+    use crate::models::distributions::uniform::UniformDistributionModel;
+    use crate::models::ppm::{PpmModel, DEFAULT_PPM_ORDER};
+    use crate::parser::{BitParser, ByteParser};
+    use crate::sim::{BitSIM, DefaultSIM, Symbol, SymbolIndexMapping};
+    use std::cell::Cell;
+
+    fn parser(bit_mode: bool) -> Box<dyn Parser> {
+        if bit_mode {
+            Box::new(BitParser)
+        } else {
+            Box::new(ByteParser)
+        }
+    }
+
+    fn compress_bytes<M: Model + ?Sized>(
+        model: &mut M,
+        bit_mode: bool,
+        options: &CodecOptions,
+        input: &[u8],
+    ) -> Vec<u8> {
+        let mut compressed = Vec::new();
+        compress_reader(
+            input,
+            &mut compressed,
+            model,
+            &mut parser(bit_mode),
+            options,
+        )
+        .unwrap();
+        compressed
+    }
+
+    fn decompress_bytes<M: Model + ?Sized>(
+        model: &mut M,
+        bit_mode: bool,
+        options: &CodecOptions,
+        compressed: &[u8],
+    ) -> Result<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        decompress_reader(
+            compressed,
+            &mut decompressed,
+            model,
+            &mut parser(bit_mode),
+            options,
+        )?;
+        Ok(decompressed)
+    }
+
+    /// Builds a model of every kind with the given SIM
+    fn mode_models<SIM: SymbolIndexMapping + Copy + 'static>(sim: SIM) -> [Box<dyn Model>; 3] {
+        [
+            Box::new(UniformDistributionModel::new(sim)),
+            Box::new(AdaptiveModel::new(sim)),
+            Box::new(PpmModel::new(sim, DEFAULT_PPM_ORDER)),
+        ]
+    }
+
+    #[test]
+    fn test_compress_decompress_round_trip() {
+        let input = b"abracadabra, abracadabra!".repeat(10);
+        for (bit_mode, models) in [
+            (false, mode_models(DefaultSIM)),
+            (true, mode_models(BitSIM)),
+        ] {
+            for mut model in models {
+                let options = CodecOptions::default();
+                let compressed = compress_bytes(&mut model, bit_mode, &options, &input);
+                let decompressed =
+                    decompress_bytes(&mut model, bit_mode, &options, &compressed).unwrap();
+                assert_eq!(decompressed, input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_same_model_instance_round_trip() {
+        let input = b"mississippi mississippi mississippi";
+        // Compressing trains the adaptive model, so decompression must flush it first:
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let options = CodecOptions::default();
+
+        let compressed = compress_bytes(&mut model, false, &options, input);
+        let decompressed = decompress_bytes(&mut model, false, &options, &compressed).unwrap();
+        assert_eq!(decompressed, input);
+    }
+
+    #[test]
+    fn test_empty_input_round_trip() {
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let options = CodecOptions::default();
+        let compressed = compress_bytes(&mut model, false, &options, &[]);
+        let decompressed = decompress_bytes(&mut model, false, &options, &compressed).unwrap();
+        assert!(decompressed.is_empty());
+
+        for termination in [
+            TerminationStrategy::EofSymbol,
+            TerminationStrategy::Length(0),
+            TerminationStrategy::Both(0),
+        ] {
+            let options = CodecOptions {
+                header: Some(Header {
+                    termination,
+                    ..Default::default()
+                }),
+                trailing: TrailingPolicy::MultiStream,
+                ..Default::default()
+            };
+            let mut compressed = compress_bytes(&mut model, false, &options, &[]);
+            let decompressed = decompress_bytes(&mut model, false, &options, &compressed).unwrap();
+            assert!(decompressed.is_empty(), "{termination:?}");
+
+            // An empty stream doesn't decode a byte of its own or of the stream after it:
+            compressed.extend(compress_bytes(&mut model, false, &options, b"next"));
+            let decompressed = decompress_bytes(&mut model, false, &options, &compressed).unwrap();
+            assert_eq!(decompressed, b"next", "{termination:?}");
+        }
+    }
+
+    #[test]
+    fn test_preset_dictionary() {
+        let dictionary =
+            Dictionary::new(br#"{"id": 0, "name": "", "tags": [], "active": false}"#.repeat(20));
+        let input = br#"{"id": 7, "name": "ppm", "tags": ["cli"], "active": true}"#;
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let options = CodecOptions {
+            dictionary: Some(&dictionary),
+            ..Default::default()
+        };
+
+        let with_dictionary = compress_bytes(&mut model, false, &options, input);
+        let without_dictionary = compress_bytes(&mut model, false, &CodecOptions::default(), input);
+        // Even with the hash's overhead, knowing the dictionary's statistics pays off:
+        assert!(with_dictionary.len() < without_dictionary.len());
+
+        let decompressed = decompress_bytes(&mut model, false, &options, &with_dictionary).unwrap();
+        assert_eq!(decompressed, input);
+
+        // A different dictionary is rejected:
+        let other = Dictionary::new(b"some other dictionary".to_vec());
+        let options = CodecOptions {
+            dictionary: Some(&other),
+            ..Default::default()
+        };
+        let err = decompress_bytes(&mut model, false, &options, &with_dictionary).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(DictionaryError::Mismatch { .. })
+        ));
+    }
+
+    /// Compresses _input_ with the order-0 model
+    fn compress_order0(input: &[u8]) -> Vec<u8> {
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        compress_bytes(&mut model, false, &CodecOptions::default(), input)
+    }
+
+    /// Decompresses _compressed_ with the order-0 model, and the given trailing bytes policy
+    fn decompress_with_trailing(compressed: &[u8], trailing: TrailingPolicy) -> Result<Vec<u8>> {
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let options = CodecOptions {
+            trailing,
+            ..Default::default()
+        };
+        decompress_bytes(&mut model, false, &options, compressed)
+    }
+
+    #[test]
+    fn test_trailing_ignore() {
+        let mut compressed = compress_order0(b"first stream");
+        compressed.extend_from_slice(b"trailing garbage");
+
+        let decompressed = decompress_with_trailing(&compressed, TrailingPolicy::Ignore).unwrap();
+        assert_eq!(decompressed, b"first stream");
+    }
+
+    #[test]
+    fn test_trailing_error() {
+        let compressed = compress_order0(b"first stream");
+        // Without trailing bytes, there's nothing to complain about:
+        let decompressed = decompress_with_trailing(&compressed, TrailingPolicy::Error).unwrap();
+        assert_eq!(decompressed, b"first stream");
+
+        let mut with_garbage = compressed;
+        with_garbage.extend_from_slice(b"garbage");
+        let err = decompress_with_trailing(&with_garbage, TrailingPolicy::Error).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(TrailingDataError(7))));
+    }
+
+    #[test]
+    fn test_trailing_multi_stream() {
+        let mut compressed = compress_order0(b"first, ");
+        compressed.extend(compress_order0(b"second, "));
+        compressed.extend(compress_order0(b"third"));
+
+        let decompressed =
+            decompress_with_trailing(&compressed, TrailingPolicy::MultiStream).unwrap();
+        assert_eq!(decompressed, b"first, second, third");
+    }
+
+    #[test]
+    fn test_trailing_policy_names() {
+        for policy in [
+            TrailingPolicy::Ignore,
+            TrailingPolicy::Error,
+            TrailingPolicy::MultiStream,
+        ] {
+            assert_eq!(
+                policy.to_string().parse::<TrailingPolicy>().unwrap(),
+                policy
+            );
+        }
+        assert!("junk".parse::<TrailingPolicy>().is_err());
+    }
+
+    /// A writer that accepts a limited number of bytes, like a disk that fills up
+    struct LimitedWriter {
+        capacity: usize,
+        written: Vec<u8>,
+    }
+
+    impl Write for LimitedWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let amount = buf.len().min(self.capacity - self.written.len());
+            if amount == 0 && !buf.is_empty() {
+                return Err(ErrorKind::StorageFull.into());
+            }
+            self.written.extend_from_slice(&buf[..amount]);
+            Ok(amount)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_full_output_fails_compression() {
+        let input = b"not enough room for this".repeat(100);
+        let mut writer = LimitedWriter {
+            capacity: 16,
+            written: Vec::new(),
+        };
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let err = compress_reader(
+            &input[..],
+            &mut writer,
+            &mut model,
+            &mut ByteParser,
+            &CodecOptions::default(),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(OutputWriteError::OutputFull(_))
+        ));
+        assert_eq!(writer.written.len(), 16);
+    }
+
+    /// A writer that counts its calls, and writes at most 100 bytes in each
+    #[derive(Default)]
+    struct CountingWriter {
+        writes: usize,
+        written: Vec<u8>,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            let amount = buf.len().min(100);
+            self.written.extend_from_slice(&buf[..amount]);
+            Ok(amount)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_output_written_in_chunks() {
+        let bytes: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let mut writer = CountingWriter::default();
+        assert_eq!(
+            write_output(&mut writer, bytes.iter().copied()).unwrap(),
+            1000
+        );
+        // Short writes are retried, and every byte is written in order:
+        assert_eq!(writer.written, bytes);
+        // 4 chunks, each written in up to 3 short writes:
+        assert_eq!(writer.writes, 3 + 3 + 3 + 3);
+
+        let mut writer = CountingWriter::default();
+        assert_eq!(write_output(&mut writer, []).unwrap(), 0);
+        assert_eq!(writer.writes, 0);
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let input = b"headers make streams self-describing";
+        let header = Header {
+            bit_mode: true,
+            model_id: 2,
+            ..Default::default()
+        };
+        let options = CodecOptions {
+            header: Some(header),
+            ..Default::default()
+        };
+        let mut model = AdaptiveModel::new(BitSIM);
+        let compressed = compress_bytes(&mut model, true, &options, input);
+        let (written, _) = Header::decode(&compressed).unwrap();
+        assert!(written.same_settings(&header));
+        assert_eq!(written.original_len, input.len() as u64);
+
+        let decompressed = decompress_bytes(&mut model, true, &options, &compressed).unwrap();
+        assert_eq!(decompressed, input);
+    }
+
+    #[test]
+    fn test_header_length_round_trip() {
+        let mut byte_model = AdaptiveModel::new(DefaultSIM);
+        let mut bit_model = AdaptiveModel::new(BitSIM);
+        // Payloads ending on a byte boundary and payloads ending with padding bits:
+        for len in 0..=32 {
+            let input: Vec<u8> = (0..len).map(|i| b"length"[i % 6]).collect();
+            for (bit_mode, model) in [
+                (false, &mut byte_model as &mut dyn Model),
+                (true, &mut bit_model),
+            ] {
+                let options = CodecOptions {
+                    header: Some(Header {
+                        bit_mode,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                };
+                let compressed = compress_bytes(model, bit_mode, &options, &input);
+                let decompressed =
+                    decompress_bytes(model, bit_mode, &options, &compressed).unwrap();
+                assert_eq!(decompressed, input);
+            }
+        }
+    }
+
+    /// Reads _input_, and records how many bytes _written_ counted once all of it was read
+    struct WatchedReader<'a> {
+        input: &'a [u8],
+        written: &'a Cell<usize>,
+        written_at_end: Option<usize>,
+    }
+
+    impl Read for WatchedReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let read = self.input.read(buf)?;
+            if read == 0 {
+                self.written_at_end = Some(self.written.get());
+            }
+            Ok(read)
+        }
+    }
+
+    /// Counts the bytes written into it in _0_
+    struct TallyWriter<'a>(&'a Cell<usize>);
+
+    impl Write for TallyWriter<'_> {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.set(self.0.get() + buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_known_input_len_writes_payload_while_reading() {
+        let input: Vec<u8> = (0..1 << 16).map(|i| (i * 7 % 251) as u8).collect();
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let written = Cell::new(0);
+        let compress = |model: &mut UniformDistributionModel<DefaultSIM>, input_len| {
+            let options = CodecOptions {
+                header: Some(Header::default()),
+                input_len,
+                ..Default::default()
+            };
+            written.set(0);
+            let mut reader = WatchedReader {
+                input: &input,
+                written: &written,
+                written_at_end: None,
+            };
+            let result = compress_reader(
+                &mut reader,
+                TallyWriter(&written),
+                model,
+                &mut ByteParser,
+                &options,
+            );
+            (result, reader.written_at_end)
+        };
+
+        // Without the length, the whole payload waits for the end of the input:
+        let (report, written_at_end) = compress(&mut model, None);
+        let total = report.unwrap().output_bytes();
+        assert_eq!(written_at_end, Some(0));
+        // With it, most of the payload was written by then:
+        let (report, written_at_end) = compress(&mut model, Some(input.len() as u64));
+        assert_eq!(report.unwrap().output_bytes(), total);
+        assert!(written_at_end.unwrap() > total / 2);
+
+        // A wrong length fails the compression:
+        let (report, _) = compress(&mut model, Some(input.len() as u64 + 1));
+        assert!(report.unwrap_err().is::<InputLengthChangedError>());
+    }
+
+    #[test]
+    fn test_termination_strategies_round_trip() {
+        let input = b"terminated by an eof, a length, or both";
+        for termination in [
+            TerminationStrategy::EofSymbol,
+            TerminationStrategy::Length(0),
+            TerminationStrategy::Both(0),
+        ] {
+            for bit_mode in [false, true] {
+                let options = CodecOptions {
+                    header: Some(Header {
+                        bit_mode,
+                        termination,
+                        ..Default::default()
+                    }),
+                    trailing: TrailingPolicy::MultiStream,
+                    ..Default::default()
+                };
+                let mut model: Box<dyn Model> = if bit_mode {
+                    Box::new(AdaptiveModel::new(BitSIM))
+                } else {
+                    Box::new(AdaptiveModel::new(DefaultSIM))
+                };
+                let mut compressed = compress_bytes(&mut model, bit_mode, &options, input);
+                // The header records the number of coded symbols, which are bits in bit-mode:
+                let (written, _) = Header::decode(&compressed).unwrap();
+                let symbols = input.len() as u64 * if bit_mode { 8 } else { 1 };
+                assert_eq!(written.termination, termination.with_length(symbols));
+
+                // Every stream of a multi-stream input ends where its strategy says:
+                compressed.extend(compress_bytes(&mut model, bit_mode, &options, b"next"));
+                let decompressed =
+                    decompress_bytes(&mut model, bit_mode, &options, &compressed).unwrap();
+                assert_eq!(decompressed, [&input[..], b"next"].concat());
+            }
+        }
+    }
+
+    #[test]
+    fn test_model_alphabet_must_match_mode() {
+        let options = CodecOptions {
+            header: Some(Header::default()),
+            ..Default::default()
+        };
+        let mut byte_model = AdaptiveModel::new(DefaultSIM);
+        let compressed = compress_bytes(&mut byte_model, false, &options, b"byte-mode");
+
+        // A bit-model can't decompress a byte-mode stream:
+        let mut bit_model = AdaptiveModel::new(BitSIM);
+        let err = decompress_bytes(&mut bit_model, false, &options, &compressed).unwrap_err();
+        let err = err.downcast_ref::<ModelAlphabetError>().unwrap();
+        assert_eq!(err.mode, "byte");
+        assert_eq!(err.expected, DefaultSIM.supported_symbols_count());
+        assert_eq!(err.found, BitSIM.supported_symbols_count());
+    }
+
+    #[test]
+    fn test_skipped_symbols_reported() {
+        // A model which can't compress the letter 'z':
+        let sim = DefaultSIM;
+        let mut frequencies = vec![Frequency::one(); sim.supported_symbols_count()];
+        frequencies[sim.get_index(&Symbol::Byte(b'z')).unwrap()] = Frequency::zero();
+        let mut model = CustomDistributionModel::new(sim, &frequencies).unwrap();
+
+        let mut compressed = Vec::new();
+        let report = compress_reader(
+            &b"pizza"[..],
+            &mut compressed,
+            &mut model,
+            &mut ByteParser,
+            &CodecOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(report.input_bytes, 5);
+        assert_eq!(report.skipped_symbols, 2);
+        assert_eq!(
+            decompress_bytes(&mut model, false, &CodecOptions::default(), &compressed).unwrap(),
+            b"pia"
+        );
+    }
+
+    #[test]
+    fn test_restricted_alphabet_stops_at_unsupported_byte() {
+        let options = CodecOptions {
+            header: Some(Header {
+                alphabet: Alphabet::Dna,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut model = AdaptiveModel::new(Alphabet::Dna.sim());
+        let err = compress_reader(
+            &b"GATTACA\nGATTACA"[..],
+            Vec::new(),
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ModelCfiError>(),
+            Some(&ModelCfiError::UnsupportedSymbol(Symbol::Byte(b'\n')))
+        );
+    }
+
+    #[test]
+    fn test_bit_mode_random_bits_cost_at_most_a_bit() {
+        // Pseudo-random bytes, so every bit is equally likely to be 0 or 1:
+        let mut state: u32 = 0x2545_f491;
+        let input: Vec<u8> = (0..4096)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect();
+        // Besides the data, a few bytes code the EOF and flush the interval:
+        let overhead = 8;
+
+        let options = CodecOptions::default();
+        let mut model = AdaptiveModel::new(BitSIM);
+        let compressed = compress_bytes(&mut model, true, &options, &input);
+        assert!(compressed.len() <= input.len() + overhead);
+        let decompressed = decompress_bytes(&mut model, true, &options, &compressed).unwrap();
+        assert_eq!(decompressed, input);
+
+        // Even without adapting, every bit costs 2 bits at most rather than 8 bits of a byte
+        // alphabet:
+        let mut uniform = UniformDistributionModel::new(BitSIM);
+        let compressed = compress_bytes(&mut uniform, true, &options, &input);
+        assert!(compressed.len() <= 2 * input.len() + overhead);
+    }
+
+    #[test]
+    fn test_bit_mode_needs_bit_model() {
+        // A byte model supports none of the bit symbols, so all of them are skipped:
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let report = compress_reader(
+            &b"bits"[..],
+            Vec::new(),
+            &mut model,
+            &mut BitParser,
+            &CodecOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(report.skipped_symbols, 4 * 8);
+    }
+
+    #[test]
+    fn test_trailing_bits_round_trip() {
+        // 21 meaningful bits, the last 3 bits of the last byte are padding:
+        let input = [0b10110011, 0b01011100, 0b11111000];
+        let compression_options = CodecOptions {
+            header: Some(Header {
+                bit_mode: true,
+                trailing_bits: 5,
+                checksum: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut model = AdaptiveModel::new(BitSIM);
+        let compressed = compress_bytes(&mut model, true, &compression_options, &input);
+
+        // Only the meaningful bits are compressed, so setting the padding changes nothing:
+        let mut padded = input;
+        padded[2] |= 0b111;
+        assert_eq!(
+            compress_bytes(&mut model, true, &compression_options, &padded),
+            compressed
+        );
+
+        // The trailing bits are read from the header:
+        let decompression_options = CodecOptions {
+            header: Some(Header {
+                bit_mode: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let decompressed =
+            decompress_bytes(&mut model, true, &decompression_options, &compressed).unwrap();
+        assert_eq!(decompressed, input);
+    }
+
+    #[test]
+    fn test_trailing_bits_need_bit_mode() {
+        let options = CodecOptions {
+            header: Some(Header {
+                trailing_bits: 5,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let mut compressed = Vec::new();
+        let err = compress_reader(
+            &b"bytes"[..],
+            &mut compressed,
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap_err();
+        assert!(err.downcast_ref::<PartialByteError>().is_some());
+    }
+
+    #[test]
+    fn test_header_length_mismatch_detected() {
+        let options = CodecOptions {
+            header: Some(Header::default()),
+            ..Default::default()
+        };
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let compressed = compress_bytes(&mut model, false, &options, b"exact length");
+
+        // Replace the header with one claiming a different length:
+        let (header, header_len) = Header::decode(&compressed).unwrap();
+        let mut corrupted = Header {
+            original_len: header.original_len + 1,
+            ..header
+        }
+        .encode();
+        corrupted.extend_from_slice(&compressed[header_len..]);
+
+        let err = decompress_bytes(&mut model, false, &options, &corrupted).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StreamLengthError {
+                expected: 13,
+                found: 12
+            })
+        ));
+
+        // Decompression stops once the header's length was decompressed, so data after it is
+        // found instead of being decompressed:
+        let mut corrupted = Header {
+            original_len: header.original_len - 1,
+            ..header
+        }
+        .encode();
+        corrupted.extend_from_slice(&compressed[header_len..]);
+        let err = decompress_bytes(&mut model, false, &options, &corrupted).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(ExtraDataError { expected: 11 })
+        ));
+    }
+
+    #[test]
+    fn test_checksum_detects_corruption() {
+        let options = CodecOptions {
+            header: Some(Header {
+                checksum: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let input = b"the checksum covers the original data";
+        let mut model = UniformDistributionModel::new(DefaultSIM);
+        let mut compressed = Vec::new();
+        let report = compress_reader(
+            &input[..],
+            &mut compressed,
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(report.footer_bytes, CHECKSUM_BYTES);
+        assert_eq!(
+            decompress_bytes(&mut model, false, &options, &compressed).unwrap(),
+            input
+        );
+
+        // Flipping any bit of the payload either fails the decompression, or only flips a padding
+        // bit (which doesn't change the data). The checksum catches corruptions that still decode
+        // into data of the right length:
+        let (_, header_len) = Header::decode(&compressed).unwrap();
+        let mut mismatches = 0;
+        for bit in 8 * header_len..8 * (compressed.len() - CHECKSUM_BYTES) {
+            let mut corrupted = compressed.clone();
+            corrupted[bit / 8] ^= 1 << (bit % 8);
+            match decompress_bytes(&mut model, false, &options, &corrupted) {
+                Ok(decompressed) => assert_eq!(decompressed, input),
+                Err(err) => {
+                    if let Some(IntegrityError::ChecksumMismatch { .. }) = err.downcast_ref() {
+                        mismatches += 1;
+                    }
+                }
+            }
+        }
+        assert!(mismatches > 0);
+
+        // Flipping a bit of the trailer itself is reported too:
+        let mut corrupted = compressed.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        let err = decompress_bytes(&mut model, false, &options, &corrupted).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(IntegrityError::ChecksumMismatch { .. })
+        ));
+
+        // Without its trailer, the stream can't be verified:
+        let truncated = &compressed[..compressed.len() - 1];
+        let err = decompress_bytes(&mut model, false, &options, truncated).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&IntegrityError::MissingChecksum));
+    }
+
+    #[test]
+    fn test_checksum_is_per_stream() {
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let mut compressed = Vec::new();
+        for checksum in [true, false, true] {
+            let options = CodecOptions {
+                header: Some(Header {
+                    checksum,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            compressed.extend(compress_bytes(&mut model, false, &options, b"stream"));
+        }
+
+        let options = CodecOptions {
+            trailing: TrailingPolicy::MultiStream,
+            header: Some(Header::default()),
+            ..Default::default()
+        };
+        let decompressed = decompress_bytes(&mut model, false, &options, &compressed).unwrap();
+        assert_eq!(decompressed, b"stream".repeat(3));
+    }
+
+    #[test]
+    fn test_header_detects_wrong_input() {
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let options = CodecOptions {
+            header: Some(Header::default()),
+            ..Default::default()
+        };
+
+        // Not a compressed stream:
+        let err = decompress_bytes(&mut model, false, &options, b"plain text").unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&HeaderError::BadMagic));
+
+        // A stream compressed with a dictionary, decompressed without one:
+        let dictionary = Dictionary::new(b"dictionary".to_vec());
+        let with_dictionary = CodecOptions {
+            dictionary: Some(&dictionary),
+            header: Some(Header::default()),
+            ..Default::default()
+        };
+        let compressed = compress_bytes(&mut model, false, &with_dictionary, b"text");
+        let err = decompress_bytes(&mut model, false, &options, &compressed).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&HeaderError::MissingDictionary));
+        let decompressed =
+            decompress_bytes(&mut model, false, &with_dictionary, &compressed).unwrap();
+        assert_eq!(decompressed, b"text");
+    }
+
+    #[test]
+    fn test_header_mismatch_in_multi_stream() {
+        let options_with_model = |model_id| CodecOptions {
+            trailing: TrailingPolicy::MultiStream,
+            header: Some(Header {
+                model_id,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let mut compressed = compress_bytes(&mut model, false, &options_with_model(1), b"first");
+        compressed.extend(compress_bytes(
+            &mut model,
+            false,
+            &options_with_model(2),
+            b"second",
+        ));
+
+        let err =
+            decompress_bytes(&mut model, false, &options_with_model(1), &compressed).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(HeaderError::StreamMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_tiny_input_report_shows_expansion() {
+        let dictionary = Dictionary::new(b"dictionary".to_vec());
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        let options = CodecOptions {
+            dictionary: Some(&dictionary),
+            ..Default::default()
+        };
+        let report =
+            compress_reader(&b"a"[..], Vec::new(), &mut model, &mut ByteParser, &options).unwrap();
+
+        assert_eq!(report.input_bytes, 1);
+        assert_eq!(report.header_bytes, DICTIONARY_HASH_BYTES);
+        assert!(report.payload_bytes > 0);
+        // The header alone is larger than the input:
+        assert!(report.ratio().unwrap() > 1.0);
+        assert!(report.is_expansion());
+        assert!(report.to_string().contains("EXPANDED"));
+    }
+}
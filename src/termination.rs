@@ -0,0 +1,144 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//! How decompression finds the end of a compressed stream's data. The data either ends with the
+//! EOF symbol, or its length is recorded in the stream's header (or both, cross-checking each
+//! other).
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// How the end of the coded data is found. Lengths count the data symbols the compressor coded
+/// (bytes in byte-mode, bits in bit-mode, and the parser's symbols if it doesn't code every byte as
+/// a single symbol), not including the EOF.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TerminationStrategy {
+    /// The data ends with the EOF symbol, so the stream delimits itself and its length doesn't
+    /// have to be known before it's written (e.g. when piping it)
+    #[default]
+    EofSymbol,
+    /// The data holds exactly the given number of data symbols, and doesn't end with an EOF.
+    /// Decompression stops after decoding them, instead of decoding the padding that follows
+    Length(u64),
+    /// The data holds the given number of data symbols followed by the EOF symbol, and
+    /// decompression checks that they agree
+    Both(u64),
+}
+
+impl TerminationStrategy {
+    /// Returns the strategy's id, which is stored in the header of a compressed stream
+    pub fn id(&self) -> u8 {
+        match self {
+            TerminationStrategy::EofSymbol => 0,
+            TerminationStrategy::Length(_) => 1,
+            TerminationStrategy::Both(_) => 2,
+        }
+    }
+
+    /// Returns the strategy with the given id and length (which is ignored by `EofSymbol`), or None
+    /// if no strategy has that id
+    pub fn from_id(id: u8, length: u64) -> Option<Self> {
+        match id {
+            0 => Some(TerminationStrategy::EofSymbol),
+            1 => Some(TerminationStrategy::Length(length)),
+            2 => Some(TerminationStrategy::Both(length)),
+            _ => None,
+        }
+    }
+
+    /// The number of data symbols the strategy expects, or None if only the EOF ends the data
+    pub fn length(&self) -> Option<u64> {
+        match self {
+            TerminationStrategy::EofSymbol => None,
+            TerminationStrategy::Length(length) | TerminationStrategy::Both(length) => {
+                Some(*length)
+            }
+        }
+    }
+
+    /// Whether the data ends with the EOF symbol
+    pub fn uses_eof(&self) -> bool {
+        !matches!(self, TerminationStrategy::Length(_))
+    }
+
+    /// Returns the same strategy, expecting _length_ data symbols instead (if it uses a length)
+    pub fn with_length(self, length: u64) -> Self {
+        Self::from_id(self.id(), length).expect("The strategy's own id is valid")
+    }
+}
+
+/// The strategies are named without their lengths, which compression fills in once the data was
+/// coded
+impl Display for TerminationStrategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TerminationStrategy::EofSymbol => write!(f, "eof"),
+            TerminationStrategy::Length(_) => write!(f, "length"),
+            TerminationStrategy::Both(_) => write!(f, "both"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Unknown termination strategy \"{0}\" (possible values: eof, length, both)")]
+pub struct UnknownTerminationStrategy(String);
+
+impl FromStr for TerminationStrategy {
+    type Err = UnknownTerminationStrategy;
+
+    /// Parses a strategy's name. Strategies using a length are parsed with a length of 0
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "eof" => Ok(TerminationStrategy::EofSymbol),
+            "length" => Ok(TerminationStrategy::Length(0)),
+            "both" => Ok(TerminationStrategy::Both(0)),
+            _ => Err(UnknownTerminationStrategy(s.to_string())),
+        }
+    }
+}
+
+/// Decompression of data whose EOF and length don't agree fails with one of these errors
+#[derive(Debug, Error, PartialEq)]
+pub enum TerminationError {
+    #[error("An EOF was decompressed after {found} data symbols, but the stream's length is {expected} symbols (it may be corrupted)")]
+    EarlyEof { expected: u64, found: u64 },
+    #[error("The stream's length is {0} data symbols, but they aren't followed by an EOF (it may be corrupted)")]
+    MissingEof(u64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_and_names_round_trip() {
+        for strategy in [
+            TerminationStrategy::EofSymbol,
+            TerminationStrategy::Length(7),
+            TerminationStrategy::Both(7),
+        ] {
+            assert_eq!(
+                TerminationStrategy::from_id(strategy.id(), 7),
+                Some(strategy)
+            );
+            let parsed: TerminationStrategy = strategy.to_string().parse().unwrap();
+            assert_eq!(parsed.with_length(7), strategy);
+        }
+        assert_eq!(TerminationStrategy::from_id(3, 0), None);
+        assert!("none".parse::<TerminationStrategy>().is_err());
+    }
+}
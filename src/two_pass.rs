@@ -0,0 +1,350 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::frequencies::encoding::{decode_frequencies, encode_frequencies};
+use crate::frequencies::Frequency;
+use crate::models::distributions::custom::CustomDistributionModel;
+use crate::parser::Parser;
+use crate::report::CompressionReport;
+use crate::sim::{Symbol, SymbolIndexMapping};
+use crate::stream::{compress_stream, decompress_payload, decompress_streams, CodecOptions};
+use anyhow::Result;
+use log::{debug, info};
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use thiserror::Error;
+
+/// Default limit for the number of bytes two-pass compression buffers when its input can't be read
+/// twice (256 MiB)
+pub const DEFAULT_TWO_PASS_BUFFER_LIMIT: usize = 256 << 20;
+
+#[derive(Debug, Error)]
+#[error("Two-pass compression of input that can't be read twice (such as piped input) buffers it, but it's larger than {0} bytes. Compress a file instead")]
+pub struct BufferLimitError(pub usize);
+
+/// Counts how many times every symbol appears in the input _reader_ holds, as _parser_ parses it.
+/// Returns the frequencies of the symbols' indices in _sim_ (which include a single EOF), and the
+/// input's length in bytes.
+///
+/// If the counts add up to more than _max_total_, they're halved until they don't (counts of
+/// symbols which appear in the input are rounded up, so they stay non-zero).
+pub fn count_frequencies<R: Read, SIM: SymbolIndexMapping>(
+    reader: R,
+    parser: &mut dyn Parser,
+    sim: &SIM,
+    max_total: Frequency,
+) -> Result<(Vec<Frequency>, u64)> {
+    let mut counts = vec![0u64; sim.supported_symbols_count()];
+    let mut input_len = 0;
+    let mut count = |symbols: Vec<Symbol>| {
+        for symbol in symbols {
+            if let Some(index) = sim.get_index(&symbol) {
+                counts[index] += 1;
+            }
+        }
+    };
+    for byte in BufReader::new(reader).bytes() {
+        input_len += 1;
+        count(parser.parse_byte(byte?));
+    }
+    // Finishing returns the parser to its initial state, for compressing the input:
+    count(parser.finish());
+    // The compressor ends the stream with an EOF:
+    if let Some(eof_index) = sim.get_index(&Symbol::Eof) {
+        counts[eof_index] = 1;
+    }
+
+    while counts.iter().sum::<u64>() > *max_total {
+        debug!("Two-pass: Halving the counted frequencies so they fit in a frequency table");
+        counts
+            .iter_mut()
+            .for_each(|count| *count = count.div_ceil(2));
+    }
+    let frequencies = counts
+        .into_iter()
+        .map(|count| Frequency::new(count).expect("Counts were scaled to fit in a frequency"))
+        .collect();
+    Ok((frequencies, input_len))
+}
+
+/// Compresses everything _reader_ holds into _writer_ with a model whose distribution is counted
+/// from the input itself, which gives the best order-0 compression.
+///
+/// The input is read twice: first to count its frequencies, then (after seeking back to where it
+/// started) to compress it, so neither the input nor its compression has to fit in memory. The
+/// counted table is stored at the start of the compressed stream, after the header.
+///
+/// _sim_ must support the symbols _parser_ parses into (e.g. `BitSIM` for `BitParser`).
+pub fn compress_two_pass<R, W, SIM>(
+    mut reader: R,
+    writer: W,
+    parser: &mut dyn Parser,
+    sim: SIM,
+    options: &CodecOptions,
+) -> Result<CompressionReport>
+where
+    R: Read + Seek,
+    W: Write,
+    SIM: SymbolIndexMapping,
+{
+    info!("Two-pass: Counting the input's frequencies");
+    let start = reader.stream_position()?;
+    let (frequencies, input_len) =
+        count_frequencies(&mut reader, parser, &sim, options.params().max_total())?;
+    reader.seek(SeekFrom::Start(start))?;
+
+    let mut model = CustomDistributionModel::new(sim, &frequencies)?;
+    let table = encode_frequencies(&frequencies);
+    compress_stream(
+        reader,
+        writer,
+        &mut model,
+        parser,
+        options,
+        &table,
+        Some(input_len),
+    )
+}
+
+/// Like `compress_two_pass`, but for input that can't be read twice (such as piped input): the
+/// input is buffered in memory, as long as it's at most _buffer_limit_ bytes long.
+pub fn compress_two_pass_buffered<R, W, SIM>(
+    reader: R,
+    writer: W,
+    parser: &mut dyn Parser,
+    sim: SIM,
+    options: &CodecOptions,
+    buffer_limit: usize,
+) -> Result<CompressionReport>
+where
+    R: Read,
+    W: Write,
+    SIM: SymbolIndexMapping,
+{
+    let mut buffer = Vec::new();
+    // Read one byte past the limit, to know if it was exceeded:
+    reader
+        .take(buffer_limit as u64 + 1)
+        .read_to_end(&mut buffer)?;
+    if buffer.len() > buffer_limit {
+        return Err(BufferLimitError(buffer_limit).into());
+    }
+    compress_two_pass(Cursor::new(buffer), writer, parser, sim, options)
+}
+
+/// Decompresses the compressed stream _reader_ holds into _writer_, which was compressed by
+/// `compress_two_pass` with the same parser, SIM and dictionary.
+pub fn decompress_two_pass<R, W, SIM>(
+    reader: R,
+    writer: W,
+    parser: &mut dyn Parser,
+    sim: SIM,
+    options: &CodecOptions,
+) -> Result<()>
+where
+    R: Read,
+    W: Write,
+    SIM: SymbolIndexMapping + Clone,
+{
+    decompress_streams(reader, writer, options, |input, prefix, output| {
+        let (frequencies, table_len) =
+            decode_frequencies(&input[prefix.len..], sim.supported_symbols_count())?;
+        let mut model = CustomDistributionModel::new(sim.clone(), &frequencies)?;
+
+        let data_start = prefix.len + table_len;
+        let payload_len = decompress_payload(
+            &input[data_start..],
+            output,
+            &mut model,
+            parser,
+            options,
+            prefix,
+        )?;
+        Ok(data_start + payload_len)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::Header;
+    use crate::models::adaptive::AdaptiveModel;
+    use crate::parser::{BitParser, ByteParser};
+    use crate::sim::{BitSIM, DefaultSIM};
+    use crate::stream::{compress_reader, TrailingPolicy};
+
+    /// Input with a skewed distribution of a few symbols
+    fn skewed_input() -> Vec<u8> {
+        b"aaaaaaaabbbbccd".repeat(50)
+    }
+
+    fn compress(input: &[u8], bit_mode: bool, options: &CodecOptions) -> Vec<u8> {
+        let mut compressed = Vec::new();
+        let input = Cursor::new(input);
+        if bit_mode {
+            compress_two_pass(input, &mut compressed, &mut BitParser, BitSIM, options)
+        } else {
+            compress_two_pass(input, &mut compressed, &mut ByteParser, DefaultSIM, options)
+        }
+        .unwrap();
+        compressed
+    }
+
+    fn decompress(compressed: &[u8], bit_mode: bool, options: &CodecOptions) -> Vec<u8> {
+        let mut decompressed = Vec::new();
+        if bit_mode {
+            decompress_two_pass(
+                compressed,
+                &mut decompressed,
+                &mut BitParser,
+                BitSIM,
+                options,
+            )
+        } else {
+            decompress_two_pass(
+                compressed,
+                &mut decompressed,
+                &mut ByteParser,
+                DefaultSIM,
+                options,
+            )
+        }
+        .unwrap();
+        decompressed
+    }
+
+    #[test]
+    fn test_count_frequencies() {
+        let (frequencies, input_len) =
+            count_frequencies(&b"abca"[..], &mut ByteParser, &DefaultSIM, Frequency::max())
+                .unwrap();
+        assert_eq!(input_len, 4);
+        assert_eq!(*frequencies[b'a' as usize], 2);
+        assert_eq!(*frequencies[b'b' as usize], 1);
+        assert_eq!(*frequencies[b'd' as usize], 0);
+        assert_eq!(*frequencies[DefaultSIM.get_index(&Symbol::Eof).unwrap()], 1);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = skewed_input();
+        for bit_mode in [false, true] {
+            let header = Header {
+                bit_mode,
+                ..Default::default()
+            };
+            for header in [None, Some(header)] {
+                let options = CodecOptions {
+                    header,
+                    ..Default::default()
+                };
+                let compressed = compress(&input, bit_mode, &options);
+                assert_eq!(decompress(&compressed, bit_mode, &options), input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_beats_adaptive_model() {
+        let input = skewed_input();
+        let options = CodecOptions::default();
+        let two_pass = compress(&input, false, &options);
+
+        let mut adaptive = Vec::new();
+        let mut model = AdaptiveModel::new(DefaultSIM);
+        compress_reader(
+            &input[..],
+            &mut adaptive,
+            &mut model,
+            &mut ByteParser,
+            &options,
+        )
+        .unwrap();
+        assert!(two_pass.len() < adaptive.len());
+    }
+
+    #[test]
+    fn test_buffered_matches_re_read() {
+        let input = skewed_input();
+        let options = CodecOptions {
+            header: Some(Header::default()),
+            ..Default::default()
+        };
+
+        // Re-reading a file:
+        let path = std::env::temp_dir().join(format!("two-pass-{}.txt", std::process::id()));
+        std::fs::write(&path, &input).unwrap();
+        let mut re_read = Vec::new();
+        let file = std::fs::File::open(&path).unwrap();
+        compress_two_pass(file, &mut re_read, &mut ByteParser, DefaultSIM, &options).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut buffered = Vec::new();
+        compress_two_pass_buffered(
+            &input[..],
+            &mut buffered,
+            &mut ByteParser,
+            DefaultSIM,
+            &options,
+            DEFAULT_TWO_PASS_BUFFER_LIMIT,
+        )
+        .unwrap();
+        assert_eq!(buffered, re_read);
+    }
+
+    #[test]
+    fn test_buffer_limit() {
+        let input = skewed_input();
+        let options = CodecOptions::default();
+        let err = compress_two_pass_buffered(
+            &input[..],
+            Vec::new(),
+            &mut ByteParser,
+            DefaultSIM,
+            &options,
+            input.len() - 1,
+        )
+        .unwrap_err();
+        assert!(err.downcast_ref::<BufferLimitError>().is_some());
+
+        // Input exactly at the limit is fine:
+        compress_two_pass_buffered(
+            &input[..],
+            Vec::new(),
+            &mut ByteParser,
+            DefaultSIM,
+            &options,
+            input.len(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_multi_stream() {
+        let options = CodecOptions {
+            header: Some(Header::default()),
+            trailing: TrailingPolicy::MultiStream,
+            ..Default::default()
+        };
+        // Each stream has its own table:
+        let mut compressed = compress(b"first stream, ", false, &options);
+        compressed.extend(compress(b"SECOND STREAM", false, &options));
+        assert_eq!(
+            decompress(&compressed, false, &options),
+            b"first stream, SECOND STREAM"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use thiserror::Error;
+
+/// Number of value bits in each byte of a varint
+const GROUP_BITS: u32 = 7;
+/// Mask of the continuation bit in each byte of a varint
+const CONTINUATION: u8 = 1 << GROUP_BITS;
+
+/// Largest possible length in bytes of a varint
+pub const MAX_VARINT_BYTES: usize = u64::BITS.div_ceil(GROUP_BITS) as usize;
+
+/// Errors that may occur when reading a varint
+#[derive(Debug, Error, PartialEq)]
+pub enum VarintError {
+    #[error("The input ended in the middle of a number")]
+    Truncated,
+    #[error("The input holds a number too large to decode")]
+    TooLarge,
+}
+
+/// Appends _value_ to _output_ as a LEB128 varint: 7 bits per byte, least significant group first,
+/// with the MSB of each byte set if more bytes follow. Small values therefore take fewer bytes.
+pub fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let group = (value & (CONTINUATION as u64 - 1)) as u8;
+        value >>= GROUP_BITS;
+        if value == 0 {
+            output.push(group);
+            return;
+        }
+        output.push(group | CONTINUATION);
+    }
+}
+
+/// Reads a varint written by `write_varint` from the start of _input_, and advances _input_ past
+/// it
+pub fn read_varint(input: &mut &[u8]) -> Result<u64, VarintError> {
+    let mut value: u64 = 0;
+    for shift in (0..u64::BITS).step_by(GROUP_BITS as usize) {
+        let (&byte, rest) = input.split_first().ok_or(VarintError::Truncated)?;
+        *input = rest;
+
+        let group = (byte & !CONTINUATION) as u64;
+        // Make sure no bits are lost when shifting the group into place:
+        if (group << shift) >> shift != group {
+            return Err(VarintError::TooLarge);
+        }
+        value |= group << shift;
+        if byte & CONTINUATION == 0 {
+            return Ok(value);
+        }
+    }
+    Err(VarintError::TooLarge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for value in [0, 1, 127, 128, 300, 1 << 35, u64::MAX] {
+            let mut encoded = Vec::new();
+            write_varint(&mut encoded, value);
+            let mut input = &encoded[..];
+            assert_eq!(read_varint(&mut input), Ok(value));
+            assert!(input.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_small_values_take_one_byte() {
+        let mut encoded = Vec::new();
+        write_varint(&mut encoded, 127);
+        assert_eq!(encoded, [127]);
+    }
+
+    #[test]
+    fn test_max_varint_bytes() {
+        let mut encoded = Vec::new();
+        write_varint(&mut encoded, u64::MAX);
+        assert_eq!(encoded.len(), MAX_VARINT_BYTES);
+    }
+
+    #[test]
+    fn test_invalid_varints() {
+        assert_eq!(read_varint(&mut &[0x80][..]), Err(VarintError::Truncated));
+        assert_eq!(read_varint(&mut &[][..]), Err(VarintError::Truncated));
+        // A varint that never ends:
+        assert_eq!(
+            read_varint(&mut &[0xFF; 16][..]),
+            Err(VarintError::TooLarge)
+        );
+    }
+}
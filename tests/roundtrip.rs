@@ -0,0 +1,179 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Round-trip integration tests: every builtin model, in both byte-mode and bit-mode, must
+//! decompress a set of fixtures back into the exact same bytes through the library's public API.
+
+use anyhow::Result;
+use ppm_cli::frequencies::Frequency;
+use ppm_cli::header::Header;
+use ppm_cli::models::adaptive::{AdaptiveModel, UpdateRule, DEFAULT_FREQUENCY_CAP};
+use ppm_cli::models::distributions::uniform::UniformDistributionModel;
+use ppm_cli::models::order1::Order1Model;
+use ppm_cli::models::ppm::{PpmModel, DEFAULT_PPM_ORDER};
+use ppm_cli::models::Model;
+use ppm_cli::parser::{BitParser, ByteParser, Parser};
+use ppm_cli::sim::{BitSIM, DefaultSIM, SymbolIndexMapping};
+use ppm_cli::two_pass::{compress_two_pass, decompress_two_pass};
+use ppm_cli::{compress_reader, decompress_reader, CodecOptions};
+use std::io::Cursor;
+
+/// The inputs every model must round trip, with their names for failure messages
+fn fixtures() -> Vec<(&'static str, Vec<u8>)> {
+    // A xorshift generator, so the "random" input is the same on every run:
+    let mut state: u32 = 0x9e3779b9;
+    let random = (0..4096)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    vec![
+        ("empty", Vec::new()),
+        ("single byte", vec![0x2a]),
+        (
+            "repetitive text",
+            b"she sells sea shells by the sea shore. ".repeat(50),
+        ),
+        ("random binary", random),
+    ]
+}
+
+/// Builds every builtin model whose distribution doesn't depend on the input, with the given SIM
+fn builtin_models<SIM>(sim: SIM) -> Vec<(&'static str, Box<dyn Model>)>
+where
+    SIM: SymbolIndexMapping + Copy + 'static,
+{
+    let frequency_cap = Frequency::new(DEFAULT_FREQUENCY_CAP).unwrap();
+    vec![
+        ("uniform", Box::new(UniformDistributionModel::new(sim))),
+        ("order0", Box::new(AdaptiveModel::new(sim))),
+        (
+            "order0-sublinear",
+            Box::new(AdaptiveModel::with_update_rule(
+                sim,
+                frequency_cap,
+                UpdateRule::Sublinear,
+            )),
+        ),
+        ("order1", Box::new(Order1Model::new(sim))),
+        ("ppm", Box::new(PpmModel::new(sim, DEFAULT_PPM_ORDER))),
+    ]
+}
+
+fn parser(bit_mode: bool) -> Box<dyn Parser> {
+    if bit_mode {
+        Box::new(BitParser)
+    } else {
+        Box::new(ByteParser)
+    }
+}
+
+/// Options whose header records the mode, so the streams are checked against their length
+fn options(bit_mode: bool) -> CodecOptions<'static> {
+    CodecOptions {
+        header: Some(Header {
+            bit_mode,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn round_trip(model: &mut dyn Model, bit_mode: bool, input: &[u8]) -> Result<Vec<u8>> {
+    let (mut parser, options) = (parser(bit_mode), options(bit_mode));
+    let mut compressed = Vec::new();
+    compress_reader(input, &mut compressed, model, &mut *parser, &options)?;
+    let mut decompressed = Vec::new();
+    decompress_reader(
+        &compressed[..],
+        &mut decompressed,
+        model,
+        &mut *parser,
+        &options,
+    )?;
+    Ok(decompressed)
+}
+
+fn round_trip_two_pass<SIM>(sim: SIM, bit_mode: bool, input: &[u8]) -> Result<Vec<u8>>
+where
+    SIM: SymbolIndexMapping + Copy,
+{
+    let (mut parser, options) = (parser(bit_mode), options(bit_mode));
+    let mut compressed = Vec::new();
+    compress_two_pass(
+        Cursor::new(input),
+        &mut compressed,
+        &mut *parser,
+        sim,
+        &options,
+    )?;
+    let mut decompressed = Vec::new();
+    decompress_two_pass(
+        &compressed[..],
+        &mut decompressed,
+        &mut *parser,
+        sim,
+        &options,
+    )?;
+    Ok(decompressed)
+}
+
+#[test]
+fn test_byte_mode_round_trip() {
+    for (fixture, input) in fixtures() {
+        for (name, mut model) in builtin_models(DefaultSIM) {
+            let decompressed = round_trip(&mut *model, false, &input)
+                .unwrap_or_else(|e| panic!("{name} failed on {fixture}: {e}"));
+            assert!(decompressed == input, "{name} changed {fixture}");
+        }
+    }
+}
+
+#[test]
+fn test_bit_mode_round_trip() {
+    for (fixture, input) in fixtures() {
+        for (name, mut model) in builtin_models(BitSIM) {
+            let decompressed = round_trip(&mut *model, true, &input)
+                .unwrap_or_else(|e| panic!("{name} failed on {fixture} in bit-mode: {e}"));
+            assert!(
+                decompressed == input,
+                "{name} changed {fixture} in bit-mode"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_two_pass_round_trip() {
+    for (fixture, input) in fixtures() {
+        for (bit_mode, decompressed) in [
+            (false, round_trip_two_pass(DefaultSIM, false, &input)),
+            (true, round_trip_two_pass(BitSIM, true, &input)),
+        ] {
+            let decompressed = decompressed.unwrap_or_else(|e| {
+                panic!("two-pass failed on {fixture} (bit-mode: {bit_mode}): {e}")
+            });
+            assert!(
+                decompressed == input,
+                "two-pass changed {fixture} (bit-mode: {bit_mode})"
+            );
+        }
+    }
+}
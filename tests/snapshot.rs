@@ -0,0 +1,79 @@
+// PPM-CLI: A Command-Line Interface for compressing data using Arithmetic Coding + Prediction by
+// Partial Matching
+// Copyright (C) 2025  Yair Ziv
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A trained model's snapshot survives a trip through JSON, and imports into models that code
+//! exactly like the trained one.
+#![cfg(feature = "serde")]
+
+use ppm_cli::frequencies::Cfi;
+use ppm_cli::models::adaptive::AdaptiveModel;
+use ppm_cli::models::distributions::custom::CustomDistributionModel;
+use ppm_cli::models::snapshot::ModelSnapshot;
+use ppm_cli::models::{Model, ModelCfi};
+use ppm_cli::sim::{DefaultSIM, Symbol, SymbolIndexMapping};
+
+/// Trains an adaptive model on some text
+fn trained_model() -> AdaptiveModel<DefaultSIM> {
+    let mut model = AdaptiveModel::new(DefaultSIM);
+    for &byte in b"the quick brown fox jumps over the lazy dog, then the dog sleeps" {
+        let symbol = Symbol::Byte(byte);
+        let cfi = model.get_cfi(symbol).unwrap();
+        model.update(symbol, &cfi).unwrap();
+    }
+    model
+}
+
+/// The CFI every symbol of the default SIM gets from _model_, or None if it can't be coded
+fn all_cfis(model: &dyn Model) -> Vec<Option<(u64, u64, u64)>> {
+    (0..DefaultSIM.supported_symbols_count())
+        .map(|index| {
+            let symbol = DefaultSIM.get_symbol(index).unwrap();
+            let (ModelCfi::IndexCfi(cfi) | ModelCfi::EscapeCfi(cfi)) =
+                model.get_cfi(symbol).ok()?;
+            let Cfi { start, end, total } = cfi;
+            Some((*start, *end, *total))
+        })
+        .collect()
+}
+
+#[test]
+fn trained_model_survives_json() {
+    let model = trained_model();
+    let json = serde_json::to_string(&model.export().unwrap()).unwrap();
+    let snapshot: ModelSnapshot = serde_json::from_str(&json).unwrap();
+
+    // The snapshot can be loaded as a static distribution, or keep adapting from where it was:
+    let frequencies = snapshot.frequencies_for(&DefaultSIM).unwrap();
+    let custom = CustomDistributionModel::new(DefaultSIM, &frequencies).unwrap();
+    assert_eq!(all_cfis(&custom), all_cfis(&model));
+    let mut adaptive = AdaptiveModel::new(DefaultSIM);
+    adaptive.import(&snapshot).unwrap();
+    assert_eq!(all_cfis(&adaptive), all_cfis(&model));
+    assert_eq!(custom.export(), Some(snapshot));
+}
+
+#[test]
+fn snapshot_json_is_a_custom_model_file() {
+    let json = serde_json::to_value(trained_model().export().unwrap()).unwrap();
+    assert_eq!(json["bit_model"], false);
+    assert_eq!(
+        json["frequencies"].as_array().unwrap().len(),
+        DefaultSIM.supported_symbols_count()
+    );
+    // Unnamed snapshots leave the name to the file's name:
+    assert!(json.get("name").is_none());
+}